@@ -0,0 +1,166 @@
+use crate::device::Command;
+use crate::logging;
+use crate::mapping::{AuditLogConfig, Destination};
+use anyhow::{Context, Result};
+use serde_json::{Value, json};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::thread::{self, JoinHandle};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+use tracing::error;
+
+/// Size-rotated JSON Lines log of audited mapping activations.
+///
+/// Entries are written and the file rotated on a dedicated thread, so recording an
+/// activation never blocks routing on disk I/O. Dropping the log waits for the
+/// thread to write the entries still queued.
+pub struct AuditLog {
+    entries: Option<mpsc::UnboundedSender<Value>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl AuditLog {
+    /// Open (or create) the audit log file and start its writer
+    pub fn new(config: AuditLogConfig) -> Result<Self> {
+        if let Some(parent) = Path::new(&config.path).parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create audit log directory: {parent:?}"))?;
+        }
+        let file = open(&config.path)?;
+
+        let (entries, receiver) = mpsc::unbounded_channel();
+        let writer = thread::Builder::new()
+            .name("audit log".to_string())
+            .spawn(move || write_entries(&config, file, receiver))
+            .context("Failed to start the audit log writer")?;
+
+        Ok(Self {
+            entries: Some(entries),
+            writer: Some(writer),
+        })
+    }
+
+    /// Record a mapping activation and the commands it resolved to
+    pub fn record(
+        &self,
+        mapping: &str,
+        trigger: &str,
+        commands: &[Command],
+        destination: &Destination,
+    ) -> Result<()> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+
+        let entry = json!({
+            "timestamp_ms": timestamp,
+            "mapping": mapping,
+            "trigger": trigger,
+            "destination": destination,
+            "commands": commands,
+        });
+
+        self.entries
+            .as_ref()
+            .and_then(|entries| entries.send(entry).ok())
+            .context("Audit log writer has stopped")
+    }
+}
+
+impl Drop for AuditLog {
+    fn drop(&mut self) {
+        // Closing the channel ends the writer once it has written what is queued
+        self.entries.take();
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+/// Write entries as they arrive until the channel closes
+fn write_entries(
+    config: &AuditLogConfig,
+    mut file: File,
+    mut entries: mpsc::UnboundedReceiver<Value>,
+) {
+    while let Some(entry) = entries.blocking_recv() {
+        if let Err(e) = write_entry(config, &mut file, &entry) {
+            error!(
+                "Failed to write audit log entry for '{}': {:#}",
+                entry["mapping"].as_str().unwrap_or_default(),
+                e
+            );
+        }
+    }
+}
+
+/// Append one entry, rotating the file once it reaches its size limit
+fn write_entry(config: &AuditLogConfig, file: &mut File, entry: &Value) -> Result<()> {
+    writeln!(file, "{entry}").context("Failed to write audit log entry")?;
+
+    if file.metadata()?.len() >= config.max_bytes {
+        logging::rotate_files(Path::new(&config.path), config.max_files)
+            .context("Failed to rotate audit log")?;
+        *file = open(&config.path)?;
+    }
+
+    Ok(())
+}
+
+fn open(path: &str) -> Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open audit log file: {path:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_are_written_and_rotated() {
+        let dir = std::env::temp_dir().join(format!("midi-router-audit-{}", std::process::id()));
+        let path = dir.join("audit.jsonl");
+        let config = AuditLogConfig {
+            path: path.to_string_lossy().into_owned(),
+            max_bytes: 200,
+            max_files: 2,
+        };
+        let destination = Destination::RtpMidi {
+            session_name: "Main".to_string(),
+        };
+
+        let audit_log = AuditLog::new(config).unwrap();
+        for mapping in ["first", "second", "third"] {
+            audit_log
+                .record(mapping, "program change 1", &[], &destination)
+                .unwrap();
+        }
+        drop(audit_log);
+
+        let rotated = fs::read_to_string(dir.join("audit.jsonl.1")).unwrap();
+        let current = fs::read_to_string(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        // Two entries pass the size limit, so the third starts a new file
+        let entries = |text: &str| -> Vec<Value> {
+            text.lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect()
+        };
+        let rotated = entries(&rotated);
+        let current = entries(&current);
+        assert_eq!(rotated.len(), 2);
+        assert_eq!(rotated[0]["mapping"], "first");
+        assert_eq!(rotated[0]["trigger"], "program change 1");
+        assert_eq!(rotated[0]["destination"]["session_name"], "Main");
+        assert_eq!(rotated[1]["mapping"], "second");
+        assert_eq!(current.len(), 1);
+        assert_eq!(current[0]["mapping"], "third");
+    }
+}
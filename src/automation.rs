@@ -0,0 +1,113 @@
+use crate::mapping::Automation;
+use crate::processor::MidiProcessor;
+use crate::supervisor::supervise;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+
+/// How often running automations are checked for due steps
+const TICK_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Tempo used for automations until a tempo has been received
+pub const DEFAULT_BPM: f64 = 120.0;
+
+/// Position of a running automation
+struct RunningAutomation {
+    /// Index of the next step to run
+    step: usize,
+    /// When the next step is due
+    due: Instant,
+}
+
+/// Tracks which automations are running and when their next steps are due
+pub struct AutomationState {
+    running: HashMap<String, RunningAutomation>,
+}
+
+impl AutomationState {
+    pub fn new() -> Self {
+        Self {
+            running: HashMap::new(),
+        }
+    }
+
+    /// Start an automation from its first step
+    pub fn start(&mut self, name: &str) {
+        self.running.insert(
+            name.to_string(),
+            RunningAutomation {
+                step: 0,
+                due: Instant::now(),
+            },
+        );
+    }
+
+    /// Stop an automation, returning whether it was running
+    pub fn stop(&mut self, name: &str) -> bool {
+        self.running.remove(name).is_some()
+    }
+
+    pub fn is_running(&self, name: &str) -> bool {
+        self.running.contains_key(name)
+    }
+
+    /// Return the index of the automation's step if it is due, scheduling the one after it
+    pub fn take_due(&mut self, automation: &Automation, bpm: f64, now: Instant) -> Option<usize> {
+        let running = self.running.get_mut(&automation.name)?;
+        if running.due > now {
+            return None;
+        }
+
+        let Some(step) = automation.steps.get(running.step) else {
+            self.running.remove(&automation.name);
+            return None;
+        };
+        let index = running.step;
+
+        // Schedule from the previous due time so steps don't drift
+        let beat = Duration::from_secs_f64(60.0 / bpm);
+        running.due = (running.due + beat.mul_f64(step.beats.max(0.0))).max(now);
+        running.step += 1;
+
+        if running.step >= automation.steps.len() {
+            if automation.repeat {
+                running.step = 0;
+            } else {
+                // Keep the final step's duration by finishing on the next check
+                running.step = automation.steps.len();
+            }
+        }
+
+        Some(index)
+    }
+}
+
+/// Runs the steps of active automations in time with the current tempo
+pub struct AutomationMonitor {
+    processor: Arc<MidiProcessor>,
+}
+
+impl AutomationMonitor {
+    pub fn new(processor: Arc<MidiProcessor>) -> Self {
+        Self { processor }
+    }
+
+    /// Start running automation steps in the background
+    pub fn start(&self) -> JoinHandle<()> {
+        let processor = Arc::clone(&self.processor);
+
+        supervise("automations", move || {
+            let processor = Arc::clone(&processor);
+            async move {
+                let mut interval = tokio::time::interval(TICK_INTERVAL);
+                interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+                loop {
+                    interval.tick().await;
+                    processor.run_due_automations().await;
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,49 @@
+use crate::mapping::BankConfig;
+
+/// Tracks the active bank
+pub struct BankState {
+    active: usize,
+}
+
+impl BankState {
+    pub fn new() -> Self {
+        Self { active: 0 }
+    }
+
+    /// Index of the active bank
+    pub fn active(&self) -> usize {
+        self.active
+    }
+
+    /// Select a bank by index, ignoring out-of-range indices
+    pub fn select(&mut self, config: &BankConfig, index: usize) -> bool {
+        if index < config.names.len() {
+            self.active = index;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Move to the next bank, wrapping around
+    pub fn next(&mut self, config: &BankConfig) {
+        if !config.names.is_empty() {
+            self.active = (self.active + 1) % config.names.len();
+        }
+    }
+
+    /// Move to the previous bank, wrapping around
+    pub fn previous(&mut self, config: &BankConfig) {
+        if !config.names.is_empty() {
+            self.active = (self.active + config.names.len() - 1) % config.names.len();
+        }
+    }
+}
+
+/// Whether a mapping scoped to `bank` is active when `active_bank` is selected
+pub fn in_bank(bank: &Option<String>, active_bank: Option<&str>) -> bool {
+    match bank {
+        None => true,
+        Some(bank) => Some(bank.as_str()) == active_bank,
+    }
+}
@@ -0,0 +1,308 @@
+use crate::device::{Command, Device, DeviceConfig, DeviceType, Program, TempoSpec};
+use crate::mapping::{Destination, RtpMidiSession};
+use crate::mapping::{DeviceMapping, MapConfig, ProgramDisplay};
+use crate::migration::{DEVICE_CONFIG_VERSION, MAP_CONFIG_VERSION};
+use anyhow::{Result, bail};
+use std::collections::HashSet;
+
+/// Builds a [`Device`] with checked program numbers
+pub struct DeviceBuilder {
+    device: Device,
+}
+
+impl DeviceBuilder {
+    pub fn new(id: impl Into<String>, name: impl Into<String>, device_type: DeviceType) -> Self {
+        Self {
+            device: Device {
+                id: id.into(),
+                name: name.into(),
+                device_type,
+                programs: Vec::new(),
+                tempo_spec: None,
+            },
+        }
+    }
+
+    /// Add a program that runs the commands when selected
+    pub fn program(mut self, number: u8, name: impl Into<String>, commands: Vec<Command>) -> Self {
+        self.device.programs.push(Program {
+            number,
+            bank_msb: None,
+            bank_lsb: None,
+            name: name.into(),
+            commands: commands.into_iter().map(Into::into).collect(),
+            transactional: false,
+            sequence: Vec::new(),
+        });
+        self
+    }
+
+    /// Add a program whose commands are validated before any is sent
+    pub fn transactional_program(
+        self,
+        number: u8,
+        name: impl Into<String>,
+        commands: Vec<Command>,
+    ) -> Self {
+        let mut builder = self.program(number, name, commands);
+        if let Some(program) = builder.device.programs.last_mut() {
+            program.transactional = true;
+        }
+        builder
+    }
+
+    /// Add a program selected by a Program Change after Bank Select MSB (and LSB if given)
+    pub fn banked_program(
+        self,
+        bank_msb: u8,
+        bank_lsb: Option<u8>,
+        number: u8,
+        name: impl Into<String>,
+        commands: Vec<Command>,
+    ) -> Self {
+        let mut builder = self.program(number, name, commands);
+        if let Some(program) = builder.device.programs.last_mut() {
+            program.bank_msb = Some(bank_msb);
+            program.bank_lsb = bank_lsb;
+        }
+        builder
+    }
+
+    /// Set how tempo updates are sent to the device
+    pub fn tempo_spec(mut self, tempo_spec: TempoSpec) -> Self {
+        self.device.tempo_spec = Some(tempo_spec);
+        self
+    }
+
+    /// Check program numbers and return the device
+    pub fn build(self) -> Result<Device> {
+        let mut numbers = HashSet::new();
+        for program in &self.device.programs {
+            if program.number > 127 {
+                bail!(
+                    "Program '{}' of device '{}' has number {} (must be 0-127)",
+                    program.name,
+                    self.device.id,
+                    program.number
+                );
+            }
+            if !numbers.insert((program.bank_msb, program.bank_lsb, program.number)) {
+                bail!(
+                    "Device '{}' has more than one program numbered {} in the same bank",
+                    self.device.id,
+                    program.number
+                );
+            }
+        }
+        Ok(self.device)
+    }
+}
+
+/// Builds a [`DeviceMapping`] with checked MIDI channels
+pub struct MappingBuilder {
+    mapping: DeviceMapping,
+}
+
+impl MappingBuilder {
+    pub fn new(device_id: impl Into<String>, listen_channel: u8, destination: Destination) -> Self {
+        Self {
+            mapping: DeviceMapping {
+                device_id: device_id.into(),
+                listen_channel,
+                send_channel: None,
+                destination,
+                audit: false,
+                bank: None,
+                display: None,
+                cooldown_ms: None,
+                settle_ms: None,
+                priority: 0,
+            },
+        }
+    }
+
+    /// MIDI channel to send commands on (1-16)
+    pub fn send_channel(mut self, channel: u8) -> Self {
+        self.mapping.send_channel = Some(channel);
+        self
+    }
+
+    /// Write activations to the audit log
+    pub fn audit(mut self, audit: bool) -> Self {
+        self.mapping.audit = audit;
+        self
+    }
+
+    /// Only run the mapping while the bank is active
+    pub fn bank(mut self, bank: impl Into<String>) -> Self {
+        self.mapping.bank = Some(bank.into());
+        self
+    }
+
+    /// Show the selected program's name on a controller display
+    pub fn display(mut self, display: ProgramDisplay) -> Self {
+        self.mapping.display = Some(display);
+        self
+    }
+
+    /// Ignore repeat program changes within this many milliseconds
+    pub fn cooldown_ms(mut self, cooldown_ms: u64) -> Self {
+        self.mapping.cooldown_ms = Some(cooldown_ms);
+        self
+    }
+
+    /// Run only the last of a burst of program changes, this many milliseconds after it
+    pub fn settle_ms(mut self, settle_ms: u64) -> Self {
+        self.mapping.settle_ms = Some(settle_ms);
+        self
+    }
+
+    /// Rank the mapping among mappings matching the same Program Change
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.mapping.priority = priority;
+        self
+    }
+
+    /// Check channels and return the mapping
+    pub fn build(self) -> Result<DeviceMapping> {
+        if !(1..=16).contains(&self.mapping.listen_channel) {
+            bail!(
+                "Mapping for device '{}' listens on channel {} (must be 1-16)",
+                self.mapping.device_id,
+                self.mapping.listen_channel
+            );
+        }
+        if let Some(channel) = self.mapping.send_channel
+            && !(1..=16).contains(&channel)
+        {
+            bail!(
+                "Mapping for device '{}' sends on channel {} (must be 1-16)",
+                self.mapping.device_id,
+                channel
+            );
+        }
+        Ok(self.mapping)
+    }
+}
+
+impl DeviceConfig {
+    /// Empty device configuration at the current schema version
+    pub fn new() -> Self {
+        Self {
+            version: DEVICE_CONFIG_VERSION,
+            devices: Default::default(),
+        }
+    }
+
+    /// Add a device, replacing any with the same ID
+    pub fn with_device(mut self, device: Device) -> Self {
+        self.devices.insert(device.id.clone(), device);
+        self
+    }
+}
+
+impl Default for DeviceConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MapConfig {
+    /// Empty map configuration at the current schema version
+    pub fn new() -> Self {
+        Self {
+            version: MAP_CONFIG_VERSION,
+            subsystems: Default::default(),
+            rtp_midi_sessions: Vec::new(),
+            osc_destinations: Default::default(),
+            destination_groups: Default::default(),
+            osc_sources: Vec::new(),
+            virtual_ports: Vec::new(),
+            device_mappings: Vec::new(),
+            note_mappings: Vec::new(),
+            note_program_rules: Vec::new(),
+            note_forward_rules: Vec::new(),
+            control_forward_rules: Vec::new(),
+            banked_program_rules: Vec::new(),
+            combo_mappings: Vec::new(),
+            step_mappings: Vec::new(),
+            osc_mappings: Vec::new(),
+            osc_control_rules: Vec::new(),
+            osc_forward_rules: Vec::new(),
+            sysex_mappings: Vec::new(),
+            match_policies: Default::default(),
+            inactivity_rules: Vec::new(),
+            automations: Vec::new(),
+            cue_lists: Vec::new(),
+            captures: Vec::new(),
+            tempo_limits: Default::default(),
+            tempo_stats: Default::default(),
+            tempo_domains: Vec::new(),
+            tap_inputs: Vec::new(),
+            clock_outputs: Vec::new(),
+            link: None,
+            tempo_displays: Vec::new(),
+            show_lock: None,
+            plugins: Vec::new(),
+            identity_file: None,
+            loop_suppression: None,
+            time_source: Default::default(),
+            overload: None,
+            random_seed: None,
+            banks: None,
+            audit_log: None,
+            event_sinks: Vec::new(),
+        }
+    }
+
+    /// Add an RTP MIDI session
+    pub fn with_session(mut self, session: RtpMidiSession) -> Self {
+        self.rtp_midi_sessions.push(session);
+        self
+    }
+
+    /// Add a device mapping
+    pub fn with_mapping(mut self, mapping: DeviceMapping) -> Self {
+        self.device_mappings.push(mapping);
+        self
+    }
+
+    /// Add a destination group
+    pub fn with_destination_group(
+        mut self,
+        group_name: impl Into<String>,
+        members: Vec<Destination>,
+    ) -> Self {
+        self.destination_groups.insert(group_name.into(), members);
+        self
+    }
+}
+
+impl Default for MapConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Destination {
+    /// Destination for an RTP MIDI session
+    pub fn rtp_midi(session_name: impl Into<String>) -> Self {
+        Destination::RtpMidi {
+            session_name: session_name.into(),
+        }
+    }
+
+    /// Destination for a named OSC destination
+    pub fn osc(destination_name: impl Into<String>) -> Self {
+        Destination::Osc {
+            destination_name: destination_name.into(),
+        }
+    }
+
+    /// Destination for a named destination group
+    pub fn group(group_name: impl Into<String>) -> Self {
+        Destination::Group {
+            group_name: group_name.into(),
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use crate::cli::RunArgs;
+use anyhow::{Context, Result, bail};
+use clap::{Args, Subcommand};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+/// Format version of the bundle manifest
+const BUNDLE_VERSION: u64 = 1;
+const MANIFEST_ENTRY: &str = "manifest.json";
+const DEVICES_ENTRY: &str = "devices.json";
+const MAP_ENTRY: &str = "map.json";
+const ASSETS_DIR: &str = "assets";
+
+/// Options for moving a complete rig between machines
+#[derive(Debug, Args)]
+pub struct BundleArgs {
+    #[command(subcommand)]
+    pub action: BundleAction,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum BundleAction {
+    /// Write the current configs and the files they reference to an archive
+    Export {
+        /// Archive to create
+        output: PathBuf,
+    },
+    /// Unpack an archive into `--device-config`, `--map-config` and an `assets` directory
+    Import {
+        /// Archive to read
+        input: PathBuf,
+        /// Overwrite existing config files
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+/// Description of a bundle's contents
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    version: u64,
+    /// Archive entries of referenced files, under `assets/`
+    assets: Vec<String>,
+}
+
+pub fn run(args: &BundleArgs, current: &RunArgs) -> Result<()> {
+    match args.action {
+        BundleAction::Export { ref output } => export(output, current),
+        BundleAction::Import { ref input, force } => import(input, force, current),
+    }
+}
+
+/// Export the raw config files, so encrypted secrets stay encrypted
+fn export(output: &Path, current: &RunArgs) -> Result<()> {
+    let devices = fs::read(&current.device_config)
+        .with_context(|| format!("Failed to read {:?}", current.device_config))?;
+    let mut map = read_json(&current.map_config)?;
+
+    // Copy referenced files into the bundle and point the map at the copies
+    let mut assets = Vec::new();
+    for reference in asset_references(&mut map) {
+        let Value::String(path) = reference else {
+            continue;
+        };
+        let source = PathBuf::from(path.as_str());
+        let name = source
+            .file_name()
+            .with_context(|| format!("Referenced file {path:?} has no file name"))?
+            .to_string_lossy()
+            .into_owned();
+        let entry = format!("{ASSETS_DIR}/{name}");
+        if !assets.iter().any(|(existing, _)| *existing == entry) {
+            let content =
+                fs::read(&source).with_context(|| format!("Failed to read asset {source:?}"))?;
+            assets.push((entry.clone(), content));
+        }
+        *path = entry;
+    }
+
+    let manifest = Manifest {
+        version: BUNDLE_VERSION,
+        assets: assets.iter().map(|(entry, _)| entry.clone()).collect(),
+    };
+
+    let file = File::create(output).with_context(|| format!("Failed to create {output:?}"))?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default();
+    zip.start_file(MANIFEST_ENTRY, options)?;
+    zip.write_all(&serde_json::to_vec_pretty(&manifest)?)?;
+    zip.start_file(DEVICES_ENTRY, options)?;
+    zip.write_all(&devices)?;
+    zip.start_file(MAP_ENTRY, options)?;
+    zip.write_all(&serde_json::to_vec_pretty(&map)?)?;
+    for (entry, content) in &assets {
+        zip.start_file(entry.as_str(), options)?;
+        zip.write_all(content)?;
+    }
+    zip.finish()?;
+
+    println!(
+        "Exported configs and {} asset(s) to {:?}",
+        assets.len(),
+        output
+    );
+    Ok(())
+}
+
+fn import(input: &Path, force: bool, current: &RunArgs) -> Result<()> {
+    if current.read_only {
+        bail!("Refusing to write configs in read-only mode");
+    }
+    for path in [&current.device_config, &current.map_config] {
+        if path.exists() && !force {
+            bail!("{path:?} already exists; pass --force to overwrite it");
+        }
+    }
+
+    let file = File::open(input).with_context(|| format!("Failed to open {input:?}"))?;
+    let mut zip = ZipArchive::new(file).with_context(|| format!("{input:?} is not a bundle"))?;
+
+    let manifest: Manifest = serde_json::from_slice(&read_entry(&mut zip, MANIFEST_ENTRY)?)
+        .context("Invalid bundle manifest")?;
+    if manifest.version > BUNDLE_VERSION {
+        bail!(
+            "Bundle version {} is newer than this router supports ({})",
+            manifest.version,
+            BUNDLE_VERSION
+        );
+    }
+
+    // Check everything before writing anything
+    let devices = read_entry(&mut zip, DEVICES_ENTRY)?;
+    serde_json::from_slice::<Value>(&devices).context("Invalid devices.json in bundle")?;
+    let mut map: Value = serde_json::from_slice(&read_entry(&mut zip, MAP_ENTRY)?)
+        .context("Invalid map.json in bundle")?;
+    let mut assets = Vec::new();
+    for entry in &manifest.assets {
+        let name = entry
+            .strip_prefix(&format!("{ASSETS_DIR}/"))
+            .filter(|name| !name.is_empty() && !name.contains(['/', '\\']) && *name != "..")
+            .with_context(|| format!("Invalid asset entry {entry:?} in bundle"))?;
+        assets.push((
+            entry.clone(),
+            name.to_string(),
+            read_entry(&mut zip, entry)?,
+        ));
+    }
+
+    // Assets go next to the map config, and the map points at them there
+    let assets_dir = current
+        .map_config
+        .parent()
+        .unwrap_or(Path::new(""))
+        .join(ASSETS_DIR);
+    for reference in asset_references(&mut map) {
+        let Value::String(path) = reference else {
+            continue;
+        };
+        if let Some((_, name, _)) = assets.iter().find(|(entry, _, _)| entry == path) {
+            *path = assets_dir.join(name).to_string_lossy().into_owned();
+        }
+    }
+
+    if !assets.is_empty() {
+        fs::create_dir_all(&assets_dir)
+            .with_context(|| format!("Failed to create {assets_dir:?}"))?;
+    }
+    for (_, name, content) in &assets {
+        let path = assets_dir.join(name);
+        fs::write(&path, content).with_context(|| format!("Failed to write {path:?}"))?;
+    }
+    for path in [&current.device_config, &current.map_config] {
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent).with_context(|| format!("Failed to create {parent:?}"))?;
+        }
+    }
+    fs::write(&current.device_config, &devices)
+        .with_context(|| format!("Failed to write {:?}", current.device_config))?;
+    fs::write(&current.map_config, serde_json::to_vec_pretty(&map)?)
+        .with_context(|| format!("Failed to write {:?}", current.map_config))?;
+
+    println!(
+        "Imported configs to {:?} and {:?} with {} asset(s)",
+        current.device_config,
+        current.map_config,
+        assets.len()
+    );
+    Ok(())
+}
+
+/// Values in the map config that name files the rig depends on
+fn asset_references(map: &mut Value) -> Vec<&mut Value> {
+    let mut references = Vec::new();
+    if let Some(Value::Array(plugins)) = map.get_mut("plugins") {
+        for plugin in plugins {
+            if let Some(path) = plugin.get_mut("path") {
+                references.push(path);
+            }
+        }
+    }
+    references
+}
+
+fn read_json(path: &Path) -> Result<Value> {
+    let content = fs::read_to_string(path).with_context(|| format!("Failed to read {path:?}"))?;
+    serde_json::from_str(&content).with_context(|| format!("Failed to parse {path:?}"))
+}
+
+fn read_entry(zip: &mut ZipArchive<File>, name: &str) -> Result<Vec<u8>> {
+    let mut entry = zip
+        .by_name(name)
+        .with_context(|| format!("Bundle has no {name}"))?;
+    let mut content = Vec::new();
+    entry.read_to_end(&mut content)?;
+    Ok(content)
+}
@@ -0,0 +1,102 @@
+use crate::bundle::BundleArgs;
+use crate::config_diff::DiffConfigArgs;
+#[cfg(feature = "mdns")]
+use crate::discovery::DiscoverArgs;
+use crate::graph::GraphArgs;
+use crate::import::ImportProgramsArgs;
+use crate::logging::LogArgs;
+use crate::service::InstallServiceArgs;
+#[cfg(windows)]
+use crate::service::RunServiceArgs;
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+
+/// MIDI patch mapping router for RTP MIDI and OSC
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(flatten)]
+    pub logging: LogArgs,
+    #[command(flatten)]
+    pub run: RunArgs,
+    #[command(subcommand)]
+    pub command: Option<CliCommand>,
+}
+
+/// Options used when running the router
+#[derive(Debug, Clone, Args)]
+pub struct RunArgs {
+    /// Path to the device configuration file
+    #[arg(
+        long,
+        env = "MIDI_ROUTER_DEVICE_CONFIG",
+        default_value = "config/devices.json"
+    )]
+    pub device_config: PathBuf,
+    /// Path to the map configuration file
+    #[arg(
+        long,
+        env = "MIDI_ROUTER_MAP_CONFIG",
+        default_value = "config/map.json"
+    )]
+    pub map_config: PathBuf,
+    /// Contexts file listing several isolated routing contexts (overrides the config paths)
+    #[arg(long, env = "MIDI_ROUTER_CONTEXTS")]
+    pub contexts: Option<PathBuf>,
+    /// Port to serve the `/healthz` endpoint on (disabled if unset)
+    #[arg(long, env = "MIDI_ROUTER_HEALTH_PORT")]
+    pub health_port: Option<u16>,
+    /// Token the config editor requires as `Authorization: Bearer <token>`; without one it
+    /// only answers requests from this machine
+    #[cfg(feature = "http")]
+    #[arg(long, env = "MIDI_ROUTER_HEALTH_TOKEN", hide_env_values = true)]
+    pub health_token: Option<String>,
+    /// Reject config writes and control requests; routing runs as normal
+    #[arg(long, env = "MIDI_ROUTER_READ_ONLY")]
+    pub read_only: bool,
+    /// Apply changes to the config files without restarting
+    #[arg(long, env = "MIDI_ROUTER_HOT_RELOAD")]
+    pub hot_reload: bool,
+}
+
+/// Subcommands; with no subcommand the router runs in the foreground
+#[derive(Debug, Subcommand)]
+pub enum CliCommand {
+    /// Register the router to start at boot (launchd on macOS, service on Windows)
+    InstallService(InstallServiceArgs),
+    /// Run under the Windows service control manager (used by the installed service)
+    #[cfg(windows)]
+    #[command(hide = true)]
+    RunService(RunServiceArgs),
+    /// Query the health endpoint of a running router and exit non-zero if unhealthy
+    #[cfg(feature = "http")]
+    Healthcheck,
+    /// Print the semantic differences between older configs and the current ones
+    DiffConfig(DiffConfigArgs),
+    /// Print the routing graph of the current configs as Graphviz DOT or Mermaid
+    Graph(GraphArgs),
+    /// List RTP MIDI services visible on the network, for use in `connect_to`
+    #[cfg(feature = "mdns")]
+    Discover(DiscoverArgs),
+    /// Add programs to a device in `--device-config` from a CSV program list
+    ImportPrograms(ImportProgramsArgs),
+    /// Encrypt a value read from stdin for use as a config secret
+    EncryptSecret(EncryptSecretArgs),
+    /// Export or import a complete rig (configs and referenced files) as one archive
+    Bundle(BundleArgs),
+    /// Check the configs and their cross-references, exiting non-zero if there are problems
+    Validate,
+    /// Check that ports bind, remotes resolve and RTP MIDI and OSC work, as a pre-show check
+    Selftest,
+    /// Run the router with a live status display and keyboard controls in the terminal
+    #[cfg(feature = "tui")]
+    Tui,
+}
+
+/// Options for encrypting a config secret
+#[derive(Debug, Args)]
+pub struct EncryptSecretArgs {
+    /// age recipient (`age1...`) whose identity will decrypt the secret
+    #[arg(long)]
+    pub recipient: String,
+}
@@ -0,0 +1,125 @@
+use crate::mapping::ClockOutput;
+use crate::processor::MidiProcessor;
+use crate::supervisor::supervise;
+use midi_types::MidiMessage;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+/// Timing Clock messages per quarter note
+const PULSES_PER_QUARTER: f64 = 24.0;
+
+/// How often a stopped clock checks whether its tempo domain has a tempo yet
+const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Sends MIDI clock at the current tempo to the configured clock outputs
+pub struct ClockMonitor {
+    processor: Arc<MidiProcessor>,
+}
+
+impl ClockMonitor {
+    pub fn new(processor: Arc<MidiProcessor>) -> Self {
+        Self { processor }
+    }
+
+    /// Start one clock per output in the background
+    pub fn start(&self, outputs: &[ClockOutput]) -> Vec<JoinHandle<()>> {
+        outputs
+            .iter()
+            .map(|output| {
+                let processor = Arc::clone(&self.processor);
+                let output = output.clone();
+                supervise(
+                    format!("MIDI clock to '{}'", output.session_name),
+                    move || run(Arc::clone(&processor), output.clone()),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Send Timing Clock while the clock is running and the tempo domain has a tempo,
+/// with Start and Stop around each run
+async fn run(processor: Arc<MidiProcessor>, output: ClockOutput) {
+    let mut running = processor.watch_clock();
+    let mut started = false;
+    let mut failing = false;
+    let mut next_pulse = Instant::now();
+
+    loop {
+        let is_running = *running.borrow_and_update();
+        let bpm = if is_running {
+            processor.tempo(&output.tempo_domain).await
+        } else {
+            None
+        };
+
+        let Some(bpm) = bpm else {
+            if started {
+                started = false;
+                info!("Stopping MIDI clock to '{}'", output.session_name);
+                if output.transport {
+                    send(&processor, &output, MidiMessage::Stop, &mut failing).await;
+                }
+            }
+            tokio::select! {
+                _ = running.changed() => {}
+                _ = tokio::time::sleep(IDLE_POLL_INTERVAL) => {}
+            }
+            continue;
+        };
+
+        if !started {
+            started = true;
+            next_pulse = Instant::now();
+            info!(
+                "Starting MIDI clock to '{}' at {} BPM",
+                output.session_name, bpm
+            );
+            if output.transport {
+                send(&processor, &output, MidiMessage::Start, &mut failing).await;
+            }
+        }
+
+        send(&processor, &output, MidiMessage::TimingClock, &mut failing).await;
+
+        // Schedule from the previous pulse so send times don't drift, but don't
+        // burst to catch up after falling behind
+        next_pulse += Duration::from_secs_f64(60.0 / (bpm * PULSES_PER_QUARTER));
+        let now = Instant::now();
+        if next_pulse < now {
+            next_pulse = now;
+        }
+
+        tokio::select! {
+            _ = running.changed() => {}
+            _ = tokio::time::sleep_until(next_pulse) => {}
+        }
+    }
+}
+
+/// Send a clock message, warning only when sending starts or stops failing
+async fn send(
+    processor: &MidiProcessor,
+    output: &ClockOutput,
+    message: MidiMessage,
+    failing: &mut bool,
+) {
+    match processor.send_clock(&output.session_name, message).await {
+        Ok(()) if *failing => {
+            *failing = false;
+            info!("Sending MIDI clock to '{}' recovered", output.session_name);
+        }
+        Ok(()) => {}
+        Err(e) if !*failing => {
+            *failing = true;
+            warn!(
+                "Failed to send MIDI clock to '{}': {}",
+                output.session_name, e
+            );
+        }
+        Err(_) => {}
+    }
+}
@@ -0,0 +1,546 @@
+use crate::device::{Command, CommandValue, OscArg};
+use crate::sysex;
+use anyhow::{Context, Result, bail};
+
+/// Largest Song Position Pointer, the 14-bit maximum
+pub const MAX_SONG_POSITION: u16 = 16383;
+
+/// Parse a command written in the compact syntax.
+///
+/// - `PC 12`: Program Change, `PC 12 bank=1,0`: after Bank Select MSB 1 and LSB 0
+/// - `CC 80=127`: Control Change
+/// - `ON 60=100` / `OFF 60`: Note On / Note Off
+/// - `START` / `STOP` / `CONTINUE`: transport
+/// - `SONG 3`: Song Select, `SPP 64`: Song Position Pointer in sixteenth notes
+/// - `OSC /scene/up i:1 f:0.5 s:text b:true`: OSC message with typed arguments
+/// - `SYSEX F0 00 21 24 F7`: raw SysEx message in hex
+pub fn parse_command(text: &str) -> Result<Command> {
+    let mut parts = text.split_whitespace();
+    let Some(keyword) = parts.next() else {
+        bail!("Empty command");
+    };
+    let rest: Vec<&str> = parts.collect();
+
+    let command = match keyword.to_ascii_uppercase().as_str() {
+        "PC" => {
+            let (program, bank_msb, bank_lsb) = match rest[..] {
+                [program] => (program, None, None),
+                [program, bank] => {
+                    let Some(bank) = bank.strip_prefix("bank=") else {
+                        bail!("Expected `PC <program> [bank=<msb>[,<lsb>]]`, got `{text}`");
+                    };
+                    let (msb, lsb) = match bank.split_once(',') {
+                        Some((msb, lsb)) => (parse_u8(msb)?, Some(parse_u8(lsb)?)),
+                        None => (parse_u8(bank)?, None),
+                    };
+                    (program, Some(msb), lsb)
+                }
+                _ => bail!("Expected `PC <program> [bank=<msb>[,<lsb>]]`, got `{text}`"),
+            };
+            Command::ProgramChange {
+                program: parse_u8(program)?.into(),
+                bank_msb,
+                bank_lsb,
+            }
+        }
+        "CC" => {
+            let [assignment] = rest[..] else {
+                bail!("Expected `CC <controller>=<value>`, got `{text}`");
+            };
+            let (controller, value) = parse_assignment(assignment)?;
+            Command::ControlChange {
+                controller,
+                value: value.into(),
+            }
+        }
+        "ON" => {
+            let [assignment] = rest[..] else {
+                bail!("Expected `ON <note>=<velocity>`, got `{text}`");
+            };
+            let (note, velocity) = parse_assignment(assignment)?;
+            Command::NoteOn {
+                note,
+                velocity: velocity.into(),
+            }
+        }
+        "OFF" => {
+            let [note] = rest[..] else {
+                bail!("Expected `OFF <note>`, got `{text}`");
+            };
+            Command::NoteOff {
+                note: parse_u8(note)?,
+            }
+        }
+        "START" | "STOP" | "CONTINUE" => {
+            if !rest.is_empty() {
+                bail!("`{keyword}` takes no arguments, got `{text}`");
+            }
+            match keyword.to_ascii_uppercase().as_str() {
+                "START" => Command::Start,
+                "STOP" => Command::Stop,
+                _ => Command::Continue,
+            }
+        }
+        "SONG" => {
+            let [song] = rest[..] else {
+                bail!("Expected `SONG <song>`, got `{text}`");
+            };
+            Command::SongSelect {
+                song: parse_u8(song)?,
+            }
+        }
+        "SPP" => {
+            let [position] = rest[..] else {
+                bail!("Expected `SPP <sixteenths>`, got `{text}`");
+            };
+            Command::SongPosition {
+                position: parse_song_position(position)?,
+            }
+        }
+        "OSC" => {
+            let Some((address, args)) = rest.split_first() else {
+                bail!("Expected `OSC <address> [args]`, got `{text}`");
+            };
+            if !address.starts_with('/') {
+                bail!("OSC address `{address}` must start with `/`");
+            }
+            Command::Osc {
+                address: address.to_string(),
+                args: args
+                    .iter()
+                    .map(|arg| parse_osc_arg(arg))
+                    .collect::<Result<_>>()?,
+            }
+        }
+        "SYSEX" => {
+            if rest.is_empty() {
+                bail!("Expected `SYSEX <hex bytes>`, got `{text}`");
+            }
+            Command::SysEx {
+                data: sysex::hex::parse(&rest.join(" ")).map_err(anyhow::Error::msg)?,
+            }
+        }
+        other => bail!(
+            "Unknown command `{other}` (expected PC, CC, ON, OFF, START, STOP, CONTINUE, SONG, SPP, OSC or SYSEX)"
+        ),
+    };
+    Ok(command)
+}
+
+/// Write a command in the compact syntax, if the syntax can express it.
+///
+/// Random values, display text and OSC arguments that are normalized or contain
+/// whitespace or `;` have no compact form.
+pub fn format_command(command: &Command) -> Option<String> {
+    let text = match command {
+        Command::ProgramChange {
+            program,
+            bank_msb,
+            bank_lsb,
+        } => match (bank_msb, bank_lsb) {
+            (None, None) => format!("PC {}", fixed(program)?),
+            (Some(msb), None) => format!("PC {} bank={}", fixed(program)?, data_byte(*msb)?),
+            (Some(msb), Some(lsb)) => format!(
+                "PC {} bank={},{}",
+                fixed(program)?,
+                data_byte(*msb)?,
+                data_byte(*lsb)?
+            ),
+            (None, Some(_)) => return None,
+        },
+        Command::ControlChange { controller, value } => {
+            format!("CC {}={}", data_byte(*controller)?, fixed(value)?)
+        }
+        Command::NoteOn { note, velocity } => {
+            format!("ON {}={}", data_byte(*note)?, fixed(velocity)?)
+        }
+        Command::NoteOff { note } => format!("OFF {}", data_byte(*note)?),
+        Command::Start => "START".to_string(),
+        Command::Stop => "STOP".to_string(),
+        Command::Continue => "CONTINUE".to_string(),
+        Command::SongSelect { song } => format!("SONG {}", data_byte(*song)?),
+        Command::SongPosition { position } => {
+            if *position > MAX_SONG_POSITION {
+                return None;
+            }
+            format!("SPP {position}")
+        }
+        Command::DisplayText { .. } => return None,
+        Command::SysEx { data } => {
+            if data.is_empty() {
+                return None;
+            }
+            let hex: Vec<String> = data.iter().map(|byte| format!("{byte:02X}")).collect();
+            format!("SYSEX {}", hex.join(" "))
+        }
+        Command::Osc { address, args } => {
+            if !address.starts_with('/')
+                || address.contains(char::is_whitespace)
+                || address.contains(';')
+            {
+                return None;
+            }
+            let mut text = format!("OSC {address}");
+            for arg in args {
+                text.push(' ');
+                text.push_str(&format_osc_arg(arg)?);
+            }
+            text
+        }
+    };
+    Some(text)
+}
+
+/// Parse several `;`-separated commands
+pub fn parse_commands(text: &str) -> Result<Vec<Command>> {
+    text.split(';')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .map(parse_command)
+        .collect()
+}
+
+/// The value of a command that always sends the same value
+fn fixed(value: &CommandValue) -> Option<u8> {
+    match value {
+        CommandValue::Fixed(value) => data_byte(*value),
+        _ => None,
+    }
+}
+
+/// A value that the compact syntax accepts back (0-127)
+fn data_byte(value: u8) -> Option<u8> {
+    (value <= 127).then_some(value)
+}
+
+/// Write an OSC argument with its type prefix
+fn format_osc_arg(arg: &OscArg) -> Option<String> {
+    let text = match arg {
+        OscArg::Int { value } => format!("i:{value}"),
+        OscArg::Float { value } => format!("f:{value}"),
+        OscArg::String { value } => {
+            if value.contains(char::is_whitespace) || value.contains(';') {
+                return None;
+            }
+            format!("s:{value}")
+        }
+        OscArg::Bool { value } => format!("b:{value}"),
+        OscArg::Normalized { .. } | OscArg::Variable { .. } => return None,
+    };
+    Some(text)
+}
+
+/// Parse `<number>=<number>` into two MIDI data bytes
+fn parse_assignment(text: &str) -> Result<(u8, u8)> {
+    let (left, right) = text
+        .split_once('=')
+        .with_context(|| format!("Expected `<number>=<value>`, got `{text}`"))?;
+    Ok((parse_u8(left)?, parse_u8(right)?))
+}
+
+/// Parse a MIDI data byte (0-127)
+fn parse_u8(text: &str) -> Result<u8> {
+    let value: u8 = text
+        .parse()
+        .with_context(|| format!("`{text}` is not a number"))?;
+    if value > 127 {
+        bail!("{value} is out of range (must be 0-127)");
+    }
+    Ok(value)
+}
+
+/// Parse a Song Position Pointer (0-16383)
+fn parse_song_position(text: &str) -> Result<u16> {
+    let value: u16 = text
+        .parse()
+        .with_context(|| format!("`{text}` is not a number"))?;
+    if value > MAX_SONG_POSITION {
+        bail!("{value} is out of range (must be 0-{MAX_SONG_POSITION})");
+    }
+    Ok(value)
+}
+
+/// Parse a typed OSC argument such as `i:1` or `s:text`
+pub(crate) fn parse_osc_arg(text: &str) -> Result<OscArg> {
+    let (kind, value) = text
+        .split_once(':')
+        .with_context(|| format!("OSC argument `{text}` needs a type prefix (i:, f:, s: or b:)"))?;
+    let arg = match kind {
+        "i" => OscArg::Int {
+            value: value
+                .parse()
+                .with_context(|| format!("`{value}` is not an integer"))?,
+        },
+        "f" => OscArg::Float {
+            value: value
+                .parse()
+                .with_context(|| format!("`{value}` is not a number"))?,
+        },
+        "s" => OscArg::String {
+            value: value.to_string(),
+        },
+        "b" => OscArg::Bool {
+            value: value
+                .parse()
+                .with_context(|| format!("`{value}` is not true or false"))?,
+        },
+        other => bail!("Unknown OSC argument type `{other}` (expected i, f, s or b)"),
+    };
+    Ok(arg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::DisplayProtocol;
+
+    /// Parse a command, format it and parse the result again, returning the formatted text
+    fn round_trip(text: &str) -> String {
+        let command = parse_command(text).unwrap();
+        let formatted =
+            format_command(&command).unwrap_or_else(|| panic!("`{text}` has no compact form"));
+        let reparsed = parse_command(&formatted).unwrap();
+        assert_eq!(format!("{command:?}"), format!("{reparsed:?}"));
+        formatted
+    }
+
+    #[test]
+    fn every_keyword_round_trips() {
+        for text in [
+            "PC 12",
+            "PC 0 bank=1",
+            "PC 127 bank=1,0",
+            "CC 80=127",
+            "ON 60=100",
+            "OFF 60",
+            "START",
+            "STOP",
+            "CONTINUE",
+            "SONG 3",
+            "SPP 64",
+            "OSC /scene/up",
+            "OSC /scene/up i:-1 f:0.5 s:text b:true",
+            "SYSEX F0 00 21 24 F7",
+        ] {
+            assert_eq!(round_trip(text), text);
+        }
+    }
+
+    #[test]
+    fn formatting_normalizes_the_text() {
+        assert_eq!(round_trip("pc 12"), "PC 12");
+        assert_eq!(round_trip("  cc   7=100 "), "CC 7=100");
+        assert_eq!(round_trip("Start"), "START");
+        assert_eq!(round_trip("sysex f0 7e7f f7"), "SYSEX F0 7E 7F F7");
+        assert_eq!(round_trip("OSC /a f:1.0"), "OSC /a f:1");
+    }
+
+    #[test]
+    fn bank_forms() {
+        let Command::ProgramChange {
+            program,
+            bank_msb,
+            bank_lsb,
+        } = parse_command("PC 5 bank=2,3").unwrap()
+        else {
+            panic!("not a program change");
+        };
+        assert!(matches!(program, CommandValue::Fixed(5)));
+        assert_eq!((bank_msb, bank_lsb), (Some(2), Some(3)));
+
+        let Command::ProgramChange {
+            bank_msb, bank_lsb, ..
+        } = parse_command("PC 5 bank=2").unwrap()
+        else {
+            panic!("not a program change");
+        };
+        assert_eq!((bank_msb, bank_lsb), (Some(2), None));
+
+        for text in [
+            "PC 5 bank=",
+            "PC 5 bank=2,",
+            "PC 5 bank=128",
+            "PC 5 bank=1,128",
+            "PC 5 bnk=1",
+            "PC 5 bank=1 bank=2",
+        ] {
+            assert!(parse_command(text).is_err(), "{text}");
+        }
+    }
+
+    #[test]
+    fn song_position_bounds() {
+        assert_eq!(round_trip("SPP 0"), "SPP 0");
+        assert_eq!(round_trip("SPP 16383"), "SPP 16383");
+        assert!(parse_command("SPP 16384").is_err());
+        assert!(parse_command("SPP -1").is_err());
+        assert!(parse_command("SPP").is_err());
+        assert!(format_command(&Command::SongPosition { position: 16384 }).is_none());
+    }
+
+    #[test]
+    fn osc_argument_types() {
+        let Command::Osc { address, args } =
+            parse_command("OSC /mix i:3 f:-0.25 s:vocals b:false").unwrap()
+        else {
+            panic!("not an OSC command");
+        };
+        assert_eq!(address, "/mix");
+        assert!(matches!(
+            args[..],
+            [
+                OscArg::Int { value: 3 },
+                OscArg::Float { value: -0.25 },
+                OscArg::String { .. },
+                OscArg::Bool { value: false },
+            ]
+        ));
+        assert!(matches!(&args[2], OscArg::String { value } if value == "vocals"));
+
+        // Strings keep anything after the prefix, including further colons
+        let Command::Osc { args, .. } = parse_command("OSC /a s:a:b").unwrap() else {
+            panic!("not an OSC command");
+        };
+        assert!(matches!(&args[0], OscArg::String { value } if value == "a:b"));
+
+        for text in [
+            "OSC /a 1",
+            "OSC /a x:1",
+            "OSC /a i:1.5",
+            "OSC /a f:x",
+            "OSC /a b:yes",
+            "OSC scene",
+            "OSC",
+        ] {
+            assert!(parse_command(text).is_err(), "{text}");
+        }
+    }
+
+    #[test]
+    fn invalid_commands() {
+        for text in [
+            "",
+            "   ",
+            "PC",
+            "PC 128",
+            "PC x",
+            "CC 80",
+            "CC 80=128",
+            "CC 128=0",
+            "ON 60",
+            "OFF",
+            "OFF 60 1",
+            "START 1",
+            "SONG",
+            "SONG 128",
+            "SYSEX",
+            "SYSEX F0 7",
+            "SYSEX ZZ",
+            "FOO 1",
+        ] {
+            assert!(parse_command(text).is_err(), "{text}");
+        }
+    }
+
+    #[test]
+    fn commands_without_a_compact_form() {
+        let none = [
+            Command::ProgramChange {
+                program: CommandValue::Range { min: 1, max: 5 },
+                bank_msb: None,
+                bank_lsb: None,
+            },
+            Command::ProgramChange {
+                program: 1.into(),
+                bank_msb: None,
+                bank_lsb: Some(0),
+            },
+            Command::ProgramChange {
+                program: 1.into(),
+                bank_msb: Some(128),
+                bank_lsb: None,
+            },
+            Command::ControlChange {
+                controller: 7,
+                value: CommandValue::Choice {
+                    choices: vec![1, 2],
+                },
+            },
+            Command::ControlChange {
+                controller: 7,
+                value: CommandValue::Variable {
+                    variable: "level".to_string(),
+                    offset: 0,
+                },
+            },
+            Command::ControlChange {
+                controller: 200,
+                value: 1.into(),
+            },
+            Command::NoteOn {
+                note: 60,
+                velocity: 200.into(),
+            },
+            Command::NoteOff { note: 128 },
+            Command::SongSelect { song: 128 },
+            Command::DisplayText {
+                protocol: DisplayProtocol::Mcu { offset: 0 },
+                text: "Verse".to_string(),
+            },
+            Command::SysEx { data: vec![] },
+            Command::Osc {
+                address: "scene".to_string(),
+                args: vec![],
+            },
+            Command::Osc {
+                address: "/scene up".to_string(),
+                args: vec![],
+            },
+            Command::Osc {
+                address: "/scene;up".to_string(),
+                args: vec![],
+            },
+            Command::Osc {
+                address: "/a".to_string(),
+                args: vec![OscArg::String {
+                    value: "two words".to_string(),
+                }],
+            },
+            Command::Osc {
+                address: "/a".to_string(),
+                args: vec![OscArg::String {
+                    value: "a;b".to_string(),
+                }],
+            },
+            Command::Osc {
+                address: "/a".to_string(),
+                args: vec![OscArg::Normalized {
+                    value: 0.5,
+                    min: 0.0,
+                    max: 1.0,
+                }],
+            },
+            Command::Osc {
+                address: "/a".to_string(),
+                args: vec![OscArg::Variable {
+                    name: "level".to_string(),
+                    offset: 0.0,
+                    min: None,
+                    max: None,
+                }],
+            },
+        ];
+        for command in none {
+            assert_eq!(format_command(&command), None, "{command:?}");
+        }
+    }
+
+    #[test]
+    fn several_commands() {
+        let commands = parse_commands("PC 1; CC 7=100 ;; START;").unwrap();
+        let formatted: Vec<_> = commands.iter().filter_map(format_command).collect();
+        assert_eq!(formatted, ["PC 1", "CC 7=100", "START"]);
+        assert!(parse_commands("PC 1; CC 7").is_err());
+        assert!(parse_commands("").unwrap().is_empty());
+    }
+}
@@ -1,8 +1,15 @@
 use crate::device::DeviceConfig;
+use crate::diagnostics;
 use crate::mapping::MapConfig;
-use anyhow::{Context, Result};
+use crate::migration;
+use crate::secrets;
+use anyhow::{Context, Result, anyhow};
+use serde::de::{DeserializeOwned, DeserializeSeed, IgnoredAny, IntoDeserializer, MapAccess};
+use std::collections::hash_map::DefaultHasher;
 use std::fs;
+use std::hash::{Hash, Hasher};
 use std::path::Path;
+use tracing::warn;
 
 /// Configuration loader for device and mapping configurations
 pub struct ConfigLoader;
@@ -10,23 +17,218 @@ pub struct ConfigLoader;
 impl ConfigLoader {
     /// Load device configuration from a JSON file
     pub fn load_device_config<P: AsRef<Path>>(path: P) -> Result<DeviceConfig> {
+        let (config, unknown) = Self::read_device_config(path.as_ref())?;
+        Self::warn_unknown(&unknown, path.as_ref());
+        Ok(config)
+    }
+
+    /// Load mapping configuration from a JSON file
+    pub fn load_map_config<P: AsRef<Path>>(path: P) -> Result<MapConfig> {
+        let (config, unknown) = Self::read_map_config(path.as_ref())?;
+        Self::warn_unknown(&unknown, path.as_ref());
+        Ok(config)
+    }
+
+    /// Load device configuration along with the paths of keys that are not part of the schema
+    pub fn read_device_config<P: AsRef<Path>>(path: P) -> Result<(DeviceConfig, Vec<String>)> {
         let content = fs::read_to_string(path.as_ref())
             .with_context(|| format!("Failed to read device config file: {:?}", path.as_ref()))?;
 
-        let config: DeviceConfig =
+        let mut value: serde_json::Value =
             serde_json::from_str(&content).with_context(|| "Failed to parse device config JSON")?;
+        let migrated = migration::migrate_device_config(&mut value)?;
+        // Decrypted documents no longer match the file either
+        let migrated = secrets::decrypt_values(&mut value)? || migrated;
 
-        Ok(config)
+        Self::deserialize("device config", &content, value, migrated)
     }
 
-    /// Load mapping configuration from a JSON file
-    pub fn load_map_config<P: AsRef<Path>>(path: P) -> Result<MapConfig> {
+    /// Load mapping configuration along with the paths of keys that are not part of the schema
+    pub fn read_map_config<P: AsRef<Path>>(path: P) -> Result<(MapConfig, Vec<String>)> {
         let content = fs::read_to_string(path.as_ref())
             .with_context(|| format!("Failed to read map config file: {:?}", path.as_ref()))?;
 
-        let config: MapConfig =
+        let mut value: serde_json::Value =
             serde_json::from_str(&content).with_context(|| "Failed to parse map config JSON")?;
+        let migrated = migration::migrate_map_config(&mut value)?;
+        // Decrypted documents no longer match the file either
+        let migrated = secrets::decrypt_values(&mut value)? || migrated;
 
-        Ok(config)
+        Self::deserialize("map config", &content, value, migrated)
+    }
+
+    /// Whether a config file contains encrypted or keyring secrets
+    pub fn has_secrets<P: AsRef<Path>>(path: P) -> Result<bool> {
+        let content = fs::read_to_string(path.as_ref())
+            .with_context(|| format!("Failed to read config file: {:?}", path.as_ref()))?;
+        let value: serde_json::Value = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse config JSON: {:?}", path.as_ref()))?;
+        Ok(secrets::contains_secrets(&value))
+    }
+
+    /// Write device configuration to a JSON file
+    pub fn save_device_config<P: AsRef<Path>>(path: P, config: &DeviceConfig) -> Result<()> {
+        let content = serde_json::to_string_pretty(config)?;
+        fs::write(path.as_ref(), content)
+            .with_context(|| format!("Failed to write device config file: {:?}", path.as_ref()))
+    }
+
+    /// Write mapping configuration to a JSON file
+    pub fn save_map_config<P: AsRef<Path>>(path: P, config: &MapConfig) -> Result<()> {
+        let content = serde_json::to_string_pretty(config)?;
+        fs::write(path.as_ref(), content)
+            .with_context(|| format!("Failed to write map config file: {:?}", path.as_ref()))
+    }
+
+    /// Deserialize a config document, collecting the path of every key that is not part of the schema.
+    ///
+    /// Unmigrated documents are read from the original text so errors carry line numbers;
+    /// migrated documents no longer match the file, so the error's JSON path is looked up
+    /// in the original text instead.
+    fn deserialize<T: DeserializeOwned>(
+        kind: &str,
+        content: &str,
+        value: serde_json::Value,
+        migrated: bool,
+    ) -> Result<(T, Vec<String>)> {
+        let mut unknown = Vec::new();
+        let mut record = |field: serde_ignored::Path<'_>| unknown.push(field.to_string());
+        let result = if migrated {
+            let deserializer = serde_ignored::Deserializer::new(value, &mut record);
+            serde_path_to_error::deserialize(deserializer)
+        } else {
+            let mut json = serde_json::Deserializer::from_str(content);
+            let deserializer = serde_ignored::Deserializer::new(&mut json, &mut record);
+            serde_path_to_error::deserialize(deserializer)
+        };
+        let config =
+            result.map_err(|e| anyhow!(diagnostics::describe_config_error(kind, &e, content)))?;
+
+        Ok((config, unknown))
+    }
+
+    fn warn_unknown(unknown: &[String], path: &Path) {
+        for field in unknown {
+            warn!("Unknown config key '{}' in {:?} was ignored", field, path);
+        }
+    }
+
+    /// Hash the contents of the given config files to identify the loaded version
+    pub fn content_hash<P: AsRef<Path>>(paths: &[P]) -> Result<String> {
+        let mut hasher = DefaultHasher::new();
+        for path in paths {
+            let content = fs::read(path.as_ref())
+                .with_context(|| format!("Failed to read config file: {:?}", path.as_ref()))?;
+            content.hash(&mut hasher);
+        }
+        Ok(format!("{:016x}", hasher.finish()))
+    }
+}
+
+/// Map access that skips keys outside the known ones as ignored values.
+///
+/// Internally tagged enums and other deserializers that buffer an object consume
+/// every key, so `serde_ignored` never sees the unknown ones. Reading the object
+/// through this first lets `serde_ignored` report them with their full path.
+pub struct KnownKeys<A> {
+    map: A,
+    known: fn(&str) -> bool,
+}
+
+impl<A> KnownKeys<A> {
+    pub fn new(map: A, known: fn(&str) -> bool) -> Self {
+        Self { map, known }
+    }
+}
+
+impl<'de, A: MapAccess<'de>> MapAccess<'de> for KnownKeys<A> {
+    type Error = A::Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, A::Error> {
+        while let Some(key) = self.map.next_key::<String>()? {
+            if (self.known)(&key) {
+                return seed
+                    .deserialize(IntoDeserializer::<A::Error>::into_deserializer(key))
+                    .map(Some);
+            }
+            self.map.next_value::<IgnoredAny>()?;
+        }
+        Ok(None)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, A::Error> {
+        self.map.next_value_seed(seed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unknown keys of a device config, read from the text and from a migrated document
+    fn unknown_device_keys(content: &str) -> Vec<String> {
+        let value: serde_json::Value = serde_json::from_str(content).unwrap();
+        let (_, from_text): (DeviceConfig, _) =
+            ConfigLoader::deserialize("device config", content, value.clone(), false).unwrap();
+        let (_, migrated): (DeviceConfig, _) =
+            ConfigLoader::deserialize("device config", content, value, true).unwrap();
+        assert_eq!(from_text, migrated);
+        from_text
+    }
+
+    fn device_config(commands: &str) -> String {
+        format!(
+            r#"{{"devices": {{"amp": {{"id": "amp", "name": "Amp", "device_type": "midi",
+                "programs": [{{"number": 0, "name": "Clean", "commands": {commands}}}]}}}}}}"#
+        )
+    }
+
+    #[test]
+    fn unknown_command_keys() {
+        let content = device_config(
+            r#"[{"type": "program_change", "program": 1, "bank_mbs": 2}, "CC 7=100"]"#,
+        );
+        assert_eq!(
+            unknown_device_keys(&content),
+            ["devices.amp.programs.0.commands.0.bank_mbs"]
+        );
+    }
+
+    #[test]
+    fn unknown_program_command_options() {
+        let content = device_config(
+            r#"[{"type": "note_on", "note": 60, "velocity": 100, "delay_msec": 50,
+                 "destination": {"type": "rtp_midi", "session_name": "Main", "chanel": 2}}]"#,
+        );
+        assert_eq!(
+            unknown_device_keys(&content),
+            [
+                "devices.amp.programs.0.commands.0.delay_msec",
+                "devices.amp.programs.0.commands.0.destination.chanel",
+            ]
+        );
+    }
+
+    #[test]
+    fn known_program_command_keys() {
+        let content = device_config(
+            r#"[{"type": "program_change", "program": 1, "bank_msb": 2, "delay_ms": 10,
+                 "wait_after_ms": 20, "destination": {"type": "osc", "destination_name": "lights"}}]"#,
+        );
+        assert!(unknown_device_keys(&content).is_empty());
+    }
+
+    #[test]
+    fn unknown_destination_keys() {
+        let content = r#"{"rtp_midi_sessions": [], "osc_destinations": {}, "osc_sources": [],
+            "device_mappings": [{"device_id": "amp", "listen_channel": 1, "send_channel": 1,
+                "destination": {"type": "rtp_midi", "session_name": "Main", "chanel": 2}}]}"#;
+        let value: serde_json::Value = serde_json::from_str(content).unwrap();
+        let (_, unknown): (MapConfig, _) =
+            ConfigLoader::deserialize("map config", content, value, false).unwrap();
+        assert_eq!(unknown, ["device_mappings.0.destination.chanel"]);
     }
 }
@@ -0,0 +1,395 @@
+use crate::cli::RunArgs;
+use crate::config::ConfigLoader;
+use crate::device::{Device, DeviceConfig};
+use crate::mapping::MapConfig;
+use anyhow::Result;
+use clap::Args;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+
+/// Options for comparing an older version of the configs with the current one
+#[derive(Debug, Args)]
+pub struct DiffConfigArgs {
+    /// Device configuration file to compare against `--device-config`
+    #[arg(long)]
+    pub old_device_config: PathBuf,
+    /// Map configuration file to compare against `--map-config`
+    #[arg(long)]
+    pub old_map_config: PathBuf,
+}
+
+/// A single semantic difference between two configs
+#[derive(Debug)]
+pub enum Change {
+    Added(String),
+    Removed(String),
+    Changed(String),
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Added(item) => write!(f, "+ {item}"),
+            Change::Removed(item) => write!(f, "- {item}"),
+            Change::Changed(item) => write!(f, "~ {item}"),
+        }
+    }
+}
+
+/// Print the differences between the old configs and the current ones
+pub fn run(args: &DiffConfigArgs, current: &RunArgs) -> Result<()> {
+    let old_devices = ConfigLoader::load_device_config(&args.old_device_config)?;
+    let old_map = ConfigLoader::load_map_config(&args.old_map_config)?;
+    let new_devices = ConfigLoader::load_device_config(&current.device_config)?;
+    let new_map = ConfigLoader::load_map_config(&current.map_config)?;
+
+    let mut changes = diff_devices(&old_devices, &new_devices);
+    changes.extend(diff_maps(&old_map, &new_map));
+
+    if changes.is_empty() {
+        println!("No differences");
+    } else {
+        for change in &changes {
+            println!("{change}");
+        }
+        println!("{} difference(s)", changes.len());
+    }
+    Ok(())
+}
+
+/// Compare two device configs, including program renumbering
+pub fn diff_devices(old: &DeviceConfig, new: &DeviceConfig) -> Vec<Change> {
+    let mut changes = Vec::new();
+    let old_devices: BTreeMap<_, _> = old.devices.iter().collect();
+    let new_devices: BTreeMap<_, _> = new.devices.iter().collect();
+
+    for (id, device) in &old_devices {
+        if !new_devices.contains_key(id) {
+            changes.push(Change::Removed(format!("device '{id}'")));
+        } else {
+            diff_device(id, device, new_devices[id], &mut changes);
+        }
+    }
+    for id in new_devices.keys() {
+        if !old_devices.contains_key(id) {
+            changes.push(Change::Added(format!("device '{id}'")));
+        }
+    }
+
+    changes
+}
+
+fn diff_device(id: &str, old: &Device, new: &Device, changes: &mut Vec<Change>) {
+    if old.name != new.name {
+        changes.push(Change::Changed(format!(
+            "device '{id}' renamed '{}' -> '{}'",
+            old.name, new.name
+        )));
+    }
+    if !same(&old.device_type, &new.device_type) {
+        changes.push(Change::Changed(format!("device '{id}' type")));
+    }
+    if !same(&old.tempo_spec, &new.tempo_spec) {
+        changes.push(Change::Changed(format!("device '{id}' tempo spec")));
+    }
+
+    // Programs are matched by name so renumbering shows up as such
+    let old_programs: BTreeMap<_, _> = old.programs.iter().map(|p| (&p.name, p)).collect();
+    let new_programs: BTreeMap<_, _> = new.programs.iter().map(|p| (&p.name, p)).collect();
+
+    for (name, program) in &old_programs {
+        match new_programs.get(name) {
+            None => changes.push(Change::Removed(format!(
+                "device '{id}' program {} '{name}'",
+                program.number
+            ))),
+            Some(new_program) => {
+                if program.number != new_program.number {
+                    changes.push(Change::Changed(format!(
+                        "device '{id}' program '{name}' renumbered {} -> {}",
+                        program.number, new_program.number
+                    )));
+                }
+                if (program.bank_msb, program.bank_lsb)
+                    != (new_program.bank_msb, new_program.bank_lsb)
+                {
+                    changes.push(Change::Changed(format!(
+                        "device '{id}' program '{name}' bank"
+                    )));
+                }
+                if !same(&program.commands, &new_program.commands)
+                    || program.transactional != new_program.transactional
+                {
+                    changes.push(Change::Changed(format!(
+                        "device '{id}' program '{name}' commands"
+                    )));
+                }
+                if program.sequence != new_program.sequence {
+                    changes.push(Change::Changed(format!(
+                        "device '{id}' program '{name}' sequence"
+                    )));
+                }
+            }
+        }
+    }
+    for (name, program) in &new_programs {
+        if !old_programs.contains_key(name) {
+            changes.push(Change::Added(format!(
+                "device '{id}' program {} '{name}'",
+                program.number
+            )));
+        }
+    }
+}
+
+/// Compare two map configs section by section
+pub fn diff_maps(old: &MapConfig, new: &MapConfig) -> Vec<Change> {
+    let mut changes = Vec::new();
+
+    diff_named(
+        "RTP MIDI session",
+        old.rtp_midi_sessions.iter().map(|s| (s.name.clone(), s)),
+        new.rtp_midi_sessions.iter().map(|s| (s.name.clone(), s)),
+        &mut changes,
+    );
+    diff_named(
+        "OSC destination",
+        old.osc_destinations.iter().map(|(n, d)| (n.clone(), d)),
+        new.osc_destinations.iter().map(|(n, d)| (n.clone(), d)),
+        &mut changes,
+    );
+    diff_named(
+        "destination group",
+        old.destination_groups.iter().map(|(n, g)| (n.clone(), g)),
+        new.destination_groups.iter().map(|(n, g)| (n.clone(), g)),
+        &mut changes,
+    );
+    diff_named(
+        "OSC source",
+        old.osc_sources.iter().map(|s| (s.name.clone(), s)),
+        new.osc_sources.iter().map(|s| (s.name.clone(), s)),
+        &mut changes,
+    );
+    diff_named(
+        "virtual port",
+        old.virtual_ports.iter().map(|p| (p.name.clone(), p)),
+        new.virtual_ports.iter().map(|p| (p.name.clone(), p)),
+        &mut changes,
+    );
+    diff_named(
+        "device mapping",
+        old.device_mappings.iter().map(|m| {
+            (
+                format!("{} on channel {}", m.device_id, m.listen_channel),
+                m,
+            )
+        }),
+        new.device_mappings.iter().map(|m| {
+            (
+                format!("{} on channel {}", m.device_id, m.listen_channel),
+                m,
+            )
+        }),
+        &mut changes,
+    );
+    diff_named(
+        "note mapping",
+        old.note_mappings.iter().map(|m| (m.name.clone(), m)),
+        new.note_mappings.iter().map(|m| (m.name.clone(), m)),
+        &mut changes,
+    );
+    diff_named(
+        "note program rule",
+        old.note_program_rules.iter().map(|r| (r.name.clone(), r)),
+        new.note_program_rules.iter().map(|r| (r.name.clone(), r)),
+        &mut changes,
+    );
+    diff_named(
+        "note forwarding rule",
+        old.note_forward_rules.iter().map(|r| (r.name.clone(), r)),
+        new.note_forward_rules.iter().map(|r| (r.name.clone(), r)),
+        &mut changes,
+    );
+    diff_named(
+        "control forwarding rule",
+        old.control_forward_rules
+            .iter()
+            .map(|r| (r.name.clone(), r)),
+        new.control_forward_rules
+            .iter()
+            .map(|r| (r.name.clone(), r)),
+        &mut changes,
+    );
+    diff_named(
+        "banked program rule",
+        old.banked_program_rules.iter().map(|r| (r.name.clone(), r)),
+        new.banked_program_rules.iter().map(|r| (r.name.clone(), r)),
+        &mut changes,
+    );
+    diff_named(
+        "combo mapping",
+        old.combo_mappings.iter().map(|m| (m.name.clone(), m)),
+        new.combo_mappings.iter().map(|m| (m.name.clone(), m)),
+        &mut changes,
+    );
+    diff_named(
+        "step mapping",
+        old.step_mappings.iter().map(|m| (m.name.clone(), m)),
+        new.step_mappings.iter().map(|m| (m.name.clone(), m)),
+        &mut changes,
+    );
+    diff_named(
+        "OSC mapping",
+        old.osc_mappings.iter().map(|m| (m.name.clone(), m)),
+        new.osc_mappings.iter().map(|m| (m.name.clone(), m)),
+        &mut changes,
+    );
+    diff_named(
+        "OSC control rule",
+        old.osc_control_rules.iter().map(|r| (r.name.clone(), r)),
+        new.osc_control_rules.iter().map(|r| (r.name.clone(), r)),
+        &mut changes,
+    );
+    diff_named(
+        "OSC forwarding rule",
+        old.osc_forward_rules.iter().map(|r| (r.name.clone(), r)),
+        new.osc_forward_rules.iter().map(|r| (r.name.clone(), r)),
+        &mut changes,
+    );
+    diff_named(
+        "SysEx mapping",
+        old.sysex_mappings.iter().map(|m| (m.name.clone(), m)),
+        new.sysex_mappings.iter().map(|m| (m.name.clone(), m)),
+        &mut changes,
+    );
+    diff_named(
+        "automation",
+        old.automations.iter().map(|a| (a.name.clone(), a)),
+        new.automations.iter().map(|a| (a.name.clone(), a)),
+        &mut changes,
+    );
+    diff_named(
+        "cue list",
+        old.cue_lists.iter().map(|c| (c.name.clone(), c)),
+        new.cue_lists.iter().map(|c| (c.name.clone(), c)),
+        &mut changes,
+    );
+    diff_named(
+        "capture",
+        old.captures.iter().map(|c| (c.variable.clone(), c)),
+        new.captures.iter().map(|c| (c.variable.clone(), c)),
+        &mut changes,
+    );
+    diff_named(
+        "tempo domain",
+        old.tempo_domains.iter().map(|d| (d.name.clone(), d)),
+        new.tempo_domains.iter().map(|d| (d.name.clone(), d)),
+        &mut changes,
+    );
+    diff_named(
+        "tap input",
+        old.tap_inputs.iter().map(|t| (t.name.clone(), t)),
+        new.tap_inputs.iter().map(|t| (t.name.clone(), t)),
+        &mut changes,
+    );
+    diff_named(
+        "clock output",
+        old.clock_outputs
+            .iter()
+            .map(|c| (c.session_name.clone(), c)),
+        new.clock_outputs
+            .iter()
+            .map(|c| (c.session_name.clone(), c)),
+        &mut changes,
+    );
+    diff_named(
+        "inactivity rule",
+        old.inactivity_rules.iter().map(|r| (r.name.clone(), r)),
+        new.inactivity_rules.iter().map(|r| (r.name.clone(), r)),
+        &mut changes,
+    );
+
+    if !same(&old.banks, &new.banks) {
+        changes.push(Change::Changed("banks".to_string()));
+    }
+    if !same(&old.audit_log, &new.audit_log) {
+        changes.push(Change::Changed("audit log".to_string()));
+    }
+    if !same(&old.event_sinks, &new.event_sinks) {
+        changes.push(Change::Changed("event sinks".to_string()));
+    }
+    if !same(&old.subsystems, &new.subsystems) {
+        changes.push(Change::Changed("subsystems".to_string()));
+    }
+    if !same(&old.tempo_displays, &new.tempo_displays) {
+        changes.push(Change::Changed("tempo displays".to_string()));
+    }
+    if !same(&old.match_policies, &new.match_policies) {
+        changes.push(Change::Changed("match policies".to_string()));
+    }
+    if !same(&old.tempo_limits, &new.tempo_limits) {
+        changes.push(Change::Changed("tempo limits".to_string()));
+    }
+    if !same(&old.tempo_stats, &new.tempo_stats) {
+        changes.push(Change::Changed("tempo statistics".to_string()));
+    }
+    if !same(&old.plugins, &new.plugins) {
+        changes.push(Change::Changed("plugins".to_string()));
+    }
+    if !same(&old.link, &new.link) {
+        changes.push(Change::Changed("Ableton Link".to_string()));
+    }
+    if !same(&old.show_lock, &new.show_lock) {
+        changes.push(Change::Changed("show lock".to_string()));
+    }
+    if old.identity_file != new.identity_file {
+        changes.push(Change::Changed("identity file".to_string()));
+    }
+    if !same(&old.loop_suppression, &new.loop_suppression) {
+        changes.push(Change::Changed("loop suppression".to_string()));
+    }
+    if !same(&old.time_source, &new.time_source) {
+        changes.push(Change::Changed("time source".to_string()));
+    }
+    if !same(&old.overload, &new.overload) {
+        changes.push(Change::Changed("overload detection".to_string()));
+    }
+    if old.random_seed != new.random_seed {
+        changes.push(Change::Changed("random seed".to_string()));
+    }
+
+    changes
+}
+
+/// Compare two keyed collections, reporting added, removed and changed entries
+fn diff_named<'a, T: Serialize + 'a>(
+    kind: &str,
+    old: impl Iterator<Item = (String, &'a T)>,
+    new: impl Iterator<Item = (String, &'a T)>,
+    changes: &mut Vec<Change>,
+) {
+    let old: BTreeMap<_, _> = old.collect();
+    let new: BTreeMap<_, _> = new.collect();
+
+    for (name, item) in &old {
+        match new.get(name) {
+            None => changes.push(Change::Removed(format!("{kind} '{name}'"))),
+            Some(new_item) if !same(*item, *new_item) => {
+                changes.push(Change::Changed(format!("{kind} '{name}'")))
+            }
+            Some(_) => {}
+        }
+    }
+    for name in new.keys() {
+        if !old.contains_key(name) {
+            changes.push(Change::Added(format!("{kind} '{name}'")));
+        }
+    }
+}
+
+/// Whether two values serialize to the same JSON
+pub fn same<T: Serialize + ?Sized>(old: &T, new: &T) -> bool {
+    serde_json::to_value(old).ok() == serde_json::to_value(new).ok()
+}
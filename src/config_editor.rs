@@ -0,0 +1,115 @@
+use crate::config::ConfigLoader;
+use crate::device::DeviceConfig;
+use crate::hot_reload;
+use crate::mapping::MapConfig;
+use crate::status::{ReloadResult, RouterStatus};
+use crate::validator::ConfigValidator;
+use anyhow::{Result, bail};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+/// Reads and replaces a routing context's config files for the web UI,
+/// applying saved changes like a hot reload
+pub struct ConfigEditor {
+    device_path: PathBuf,
+    map_path: PathBuf,
+    device_config: Arc<RwLock<DeviceConfig>>,
+    map_config: Arc<RwLock<MapConfig>>,
+    status: Arc<RouterStatus>,
+}
+
+impl ConfigEditor {
+    pub fn new(
+        device_path: &Path,
+        map_path: &Path,
+        device_config: Arc<RwLock<DeviceConfig>>,
+        map_config: Arc<RwLock<MapConfig>>,
+        status: Arc<RouterStatus>,
+    ) -> Self {
+        Self {
+            device_path: device_path.to_path_buf(),
+            map_path: map_path.to_path_buf(),
+            device_config,
+            map_config,
+            status,
+        }
+    }
+
+    /// Read the device config file
+    pub fn device_config(&self) -> Result<DeviceConfig> {
+        Self::check_no_secrets(&self.device_path)?;
+        ConfigLoader::load_device_config(&self.device_path)
+    }
+
+    /// Read the map config file
+    pub fn map_config(&self) -> Result<MapConfig> {
+        Self::check_no_secrets(&self.map_path)?;
+        ConfigLoader::load_map_config(&self.map_path)
+    }
+
+    /// Write the device config file and apply it
+    pub async fn save_device_config(&self, config: DeviceConfig) -> Result<()> {
+        Self::check_no_secrets(&self.device_path)?;
+        ConfigValidator::new(&config, &*self.map_config.read().await).validate()?;
+        let hash = self.content_hash();
+        ConfigLoader::save_device_config(&self.device_path, &config)?;
+
+        *self.device_config.write().await = config;
+        info!("Saved and applied {:?}", self.device_path);
+        self.saved(hash).await;
+        Ok(())
+    }
+
+    /// Write the map config file and apply it
+    pub async fn save_map_config(&self, mut config: MapConfig) -> Result<()> {
+        Self::check_no_secrets(&self.map_path)?;
+        ConfigValidator::new(&*self.device_config.read().await, &config).validate()?;
+        let hash = self.content_hash();
+        ConfigLoader::save_map_config(&self.map_path, &config)?;
+
+        {
+            let mut current = self.map_config.write().await;
+            if hot_reload::needs_restart(&current, &config) {
+                warn!(
+                    "Session, OSC listener or plugin changes in {:?} take effect on the next full reload",
+                    self.map_path
+                );
+                hot_reload::keep_started(&current, &mut config);
+            }
+            *current = config;
+        }
+        info!("Saved and applied {:?}", self.map_path);
+        self.saved(hash).await;
+        Ok(())
+    }
+
+    /// Saving would write decrypted values back in plain text, and reading would serve them
+    fn check_no_secrets(path: &Path) -> Result<()> {
+        if ConfigLoader::has_secrets(path)? {
+            bail!("{path:?} contains secrets; edit it by hand to keep them encrypted");
+        }
+        Ok(())
+    }
+
+    fn content_hash(&self) -> Option<String> {
+        ConfigLoader::content_hash(&[&self.device_path, &self.map_path]).ok()
+    }
+
+    /// Report the save like a reload, keeping the reported hash current
+    async fn saved(&self, previous_hash: Option<String>) {
+        // The reported hash covers these files only if they are the sole context
+        if let Some(hash) = self.content_hash()
+            && previous_hash == Some(self.status.config_hash().await)
+        {
+            self.status.set_config_hash(hash).await;
+        }
+        self.status
+            .set_last_reload(ReloadResult::new(
+                true,
+                "Applied configs saved from the web UI",
+            ))
+            .await;
+    }
+}
@@ -0,0 +1,541 @@
+use crate::audit::AuditLog;
+use crate::automation::AutomationMonitor;
+use crate::clock::ClockMonitor;
+use crate::config::ConfigLoader;
+#[cfg(feature = "http")]
+use crate::config_editor::ConfigEditor;
+use crate::cue::CueMonitor;
+use crate::device::DeviceConfig;
+use crate::hot_reload::ConfigWatcher;
+use crate::inactivity::InactivityMonitor;
+#[cfg(feature = "link")]
+use crate::link::LinkMonitor;
+use crate::mapping::{MapConfig, OscSource};
+#[cfg(feature = "osc-input")]
+use crate::osc_listener::OscListener;
+use crate::osc_queue::OscQueueMonitor;
+use crate::plugins::PluginRegistry;
+use crate::processor::MidiProcessor;
+use crate::router::MidiRouter;
+use crate::sequence::SequenceMonitor;
+use crate::session_manager::SessionManager;
+use crate::settle::SettleMonitor;
+use crate::sinks;
+use crate::status::{OscListenerStatus, ReloadResult, RouterStatus};
+use crate::supervisor::supervise;
+use crate::time_source::TimeSource;
+use crate::timed::TimedProgramMonitor;
+use crate::validator::ConfigValidator;
+use crate::virtual_ports;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::sync::{RwLock, broadcast};
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+
+/// How often the contexts file is checked for changes
+const CONTEXTS_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Name of the context used when no contexts file is given
+pub const DEFAULT_CONTEXT: &str = "default";
+
+/// An isolated routing context listed in the contexts file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContextConfig {
+    /// Unique name of the context
+    pub name: String,
+    /// Path to the context's device configuration
+    pub device_config: PathBuf,
+    /// Path to the context's map configuration
+    pub map_config: PathBuf,
+    /// Whether the context is loaded
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// Contents of the contexts file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextsFile {
+    pub contexts: Vec<ContextConfig>,
+}
+
+/// A running set of sessions, OSC listeners and monitors built from one pair of configs
+pub struct RoutingContext {
+    name: String,
+    status: Arc<RouterStatus>,
+    session_manager: SessionManager,
+    osc_sources: Vec<String>,
+    tasks: Vec<JoinHandle<()>>,
+    plugins: Arc<PluginRegistry>,
+    // Kept alive for as long as the context runs
+    _router: MidiRouter,
+}
+
+impl RoutingContext {
+    /// Load the configs and start routing
+    pub async fn start(
+        name: &str,
+        device_config_path: &Path,
+        map_config_path: &Path,
+        status: Arc<RouterStatus>,
+        hot_reload: bool,
+    ) -> Result<Self> {
+        info!("Starting routing context '{}'", name);
+
+        // Load configurations
+        let device_config = load_device_config(device_config_path)?;
+        let map_config = load_map_config(map_config_path)?;
+        ConfigValidator::new(&device_config, &map_config).validate()?;
+
+        // Wrap in Arc<RwLock> for shared access
+        let device_config = Arc::new(RwLock::new(device_config));
+        let map_config = Arc::new(RwLock::new(map_config));
+
+        // Create MIDI processor
+        let mut processor = MidiProcessor::new(device_config.clone(), map_config.clone())?;
+
+        // Open the audit log if configured
+        if let Some(audit_config) = map_config.read().await.audit_log.clone() {
+            info!("Writing audit log to {}", audit_config.path);
+            processor.set_audit_log(AuditLog::new(audit_config)?);
+        }
+
+        // Stream routed events to monitors
+        processor.set_event_publisher(status.events().publisher(name));
+
+        let mut tasks = Vec::new();
+
+        // Archive routed events in external sinks
+        let event_sinks = sinks::build(&*map_config.read().await)?;
+        tasks.extend(sinks::start(name, event_sinks, &status.events()));
+
+        // Drop outgoing messages while output is frozen
+        processor.set_output_freeze(status.output_freeze());
+
+        // Use a fixed seed for randomized values if configured
+        if let Some(seed) = map_config.read().await.random_seed {
+            info!("Using random seed {}", seed);
+            processor.set_random_seed(seed);
+        }
+
+        // Clock for timestamps sent to peers, watched for NTP steps
+        let time_source = Arc::new(TimeSource::new(map_config.read().await.time_source.clone()));
+        tasks.push(time_source.start_monitor());
+        processor.set_time_source(time_source);
+
+        // Load plugins; messages they inject are routed below
+        let (plugins, injected) = PluginRegistry::load(&map_config.read().await.plugins)?;
+        let plugins = Arc::new(plugins);
+        processor.set_plugins(plugins.clone());
+
+        // Create session manager
+        let session_manager = SessionManager::new(status.output_freeze());
+        tasks.push(session_manager.start_monitor());
+
+        // Drop messages looped back by cross-connected routers
+        if let Some(ref loop_suppression) = map_config.read().await.loop_suppression {
+            session_manager
+                .set_loop_suppression(Duration::from_millis(loop_suppression.window_ms))
+                .await;
+        }
+
+        // Set up processor with session manager
+        processor.set_session_manager(session_manager.clone());
+
+        let processor = Arc::new(processor);
+
+        // Create MIDI router
+        let mut router = MidiRouter::new(processor.clone(), session_manager.clone());
+
+        // Report the sessions first, so ones that fail to bind show up as unbound
+        status
+            .add_session_manager(name, session_manager.clone())
+            .await;
+
+        // Initialize RTP MIDI sessions
+        {
+            let map_config_read = map_config.read().await;
+            router.initialize_sessions(&map_config_read).await?;
+        }
+        status.add_processor(name, processor.clone()).await;
+        #[cfg(feature = "http")]
+        status
+            .add_config_editor(
+                name,
+                ConfigEditor::new(
+                    device_config_path,
+                    map_config_path,
+                    device_config.clone(),
+                    map_config.clone(),
+                    status.clone(),
+                ),
+            )
+            .await;
+
+        // Create virtual MIDI ports for local applications
+        tasks.extend(
+            virtual_ports::start(
+                &map_config.read().await.virtual_ports,
+                processor.clone(),
+                &session_manager,
+            )
+            .await?,
+        );
+
+        // Initialize OSC listeners
+        let osc_sources = {
+            let map_config_read = map_config.read().await;
+            let sources = started_osc_sources(&map_config_read);
+            #[cfg(feature = "osc-input")]
+            if !sources.is_empty() {
+                let osc_listener = OscListener::new(processor.clone(), status.clone());
+                tasks.extend(osc_listener.start_listeners(&sources).await?);
+                info!("Started {} OSC listeners", sources.len());
+            }
+            #[cfg(not(feature = "osc-input"))]
+            if !sources.is_empty() {
+                tracing::warn!("OSC sources not started: built without the `osc-input` feature");
+            }
+            sources.into_iter().map(|source| source.name).collect()
+        };
+
+        // Route messages injected by plugins
+        let plugin_processor = processor.clone();
+        let injected = Arc::new(tokio::sync::Mutex::new(injected));
+        tasks.push(supervise("plugin input", move || {
+            let injected = Arc::clone(&injected);
+            let plugin_processor = plugin_processor.clone();
+            async move {
+                let mut injected = injected.lock().await;
+                while let Some((source, message)) = injected.recv().await {
+                    if let Err(e) = plugin_processor
+                        .process_midi_message(&source, message)
+                        .await
+                    {
+                        error!("Error processing plugin MIDI message: {}", e);
+                    }
+                }
+            }
+        }));
+
+        // Resend tempo to sessions a participant rejoined
+        let rejoin_manager = session_manager.clone();
+        let rejoin_processor = processor.clone();
+        tasks.push(supervise("rejoin tempo resend", move || {
+            let mut rejoins = rejoin_manager.subscribe_rejoins();
+            let rejoin_processor = rejoin_processor.clone();
+            async move {
+                loop {
+                    match rejoins.recv().await {
+                        Ok(session) => {
+                            if let Err(e) = rejoin_processor.resend_tempo(&session).await {
+                                error!("Error resending tempo to session '{}': {}", session, e);
+                            }
+                        }
+                        Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    }
+                }
+            }
+        }));
+
+        // Swap in config changes without restarting
+        if hot_reload {
+            tasks.push(
+                ConfigWatcher::new(
+                    device_config_path,
+                    map_config_path,
+                    device_config.clone(),
+                    map_config.clone(),
+                    status.clone(),
+                )
+                .start()?,
+            );
+        }
+
+        // Start inactivity monitoring
+        tasks.push(InactivityMonitor::new(processor.clone()).start());
+
+        // Run tempo-synced automations
+        tasks.push(AutomationMonitor::new(processor.clone()).start());
+
+        // Run cues once their waits have elapsed
+        tasks.push(CueMonitor::new(processor.clone()).start());
+
+        // Run program sequence steps once their barriers pass
+        tasks.push(SequenceMonitor::new(processor.clone()).start());
+
+        // Send the commands of programs paused between them
+        tasks.push(TimedProgramMonitor::new(processor.clone()).start());
+
+        // Send MIDI clock at the current tempo
+        tasks.extend(
+            ClockMonitor::new(processor.clone()).start(&map_config.read().await.clock_outputs),
+        );
+
+        // Keep a tempo in sync with Ableton Link
+        if let Some(ref link) = map_config.read().await.link {
+            #[cfg(feature = "link")]
+            tasks.push(LinkMonitor::new(processor.clone()).start(link));
+            #[cfg(not(feature = "link"))]
+            tracing::warn!(
+                "Ableton Link for tempo '{}' not started: built without the `link` feature",
+                link.tempo_domain
+            );
+        }
+
+        // Run programs once bursts of program changes settle
+        tasks.push(SettleMonitor::new(processor.clone()).start());
+
+        // Watch OSC destinations with offline queues and replay them when they reply
+        tasks.push(OscQueueMonitor::new(processor.clone()).start());
+
+        let session_count = router.get_session_names().await.len();
+        info!("Routing context '{name}' ready with {session_count} sessions");
+        if status.prints_to_console() {
+            print_port_summary(
+                name,
+                &*map_config.read().await,
+                &status.osc_listeners().await,
+            );
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            status,
+            session_manager,
+            osc_sources,
+            tasks,
+            plugins,
+            _router: router,
+        })
+    }
+
+    /// Stop the context's background tasks and release its sessions
+    pub async fn stop(self) {
+        info!("Stopping routing context '{}'", self.name);
+        for task in &self.tasks {
+            task.abort();
+        }
+        self.plugins.stop();
+        self.status
+            .remove_session_manager(&self.name, &self.osc_sources)
+            .await;
+        self.session_manager.remove_all().await;
+    }
+}
+
+/// Keeps the loaded contexts in line with the contexts file
+pub struct ContextManager {
+    path: PathBuf,
+    status: Arc<RouterStatus>,
+    contexts: HashMap<String, (ContextConfig, RoutingContext)>,
+    modified: Option<SystemTime>,
+    hot_reload: bool,
+}
+
+impl ContextManager {
+    pub fn new(path: &Path, status: Arc<RouterStatus>, hot_reload: bool) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            status,
+            contexts: HashMap::new(),
+            modified: None,
+            hot_reload,
+        }
+    }
+
+    /// Load enabled contexts and unload removed, disabled or changed ones
+    pub async fn sync(&mut self) -> Result<()> {
+        let content = std::fs::read_to_string(&self.path)
+            .with_context(|| format!("Failed to read contexts file: {:?}", self.path))?;
+        let file: ContextsFile = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse contexts file: {:?}", self.path))?;
+
+        let wanted: HashMap<String, ContextConfig> = file
+            .contexts
+            .into_iter()
+            .filter(|context| context.enabled)
+            .map(|context| (context.name.clone(), context))
+            .collect();
+
+        // Unload contexts that are gone or whose settings changed
+        let stale: Vec<String> = self
+            .contexts
+            .iter()
+            .filter(|(name, (config, _))| wanted.get(*name) != Some(config))
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in stale {
+            if let Some((_, context)) = self.contexts.remove(&name) {
+                context.stop().await;
+            }
+        }
+
+        // Load contexts that are not running yet
+        for (name, config) in wanted {
+            if self.contexts.contains_key(&name) {
+                continue;
+            }
+            match RoutingContext::start(
+                &name,
+                &config.device_config,
+                &config.map_config,
+                self.status.clone(),
+                self.hot_reload,
+            )
+            .await
+            {
+                Ok(context) => {
+                    self.contexts.insert(name, (config, context));
+                }
+                Err(e) => error!("Failed to start routing context '{}': {}", name, e),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Restart every context so config changes take effect
+    pub async fn reload_all(&mut self) -> Result<()> {
+        for (_, (_, context)) in self.contexts.drain() {
+            context.stop().await;
+        }
+        self.sync().await
+    }
+
+    /// Re-sync whenever the contexts file changes, and reload on request
+    pub fn watch(mut self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            self.modified = self.modified_time();
+            let mut interval = tokio::time::interval(CONTEXTS_POLL_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = self.status.reload_requested() => {
+                        info!("Reloading all routing contexts");
+                        let result = match self.reload_all().await {
+                            Ok(()) => ReloadResult::new(true, "Reloaded all contexts"),
+                            Err(e) => {
+                                error!("Failed to reload contexts: {}", e);
+                                ReloadResult::new(false, e.to_string())
+                            }
+                        };
+                        self.status.set_last_reload(result).await;
+                        continue;
+                    }
+                }
+
+                let modified = self.modified_time();
+                if modified == self.modified {
+                    continue;
+                }
+                self.modified = modified;
+
+                info!("Contexts file {:?} changed", self.path);
+                if let Err(e) = self.sync().await {
+                    error!("Failed to apply contexts file: {}", e);
+                }
+            }
+        })
+    }
+
+    fn modified_time(&self) -> Option<SystemTime> {
+        std::fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+}
+
+/// OSC sources to listen on: the enabled ones, unless the OSC listener is switched off
+pub fn started_osc_sources(map_config: &MapConfig) -> Vec<OscSource> {
+    if !map_config.subsystems.osc_listener {
+        if !map_config.osc_sources.is_empty() {
+            info!("OSC listener is disabled, not starting OSC sources");
+        }
+        return Vec::new();
+    }
+    map_config
+        .osc_sources
+        .iter()
+        .filter(|source| {
+            if !source.enabled {
+                info!("OSC source '{}' is disabled, not starting it", source.name);
+            }
+            source.enabled
+        })
+        .cloned()
+        .collect()
+}
+
+/// Print the ports a context is listening on to stdout
+fn print_port_summary(name: &str, map_config: &MapConfig, osc_listeners: &[OscListenerStatus]) {
+    println!("Listening ports ({name}):");
+    for session in map_config.rtp_midi_sessions.iter().filter(|s| s.enabled) {
+        if session.send_only && session.port == 0 {
+            println!(
+                "  rtp-midi   auto/udp               session '{}' (send-only)",
+                session.name
+            );
+            continue;
+        }
+        // The validator rejects a control port without room for the data port after it
+        let Some(data_port) = session.port.checked_add(1) else {
+            continue;
+        };
+        println!(
+            "  rtp-midi  {:>5}/udp, {:>5}/udp  session '{}'",
+            session.port, data_port, session.name
+        );
+    }
+    for source in map_config
+        .osc_sources
+        .iter()
+        .filter(|s| s.enabled && map_config.subsystems.osc_listener)
+    {
+        // A source that fell back to another port is shown on the port it got
+        let port = osc_listeners
+            .iter()
+            .find(|listener| listener.name == source.name)
+            .and_then(|listener| listener.bound_port)
+            .unwrap_or(source.port);
+        println!(
+            "  osc       {:>5}/udp              source '{}'",
+            port, source.name
+        );
+    }
+}
+
+fn load_device_config(path: &Path) -> Result<DeviceConfig> {
+    match ConfigLoader::load_device_config(path) {
+        Ok(config) => {
+            info!("Loaded device configuration from {:?}", path);
+            Ok(config)
+        }
+        Err(e) => {
+            error!("Failed to load device config: {e}.");
+            Err(e)
+        }
+    }
+}
+
+fn load_map_config(path: &Path) -> Result<MapConfig> {
+    match ConfigLoader::load_map_config(path) {
+        Ok(config) => {
+            info!("Loaded map configuration from {:?}", path);
+            Ok(config)
+        }
+        Err(e) => {
+            error!("Failed to load map config: {e}");
+            Err(e)
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Tracks when each trigger last fired so it can't fire again within its cooldown
+pub struct Cooldowns {
+    /// Time of the last accepted activation keyed by trigger
+    last_fired: HashMap<String, Instant>,
+}
+
+impl Cooldowns {
+    pub fn new() -> Self {
+        Self {
+            last_fired: HashMap::new(),
+        }
+    }
+
+    /// Record an activation and return whether it is outside the cooldown window
+    pub fn try_fire(&mut self, key: &str, cooldown_ms: Option<u64>) -> bool {
+        let Some(cooldown_ms) = cooldown_ms.filter(|&ms| ms > 0) else {
+            return true;
+        };
+
+        let now = Instant::now();
+        if let Some(last) = self.last_fired.get(key)
+            && now.duration_since(*last) < Duration::from_millis(cooldown_ms)
+        {
+            return false;
+        }
+        self.last_fired.insert(key.to_string(), now);
+        true
+    }
+}
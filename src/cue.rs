@@ -0,0 +1,142 @@
+use crate::mapping::CueList;
+use crate::processor::MidiProcessor;
+use crate::supervisor::supervise;
+#[cfg(any(feature = "http", feature = "osc-input"))]
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+
+/// How often cue lists are checked for cues whose wait has elapsed
+const TICK_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Operator action on a cue list
+#[cfg(any(feature = "http", feature = "osc-input"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CueAction {
+    /// Fire the next cue
+    Go,
+    /// Cancel cues that are still waiting, keeping the position
+    Stop,
+    /// Cancel waiting cues and return to the first cue
+    Reset,
+}
+
+#[cfg(feature = "osc-input")]
+impl CueAction {
+    /// Parse the last segment of an OSC address such as `/cue/go`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "go" => Some(CueAction::Go),
+            "stop" => Some(CueAction::Stop),
+            "reset" => Some(CueAction::Reset),
+            _ => None,
+        }
+    }
+}
+
+/// Position of a cue list
+#[derive(Default)]
+struct CuePosition {
+    /// Index of the cue the next GO fires
+    next: usize,
+    /// Cues that have been fired and are waiting, with when they are due
+    waiting: Vec<(usize, Instant)>,
+}
+
+/// Tracks the position of each cue list and the cues waiting to run
+pub struct CueState {
+    positions: HashMap<String, CuePosition>,
+}
+
+impl CueState {
+    pub fn new() -> Self {
+        Self {
+            positions: HashMap::new(),
+        }
+    }
+
+    /// Fire the next cue of a list, returning its index, or `None` at the end of the list
+    pub fn go(&mut self, list: &CueList, now: Instant) -> Option<usize> {
+        let position = self.positions.entry(list.name.clone()).or_default();
+        schedule(position, list, now)
+    }
+
+    /// Cancel the list's waiting cues
+    #[cfg(any(feature = "http", feature = "osc-input"))]
+    pub fn stop(&mut self, name: &str) {
+        if let Some(position) = self.positions.get_mut(name) {
+            position.waiting.clear();
+        }
+    }
+
+    /// Cancel the list's waiting cues and return it to the first cue
+    #[cfg(any(feature = "http", feature = "osc-input"))]
+    pub fn reset(&mut self, name: &str) {
+        self.positions.remove(name);
+    }
+
+    /// Return the indices of the list's cues whose wait has elapsed, firing the
+    /// cues that follow them
+    pub fn take_due(&mut self, list: &CueList, now: Instant) -> Vec<usize> {
+        let Some(position) = self.positions.get_mut(&list.name) else {
+            return Vec::new();
+        };
+
+        let mut due = Vec::new();
+        while let Some(i) = position.waiting.iter().position(|&(_, at)| at <= now) {
+            let (index, at) = position.waiting.remove(i);
+            let Some(cue) = list.cues.get(index) else {
+                continue;
+            };
+            due.push(index);
+            if cue.follow {
+                // Schedule from the due time so follow chains don't drift
+                schedule(position, list, at);
+            }
+        }
+        due
+    }
+}
+
+/// Queue the list's next cue to run after its wait
+fn schedule(position: &mut CuePosition, list: &CueList, from: Instant) -> Option<usize> {
+    let index = position.next;
+    let cue = list.cues.get(index)?;
+    position
+        .waiting
+        .push((index, from + Duration::from_millis(cue.wait_ms)));
+    position.next += 1;
+    Some(index)
+}
+
+/// Runs cues once their waits have elapsed
+pub struct CueMonitor {
+    processor: Arc<MidiProcessor>,
+}
+
+impl CueMonitor {
+    pub fn new(processor: Arc<MidiProcessor>) -> Self {
+        Self { processor }
+    }
+
+    /// Start running waiting cues in the background
+    pub fn start(&self) -> JoinHandle<()> {
+        let processor = Arc::clone(&self.processor);
+
+        supervise("cues", move || {
+            let processor = Arc::clone(&processor);
+            async move {
+                let mut interval = tokio::time::interval(TICK_INTERVAL);
+                interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+                loop {
+                    interval.tick().await;
+                    processor.run_due_cues().await;
+                }
+            }
+        })
+    }
+}
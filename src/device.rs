@@ -1,5 +1,12 @@
-use serde::{Deserialize, Serialize};
+use crate::command_dsl;
+use crate::config::KnownKeys;
+use crate::mapping::Destination;
+use serde::de::value::{MapAccessDeserializer, StrDeserializer};
+use serde::de::{self, IgnoredAny, MapAccess, Visitor};
+use serde::ser;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::HashMap;
+use std::fmt;
 
 /// Types of devices that can send commands
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -12,19 +19,272 @@ pub enum DeviceType {
     Osc,
 }
 
-/// A command that can be sent by a device
+/// A command that can be sent by a device.
+///
+/// Written either as an object or in the compact syntax of [`crate::command_dsl`],
+/// and serialized in the compact syntax whenever it can express the command.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(tag = "type")]
+#[serde(tag = "type", remote = "Self")]
 pub enum Command {
-    /// MIDI Program Change command
+    /// MIDI Program Change command, preceded by Bank Select (CC0/CC32) when a bank is given
     #[serde(rename = "program_change")]
-    ProgramChange { program: u8 },
+    ProgramChange {
+        program: CommandValue,
+        #[serde(default)]
+        bank_msb: Option<u8>,
+        #[serde(default)]
+        bank_lsb: Option<u8>,
+    },
     /// MIDI Control Change command
     #[serde(rename = "control_change")]
-    ControlChange { controller: u8, value: u8 },
+    ControlChange { controller: u8, value: CommandValue },
+    /// MIDI Note On command
+    #[serde(rename = "note_on")]
+    NoteOn { note: u8, velocity: CommandValue },
+    /// MIDI Note Off command
+    #[serde(rename = "note_off")]
+    NoteOff { note: u8 },
+    /// MIDI Start: play from the beginning of the song
+    #[serde(rename = "start")]
+    Start,
+    /// MIDI Stop
+    #[serde(rename = "stop")]
+    Stop,
+    /// MIDI Continue: play from the current song position
+    #[serde(rename = "continue")]
+    Continue,
+    /// MIDI Song Select (0-127)
+    #[serde(rename = "song_select")]
+    SongSelect { song: u8 },
+    /// MIDI Song Position Pointer, in sixteenth notes from the start of the song (0-16383)
+    #[serde(rename = "song_position")]
+    SongPosition { position: u16 },
+    /// Write text to a controller display via SysEx
+    #[serde(rename = "display_text")]
+    DisplayText {
+        protocol: DisplayProtocol,
+        text: String,
+    },
     /// OSC message command
     #[serde(rename = "osc")]
     Osc { address: String, args: Vec<OscArg> },
+    /// Raw System Exclusive message, F0/F7 framing added when missing
+    #[serde(rename = "sysex")]
+    SysEx {
+        #[serde(with = "crate::sysex::hex")]
+        data: Vec<u8>,
+    },
+}
+
+impl Command {
+    /// Names of the fields of every command type, as they appear in a command object
+    const FIELDS: &[&str] = &[
+        "type",
+        "program",
+        "bank_msb",
+        "bank_lsb",
+        "controller",
+        "value",
+        "note",
+        "velocity",
+        "song",
+        "position",
+        "protocol",
+        "text",
+        "address",
+        "args",
+        "data",
+    ];
+}
+
+impl Serialize for Command {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match command_dsl::format_command(self) {
+            Some(compact) => serializer.serialize_str(&compact),
+            None => Command::serialize(self, serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Command {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct CommandVisitor;
+
+        impl<'de> Visitor<'de> for CommandVisitor {
+            type Value = Command;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a command object or a compact command such as \"PC 12\"")
+            }
+
+            fn visit_str<E: de::Error>(self, text: &str) -> Result<Command, E> {
+                command_dsl::parse_command(text).map_err(|e| E::custom(format!("{e:#}")))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Command, A::Error> {
+                let map = KnownKeys::new(map, |key| Command::FIELDS.contains(&key));
+                Command::deserialize(MapAccessDeserializer::new(map))
+            }
+        }
+
+        deserializer.deserialize_any(CommandVisitor)
+    }
+}
+
+/// A command of a program, sent to the mapping's destination unless it names its own,
+/// optionally with pauses around it.
+///
+/// Written like any other command; the object form takes the extra fields of
+/// [`CommandOptions`].
+#[derive(Debug, Clone)]
+pub struct ProgramCommand {
+    pub command: Command,
+    pub options: CommandOptions,
+}
+
+/// Fields a program command adds to the command
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CommandOptions {
+    /// Destination used instead of the mapping's (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub destination: Option<Destination>,
+    /// Pause before the command is sent, in milliseconds
+    #[serde(skip_serializing_if = "is_zero")]
+    pub delay_ms: u64,
+    /// Pause after the command is sent before the next one, in milliseconds
+    #[serde(skip_serializing_if = "is_zero")]
+    pub wait_after_ms: u64,
+}
+
+fn is_zero(value: &u64) -> bool {
+    *value == 0
+}
+
+impl From<Command> for ProgramCommand {
+    fn from(command: Command) -> Self {
+        ProgramCommand {
+            command,
+            options: CommandOptions::default(),
+        }
+    }
+}
+
+impl Serialize for ProgramCommand {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.options == CommandOptions::default() {
+            return self.command.serialize(serializer);
+        }
+
+        let mut object = Command::serialize(&self.command, serde_json::value::Serializer)
+            .map_err(ser::Error::custom)?;
+        let options = serde_json::to_value(&self.options).map_err(ser::Error::custom)?;
+        if let (serde_json::Value::Object(fields), serde_json::Value::Object(options)) =
+            (&mut object, options)
+        {
+            fields.extend(options);
+        }
+        object.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ProgramCommand {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ProgramCommandVisitor;
+
+        impl<'de> Visitor<'de> for ProgramCommandVisitor {
+            type Value = ProgramCommand;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a command object or a compact command such as \"PC 12\"")
+            }
+
+            fn visit_str<E: de::Error>(self, text: &str) -> Result<ProgramCommand, E> {
+                <Command as Deserialize>::deserialize(StrDeserializer::new(text))
+                    .map(ProgramCommand::from)
+            }
+
+            // The options are read straight from the map, so unknown keys in them are
+            // reported like any other; unknown keys are skipped the same way
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<ProgramCommand, A::Error> {
+                let mut command = serde_json::Map::new();
+                let mut options = CommandOptions::default();
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "destination" => options.destination = Some(map.next_value()?),
+                        "delay_ms" => options.delay_ms = map.next_value()?,
+                        "wait_after_ms" => options.wait_after_ms = map.next_value()?,
+                        name if Command::FIELDS.contains(&name) => {
+                            command.insert(key, map.next_value()?);
+                        }
+                        _ => {
+                            map.next_value::<IgnoredAny>()?;
+                        }
+                    }
+                }
+                Ok(ProgramCommand {
+                    command: Command::deserialize(serde_json::Value::Object(command))
+                        .map_err(de::Error::custom)?,
+                    options,
+                })
+            }
+        }
+
+        deserializer.deserialize_any(ProgramCommandVisitor)
+    }
+}
+
+/// Consecutive commands of a program sent to one destination without a pause
+#[derive(Debug, Clone)]
+pub struct CommandBatch {
+    /// Pause before the batch is sent, in milliseconds
+    pub delay_ms: u64,
+    pub destination: Destination,
+    pub commands: Vec<Command>,
+}
+
+/// Value of a MIDI command: a fixed number, one picked at random each time it is sent,
+/// or a captured variable
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum CommandValue {
+    /// Always the same value
+    Fixed(u8),
+    /// Any value between `min` and `max` (inclusive)
+    Range { min: u8, max: u8 },
+    /// One of the listed values
+    Choice { choices: Vec<u8> },
+    /// A captured variable plus `offset`, clamped to 0-127; the variable keeps the sent value
+    Variable {
+        variable: String,
+        #[serde(default)]
+        offset: i16,
+    },
+}
+
+impl From<u8> for CommandValue {
+    fn from(value: u8) -> Self {
+        CommandValue::Fixed(value)
+    }
+}
+
+/// SysEx protocol used to write text to a controller display
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum DisplayProtocol {
+    /// Mackie Control LCD, starting at a character offset (0-111)
+    #[serde(rename = "mcu")]
+    Mcu {
+        #[serde(default)]
+        offset: u8,
+    },
+    /// Generic SysEx: prefix bytes, then the ASCII text, then F7
+    #[serde(rename = "sysex")]
+    Sysex {
+        #[serde(with = "crate::sysex::hex")]
+        prefix: Vec<u8>,
+        max_length: usize,
+    },
 }
 
 /// Type of argument for raw tempo commands
@@ -59,6 +319,18 @@ pub enum OscArg {
     Bool { value: bool },
     #[serde(rename = "normalized")]
     Normalized { value: f32, min: f32, max: f32 },
+    /// A captured variable plus `offset` as a float, clamped to `min`-`max` when given;
+    /// the variable keeps the sent value
+    #[serde(rename = "variable")]
+    Variable {
+        name: String,
+        #[serde(default)]
+        offset: f32,
+        #[serde(default)]
+        min: Option<f32>,
+        #[serde(default)]
+        max: Option<f32>,
+    },
 }
 
 /// A program definition for a device
@@ -66,10 +338,85 @@ pub enum OscArg {
 pub struct Program {
     /// Program number (0-127 for MIDI)
     pub number: u8,
+    /// Bank Select MSB (CC0) the program is in (any bank if unset)
+    #[serde(default)]
+    pub bank_msb: Option<u8>,
+    /// Bank Select LSB (CC32) the program is in (any bank if unset)
+    #[serde(default)]
+    pub bank_lsb: Option<u8>,
     /// Human-readable name for the program
     pub name: String,
     /// Commands to execute when this program is activated
-    pub commands: Vec<Command>,
+    pub commands: Vec<ProgramCommand>,
+    /// Validate every command before sending any, and send none if one would fail
+    #[serde(default)]
+    pub transactional: bool,
+    /// Programs of other devices run after this one, in order, each once its barrier
+    /// has passed
+    #[serde(default)]
+    pub sequence: Vec<SequenceStep>,
+}
+
+/// A program of another device run as part of a program's sequence
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SequenceStep {
+    /// Device whose program runs
+    pub device_id: String,
+    /// Program number to run
+    pub program: u8,
+    /// Wait after the previous step before this one runs
+    #[serde(default)]
+    pub barrier: Barrier,
+}
+
+/// Wait between two sequence steps: until a confirmation arrives or the timeout
+/// elapses, whichever comes first
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Barrier {
+    /// OSC address or address pattern of the message confirming the previous step,
+    /// e.g. a console's reply to a mute (without one, the full timeout is waited)
+    pub confirm_address: Option<String>,
+    /// Longest wait in milliseconds
+    pub timeout_ms: u64,
+}
+
+impl Program {
+    /// Whether a Program Change to `number` selects this program, given the
+    /// Bank Select MSB and LSB last received on the channel
+    pub fn is_selected_by(&self, number: u8, bank_msb: u8, bank_lsb: u8) -> bool {
+        self.number == number
+            && self.bank_msb.is_none_or(|msb| msb == bank_msb)
+            && self.bank_lsb.is_none_or(|lsb| lsb == bank_lsb)
+    }
+
+    /// Whether the program is tied to a bank
+    pub fn has_bank(&self) -> bool {
+        self.bank_msb.is_some() || self.bank_lsb.is_some()
+    }
+
+    /// The program's commands split into batches at every pause and change of
+    /// destination, `default` for commands without their own
+    pub fn batches(&self, default: &Destination) -> Vec<CommandBatch> {
+        let mut batches: Vec<CommandBatch> = Vec::new();
+        let mut wait_after_ms = 0;
+        for command in &self.commands {
+            let destination = command.options.destination.as_ref().unwrap_or(default);
+            let delay_ms = wait_after_ms + command.options.delay_ms;
+            wait_after_ms = command.options.wait_after_ms;
+            match batches.last_mut() {
+                Some(last) if delay_ms == 0 && last.destination == *destination => {
+                    last.commands.push(command.command.clone())
+                }
+                _ => batches.push(CommandBatch {
+                    delay_ms,
+                    destination: destination.clone(),
+                    commands: vec![command.command.clone()],
+                }),
+            }
+        }
+        batches
+    }
 }
 
 /// Device configuration
@@ -91,17 +438,101 @@ pub struct Device {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum TempoSpec {
-    /// Send tap tempo (4 quarter note taps using specified commands)
+    /// Send tap tempo (taps using specified commands, 4 quarter notes by default)
     #[serde(rename = "tap_tempo")]
-    TapTempo { commands: Vec<Command> },
+    TapTempo {
+        commands: Vec<Command>,
+        /// Taps in each sequence
+        #[serde(default = "default_tap_count")]
+        taps: u32,
+        /// Note value between taps
+        #[serde(default)]
+        subdivision: TapSubdivision,
+        /// Random variation of each interval, up to this many milliseconds either way
+        #[serde(default)]
+        jitter_ms: u64,
+        /// Times the sequence is sent again after the first
+        #[serde(default)]
+        repeats: u32,
+        /// Pause between repeated sequences (one tap interval if unset)
+        #[serde(default)]
+        repeat_gap_ms: Option<u64>,
+        /// Factor the tempo is multiplied by, e.g. 0.5 for half time (optional)
+        #[serde(default)]
+        multiplier: Option<f64>,
+        /// Lowest tempo sent to the device (optional)
+        #[serde(default)]
+        min_bpm: Option<f64>,
+        /// Highest tempo sent to the device (optional)
+        #[serde(default)]
+        max_bpm: Option<f64>,
+    },
     /// Send raw tempo value
     #[serde(rename = "raw_tempo")]
     RawTempo {
         commands: Vec<Command>,
         data_type: TempoDataType,
+        /// Factor the tempo is multiplied by, e.g. 0.5 for half time (optional)
+        #[serde(default)]
+        multiplier: Option<f64>,
+        /// Lowest tempo sent to the device (optional)
+        #[serde(default)]
+        min_bpm: Option<f64>,
+        /// Highest tempo sent to the device (optional)
+        #[serde(default)]
+        max_bpm: Option<f64>,
     },
 }
 
+impl TempoSpec {
+    /// Tempo sent to the device: multiplied by `multiplier` (e.g. 0.5 for half time),
+    /// then clamped to `min_bpm`-`max_bpm`
+    pub fn device_bpm(&self, bpm: f64) -> f64 {
+        let (TempoSpec::TapTempo {
+            multiplier,
+            min_bpm,
+            max_bpm,
+            ..
+        }
+        | TempoSpec::RawTempo {
+            multiplier,
+            min_bpm,
+            max_bpm,
+            ..
+        }) = self;
+        let bpm = bpm * multiplier.unwrap_or(1.0);
+        let bpm = min_bpm.map_or(bpm, |min| bpm.max(min));
+        max_bpm.map_or(bpm, |max| bpm.min(max))
+    }
+}
+
+fn default_tap_count() -> u32 {
+    4
+}
+
+/// Note value between tap tempo taps
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TapSubdivision {
+    #[default]
+    Quarter,
+    Eighth,
+    DottedEighth,
+    DottedQuarter,
+}
+
+impl TapSubdivision {
+    /// Length in quarter notes
+    pub fn quarter_notes(self) -> f64 {
+        match self {
+            TapSubdivision::Quarter => 1.0,
+            TapSubdivision::Eighth => 0.5,
+            TapSubdivision::DottedEighth => 0.75,
+            TapSubdivision::DottedQuarter => 1.5,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum TempoDataType {
@@ -114,6 +545,9 @@ pub enum TempoDataType {
 /// Collection of all device configurations
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DeviceConfig {
+    /// Schema version of the file
+    #[serde(default)]
+    pub version: u64,
     /// Map of device ID to device configuration
     pub devices: HashMap<String, Device>,
 }
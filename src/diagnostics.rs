@@ -0,0 +1,205 @@
+use serde_path_to_error::{Path, Segment};
+
+/// Build a human-readable description of a config deserialization error. Errors from
+/// a document that was changed after parsing (e.g. migrated) carry no position, so the
+/// error's path is looked up in the original `content` instead.
+pub fn describe_config_error(
+    kind: &str,
+    error: &serde_path_to_error::Error<serde_json::Error>,
+    content: &str,
+) -> String {
+    let inner = error.inner();
+    let message = strip_position(inner);
+
+    let mut description = format!("Invalid {kind}");
+    let path = error.path().to_string();
+    if !path.is_empty() && path != "." {
+        description.push_str(&format!(" at `{path}`"));
+    }
+    let position = if inner.line() > 0 {
+        Some((inner.line(), inner.column()))
+    } else {
+        locate(content, error.path())
+    };
+    if let Some((line, column)) = position {
+        description.push_str(&format!(" (line {line}, column {column})"));
+    }
+    description.push_str(&format!(": {message}"));
+
+    if let Some(suggestion) = suggest(&message) {
+        description.push_str(&format!(". Did you mean `{suggestion}`?"));
+    }
+
+    description
+}
+
+/// The error message without serde_json's trailing " at line X column Y"
+fn strip_position(error: &serde_json::Error) -> String {
+    let message = error.to_string();
+    let suffix = format!(" at line {} column {}", error.line(), error.column());
+    message
+        .strip_suffix(&suffix)
+        .map(str::to_string)
+        .unwrap_or(message)
+}
+
+/// Line and column of the value at a path in a JSON document, or of the deepest part of
+/// the path the document has when the rest was added or renamed by a migration
+fn locate(content: &str, path: &Path) -> Option<(usize, usize)> {
+    let bytes = content.as_bytes();
+    let mut pos = skip_whitespace(bytes, 0);
+    if pos == bytes.len() {
+        return None;
+    }
+    for segment in path.iter() {
+        let found = match segment {
+            Segment::Map { key } => find_key(content, pos, key),
+            Segment::Seq { index } => find_index(bytes, pos, *index),
+            // Externally tagged variants are keys; other enums stay on the same value
+            Segment::Enum { variant } => find_key(content, pos, variant).or(Some(pos)),
+            Segment::Unknown => None,
+        };
+        match found {
+            Some(next) => pos = next,
+            None => break,
+        }
+    }
+
+    let before = &content[..pos];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+    Some((line, column))
+}
+
+/// Position of the value of `key` in the object at `pos`
+fn find_key(content: &str, pos: usize, key: &str) -> Option<usize> {
+    let bytes = content.as_bytes();
+    if bytes.get(pos) != Some(&b'{') {
+        return None;
+    }
+    let mut pos = skip_whitespace(bytes, pos + 1);
+    while bytes.get(pos) == Some(&b'"') {
+        let end = string_end(bytes, pos)?;
+        let name: String = serde_json::from_str(&content[pos..end]).ok()?;
+        pos = skip_whitespace(bytes, end);
+        if bytes.get(pos) != Some(&b':') {
+            return None;
+        }
+        pos = skip_whitespace(bytes, pos + 1);
+        if name == key {
+            return Some(pos);
+        }
+        pos = skip_whitespace(bytes, skip_value(bytes, pos)?);
+        if bytes.get(pos) != Some(&b',') {
+            return None;
+        }
+        pos = skip_whitespace(bytes, pos + 1);
+    }
+    None
+}
+
+/// Position of element `index` of the array at `pos`
+fn find_index(bytes: &[u8], pos: usize, index: usize) -> Option<usize> {
+    if bytes.get(pos) != Some(&b'[') {
+        return None;
+    }
+    let mut pos = skip_whitespace(bytes, pos + 1);
+    for _ in 0..index {
+        if bytes.get(pos) == Some(&b']') {
+            return None;
+        }
+        pos = skip_whitespace(bytes, skip_value(bytes, pos)?);
+        if bytes.get(pos) != Some(&b',') {
+            return None;
+        }
+        pos = skip_whitespace(bytes, pos + 1);
+    }
+    (bytes.get(pos) != Some(&b']')).then_some(pos)
+}
+
+/// Position just after the value at `pos`
+fn skip_value(bytes: &[u8], pos: usize) -> Option<usize> {
+    match bytes.get(pos)? {
+        b'"' => string_end(bytes, pos),
+        b'{' | b'[' => {
+            let mut depth = 0;
+            let mut pos = pos;
+            loop {
+                match bytes.get(pos)? {
+                    b'"' => {
+                        pos = string_end(bytes, pos)?;
+                        continue;
+                    }
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Some(pos + 1);
+                        }
+                    }
+                    _ => {}
+                }
+                pos += 1;
+            }
+        }
+        _ => Some(
+            pos + bytes[pos..]
+                .iter()
+                .position(|b| matches!(b, b',' | b'}' | b']') || b.is_ascii_whitespace())
+                .unwrap_or(bytes.len() - pos),
+        ),
+    }
+}
+
+/// Position just after the string starting at `pos`
+fn string_end(bytes: &[u8], pos: usize) -> Option<usize> {
+    let mut pos = pos + 1;
+    loop {
+        match bytes.get(pos)? {
+            b'\\' => pos += 2,
+            b'"' => return Some(pos + 1),
+            _ => pos += 1,
+        }
+    }
+}
+
+fn skip_whitespace(bytes: &[u8], pos: usize) -> usize {
+    pos + bytes[pos.min(bytes.len())..]
+        .iter()
+        .take_while(|b| b.is_ascii_whitespace())
+        .count()
+}
+
+/// Suggest the closest expected name for "unknown variant/field `x`, expected ..." messages
+fn suggest(message: &str) -> Option<String> {
+    if !message.starts_with("unknown variant") && !message.starts_with("unknown field") {
+        return None;
+    }
+
+    // Names appear in backticks: the unknown one first, then the expected ones
+    let mut names = message.split('`').skip(1).step_by(2);
+    let unknown = names.next()?;
+
+    names
+        .map(|candidate| (levenshtein(unknown, candidate), candidate))
+        .filter(|(distance, candidate)| *distance <= (candidate.len() / 3).max(2))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate.to_string())
+}
+
+/// Edit distance between two strings
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
@@ -0,0 +1,101 @@
+use anyhow::Result;
+use clap::Args;
+use mdns_sd::{ServiceDaemon, ServiceEvent};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// DNS-SD service type advertised by RTP MIDI sessions
+const RTP_MIDI_SERVICE: &str = "_apple-midi._udp.local.";
+
+/// Options for listing RTP MIDI services on the network
+#[derive(Debug, Args)]
+pub struct DiscoverArgs {
+    /// Seconds to browse for services
+    #[arg(long, default_value_t = 3)]
+    pub timeout_secs: u64,
+}
+
+/// An RTP MIDI service seen on the network
+#[derive(Debug, Clone, Serialize)]
+pub struct DiscoveredService {
+    /// Advertised session name
+    pub name: String,
+    /// Host name of the advertising machine
+    pub host: String,
+    /// Addresses the host resolved to
+    pub addresses: Vec<String>,
+    /// Control port of the session
+    pub port: u16,
+}
+
+/// Browse for RTP MIDI services for the given time
+pub async fn browse(timeout: Duration) -> Result<Vec<DiscoveredService>> {
+    tokio::task::spawn_blocking(move || browse_blocking(timeout)).await?
+}
+
+fn browse_blocking(timeout: Duration) -> Result<Vec<DiscoveredService>> {
+    let daemon = ServiceDaemon::new()?;
+    let receiver = daemon.browse(RTP_MIDI_SERVICE)?;
+    let deadline = Instant::now() + timeout;
+
+    // Keyed by full service name so re-announcements replace earlier entries
+    let mut services = BTreeMap::new();
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+        let Ok(event) = receiver.recv_timeout(remaining) else {
+            break;
+        };
+        if let ServiceEvent::ServiceResolved(info) = event {
+            debug!("Discovered RTP MIDI service {}", info.get_fullname());
+            let name = info
+                .get_fullname()
+                .trim_end_matches(RTP_MIDI_SERVICE)
+                .trim_end_matches('.')
+                .to_string();
+            let mut addresses: Vec<String> = info
+                .get_addresses()
+                .iter()
+                .map(|addr| addr.to_string())
+                .collect();
+            addresses.sort();
+            services.insert(
+                info.get_fullname().to_string(),
+                DiscoveredService {
+                    name,
+                    host: info.get_hostname().trim_end_matches('.').to_string(),
+                    addresses,
+                    port: info.get_port(),
+                },
+            );
+        }
+    }
+
+    let _ = daemon.shutdown();
+    Ok(services.into_values().collect())
+}
+
+/// Print the RTP MIDI services visible on the network
+pub async fn run(args: &DiscoverArgs) -> Result<()> {
+    let services = browse(Duration::from_secs(args.timeout_secs)).await?;
+    if services.is_empty() {
+        println!("No RTP MIDI services found");
+        return Ok(());
+    }
+
+    for service in &services {
+        println!(
+            "{}  {}:{}  ({})",
+            service.name,
+            service.host,
+            service.port,
+            service.addresses.join(", ")
+        );
+        let host = service.addresses.first().unwrap_or(&service.host);
+        println!(
+            "  {{ \"name\": \"{}\", \"host\": \"{}\", \"port\": {} }}",
+            service.name, host, service.port
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use crate::device::Command;
+use crate::mapping::Destination;
+use serde::Serialize;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+
+/// Events buffered per subscriber before a slow one starts missing events
+const CAPACITY: usize = 1024;
+
+/// Something the router processed, as streamed to monitors
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RoutedEvent {
+    /// An incoming MIDI or OSC message, or a control request
+    Input {
+        timestamp_ms: u64,
+        context: String,
+        source: String,
+        message: String,
+    },
+    /// A mapping fired and the commands it sent
+    Mapping {
+        timestamp_ms: u64,
+        context: String,
+        mapping: String,
+        trigger: String,
+        destination: Destination,
+        commands: Vec<Command>,
+    },
+}
+
+impl RoutedEvent {
+    /// Name of the routing context the event happened in
+    pub fn context(&self) -> &str {
+        match self {
+            RoutedEvent::Input { context, .. } | RoutedEvent::Mapping { context, .. } => context,
+        }
+    }
+
+    /// The event's `type`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RoutedEvent::Input { .. } => "input",
+            RoutedEvent::Mapping { .. } => "mapping",
+        }
+    }
+}
+
+/// Broadcasts routed events from every routing context to any number of monitors
+pub struct EventStream {
+    sender: broadcast::Sender<RoutedEvent>,
+}
+
+impl EventStream {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CAPACITY);
+        Self { sender }
+    }
+
+    /// Receive events published from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<RoutedEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Handle for a routing context to publish its events
+    pub fn publisher(&self, context: &str) -> EventPublisher {
+        EventPublisher {
+            sender: self.sender.clone(),
+            context: context.to_string(),
+        }
+    }
+}
+
+/// Publishes one routing context's events; does nothing while no monitor is subscribed
+pub struct EventPublisher {
+    sender: broadcast::Sender<RoutedEvent>,
+    context: String,
+}
+
+impl EventPublisher {
+    pub fn input(&self, source: &str, message: &str) {
+        if self.sender.receiver_count() == 0 {
+            return;
+        }
+        let _ = self.sender.send(RoutedEvent::Input {
+            timestamp_ms: timestamp_ms(),
+            context: self.context.clone(),
+            source: source.to_string(),
+            message: message.to_string(),
+        });
+    }
+
+    pub fn mapping(
+        &self,
+        mapping: &str,
+        trigger: &str,
+        commands: &[Command],
+        destination: &Destination,
+    ) {
+        if self.sender.receiver_count() == 0 {
+            return;
+        }
+        let _ = self.sender.send(RoutedEvent::Mapping {
+            timestamp_ms: timestamp_ms(),
+            context: self.context.clone(),
+            mapping: mapping.to_string(),
+            trigger: trigger.to_string(),
+            destination: destination.clone(),
+            commands: commands.to_vec(),
+        });
+    }
+}
+
+fn timestamp_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
@@ -0,0 +1,69 @@
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::sync::{Arc, PoisonError, RwLock};
+use tracing::warn;
+
+/// Runtime switch that drops outgoing traffic, for every output or per destination,
+/// while the router keeps logging what it would have sent
+#[derive(Clone, Default)]
+pub struct OutputFreeze {
+    state: Arc<RwLock<FreezeState>>,
+}
+
+/// Which outputs are frozen
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FreezeState {
+    /// Whether every output is frozen
+    pub all: bool,
+    /// Sessions, virtual ports and OSC destinations frozen on their own
+    pub destinations: BTreeSet<String>,
+}
+
+impl OutputFreeze {
+    /// Freeze or thaw one destination, or every output if `destination` is `None`.
+    /// Thawing every output also thaws the destinations frozen on their own.
+    pub fn set(&self, destination: Option<&str>, frozen: bool) {
+        let mut state = self.state.write().unwrap_or_else(PoisonError::into_inner);
+        match destination {
+            None => {
+                state.all = frozen;
+                if !frozen {
+                    state.destinations.clear();
+                }
+                warn!("Output {}", if frozen { "frozen" } else { "thawed" });
+            }
+            Some(destination) => {
+                if frozen {
+                    state.destinations.insert(destination.to_string());
+                } else {
+                    state.destinations.remove(destination);
+                }
+                warn!(
+                    "Output to '{}' {}",
+                    destination,
+                    if frozen { "frozen" } else { "thawed" }
+                );
+            }
+        }
+    }
+
+    /// Freeze every output if it isn't frozen, or thaw everything if it is
+    #[cfg_attr(not(unix), allow(dead_code))]
+    pub fn toggle(&self) {
+        let frozen = self.state().all;
+        self.set(None, !frozen);
+    }
+
+    /// Whether traffic to a destination is currently dropped
+    pub fn is_frozen(&self, destination: &str) -> bool {
+        let state = self.state.read().unwrap_or_else(PoisonError::into_inner);
+        state.all || state.destinations.contains(destination)
+    }
+
+    pub fn state(&self) -> FreezeState {
+        self.state
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
@@ -0,0 +1,504 @@
+use crate::cli::RunArgs;
+use crate::config::ConfigLoader;
+use crate::device::DeviceConfig;
+use crate::mapping::{Destination, MAIN_TEMPO_DOMAIN, MapConfig, ProgramSource, Trigger};
+use crate::tempo;
+use anyhow::Result;
+use clap::{Args, ValueEnum};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// Output format for the routing graph
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum GraphFormat {
+    /// Graphviz DOT
+    Dot,
+    /// Mermaid flowchart
+    Mermaid,
+}
+
+/// Options for rendering the routing graph
+#[derive(Debug, Args)]
+pub struct GraphArgs {
+    /// Output format
+    #[arg(long, value_enum, default_value = "dot")]
+    pub format: GraphFormat,
+}
+
+/// Kind of node, used for shapes and grouping
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum NodeKind {
+    Source,
+    Mapping,
+    Destination,
+}
+
+/// Sources, mappings and destinations with the routes between them
+struct Graph {
+    /// Node labels and kinds in insertion order
+    nodes: Vec<(String, NodeKind)>,
+    /// Node index by key
+    index: HashMap<String, usize>,
+    /// Edges between node indices, with optional labels
+    edges: Vec<(usize, usize, Option<String>)>,
+}
+
+impl Graph {
+    fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            index: HashMap::new(),
+            edges: Vec::new(),
+        }
+    }
+
+    /// Get or add the node for a key
+    fn node(&mut self, key: &str, label: &str, kind: NodeKind) -> usize {
+        if let Some(&index) = self.index.get(key) {
+            return index;
+        }
+        self.nodes.push((label.to_string(), kind));
+        self.index.insert(key.to_string(), self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+
+    fn edge(&mut self, from: usize, to: usize, label: Option<String>) {
+        if !self
+            .edges
+            .iter()
+            .any(|(f, t, l)| *f == from && *t == to && *l == label)
+        {
+            self.edges.push((from, to, label));
+        }
+    }
+}
+
+/// Print the routing graph of the current configs
+pub fn run(args: &GraphArgs, current: &RunArgs) -> Result<()> {
+    let device_config = ConfigLoader::load_device_config(&current.device_config)?;
+    let map_config = ConfigLoader::load_map_config(&current.map_config)?;
+
+    let graph = build(&device_config, &map_config);
+    let output = match args.format {
+        GraphFormat::Dot => render_dot(&graph),
+        GraphFormat::Mermaid => render_mermaid(&graph),
+    };
+    print!("{output}");
+    Ok(())
+}
+
+/// Build the sources -> mappings -> destinations graph
+fn build(device_config: &DeviceConfig, map_config: &MapConfig) -> Graph {
+    let mut graph = Graph::new();
+
+    // Session names by alias so destinations point at the real session
+    let mut session_names = HashMap::new();
+    for session in &map_config.rtp_midi_sessions {
+        for alias in &session.aliases {
+            session_names.insert(alias.clone(), session.name.clone());
+        }
+    }
+
+    // All MIDI input from listening sessions and virtual ports reaches every mapping
+    let mut inputs: Vec<usize> = map_config
+        .rtp_midi_sessions
+        .iter()
+        .filter(|session| session.listen)
+        .map(|session| {
+            graph.node(
+                &format!("in:{}", session.name),
+                &format!("RTP MIDI in: {}", session.name),
+                NodeKind::Source,
+            )
+        })
+        .collect();
+    inputs.extend(
+        map_config
+            .virtual_ports
+            .iter()
+            .filter(|port| port.listen)
+            .map(|port| {
+                graph.node(
+                    &format!("in:{}", port.name),
+                    &format!("Virtual in: {} In", port.name),
+                    NodeKind::Source,
+                )
+            }),
+    );
+
+    let endpoint = |graph: &mut Graph, destination: &Destination| match destination {
+        Destination::RtpMidi { session_name } => {
+            let name = session_names.get(session_name).unwrap_or(session_name);
+            graph.node(
+                &format!("out:{name}"),
+                &format!("RTP MIDI out: {name}"),
+                NodeKind::Destination,
+            )
+        }
+        Destination::Osc { destination_name } => graph.node(
+            &format!("osc:{destination_name}"),
+            &format!("OSC out: {destination_name}"),
+            NodeKind::Destination,
+        ),
+        Destination::Group { group_name } => graph.node(
+            &format!("group:{group_name}"),
+            &format!("Group: {group_name}"),
+            NodeKind::Mapping,
+        ),
+    };
+
+    // Groups lead on to each of their members
+    let destination = |graph: &mut Graph, destination: &Destination| {
+        let node = endpoint(graph, destination);
+        if let Destination::Group { group_name } = destination {
+            for member in map_config
+                .destination_groups
+                .get(group_name)
+                .into_iter()
+                .flatten()
+            {
+                if !matches!(member, Destination::Group { .. }) {
+                    let output = endpoint(graph, member);
+                    graph.edge(node, output, None);
+                }
+            }
+        }
+        node
+    };
+
+    let route = |graph: &mut Graph,
+                 key: String,
+                 label: String,
+                 label_in: Option<String>,
+                 to: &Destination| {
+        let mapping = graph.node(&key, &label, NodeKind::Mapping);
+        for &input in &inputs {
+            graph.edge(input, mapping, label_in.clone());
+        }
+        let output = destination(graph, to);
+        graph.edge(mapping, output, None);
+    };
+
+    for mapping in &map_config.device_mappings {
+        let device = device_config.get_device(&mapping.device_id);
+        let name = device
+            .map(|device| device.name.as_str())
+            .unwrap_or(&mapping.device_id);
+        let key = format!("device:{}:{}", mapping.device_id, mapping.listen_channel);
+        let label = format!("Device: {name}");
+        route(
+            &mut graph,
+            key.clone(),
+            label.clone(),
+            Some(format!("PC ch {}", mapping.listen_channel)),
+            &mapping.destination,
+        );
+
+        // Program commands sent elsewhere than the mapping's destination
+        for program in device.into_iter().flat_map(|device| &device.programs) {
+            for command in &program.commands {
+                if let Some(ref to) = command.options.destination {
+                    let node = graph.node(&key, &label, NodeKind::Mapping);
+                    let output = destination(&mut graph, to);
+                    graph.edge(node, output, Some(program.name.clone()));
+                }
+            }
+        }
+    }
+    for mapping in &map_config.note_mappings {
+        route(
+            &mut graph,
+            format!("note:{}", mapping.name),
+            format!("Note: {}", mapping.name),
+            Some(format!(
+                "note {} ch {}{}",
+                mapping.note,
+                mapping.listen_channel,
+                if mapping.on_release { " off" } else { "" }
+            )),
+            &mapping.destination,
+        );
+    }
+    for rule in &map_config.note_program_rules {
+        route(
+            &mut graph,
+            format!("note_program:{}", rule.name),
+            format!("Notes to PC: {}", rule.name),
+            Some(format!(
+                "notes {}-{} ch {}",
+                rule.first_note, rule.last_note, rule.listen_channel
+            )),
+            &rule.destination,
+        );
+    }
+    for rule in &map_config.note_forward_rules {
+        route(
+            &mut graph,
+            format!("note_forward:{}", rule.name),
+            format!("Note forward: {}", rule.name),
+            Some(format!(
+                "notes {}-{} ch {}",
+                rule.first_note, rule.last_note, rule.listen_channel
+            )),
+            &rule.destination,
+        );
+    }
+    for rule in &map_config.control_forward_rules {
+        route(
+            &mut graph,
+            format!("control_forward:{}", rule.name),
+            format!("CC forward: {}", rule.name),
+            Some(format!("cc {} ch {}", rule.controller, rule.listen_channel)),
+            &rule.destination,
+        );
+    }
+    for rule in &map_config.osc_forward_rules {
+        let messages = if rule.messages.is_empty() {
+            "all".to_string()
+        } else {
+            let kinds: Vec<_> = rule.messages.iter().map(|kind| kind.name()).collect();
+            kinds.join(", ")
+        };
+        route(
+            &mut graph,
+            format!("osc_forward:{}", rule.name),
+            format!("MIDI to OSC: {}", rule.name),
+            Some(match rule.listen_channel {
+                Some(channel) => format!("{messages} ch {channel}"),
+                None => messages,
+            }),
+            &Destination::Osc {
+                destination_name: rule.destination_name.clone(),
+            },
+        );
+    }
+    for rule in &map_config.banked_program_rules {
+        route(
+            &mut graph,
+            format!("banked_program:{}", rule.name),
+            format!("Banked PC: {}", rule.name),
+            Some(format!(
+                "CC {} + {} ch {}",
+                rule.bank_controller,
+                match rule.program_source {
+                    ProgramSource::ProgramChange => "PC".to_string(),
+                    ProgramSource::ControlChange { controller } => format!("CC {controller}"),
+                },
+                rule.listen_channel
+            )),
+            &rule.destination,
+        );
+    }
+    for mapping in &map_config.sysex_mappings {
+        route(
+            &mut graph,
+            format!("sysex:{}", mapping.name),
+            format!("SysEx: {}", mapping.name),
+            Some(mapping.pattern.to_string()),
+            &mapping.destination,
+        );
+    }
+    for combo in &map_config.combo_mappings {
+        route(
+            &mut graph,
+            format!("combo:{}", combo.name),
+            format!("Combo: {}", combo.name),
+            Some(describe_triggers(&combo.triggers)),
+            &combo.destination,
+        );
+    }
+    for step in &map_config.step_mappings {
+        route(
+            &mut graph,
+            format!("step:{}", step.name),
+            format!("Steps: {}", step.name),
+            Some(describe_triggers(std::slice::from_ref(&step.trigger))),
+            &step.destination,
+        );
+    }
+    for automation in &map_config.automations {
+        route(
+            &mut graph,
+            format!("automation:{}", automation.name),
+            format!("Automation: {}", automation.name),
+            Some(describe_triggers(std::slice::from_ref(
+                &automation.start_trigger,
+            ))),
+            &automation.destination,
+        );
+    }
+    for cue_list in &map_config.cue_lists {
+        route(
+            &mut graph,
+            format!("cue:{}", cue_list.name),
+            format!("Cue list: {}", cue_list.name),
+            cue_list
+                .go_trigger
+                .as_ref()
+                .map(|trigger| describe_triggers(std::slice::from_ref(trigger))),
+            &cue_list.destination,
+        );
+    }
+    for rule in &map_config.inactivity_rules {
+        let mapping = graph.node(
+            &format!("inactivity:{}", rule.name),
+            &format!("Inactivity: {}", rule.name),
+            NodeKind::Mapping,
+        );
+        let output = destination(&mut graph, &rule.destination);
+        graph.edge(
+            mapping,
+            output,
+            Some(format!("after {}s", rule.timeout_secs)),
+        );
+    }
+
+    let osc_inputs: Vec<usize> = map_config
+        .osc_sources
+        .iter()
+        .map(|source| {
+            graph.node(
+                &format!("osc_in:{}", source.name),
+                &format!("OSC in: {} ({})", source.name, source.port),
+                NodeKind::Source,
+            )
+        })
+        .collect();
+    for mapping in &map_config.osc_mappings {
+        let node = graph.node(
+            &format!("osc_mapping:{}", mapping.name),
+            &format!("OSC: {}", mapping.name),
+            NodeKind::Mapping,
+        );
+        for &input in &osc_inputs {
+            graph.edge(input, node, Some(mapping.address.clone()));
+        }
+        let output = destination(&mut graph, &mapping.destination);
+        graph.edge(node, output, None);
+    }
+
+    for rule in &map_config.osc_control_rules {
+        let node = graph.node(
+            &format!("osc_control:{}", rule.name),
+            &format!("OSC to CC: {}", rule.name),
+            NodeKind::Mapping,
+        );
+        for &input in &osc_inputs {
+            graph.edge(input, node, Some(rule.address.clone()));
+        }
+        let output = destination(
+            &mut graph,
+            &Destination::RtpMidi {
+                session_name: rule.session_name.clone(),
+            },
+        );
+        graph.edge(node, output, None);
+    }
+
+    // Tempo from OSC sources fans out to every device with a tempo spec in its domain
+    if !osc_inputs.is_empty() {
+        let domains = std::iter::once(MAIN_TEMPO_DOMAIN)
+            .chain(map_config.tempo_domains.iter().map(|d| d.name.as_str()));
+        for domain in domains {
+            let tempo = if domain == MAIN_TEMPO_DOMAIN {
+                graph.node("tempo", "Tempo", NodeKind::Mapping)
+            } else {
+                graph.node(
+                    &format!("tempo:{domain}"),
+                    &format!("Tempo: {domain}"),
+                    NodeKind::Mapping,
+                )
+            };
+            for (source, &input) in map_config.osc_sources.iter().zip(&osc_inputs) {
+                let label = if tempo::source_domain(map_config, &source.name) == domain {
+                    format!("/tempo/raw, /tempo/{domain}")
+                } else {
+                    format!("/tempo/{domain}")
+                };
+                graph.edge(input, tempo, Some(label));
+            }
+            for mapping in &map_config.device_mappings {
+                let has_tempo = device_config
+                    .get_device(&mapping.device_id)
+                    .is_some_and(|device| device.tempo_spec.is_some());
+                if has_tempo && tempo::device_domain(map_config, &mapping.device_id) == domain {
+                    let output = destination(&mut graph, &mapping.destination);
+                    graph.edge(tempo, output, Some(mapping.device_id.clone()));
+                }
+            }
+        }
+    }
+
+    graph
+}
+
+/// Short description of the triggers of a mapping
+fn describe_triggers(triggers: &[Trigger]) -> String {
+    triggers
+        .iter()
+        .map(|trigger| match trigger {
+            Trigger::Note { channel, note } => format!("note {note} ch {channel}"),
+            Trigger::ControlChange {
+                channel,
+                controller,
+                ..
+            } => format!("CC {controller} ch {channel}"),
+        })
+        .collect::<Vec<_>>()
+        .join(" + ")
+}
+
+fn render_dot(graph: &Graph) -> String {
+    let mut out = String::from("digraph midi_router {\n    rankdir=LR;\n");
+    for (index, (label, kind)) in graph.nodes.iter().enumerate() {
+        let shape = match kind {
+            NodeKind::Source => "invhouse",
+            NodeKind::Mapping => "box",
+            NodeKind::Destination => "house",
+        };
+        let _ = writeln!(
+            out,
+            "    n{index} [label=\"{}\", shape={shape}];",
+            label.replace('"', "\\\"")
+        );
+    }
+    for (from, to, label) in &graph.edges {
+        match label {
+            Some(label) => {
+                let _ = writeln!(
+                    out,
+                    "    n{from} -> n{to} [label=\"{}\"];",
+                    label.replace('"', "\\\"")
+                );
+            }
+            None => {
+                let _ = writeln!(out, "    n{from} -> n{to};");
+            }
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn render_mermaid(graph: &Graph) -> String {
+    let mut out = String::from("flowchart LR\n");
+    for (index, (label, kind)) in graph.nodes.iter().enumerate() {
+        let label = label.replace('"', "#quot;");
+        let _ = match kind {
+            NodeKind::Source => writeln!(out, "    n{index}[/\"{label}\"/]"),
+            NodeKind::Mapping => writeln!(out, "    n{index}[\"{label}\"]"),
+            NodeKind::Destination => writeln!(out, "    n{index}[\\\"{label}\"\\]"),
+        };
+    }
+    for (from, to, label) in &graph.edges {
+        let _ = match label {
+            Some(label) => writeln!(
+                out,
+                "    n{from} -->|\"{}\"| n{to}",
+                label.replace('"', "#quot;")
+            ),
+            None => writeln!(out, "    n{from} --> n{to}"),
+        };
+    }
+    out
+}
@@ -0,0 +1,609 @@
+use crate::config_editor::ConfigEditor;
+use crate::cue::CueAction;
+use crate::device::DeviceConfig;
+#[cfg(feature = "mdns")]
+use crate::discovery::{self, DiscoveredService};
+use crate::events::RoutedEvent;
+use crate::freeze::FreezeState;
+use crate::mapping::{MAIN_TEMPO_DOMAIN, MapConfig};
+use crate::session_manager::SessionStatus;
+use crate::status::{
+    ContextState, ContextTempoStats, ControlOutcome, DeviceSummary, OscListenerStatus,
+    ReloadResult, RouterStatus,
+};
+use anyhow::{Context, Result, bail};
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{ConnectInfo, Path, Request, State};
+use axum::http::{StatusCode, header};
+use axum::middleware::{self, Next};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::{get, post, put};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::Arc;
+#[cfg(feature = "mdns")]
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::task;
+use tracing::{error, info, warn};
+
+/// Single-page config editor served at `/`
+const WEB_UI: &str = include_str!("web_ui.html");
+
+/// Detailed readiness report
+#[derive(Debug, Serialize)]
+struct Readiness {
+    /// Whether every subsystem is ready
+    ready: bool,
+    /// Hash of the loaded config files
+    config_hash: String,
+    /// Whether control requests are rejected
+    read_only: bool,
+    /// Whether the show lock is engaged
+    show_locked: bool,
+    /// Outputs whose messages are dropped
+    output_freeze: FreezeState,
+    /// Outcome of the most recent config reload
+    last_reload: Option<ReloadResult>,
+    /// RTP MIDI session states
+    rtp_sessions: Vec<SessionStatus>,
+    /// OSC listener states
+    osc_listeners: Vec<OscListenerStatus>,
+}
+
+/// HTTP server exposing health endpoints
+pub struct HealthServer {
+    port: u16,
+    status: Arc<RouterStatus>,
+    /// Token the config editor requires, if set
+    token: Option<String>,
+}
+
+impl HealthServer {
+    pub fn new(port: u16, status: Arc<RouterStatus>, token: Option<String>) -> Self {
+        Self {
+            port,
+            status,
+            token,
+        }
+    }
+
+    /// Bind the health endpoints and serve them in the background
+    pub async fn start(&self) -> Result<()> {
+        let listener = TcpListener::bind(("0.0.0.0", self.port))
+            .await
+            .with_context(|| format!("Failed to bind health endpoint on port {}", self.port))?;
+        info!("Serving health endpoints on port {}", self.port);
+
+        let writable = middleware::from_fn_with_state(Arc::clone(&self.status), writable);
+
+        // Config files, for the web UI
+        let editor = Router::new()
+            .route("/config/{context}/devices", get(device_config))
+            .route("/config/{context}/map", get(map_config))
+            .merge(
+                Router::new()
+                    .route("/config/{context}/devices", put(save_device_config))
+                    .route("/config/{context}/map", put(save_map_config))
+                    .route_layer(writable.clone()),
+            )
+            .route_layer(middleware::from_fn_with_state(
+                self.token.clone().map(Arc::<str>::from),
+                editor_access,
+            ));
+
+        // Control requests, refused in read-only mode
+        let control = Router::new()
+            .route("/devices/{device}/program", post(program))
+            .route("/tempo", post(tempo))
+            .route("/cues/{cue_list}/{action}", post(cue))
+            .route("/sessions/{session}/connect", post(connect))
+            .route("/sessions/{session}/sniff", post(sniff))
+            .route("/sessions/{session}/rename", post(rename_session))
+            .route("/sessions/{session}/alias", post(add_session_alias))
+            .route("/lock", post(lock))
+            .route_layer(writable);
+
+        let app = Router::new()
+            .route("/", get(|| async { Html(WEB_UI) }))
+            .route("/healthz", get(|| async { "ok" }))
+            .route("/readyz", get(readyz))
+            .route("/state", get(state))
+            .route("/sessions", get(sessions))
+            .route("/devices", get(devices))
+            .route("/tempo/stats", get(tempo_stats))
+            .route("/events", get(events))
+            // The kill switch works for monitors in read-only mode too
+            .route("/freeze", post(freeze))
+            .merge(editor)
+            .merge(control);
+        #[cfg(feature = "mdns")]
+        let app = app.route("/discovery", get(discover));
+        let app = app.with_state(Arc::clone(&self.status));
+
+        task::spawn(async move {
+            let app = app.into_make_service_with_connect_info::<SocketAddr>();
+            if let Err(e) = axum::serve(listener, app).await {
+                error!("Health endpoint stopped: {}", e);
+            }
+        });
+
+        Ok(())
+    }
+}
+
+/// Refuse control requests with 403 in read-only mode
+async fn writable(
+    State(status): State<Arc<RouterStatus>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if status.is_read_only() {
+        return (StatusCode::FORBIDDEN, "Router is in read-only mode").into_response();
+    }
+    next.run(request).await
+}
+
+/// Let only holders of the token use the config editor, or if no token is set, only
+/// clients on this machine, as saved configs are applied immediately
+async fn editor_access(
+    State(token): State<Option<Arc<str>>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match token {
+        Some(token) => {
+            let given = request
+                .headers()
+                .get(header::AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("Bearer "));
+            if given != Some(&*token) {
+                return (StatusCode::UNAUTHORIZED, "Wrong or missing health token").into_response();
+            }
+        }
+        None if !peer.ip().to_canonical().is_loopback() => {
+            return (
+                StatusCode::FORBIDDEN,
+                "The config editor only answers on this machine unless --health-token is set",
+            )
+                .into_response();
+        }
+        None => {}
+    }
+    next.run(request).await
+}
+
+/// Report per-subsystem readiness, answering 503 if anything is not ready
+async fn readyz(State(status): State<Arc<RouterStatus>>) -> (StatusCode, Json<Readiness>) {
+    let rtp_sessions = status.session_statuses().await;
+    let osc_listeners = status.osc_listeners().await;
+    let last_reload = status.last_reload().await;
+
+    let ready = rtp_sessions.iter().all(|session| session.bound)
+        && osc_listeners.iter().all(|listener| listener.bound);
+
+    let readiness = Readiness {
+        ready,
+        config_hash: status.config_hash().await,
+        read_only: status.is_read_only(),
+        show_locked: status.show_locked().await,
+        output_freeze: status.output_freeze().state(),
+        last_reload,
+        rtp_sessions,
+        osc_listeners,
+    };
+
+    let code = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (code, Json(readiness))
+}
+
+/// Live state of every routing context
+async fn state(State(status): State<Arc<RouterStatus>>) -> Json<Vec<ContextState>> {
+    Json(status.context_states().await)
+}
+
+/// RTP MIDI session states
+async fn sessions(State(status): State<Arc<RouterStatus>>) -> Json<Vec<SessionStatus>> {
+    Json(status.session_statuses().await)
+}
+
+/// Configured devices and their programs
+async fn devices(State(status): State<Arc<RouterStatus>>) -> Json<Vec<DeviceSummary>> {
+    Json(status.devices().await)
+}
+
+/// Map the outcome of a control request to a response
+fn control_response(
+    outcome: Result<ControlOutcome>,
+    applied: String,
+    not_found: String,
+) -> (StatusCode, String) {
+    match outcome {
+        Ok(ControlOutcome::Applied) => (StatusCode::OK, applied),
+        Ok(ControlOutcome::Locked) => (StatusCode::LOCKED, "Show is locked".to_string()),
+        Ok(ControlOutcome::NotFound) => (StatusCode::NOT_FOUND, not_found),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// Device program to run
+#[derive(Debug, Deserialize)]
+struct ProgramRequest {
+    program: u8,
+    #[serde(default)]
+    bank_msb: Option<u8>,
+    #[serde(default)]
+    bank_lsb: Option<u8>,
+}
+
+/// Run a device program as if its program change had been received
+async fn program(
+    State(status): State<Arc<RouterStatus>>,
+    Path(device): Path<String>,
+    Json(request): Json<ProgramRequest>,
+) -> (StatusCode, String) {
+    let outcome = status
+        .run_device_program(&device, request.program, request.bank_msb, request.bank_lsb)
+        .await;
+    control_response(
+        outcome,
+        format!("Ran program {} on '{device}'", request.program),
+        format!("Program {} not found on '{device}'", request.program),
+    )
+}
+
+/// Tempo statistics of every routing context
+async fn tempo_stats(State(status): State<Arc<RouterStatus>>) -> Json<Vec<ContextTempoStats>> {
+    Json(status.tempo_stats().await)
+}
+
+/// Tempo to set
+#[derive(Debug, Deserialize)]
+struct TempoRequest {
+    bpm: f64,
+    /// Tempo domain to set (the main tempo if unset)
+    #[serde(default)]
+    domain: Option<String>,
+}
+
+/// Set the tempo of every routing context
+async fn tempo(
+    State(status): State<Arc<RouterStatus>>,
+    Json(request): Json<TempoRequest>,
+) -> (StatusCode, String) {
+    let domain = request.domain.as_deref().unwrap_or(MAIN_TEMPO_DOMAIN);
+    control_response(
+        status.set_tempo(domain, request.bpm).await,
+        format!("Tempo '{domain}' set to {:.1} BPM", request.bpm),
+        format!("No routing context has tempo domain '{domain}'"),
+    )
+}
+
+/// Fire, stop or reset a cue list
+async fn cue(
+    State(status): State<Arc<RouterStatus>>,
+    Path((cue_list, action)): Path<(String, CueAction)>,
+) -> (StatusCode, String) {
+    control_response(
+        Ok(status.cue(&cue_list, action).await),
+        format!("Cue list '{cue_list}': {action:?}"),
+        format!("Cue list '{cue_list}' not found"),
+    )
+}
+
+/// Stream routed events over a WebSocket
+async fn events(State(status): State<Arc<RouterStatus>>, ws: WebSocketUpgrade) -> Response {
+    let receiver = status.events().subscribe();
+    ws.on_upgrade(move |socket| stream_events(socket, receiver))
+}
+
+/// Send each routed event as a JSON text message until the monitor disconnects
+async fn stream_events(mut socket: WebSocket, mut receiver: broadcast::Receiver<RoutedEvent>) {
+    loop {
+        let event = match receiver.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("Event monitor fell behind, skipped {} events", skipped);
+                continue;
+            }
+            Err(RecvError::Closed) => break,
+        };
+        let text = match serde_json::to_string(&event) {
+            Ok(text) => text,
+            Err(e) => {
+                error!("Failed to serialize routed event: {}", e);
+                continue;
+            }
+        };
+        if socket.send(Message::Text(text.into())).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Config editor of a routing context, or a 404 response
+async fn editor(
+    status: &RouterStatus,
+    context: &str,
+) -> Result<Arc<ConfigEditor>, (StatusCode, String)> {
+    status.config_editor(context).await.ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            format!("Routing context '{context}' not found"),
+        )
+    })
+}
+
+/// Read a routing context's device config file
+async fn device_config(
+    State(status): State<Arc<RouterStatus>>,
+    Path(context): Path<String>,
+) -> Result<Json<DeviceConfig>, (StatusCode, String)> {
+    editor(&status, &context)
+        .await?
+        .device_config()
+        .map(Json)
+        .map_err(|e| (StatusCode::CONFLICT, format!("{e:#}")))
+}
+
+/// Read a routing context's map config file
+async fn map_config(
+    State(status): State<Arc<RouterStatus>>,
+    Path(context): Path<String>,
+) -> Result<Json<MapConfig>, (StatusCode, String)> {
+    editor(&status, &context)
+        .await?
+        .map_config()
+        .map(Json)
+        .map_err(|e| (StatusCode::CONFLICT, format!("{e:#}")))
+}
+
+/// Replace a routing context's device config file and apply it
+async fn save_device_config(
+    State(status): State<Arc<RouterStatus>>,
+    Path(context): Path<String>,
+    Json(config): Json<DeviceConfig>,
+) -> (StatusCode, String) {
+    let editor = match editor(&status, &context).await {
+        Ok(editor) => editor,
+        Err(response) => return response,
+    };
+    match editor.save_device_config(config).await {
+        Ok(()) => (StatusCode::OK, "Saved device config".to_string()),
+        Err(e) => (StatusCode::CONFLICT, format!("{e:#}")),
+    }
+}
+
+/// Replace a routing context's map config file and apply it
+async fn save_map_config(
+    State(status): State<Arc<RouterStatus>>,
+    Path(context): Path<String>,
+    Json(config): Json<MapConfig>,
+) -> (StatusCode, String) {
+    let editor = match editor(&status, &context).await {
+        Ok(editor) => editor,
+        Err(response) => return response,
+    };
+    match editor.save_map_config(config).await {
+        Ok(()) => (StatusCode::OK, "Saved map config".to_string()),
+        Err(e) => (StatusCode::CONFLICT, format!("{e:#}")),
+    }
+}
+
+/// How long the discovery endpoint browses for services
+#[cfg(feature = "mdns")]
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// List RTP MIDI services visible on the network
+#[cfg(feature = "mdns")]
+async fn discover() -> Result<Json<Vec<DiscoveredService>>, (StatusCode, String)> {
+    discovery::browse(DISCOVERY_TIMEOUT)
+        .await
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+/// Remote to invite to a session
+#[derive(Debug, Deserialize)]
+struct ConnectRequest {
+    host: String,
+    port: u16,
+}
+
+/// Invite a remote (e.g. one from `/discovery`) to a session
+async fn connect(
+    State(status): State<Arc<RouterStatus>>,
+    Path(session): Path<String>,
+    Json(request): Json<ConnectRequest>,
+) -> (StatusCode, String) {
+    let addr = match (request.host.as_str(), request.port)
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addrs| addrs.next())
+    {
+        Some(addr) => addr,
+        None => {
+            return (
+                StatusCode::BAD_REQUEST,
+                format!("Failed to resolve {}:{}", request.host, request.port),
+            );
+        }
+    };
+
+    if status.invite(&session, addr).await {
+        (
+            StatusCode::ACCEPTED,
+            format!("Invited {addr} to '{session}'"),
+        )
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            format!("Session '{session}' not found"),
+        )
+    }
+}
+
+/// New name for a session
+#[derive(Debug, Deserialize)]
+struct RenameRequest {
+    name: String,
+}
+
+/// Rename a session; the old name stays usable as an alias
+async fn rename_session(
+    State(status): State<Arc<RouterStatus>>,
+    Path(session): Path<String>,
+    Json(request): Json<RenameRequest>,
+) -> (StatusCode, String) {
+    match status.rename_session(&session, &request.name).await {
+        Ok(true) => (
+            StatusCode::OK,
+            format!("Renamed '{session}' to '{}'", request.name),
+        ),
+        Ok(false) => (
+            StatusCode::NOT_FOUND,
+            format!("Session '{session}' not found"),
+        ),
+        Err(e) => (StatusCode::CONFLICT, e.to_string()),
+    }
+}
+
+/// Alias to add to a session
+#[derive(Debug, Deserialize)]
+struct AliasRequest {
+    alias: String,
+}
+
+/// Make a session reachable under another name
+async fn add_session_alias(
+    State(status): State<Arc<RouterStatus>>,
+    Path(session): Path<String>,
+    Json(request): Json<AliasRequest>,
+) -> (StatusCode, String) {
+    if status.add_session_alias(&session, &request.alias).await {
+        (
+            StatusCode::OK,
+            format!("Added alias '{}' for '{session}'", request.alias),
+        )
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            format!("Session '{session}' not found"),
+        )
+    }
+}
+
+/// Byte sniffing toggle
+#[derive(Debug, Deserialize)]
+struct SniffRequest {
+    enabled: bool,
+}
+
+/// Start or stop logging the raw bytes of a session's messages
+async fn sniff(
+    State(status): State<Arc<RouterStatus>>,
+    Path(session): Path<String>,
+    Json(request): Json<SniffRequest>,
+) -> (StatusCode, String) {
+    if status.set_sniff(&session, request.enabled).await {
+        (
+            StatusCode::OK,
+            format!(
+                "Sniffing {} on '{session}'",
+                if request.enabled {
+                    "started"
+                } else {
+                    "stopped"
+                }
+            ),
+        )
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            format!("Session '{session}' not found"),
+        )
+    }
+}
+
+/// Show lock change with its code
+#[derive(Debug, Deserialize)]
+struct LockRequest {
+    code: String,
+    locked: bool,
+}
+
+/// Lock or unlock tempo and scene changes from non-operator sources
+async fn lock(
+    State(status): State<Arc<RouterStatus>>,
+    Json(request): Json<LockRequest>,
+) -> (StatusCode, String) {
+    match status.set_show_lock(&request.code, request.locked).await {
+        Ok(()) => (
+            StatusCode::OK,
+            format!(
+                "Show {}",
+                if request.locked { "locked" } else { "unlocked" }
+            ),
+        ),
+        Err(e) => (StatusCode::FORBIDDEN, e.to_string()),
+    }
+}
+
+/// Output freeze change, for one destination or every output
+#[derive(Debug, Deserialize)]
+struct FreezeRequest {
+    frozen: bool,
+    #[serde(default)]
+    destination: Option<String>,
+}
+
+/// Drop or resume outgoing messages
+async fn freeze(
+    State(status): State<Arc<RouterStatus>>,
+    Json(request): Json<FreezeRequest>,
+) -> (StatusCode, String) {
+    status
+        .output_freeze()
+        .set(request.destination.as_deref(), request.frozen);
+    let target = match request.destination {
+        Some(ref destination) => format!("Output to '{destination}'"),
+        None => "Output".to_string(),
+    };
+    (
+        StatusCode::OK,
+        format!(
+            "{target} {}",
+            if request.frozen { "frozen" } else { "thawed" }
+        ),
+    )
+}
+
+/// Request `/healthz` from a router on this machine, failing unless it answers 200
+pub async fn check(port: u16) -> Result<()> {
+    let mut stream = TcpStream::connect(("127.0.0.1", port))
+        .await
+        .with_context(|| format!("Failed to connect to health endpoint on port {port}"))?;
+
+    stream
+        .write_all(b"GET /healthz HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+
+    let status_line = response.lines().next().unwrap_or_default();
+    if status_line.split_whitespace().nth(1) != Some("200") {
+        bail!("Health check failed: {status_line}");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,188 @@
+use crate::config::ConfigLoader;
+use crate::config_diff;
+use crate::device::DeviceConfig;
+use crate::mapping::MapConfig;
+use crate::status::{ReloadResult, RouterStatus};
+use crate::validator::ConfigValidator;
+use anyhow::{Context, Result};
+use notify::{Event, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{RwLock, mpsc};
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+/// Time to let an editor finish saving before the files are read
+const SETTLE_DELAY: Duration = Duration::from_millis(200);
+
+/// Swaps a context's configs in place when their files change
+pub struct ConfigWatcher {
+    device_path: PathBuf,
+    map_path: PathBuf,
+    device_config: Arc<RwLock<DeviceConfig>>,
+    map_config: Arc<RwLock<MapConfig>>,
+    status: Arc<RouterStatus>,
+}
+
+impl ConfigWatcher {
+    pub fn new(
+        device_path: &Path,
+        map_path: &Path,
+        device_config: Arc<RwLock<DeviceConfig>>,
+        map_config: Arc<RwLock<MapConfig>>,
+        status: Arc<RouterStatus>,
+    ) -> Self {
+        Self {
+            device_path: device_path.to_path_buf(),
+            map_path: map_path.to_path_buf(),
+            device_config,
+            map_config,
+            status,
+        }
+    }
+
+    /// Watch both config files and reload them on every change
+    pub fn start(self) -> Result<JoinHandle<()>> {
+        let watched = [
+            watched_path(&self.device_path)?,
+            watched_path(&self.map_path)?,
+        ];
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            if let Ok(event) = event {
+                let _ = tx.send(event.paths);
+            }
+        })?;
+        // Watch the directories, as editors often replace a file instead of writing to it
+        for (dir, _) in &watched {
+            watcher
+                .watch(dir, RecursiveMode::NonRecursive)
+                .with_context(|| format!("Failed to watch {dir:?}"))?;
+        }
+        info!(
+            "Watching {:?} and {:?} for changes",
+            self.device_path, self.map_path
+        );
+
+        let mut hash = ConfigLoader::content_hash(&[&self.device_path, &self.map_path]).ok();
+        Ok(tokio::spawn(async move {
+            // Dropping the watcher stops it
+            let _watcher = watcher;
+            while let Some(paths) = rx.recv().await {
+                if !paths.iter().any(|path| is_watched(path, &watched)) {
+                    continue;
+                }
+                // A save usually produces several events; handle them as one change
+                tokio::time::sleep(SETTLE_DELAY).await;
+                while rx.try_recv().is_ok() {}
+
+                let Ok(new_hash) = ConfigLoader::content_hash(&[&self.device_path, &self.map_path])
+                else {
+                    // A file is missing, e.g. in the middle of being replaced
+                    continue;
+                };
+                if hash.as_ref() == Some(&new_hash) {
+                    continue;
+                }
+
+                let result = match self.reload().await {
+                    Ok(()) => {
+                        // The reported hash covers these files only if they are the sole context
+                        if self.status.config_hash().await == hash.clone().unwrap_or_default() {
+                            self.status.set_config_hash(new_hash.clone()).await;
+                        }
+                        hash = Some(new_hash);
+                        ReloadResult::new(true, "Applied changed configs")
+                    }
+                    Err(e) => {
+                        error!("Keeping the current configs: {:#}", e);
+                        ReloadResult::new(false, format!("{e:#}"))
+                    }
+                };
+                self.status.set_last_reload(result).await;
+            }
+        }))
+    }
+
+    /// Load both configs and swap them in together, so no message sees half of a change
+    async fn reload(&self) -> Result<()> {
+        let device_config = ConfigLoader::load_device_config(&self.device_path)?;
+        let mut map_config = ConfigLoader::load_map_config(&self.map_path)?;
+        ConfigValidator::new(&device_config, &map_config).validate()?;
+
+        // Same lock order as the processor: map config first
+        let mut current_map = self.map_config.write().await;
+        let mut current_devices = self.device_config.write().await;
+        if needs_restart(&current_map, &map_config) {
+            warn!(
+                "Session, OSC listener or plugin changes in {:?} take effect on the next full reload",
+                self.map_path
+            );
+            keep_started(&current_map, &mut map_config);
+        }
+        *current_map = map_config;
+        *current_devices = device_config;
+
+        info!(
+            "Reloaded configs from {:?} and {:?}",
+            self.device_path, self.map_path
+        );
+        Ok(())
+    }
+}
+
+/// Whether a change touches what is only set up when a context starts
+pub fn needs_restart(old: &MapConfig, new: &MapConfig) -> bool {
+    !config_diff::same(&old.rtp_midi_sessions, &new.rtp_midi_sessions)
+        || !config_diff::same(&old.osc_sources, &new.osc_sources)
+        || old.subsystems.osc_listener != new.subsystems.osc_listener
+        || !config_diff::same(&old.virtual_ports, &new.virtual_ports)
+        || !config_diff::same(&old.clock_outputs, &new.clock_outputs)
+        || !config_diff::same(&old.link, &new.link)
+        || !config_diff::same(&old.plugins, &new.plugins)
+        || !config_diff::same(&old.event_sinks, &new.event_sinks)
+}
+
+/// Keep what was set up when the context started from the running config, so the
+/// running config matches what is actually running until the next full reload
+pub fn keep_started(running: &MapConfig, new: &mut MapConfig) {
+    new.rtp_midi_sessions = running.rtp_midi_sessions.clone();
+    new.osc_sources = running.osc_sources.clone();
+    new.subsystems.osc_listener = running.subsystems.osc_listener;
+    new.virtual_ports = running.virtual_ports.clone();
+    new.clock_outputs = running.clock_outputs.clone();
+    new.link = running.link.clone();
+    new.plugins = running.plugins.clone();
+    new.event_sinks = running.event_sinks.clone();
+}
+
+/// Canonical directory and file name of a config file
+fn watched_path(path: &Path) -> Result<(PathBuf, PathBuf)> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let dir = dir
+        .canonicalize()
+        .with_context(|| format!("Failed to resolve {dir:?}"))?;
+    let file_name = path
+        .file_name()
+        .with_context(|| format!("{path:?} is not a file"))?;
+    Ok((dir, file_name.into()))
+}
+
+/// Whether an event path is one of the watched config files
+fn is_watched(path: &Path, watched: &[(PathBuf, PathBuf)]) -> bool {
+    let Some(file_name) = path.file_name() else {
+        return false;
+    };
+    let dir = path
+        .parent()
+        .and_then(|dir| dir.canonicalize().ok())
+        .unwrap_or_default();
+    watched
+        .iter()
+        .any(|(watched_dir, watched_name)| *watched_dir == dir && watched_name == file_name)
+}
@@ -0,0 +1,62 @@
+use anyhow::{Context, Result};
+use rand::RngCore;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+/// SSRCs of RTP MIDI sessions, persisted so sessions keep their identity across restarts
+pub struct SessionIdentities {
+    path: Option<PathBuf>,
+    ssrcs: HashMap<String, u32>,
+}
+
+impl SessionIdentities {
+    /// Load persisted SSRCs from the file, if one is configured
+    pub fn load(path: Option<&str>) -> Self {
+        let path = path.map(PathBuf::from);
+        let ssrcs = match path {
+            Some(ref path) if path.exists() => match Self::read(path) {
+                Ok(ssrcs) => {
+                    info!("Loaded session identities from {:?}", path);
+                    ssrcs
+                }
+                Err(e) => {
+                    warn!("Ignoring session identity file {:?}: {}", path, e);
+                    HashMap::new()
+                }
+            },
+            _ => HashMap::new(),
+        };
+        Self { path, ssrcs }
+    }
+
+    fn read(path: &Path) -> Result<HashMap<String, u32>> {
+        let content = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// SSRC for a session: the configured one, else the persisted one, else a new random one
+    pub fn ssrc(&mut self, session_name: &str, configured: Option<u32>) -> u32 {
+        let ssrc = configured
+            .or_else(|| self.ssrcs.get(session_name).copied())
+            .unwrap_or_else(|| rand::rng().next_u32());
+
+        if self.ssrcs.insert(session_name.to_string(), ssrc) != Some(ssrc)
+            && let Err(e) = self.save()
+        {
+            warn!("Failed to save session identities: {}", e);
+        }
+        ssrc
+    }
+
+    /// Write the SSRCs to the identity file, if one is configured
+    fn save(&self) -> Result<()> {
+        let Some(ref path) = self.path else {
+            return Ok(());
+        };
+        let content = serde_json::to_string_pretty(&self.ssrcs)?;
+        fs::write(path, content)
+            .with_context(|| format!("Failed to write session identity file: {:?}", path))
+    }
+}
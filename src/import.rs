@@ -0,0 +1,132 @@
+use crate::cli::RunArgs;
+use crate::command_dsl;
+use crate::config::ConfigLoader;
+use crate::device::Program;
+use anyhow::{Context, Result, bail};
+use clap::Args;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// Options for importing a device's programs from a CSV file
+#[derive(Debug, Args)]
+pub struct ImportProgramsArgs {
+    /// CSV file with `number`, `name` and `commands` columns
+    pub csv: PathBuf,
+    /// ID of the device in `--device-config` to import into
+    #[arg(long)]
+    pub device: String,
+    /// Replace all existing programs instead of merging by program number
+    #[arg(long)]
+    pub replace: bool,
+}
+
+/// A row of the program list
+#[derive(Debug, Deserialize)]
+struct ProgramRow {
+    number: u8,
+    name: String,
+    /// Commands in the compact syntax, separated by `;`
+    #[serde(default)]
+    commands: String,
+    #[serde(default)]
+    transactional: bool,
+    /// Bank Select MSB/LSB the program is in
+    #[serde(default)]
+    bank_msb: Option<u8>,
+    #[serde(default)]
+    bank_lsb: Option<u8>,
+}
+
+/// Import programs from the CSV into the device config and save it
+pub fn run(args: &ImportProgramsArgs, current: &RunArgs) -> Result<()> {
+    if current.read_only {
+        bail!(
+            "Refusing to write {:?} in read-only mode",
+            current.device_config
+        );
+    }
+    let programs = read_programs(&args.csv)?;
+
+    // Saving would write the decrypted values back in plain text
+    if ConfigLoader::has_secrets(&current.device_config)? {
+        bail!(
+            "{:?} contains secrets; add the programs by hand to keep them encrypted",
+            current.device_config
+        );
+    }
+
+    let mut device_config = ConfigLoader::load_device_config(&current.device_config)?;
+    let device = device_config
+        .devices
+        .get_mut(&args.device)
+        .with_context(|| format!("Device '{}' not found in configuration", args.device))?;
+
+    if args.replace {
+        device.programs.clear();
+    }
+    let count = programs.len();
+    for program in programs {
+        device.programs.retain(|existing| {
+            (existing.bank_msb, existing.bank_lsb, existing.number)
+                != (program.bank_msb, program.bank_lsb, program.number)
+        });
+        device.programs.push(program);
+    }
+    device
+        .programs
+        .sort_by_key(|program| (program.bank_msb, program.bank_lsb, program.number));
+
+    ConfigLoader::save_device_config(&current.device_config, &device_config)?;
+    println!(
+        "Imported {} program(s) into device '{}' in {:?}",
+        count, args.device, current.device_config
+    );
+    Ok(())
+}
+
+/// Read and parse every row of the CSV file
+fn read_programs(path: &Path) -> Result<Vec<Program>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_path(path)
+        .with_context(|| format!("Failed to open program list: {path:?}"))?;
+
+    let mut programs: Vec<Program> = Vec::new();
+    for (index, row) in reader.deserialize::<ProgramRow>().enumerate() {
+        // Line 1 is the header
+        let line = index + 2;
+        let row = row.with_context(|| format!("{path:?} line {line}"))?;
+        if row.number > 127 {
+            bail!(
+                "{:?} line {}: program number {} is out of range (must be 0-127)",
+                path,
+                line,
+                row.number
+            );
+        }
+        if programs.iter().any(|program| {
+            (program.bank_msb, program.bank_lsb, program.number)
+                == (row.bank_msb, row.bank_lsb, row.number)
+        }) {
+            bail!(
+                "{:?} line {}: program {} is listed more than once",
+                path,
+                line,
+                row.number
+            );
+        }
+
+        let commands = command_dsl::parse_commands(&row.commands)
+            .with_context(|| format!("{path:?} line {line} ('{}')", row.name))?;
+        programs.push(Program {
+            number: row.number,
+            bank_msb: row.bank_msb,
+            bank_lsb: row.bank_lsb,
+            name: row.name,
+            commands: commands.into_iter().map(Into::into).collect(),
+            transactional: row.transactional,
+            sequence: Vec::new(),
+        });
+    }
+    Ok(programs)
+}
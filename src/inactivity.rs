@@ -0,0 +1,66 @@
+use crate::processor::MidiProcessor;
+use crate::supervisor::supervise;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+
+/// How often the inactivity rules are checked
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Runs inactivity rules when no input has been received for their timeout
+pub struct InactivityMonitor {
+    processor: Arc<MidiProcessor>,
+}
+
+impl InactivityMonitor {
+    pub fn new(processor: Arc<MidiProcessor>) -> Self {
+        Self { processor }
+    }
+
+    /// Start checking inactivity rules in the background
+    pub fn start(&self) -> JoinHandle<()> {
+        let processor = Arc::clone(&self.processor);
+
+        supervise("inactivity rules", move || {
+            let processor = Arc::clone(&processor);
+            async move {
+                // Rules that have fired during the current idle period
+                let mut fired: HashSet<String> = HashSet::new();
+                let mut interval = tokio::time::interval(CHECK_INTERVAL);
+
+                loop {
+                    interval.tick().await;
+
+                    let idle = processor.idle_duration().await;
+                    for rule in processor.inactivity_rules().await {
+                        if idle < Duration::from_secs(rule.timeout_secs) {
+                            // Input arrived since the rule fired, so it can fire again
+                            fired.remove(&rule.name);
+                            continue;
+                        }
+
+                        if fired.insert(rule.name.clone()) {
+                            info!(
+                                "No input for {}s, running inactivity rule '{}'",
+                                idle.as_secs(),
+                                rule.name
+                            );
+                            if let Err(e) = processor
+                                .execute_commands(
+                                    &rule.commands,
+                                    &rule.destination,
+                                    rule.send_channel,
+                                )
+                                .await
+                            {
+                                error!("Error running inactivity rule '{}': {}", rule.name, e);
+                            }
+                        }
+                    }
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,14 @@
+//! Configuration types for the MIDI router, for tools that generate or inspect configs.
+
+pub mod builder;
+pub mod command_dsl;
+pub mod config;
+pub mod device;
+mod diagnostics;
+pub mod mapping;
+pub mod midi_fields;
+mod migration;
+pub mod plugin;
+pub mod secrets;
+pub mod sysex;
+pub mod validator;
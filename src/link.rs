@@ -0,0 +1,800 @@
+use crate::mapping::LinkConfig;
+use crate::processor::MidiProcessor;
+use anyhow::{Context, Result};
+use rand::RngCore;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tokio::sync::mpsc;
+use tokio::task::{self, JoinHandle};
+use tracing::{debug, info, warn};
+
+/// Multicast group Link peers announce themselves on
+const MULTICAST_ADDR: Ipv4Addr = Ipv4Addr::new(224, 76, 78, 75);
+const MULTICAST_PORT: u16 = 20808;
+
+/// Protocol headers of discovery and measurement messages
+const DISCOVERY_HEADER: &[u8; 8] = b"_asdp_v\x01";
+const MEASUREMENT_HEADER: &[u8; 8] = b"_link_v\x01";
+
+/// Discovery message types
+const ALIVE: u8 = 1;
+const RESPONSE: u8 = 2;
+const BYEBYE: u8 = 3;
+
+/// Measurement message types
+const PING: u8 = 1;
+const PONG: u8 = 2;
+
+/// Payload entry keys
+const TIMELINE_KEY: u32 = u32::from_be_bytes(*b"tmln");
+const SESSION_KEY: u32 = u32::from_be_bytes(*b"sess");
+const ENDPOINT_KEY: u32 = u32::from_be_bytes(*b"mep4");
+const HOST_TIME_KEY: u32 = u32::from_be_bytes(*b"hsti");
+const GHOST_TIME_KEY: u32 = u32::from_be_bytes(*b"ghst");
+const PREV_GHOST_TIME_KEY: u32 = u32::from_be_bytes(*b"prgs");
+
+/// Seconds peers keep an announcement before dropping its sender
+const TTL_SECS: u8 = 5;
+
+/// How often the router announces itself
+const ANNOUNCE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How often the router tempo is checked for changes to push to Link
+const TEMPO_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Time offsets collected before a measurement settles on their median
+const MEASUREMENT_POINTS: usize = 100;
+const PING_TIMEOUT: Duration = Duration::from_millis(50);
+const MAX_PING_TIMEOUTS: u32 = 5;
+
+/// Largest ping payload answered, as in Link
+const MAX_PING_PAYLOAD: usize = 32;
+
+/// Session time difference below which the session with the lower ID is joined
+const SESSION_EPSILON_MICROS: i64 = 500_000;
+
+/// Tempo range Link sessions accept
+const MIN_BPM: f64 = 20.0;
+const MAX_BPM: f64 = 999.0;
+
+/// Smallest tempo difference passed between Link and the router
+const TEMPO_EPSILON: f64 = 0.01;
+
+/// Peer and session IDs
+type NodeId = [u8; 8];
+
+/// Session tempo and beat position: `beat_origin` (in microbeats) falls at
+/// `time_origin` (in microseconds of session time)
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Timeline {
+    micros_per_beat: i64,
+    beat_origin: i64,
+    time_origin: i64,
+}
+
+impl Timeline {
+    fn new(bpm: f64, beat_origin: i64, time_origin: i64) -> Self {
+        Self {
+            micros_per_beat: (60_000_000.0 / bpm.clamp(MIN_BPM, MAX_BPM)).round() as i64,
+            beat_origin,
+            time_origin,
+        }
+    }
+
+    fn bpm(&self) -> f64 {
+        60_000_000.0 / self.micros_per_beat as f64
+    }
+
+    /// Beat position in microbeats at a session time
+    fn beats_at(&self, time: i64) -> i64 {
+        let elapsed = i128::from(time - self.time_origin);
+        self.beat_origin + (elapsed * 1_000_000 / i128::from(self.micros_per_beat)) as i64
+    }
+
+    /// Session time at a beat position in microbeats
+    fn time_at(&self, beats: i64) -> i64 {
+        let elapsed = i128::from(beats - self.beat_origin);
+        self.time_origin + (elapsed * i128::from(self.micros_per_beat) / 1_000_000) as i64
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        [self.micros_per_beat, self.beat_origin, self.time_origin]
+            .iter()
+            .flat_map(|value| value.to_be_bytes())
+            .collect()
+    }
+
+    fn decode(value: &[u8]) -> Option<Self> {
+        let timeline = Self {
+            micros_per_beat: read_i64(value, 0)?,
+            beat_origin: read_i64(value, 8)?,
+            time_origin: read_i64(value, 16)?,
+        };
+        (timeline.micros_per_beat > 0).then_some(timeline)
+    }
+}
+
+/// Entries of a discovery or measurement payload the bridge uses
+#[derive(Debug, Default)]
+struct Payload {
+    timeline: Option<Timeline>,
+    session: Option<NodeId>,
+    endpoint: Option<SocketAddrV4>,
+    host_time: Option<i64>,
+    ghost_time: Option<i64>,
+    prev_ghost_time: Option<i64>,
+}
+
+impl Payload {
+    /// Parse key-size-value entries, skipping unknown keys
+    fn parse(mut data: &[u8]) -> Option<Self> {
+        let mut payload = Self::default();
+        while !data.is_empty() {
+            let key = u32::from_be_bytes(data.get(0..4)?.try_into().ok()?);
+            let size = u32::from_be_bytes(data.get(4..8)?.try_into().ok()?) as usize;
+            let value = data.get(8..8 + size)?;
+            match key {
+                TIMELINE_KEY => payload.timeline = Some(Timeline::decode(value)?),
+                SESSION_KEY => payload.session = Some(value.try_into().ok()?),
+                ENDPOINT_KEY => {
+                    let ip: [u8; 4] = value.get(0..4)?.try_into().ok()?;
+                    let port = u16::from_be_bytes(value.get(4..6)?.try_into().ok()?);
+                    payload.endpoint = Some(SocketAddrV4::new(ip.into(), port));
+                }
+                HOST_TIME_KEY => payload.host_time = Some(read_i64(value, 0)?),
+                GHOST_TIME_KEY => payload.ghost_time = Some(read_i64(value, 0)?),
+                PREV_GHOST_TIME_KEY => payload.prev_ghost_time = Some(read_i64(value, 0)?),
+                _ => {}
+            }
+            data = &data[8 + size..];
+        }
+        Some(payload)
+    }
+}
+
+fn read_i64(value: &[u8], offset: usize) -> Option<i64> {
+    Some(i64::from_be_bytes(
+        value.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+fn put_entry(message: &mut Vec<u8>, key: u32, value: &[u8]) {
+    message.extend_from_slice(&key.to_be_bytes());
+    message.extend_from_slice(&(value.len() as u32).to_be_bytes());
+    message.extend_from_slice(value);
+}
+
+/// Alive or response message announcing a node's session
+fn discovery_message(
+    message_type: u8,
+    node_id: &NodeId,
+    session: &Session,
+    endpoint: SocketAddrV4,
+) -> Vec<u8> {
+    let mut message = DISCOVERY_HEADER.to_vec();
+    message.extend_from_slice(&[message_type, TTL_SECS, 0, 0]);
+    message.extend_from_slice(node_id);
+    put_entry(&mut message, TIMELINE_KEY, &session.timeline.encode());
+    put_entry(&mut message, SESSION_KEY, &session.id);
+    let mut address = endpoint.ip().octets().to_vec();
+    address.extend_from_slice(&endpoint.port().to_be_bytes());
+    put_entry(&mut message, ENDPOINT_KEY, &address);
+    message
+}
+
+/// Measurement ping carrying the host time it was sent at and the last session time
+/// the peer answered with
+fn ping_message(host_time: i64, prev_ghost_time: i64) -> Vec<u8> {
+    let mut message = MEASUREMENT_HEADER.to_vec();
+    message.push(PING);
+    put_entry(&mut message, HOST_TIME_KEY, &host_time.to_be_bytes());
+    put_entry(
+        &mut message,
+        PREV_GHOST_TIME_KEY,
+        &prev_ghost_time.to_be_bytes(),
+    );
+    message
+}
+
+/// Answer to a measurement ping with the session time, echoing the ping's payload
+fn pong_message(session: &NodeId, ghost_time: i64, ping: &[u8]) -> Option<Vec<u8>> {
+    let payload = measurement_payload(ping, PING)?;
+    if payload.len() > MAX_PING_PAYLOAD {
+        return None;
+    }
+    let mut message = MEASUREMENT_HEADER.to_vec();
+    message.push(PONG);
+    put_entry(&mut message, SESSION_KEY, session);
+    put_entry(&mut message, GHOST_TIME_KEY, &ghost_time.to_be_bytes());
+    message.extend_from_slice(payload);
+    Some(message)
+}
+
+/// Whether to leave the current session for another one, given each session's time
+/// offset from the router's host time: the older session wins, and of two sessions
+/// about as old, the one with the lower ID
+fn joins(current: &Session, other: &NodeId, other_offset: i64) -> bool {
+    let difference = other_offset - current.ghost_offset;
+    difference > SESSION_EPSILON_MICROS
+        || (difference.abs() < SESSION_EPSILON_MICROS && *other < current.id)
+}
+
+/// Whether a timeline announced within the current session replaces the current one:
+/// the timeline with the latest beat origin is the current one
+fn adopts(current: &Timeline, announced: &Timeline) -> bool {
+    announced.beat_origin > current.beat_origin
+}
+
+/// Whether a tempo differs enough from the one last passed between Link and the router
+fn tempo_changed(last_bpm: Option<f64>, bpm: f64) -> bool {
+    last_bpm.is_none_or(|last| (last - bpm).abs() >= TEMPO_EPSILON)
+}
+
+/// Timeline at a new tempo that keeps the beat position at the session time, with a
+/// later beat origin so peers adopt it
+fn retimed(current: Timeline, bpm: f64, session_time: i64) -> Timeline {
+    let beat_origin = current.beats_at(session_time).max(current.beat_origin + 1);
+    Timeline::new(bpm, beat_origin, current.time_at(beat_origin))
+}
+
+/// Split a measurement message of the given type into its payload
+fn measurement_payload(data: &[u8], message_type: u8) -> Option<&[u8]> {
+    let rest = data.strip_prefix(MEASUREMENT_HEADER)?;
+    let (&kind, payload) = rest.split_first()?;
+    (kind == message_type).then_some(payload)
+}
+
+/// Link session the router is part of
+struct Session {
+    id: NodeId,
+    timeline: Timeline,
+    /// Session time minus the router's host time
+    ghost_offset: i64,
+}
+
+/// Bridges the router tempo of one tempo domain and an Ableton Link session
+pub struct LinkMonitor {
+    processor: Arc<MidiProcessor>,
+}
+
+impl LinkMonitor {
+    pub fn new(processor: Arc<MidiProcessor>) -> Self {
+        Self { processor }
+    }
+
+    /// Join Link in the background
+    pub fn start(&self, config: &LinkConfig) -> JoinHandle<()> {
+        let processor = Arc::clone(&self.processor);
+        let domain = config.tempo_domain.clone();
+        task::spawn(async move {
+            if let Err(e) = run(processor, domain).await {
+                warn!("Ableton Link stopped: {:#}", e);
+            }
+        })
+    }
+}
+
+async fn run(processor: Arc<MidiProcessor>, domain: String) -> Result<()> {
+    let multicast = multicast_socket().context("Failed to join the Link multicast group")?;
+    let unicast = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+    let pings = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+    let endpoint = SocketAddrV4::new(
+        local_ipv4().await.unwrap_or(Ipv4Addr::LOCALHOST),
+        pings.local_addr()?.port(),
+    );
+
+    let mut bridge = Bridge::new(processor, domain, endpoint).await;
+    info!(
+        "Ableton Link started for tempo '{}', measurement endpoint {}",
+        bridge.domain, endpoint
+    );
+
+    let (measured_tx, mut measured_rx) = mpsc::channel(8);
+    let mut announce = tokio::time::interval(ANNOUNCE_INTERVAL);
+    let mut poll = tokio::time::interval(TEMPO_POLL_INTERVAL);
+    let group = SocketAddr::from((MULTICAST_ADDR, MULTICAST_PORT));
+    let mut multicast_buf = [0u8; 512];
+    let mut unicast_buf = [0u8; 512];
+    let mut ping_buf = [0u8; 512];
+
+    loop {
+        tokio::select! {
+            _ = announce.tick() => {
+                bridge.prune_peers();
+                send(&unicast, &bridge.discovery_message(ALIVE), group).await;
+            }
+            _ = poll.tick() => {
+                if bridge.push_router_tempo().await {
+                    send(&unicast, &bridge.discovery_message(ALIVE), group).await;
+                }
+            }
+            received = multicast.recv_from(&mut multicast_buf) => {
+                if let Ok((len, from)) = received {
+                    bridge.handle_discovery(&multicast_buf[..len], from, &unicast, &measured_tx).await;
+                }
+            }
+            received = unicast.recv_from(&mut unicast_buf) => {
+                if let Ok((len, from)) = received {
+                    bridge.handle_discovery(&unicast_buf[..len], from, &unicast, &measured_tx).await;
+                }
+            }
+            received = pings.recv_from(&mut ping_buf) => {
+                if let Ok((len, from)) = received
+                    && let Some(pong) = bridge.pong(&ping_buf[..len])
+                {
+                    send(&pings, &pong, from).await;
+                }
+            }
+            Some((session, offset)) = measured_rx.recv() => {
+                bridge.measured(session, offset).await;
+            }
+        }
+    }
+}
+
+/// Link state of the bridge
+struct Bridge {
+    processor: Arc<MidiProcessor>,
+    domain: String,
+    node_id: NodeId,
+    epoch: Instant,
+    endpoint: SocketAddrV4,
+    session: Session,
+    /// Latest timelines of sessions other peers are in, until measured
+    other_sessions: HashMap<NodeId, Timeline>,
+    /// Peers seen, with when their last announcement expires
+    peers: HashMap<NodeId, Instant>,
+    /// Tempo last passed between Link and the router
+    last_bpm: Option<f64>,
+}
+
+impl Bridge {
+    async fn new(processor: Arc<MidiProcessor>, domain: String, endpoint: SocketAddrV4) -> Self {
+        let mut node_id = NodeId::default();
+        rand::rng().fill_bytes(&mut node_id);
+        let bpm = processor.tempo(&domain).await.unwrap_or(120.0);
+        Self {
+            processor,
+            domain,
+            node_id,
+            epoch: Instant::now(),
+            endpoint,
+            // Found a session of our own until a peer's session wins
+            session: Session {
+                id: node_id,
+                timeline: Timeline::new(bpm, 0, 0),
+                ghost_offset: 0,
+            },
+            other_sessions: HashMap::new(),
+            peers: HashMap::new(),
+            last_bpm: None,
+        }
+    }
+
+    fn session_time(&self) -> i64 {
+        host_micros(self.epoch) + self.session.ghost_offset
+    }
+
+    /// Alive or response message announcing the router's session
+    fn discovery_message(&self, message_type: u8) -> Vec<u8> {
+        discovery_message(message_type, &self.node_id, &self.session, self.endpoint)
+    }
+
+    /// Answer a measurement ping with the session time
+    fn pong(&self, data: &[u8]) -> Option<Vec<u8>> {
+        pong_message(&self.session.id, self.session_time(), data)
+    }
+
+    async fn handle_discovery(
+        &mut self,
+        data: &[u8],
+        from: SocketAddr,
+        unicast: &UdpSocket,
+        measured: &mpsc::Sender<(NodeId, Option<i64>)>,
+    ) {
+        let Some(rest) = data.strip_prefix(DISCOVERY_HEADER) else {
+            return;
+        };
+        let (Some(&[message_type, ttl, _, _]), Some(node_id)) = (
+            rest.get(0..4).and_then(|h| <&[u8; 4]>::try_from(h).ok()),
+            rest.get(4..12).and_then(|id| NodeId::try_from(id).ok()),
+        ) else {
+            return;
+        };
+        if node_id == self.node_id {
+            return;
+        }
+
+        match message_type {
+            BYEBYE => {
+                if self.peers.remove(&node_id).is_some() {
+                    info!("Link peer left ({} peers)", self.peers.len());
+                }
+                return;
+            }
+            ALIVE => send(unicast, &self.discovery_message(RESPONSE), from).await,
+            RESPONSE => {}
+            _ => return,
+        }
+
+        let Some(payload) = Payload::parse(&rest[12..]) else {
+            debug!("Ignoring malformed Link message from {}", from);
+            return;
+        };
+        let (Some(session), Some(timeline)) = (payload.session, payload.timeline) else {
+            return;
+        };
+        let expires = Instant::now() + Duration::from_secs(ttl.into());
+        if self.peers.insert(node_id, expires).is_none() {
+            info!("Link peer joined ({} peers)", self.peers.len());
+        }
+
+        if session == self.session.id {
+            if adopts(&self.session.timeline, &timeline) {
+                self.session.timeline = timeline;
+                self.pass_link_tempo().await;
+            }
+            return;
+        }
+
+        if self.other_sessions.insert(session, timeline).is_none() {
+            let Some(endpoint) = payload.endpoint else {
+                return;
+            };
+            let epoch = self.epoch;
+            let measured = measured.clone();
+            task::spawn(async move {
+                let offset = match measure(endpoint, session, epoch).await {
+                    Ok(offset) => Some(offset),
+                    Err(e) => {
+                        debug!("Failed to measure Link session at {}: {:#}", endpoint, e);
+                        None
+                    }
+                };
+                let _ = measured.send((session, offset)).await;
+            });
+        }
+    }
+
+    /// Join a measured session if it is older, or about as old with a lower ID
+    async fn measured(&mut self, session: NodeId, offset: Option<i64>) {
+        let Some(offset) = offset else {
+            // Measure again when the session is next announced
+            self.other_sessions.remove(&session);
+            return;
+        };
+        let Some(&timeline) = self.other_sessions.get(&session) else {
+            return;
+        };
+        if joins(&self.session, &session, offset) {
+            self.other_sessions.remove(&session);
+            self.session = Session {
+                id: session,
+                timeline,
+                ghost_offset: offset,
+            };
+            info!("Joined Link session at {:.1} BPM", timeline.bpm());
+            self.pass_link_tempo().await;
+        }
+    }
+
+    /// Pass the session tempo on to the router
+    async fn pass_link_tempo(&mut self) {
+        let bpm = self.session.timeline.bpm();
+        if !tempo_changed(self.last_bpm, bpm) {
+            return;
+        }
+        if let Err(e) = self.processor.handle_link_tempo(&self.domain, bpm).await {
+            warn!("Failed to apply Link tempo: {:#}", e);
+        }
+        // The router may have limited or refused the tempo; only its own later
+        // changes are pushed back
+        self.last_bpm = self.processor.tempo(&self.domain).await.or(Some(bpm));
+    }
+
+    /// Start a new session timeline when the router tempo has changed, keeping the
+    /// beat position. Returns whether the tempo changed.
+    async fn push_router_tempo(&mut self) -> bool {
+        let Some(bpm) = self.processor.tempo(&self.domain).await else {
+            return false;
+        };
+        if !tempo_changed(self.last_bpm, bpm) {
+            return false;
+        }
+        self.last_bpm = Some(bpm);
+
+        self.session.timeline = retimed(self.session.timeline, bpm, self.session_time());
+        info!(
+            "Sending tempo {:.1} BPM to Link",
+            self.session.timeline.bpm()
+        );
+        true
+    }
+
+    fn prune_peers(&mut self) {
+        let now = Instant::now();
+        let before = self.peers.len();
+        self.peers.retain(|_, expires| *expires > now);
+        if self.peers.len() < before {
+            info!("Link peer timed out ({} peers)", self.peers.len());
+        }
+    }
+}
+
+/// Offset of a session's time from the router's host time, as the median of
+/// ping round trips to one of its peers
+async fn measure(endpoint: SocketAddrV4, session: NodeId, epoch: Instant) -> Result<i64> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+    let mut offsets = Vec::with_capacity(MEASUREMENT_POINTS + 2);
+    let mut prev_ghost_time = 0i64;
+    let mut timeouts = 0;
+    let mut buf = [0u8; 512];
+
+    while offsets.len() <= MEASUREMENT_POINTS {
+        let ping = ping_message(host_micros(epoch), prev_ghost_time);
+        socket.send_to(&ping, endpoint).await?;
+
+        let len = match tokio::time::timeout(PING_TIMEOUT, socket.recv(&mut buf)).await {
+            Ok(len) => len?,
+            Err(_) => {
+                timeouts += 1;
+                if timeouts > MAX_PING_TIMEOUTS {
+                    anyhow::bail!("no reply to pings");
+                }
+                continue;
+            }
+        };
+        let now = host_micros(epoch);
+        let Some(pong) = measurement_payload(&buf[..len], PONG).and_then(Payload::parse) else {
+            continue;
+        };
+        if pong.session != Some(session) {
+            anyhow::bail!("peer changed sessions");
+        }
+        let (Some(ghost_time), Some(sent_at)) = (pong.ghost_time, pong.host_time) else {
+            continue;
+        };
+        offsets.push(ghost_time as f64 - (now + sent_at) as f64 / 2.0);
+        if let Some(prev) = pong.prev_ghost_time
+            && prev != 0
+        {
+            offsets.push((ghost_time + prev) as f64 / 2.0 - sent_at as f64);
+        }
+        prev_ghost_time = ghost_time;
+    }
+
+    offsets.sort_by(f64::total_cmp);
+    Ok(offsets[offsets.len() / 2].round() as i64)
+}
+
+/// Microseconds since the bridge started
+fn host_micros(epoch: Instant) -> i64 {
+    epoch.elapsed().as_micros() as i64
+}
+
+async fn send(socket: &UdpSocket, message: &[u8], to: SocketAddr) {
+    if let Err(e) = socket.send_to(message, to).await {
+        debug!("Failed to send Link message to {}: {}", to, e);
+    }
+}
+
+/// Socket on the Link discovery port, shared with other Link apps on this machine
+fn multicast_socket() -> Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    #[cfg(unix)]
+    socket.set_reuse_port(true)?;
+    socket.bind(&SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, MULTICAST_PORT).into())?;
+    socket.join_multicast_v4(&MULTICAST_ADDR, &Ipv4Addr::UNSPECIFIED)?;
+    socket.set_nonblocking(true)?;
+    Ok(UdpSocket::from_std(socket.into())?)
+}
+
+/// Address of the interface multicast goes out on, for peers to measure the router at
+async fn local_ipv4() -> Option<Ipv4Addr> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await.ok()?;
+    socket
+        .connect((MULTICAST_ADDR, MULTICAST_PORT))
+        .await
+        .ok()?;
+    match socket.local_addr().ok()?.ip() {
+        IpAddr::V4(ip) if !ip.is_unspecified() => Some(ip),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NODE: NodeId = *b"node0001";
+
+    fn session(id: &[u8; 8], ghost_offset: i64) -> Session {
+        Session {
+            id: *id,
+            timeline: Timeline::new(120.0, 0, 0),
+            ghost_offset,
+        }
+    }
+
+    #[test]
+    fn timeline_round_trip() {
+        let timeline = Timeline::new(128.0, 4_000_000, -250_000);
+        let encoded = timeline.encode();
+        assert_eq!(encoded.len(), 24);
+        assert_eq!(Timeline::decode(&encoded), Some(timeline));
+        assert_eq!(timeline.micros_per_beat, 468_750);
+        assert_eq!(timeline.bpm(), 128.0);
+    }
+
+    #[test]
+    fn timeline_decode_rejects_bad_values() {
+        assert_eq!(Timeline::decode(&[0; 23]), None);
+        let mut zero_tempo = Timeline::new(120.0, 0, 0);
+        zero_tempo.micros_per_beat = 0;
+        assert_eq!(Timeline::decode(&zero_tempo.encode()), None);
+    }
+
+    #[test]
+    fn timeline_clamps_tempo() {
+        assert_eq!(Timeline::new(5.0, 0, 0).bpm(), MIN_BPM);
+        assert_eq!(Timeline::new(5000.0, 0, 0).bpm().round(), MAX_BPM);
+    }
+
+    #[test]
+    fn timeline_beats_and_times() {
+        // 120 BPM: a beat every half second
+        let timeline = Timeline::new(120.0, 1_000_000, 10_000_000);
+        assert_eq!(timeline.beats_at(10_500_000), 2_000_000);
+        assert_eq!(timeline.beats_at(9_500_000), 0);
+        assert_eq!(timeline.time_at(3_000_000), 11_000_000);
+    }
+
+    #[test]
+    fn discovery_message_round_trip() {
+        let endpoint = SocketAddrV4::new(Ipv4Addr::new(192, 168, 1, 20), 40000);
+        let session = Session {
+            id: *b"session1",
+            timeline: Timeline::new(90.0, 7, 8),
+            ghost_offset: 0,
+        };
+        let message = discovery_message(ALIVE, &NODE, &session, endpoint);
+        let rest = message.strip_prefix(DISCOVERY_HEADER).unwrap();
+        assert_eq!(&rest[0..4], &[ALIVE, TTL_SECS, 0, 0]);
+        assert_eq!(&rest[4..12], &NODE);
+
+        let payload = Payload::parse(&rest[12..]).unwrap();
+        assert_eq!(payload.session, Some(session.id));
+        assert_eq!(payload.timeline, Some(session.timeline));
+        assert_eq!(payload.endpoint, Some(endpoint));
+        assert_eq!(payload.host_time, None);
+    }
+
+    #[test]
+    fn payload_skips_unknown_keys() {
+        let mut data = Vec::new();
+        put_entry(&mut data, u32::from_be_bytes(*b"xtra"), &[1, 2, 3]);
+        put_entry(&mut data, SESSION_KEY, b"session1");
+        let payload = Payload::parse(&data).unwrap();
+        assert_eq!(payload.session, Some(*b"session1"));
+    }
+
+    #[test]
+    fn payload_rejects_malformed_entries() {
+        let mut data = Vec::new();
+        put_entry(&mut data, SESSION_KEY, b"session1");
+        // Truncated value
+        assert!(Payload::parse(&data[..data.len() - 1]).is_none());
+        // Truncated header
+        assert!(Payload::parse(&data[..6]).is_none());
+
+        let mut wrong_size = Vec::new();
+        put_entry(&mut wrong_size, SESSION_KEY, b"short");
+        assert!(Payload::parse(&wrong_size).is_none());
+        let mut wrong_size = Vec::new();
+        put_entry(&mut wrong_size, TIMELINE_KEY, &[0; 16]);
+        assert!(Payload::parse(&wrong_size).is_none());
+    }
+
+    #[test]
+    fn ping_and_pong() {
+        let ping = ping_message(1_000, 2_000);
+        let payload = Payload::parse(measurement_payload(&ping, PING).unwrap()).unwrap();
+        assert_eq!(payload.host_time, Some(1_000));
+        assert_eq!(payload.prev_ghost_time, Some(2_000));
+        assert!(measurement_payload(&ping, PONG).is_none());
+
+        let pong = pong_message(b"session1", 5_000, &ping).unwrap();
+        let payload = Payload::parse(measurement_payload(&pong, PONG).unwrap()).unwrap();
+        assert_eq!(payload.session, Some(*b"session1"));
+        assert_eq!(payload.ghost_time, Some(5_000));
+        // The ping's entries are echoed
+        assert_eq!(payload.host_time, Some(1_000));
+        assert_eq!(payload.prev_ghost_time, Some(2_000));
+    }
+
+    #[test]
+    fn pong_ignores_other_messages() {
+        let mut large = MEASUREMENT_HEADER.to_vec();
+        large.push(PING);
+        put_entry(&mut large, HOST_TIME_KEY, &[0; MAX_PING_PAYLOAD]);
+        assert!(pong_message(b"session1", 0, &large).is_none());
+
+        let pong = pong_message(b"session1", 0, &ping_message(0, 0)).unwrap();
+        assert!(pong_message(b"session1", 0, &pong).is_none());
+        assert!(pong_message(b"session1", 0, b"_asdp_v\x01\x01").is_none());
+    }
+
+    #[test]
+    fn older_session_is_joined() {
+        let current = session(b"bbbbbbbb", 0);
+        assert!(joins(&current, b"cccccccc", SESSION_EPSILON_MICROS + 1));
+        assert!(!joins(&current, b"aaaaaaaa", -SESSION_EPSILON_MICROS - 1));
+    }
+
+    #[test]
+    fn session_about_as_old_is_joined_by_lower_id() {
+        let current = session(b"bbbbbbbb", 1_000_000);
+        for offset in [
+            1_000_000,
+            1_000_000 + SESSION_EPSILON_MICROS - 1,
+            1_000_000 - SESSION_EPSILON_MICROS + 1,
+        ] {
+            assert!(joins(&current, b"aaaaaaaa", offset));
+            assert!(!joins(&current, b"cccccccc", offset));
+        }
+        // Exactly epsilon apart, neither rule applies
+        assert!(!joins(
+            &current,
+            b"aaaaaaaa",
+            1_000_000 - SESSION_EPSILON_MICROS
+        ));
+        assert!(!joins(
+            &current,
+            b"cccccccc",
+            1_000_000 + SESSION_EPSILON_MICROS
+        ));
+    }
+
+    #[test]
+    fn latest_beat_origin_is_adopted() {
+        let current = Timeline::new(120.0, 1_000, 0);
+        assert!(adopts(&current, &Timeline::new(140.0, 1_001, 0)));
+        assert!(!adopts(&current, &Timeline::new(140.0, 1_000, 0)));
+        assert!(!adopts(&current, &Timeline::new(140.0, 999, 0)));
+    }
+
+    #[test]
+    fn tempo_changes_within_epsilon_are_not_passed() {
+        assert!(tempo_changed(None, 120.0));
+        assert!(!tempo_changed(Some(120.0), 120.005));
+        assert!(tempo_changed(Some(120.0), 120.02));
+        assert!(tempo_changed(Some(120.0), 119.98));
+    }
+
+    #[test]
+    fn pushed_tempo_keeps_the_beat_position() {
+        let current = Timeline::new(120.0, 0, 0);
+        let now = 10_000_000;
+        let pushed = retimed(current, 150.0, now);
+        assert_eq!(pushed.bpm(), 150.0);
+        assert!(adopts(&current, &pushed));
+        // 20 beats in at 120 BPM, and the new tempo continues from there
+        assert_eq!(pushed.beats_at(now), 20_000_000);
+        assert_eq!(pushed.time_at(pushed.beat_origin), now);
+        assert_eq!(pushed.beats_at(now + 400_000), 21_000_000);
+    }
+
+    #[test]
+    fn pushed_tempo_moves_the_beat_origin_forward() {
+        // A push at the timeline's own origin still produces a later beat origin
+        let current = Timeline::new(120.0, 5_000_000, 2_000_000);
+        let pushed = retimed(current, 100.0, 2_000_000);
+        assert_eq!(pushed.beat_origin, 5_000_001);
+        assert!(adopts(&current, &pushed));
+    }
+}
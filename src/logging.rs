@@ -0,0 +1,165 @@
+use anyhow::{Context, Result};
+use clap::{Args, ValueEnum};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tracing::level_filters::LevelFilter;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{self, Rotation};
+use tracing_subscriber::fmt;
+use tracing_subscriber::prelude::*;
+
+/// File name prefix for log files
+const LOG_FILE_PREFIX: &str = "midi-router";
+
+/// When the log file is rolled over
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogRotation {
+    /// Start a new file every minute
+    Minutely,
+    /// Start a new file every hour
+    Hourly,
+    /// Start a new file every day
+    Daily,
+    /// Start a new file when the current one reaches `--log-max-bytes`
+    Size,
+    /// Never roll over
+    Never,
+}
+
+/// File logging options
+#[derive(Debug, Clone, Args)]
+pub struct LogArgs {
+    /// Directory to write log files to (file logging is disabled if unset)
+    #[arg(long, env = "MIDI_ROUTER_LOG_DIR")]
+    pub log_dir: Option<PathBuf>,
+    /// Log file rotation policy
+    #[arg(long, env = "MIDI_ROUTER_LOG_ROTATION", value_enum, default_value_t = LogRotation::Daily)]
+    pub log_rotation: LogRotation,
+    /// Number of rotated log files to keep
+    #[arg(long, env = "MIDI_ROUTER_LOG_MAX_FILES", default_value_t = 7)]
+    pub log_max_files: usize,
+    /// Size in bytes at which the log file is rotated when using size rotation
+    #[arg(long, env = "MIDI_ROUTER_LOG_MAX_BYTES", default_value_t = 10 * 1024 * 1024)]
+    pub log_max_bytes: u64,
+}
+
+/// Initialize console logging (unless disabled), plus file logging if a log directory
+/// is configured.
+///
+/// The returned guard must be held for the lifetime of the application so that
+/// buffered log lines are flushed on exit.
+pub fn init(args: &LogArgs, console: bool) -> Result<Option<WorkerGuard>> {
+    let (file_layer, guard) = match args.log_dir {
+        Some(ref dir) => {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create log directory: {dir:?}"))?;
+
+            let (writer, guard) = match args.log_rotation {
+                LogRotation::Size => {
+                    let path = dir.join(format!("{LOG_FILE_PREFIX}.log"));
+                    let writer =
+                        SizeRotatingWriter::new(path, args.log_max_bytes, args.log_max_files)?;
+                    tracing_appender::non_blocking(writer)
+                }
+                rotation => {
+                    let rotation = match rotation {
+                        LogRotation::Minutely => Rotation::MINUTELY,
+                        LogRotation::Hourly => Rotation::HOURLY,
+                        LogRotation::Daily => Rotation::DAILY,
+                        _ => Rotation::NEVER,
+                    };
+                    let appender = rolling::Builder::new()
+                        .rotation(rotation)
+                        .filename_prefix(LOG_FILE_PREFIX)
+                        .filename_suffix("log")
+                        .max_log_files(args.log_max_files.max(1))
+                        .build(dir)
+                        .context("Failed to create rolling log file appender")?;
+                    tracing_appender::non_blocking(appender)
+                }
+            };
+
+            let layer = fmt::layer().with_writer(writer).with_ansi(false);
+            (Some(layer), Some(guard))
+        }
+        None => (None, None),
+    };
+
+    tracing_subscriber::registry()
+        .with(LevelFilter::INFO)
+        .with(console.then(fmt::layer))
+        .with(file_layer)
+        .init();
+
+    Ok(guard)
+}
+
+/// Shift `name` -> `name.1` -> `name.2` ..., keeping at most `max_files` rotated files
+pub fn rotate_files(path: &Path, max_files: usize) -> io::Result<()> {
+    let rotated = |index: usize| PathBuf::from(format!("{}.{}", path.display(), index));
+
+    if max_files == 0 {
+        return fs::remove_file(path);
+    }
+
+    let oldest = rotated(max_files);
+    if oldest.exists() {
+        fs::remove_file(&oldest)?;
+    }
+    for index in (1..max_files).rev() {
+        let from = rotated(index);
+        if from.exists() {
+            fs::rename(&from, rotated(index + 1))?;
+        }
+    }
+    fs::rename(path, rotated(1))
+}
+
+/// Log file writer that rotates once the file reaches a size limit
+struct SizeRotatingWriter {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    file: File,
+    written: u64,
+}
+
+impl SizeRotatingWriter {
+    fn new(path: PathBuf, max_bytes: u64, max_files: usize) -> Result<Self> {
+        let file =
+            Self::open(&path).with_context(|| format!("Failed to open log file: {path:?}"))?;
+        let written = file.metadata()?.len();
+
+        Ok(Self {
+            path,
+            max_bytes,
+            max_files,
+            file,
+            written,
+        })
+    }
+
+    fn open(path: &Path) -> io::Result<File> {
+        OpenOptions::new().create(true).append(true).open(path)
+    }
+}
+
+impl Write for SizeRotatingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written > 0 && self.written + buf.len() as u64 > self.max_bytes {
+            self.file.flush()?;
+            rotate_files(&self.path, self.max_files)?;
+            self.file = Self::open(&self.path)?;
+            self.written = 0;
+        }
+
+        let written = self.file.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
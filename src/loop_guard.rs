@@ -0,0 +1,136 @@
+use midi_types::MidiMessage;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Recognizes incoming messages that this router sent itself.
+///
+/// A message whose RTP MIDI packet carries the SSRC of one of this router's own
+/// sessions originated here and came back, for example through a session connected
+/// to another session of the same router. When two routers are cross-connected, a
+/// message forwarded by one comes back from the other under the other's SSRC, so
+/// each message sent to a session is also remembered for a short window, and an
+/// identical message arriving from that same session within the window is treated
+/// as looped back. System realtime messages are never matched that way, as clock
+/// and transport from other gear look exactly like the ones this router sends.
+pub struct LoopGuard {
+    window: Duration,
+    own_ssrcs: HashSet<u32>,
+    // Session name -> messages recently sent to it
+    sent: HashMap<String, VecDeque<(MidiMessage, Instant)>>,
+}
+
+impl LoopGuard {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            own_ssrcs: HashSet::new(),
+            sent: HashMap::new(),
+        }
+    }
+
+    /// Remember the SSRC of one of this router's sessions
+    pub fn add_own_ssrc(&mut self, ssrc: u32) {
+        self.own_ssrcs.insert(ssrc);
+    }
+
+    /// Whether a packet with this sender SSRC came from one of this router's sessions
+    pub fn is_own(&self, ssrc: u32) -> bool {
+        self.own_ssrcs.contains(&ssrc)
+    }
+
+    /// Remember a message sent to a session
+    pub fn record_sent(&mut self, session_name: &str, message: MidiMessage) {
+        if is_realtime(&message) {
+            return;
+        }
+        let sent = self.sent.entry(session_name.to_string()).or_default();
+        prune(sent, self.window);
+        sent.push_back((message, Instant::now()));
+    }
+
+    /// Whether a message from a session echoes one recently sent to it; each sent message matches once
+    pub fn is_echo(&mut self, session_name: &str, message: &MidiMessage) -> bool {
+        if is_realtime(message) {
+            return false;
+        }
+        let Some(sent) = self.sent.get_mut(session_name) else {
+            return false;
+        };
+        prune(sent, self.window);
+        match sent.iter().position(|(sent, _)| sent == message) {
+            Some(index) => {
+                sent.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Forget messages older than the window
+fn prune(sent: &mut VecDeque<(MidiMessage, Instant)>, window: Duration) {
+    while let Some((_, sent_at)) = sent.front() {
+        if sent_at.elapsed() <= window {
+            break;
+        }
+        sent.pop_front();
+    }
+}
+
+fn is_realtime(message: &MidiMessage) -> bool {
+    matches!(
+        message,
+        MidiMessage::TimingClock
+            | MidiMessage::Start
+            | MidiMessage::Continue
+            | MidiMessage::Stop
+            | MidiMessage::ActiveSensing
+            | MidiMessage::Reset
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use midi_types::{Channel, Program};
+
+    fn program(number: u8) -> MidiMessage {
+        MidiMessage::ProgramChange(Channel::C1, Program::from(number))
+    }
+
+    #[test]
+    fn echo_from_the_session_it_was_sent_to() {
+        let mut guard = LoopGuard::new(Duration::from_secs(60));
+        guard.record_sent("a", program(5));
+        assert!(!guard.is_echo("b", &program(5)));
+        assert!(!guard.is_echo("a", &program(6)));
+        assert!(guard.is_echo("a", &program(5)));
+        // Each sent message is matched once
+        assert!(!guard.is_echo("a", &program(5)));
+    }
+
+    #[test]
+    fn realtime_messages_are_not_echoes() {
+        let mut guard = LoopGuard::new(Duration::from_secs(60));
+        guard.record_sent("a", MidiMessage::TimingClock);
+        guard.record_sent("a", MidiMessage::Start);
+        assert!(!guard.is_echo("a", &MidiMessage::TimingClock));
+        assert!(!guard.is_echo("a", &MidiMessage::Start));
+    }
+
+    #[test]
+    fn echoes_expire_after_the_window() {
+        let mut guard = LoopGuard::new(Duration::ZERO);
+        guard.record_sent("a", program(5));
+        std::thread::sleep(Duration::from_millis(2));
+        assert!(!guard.is_echo("a", &program(5)));
+    }
+
+    #[test]
+    fn own_ssrcs() {
+        let mut guard = LoopGuard::new(Duration::ZERO);
+        guard.add_own_ssrc(0x1234);
+        assert!(guard.is_own(0x1234));
+        assert!(!guard.is_own(0x5678));
+    }
+}
@@ -1,109 +1,302 @@
-mod config;
-mod device;
-mod mapping;
+mod audit;
+mod automation;
+mod bank;
+mod bundle;
+mod cli;
+mod clock;
+mod config_diff;
+#[cfg(feature = "http")]
+mod config_editor;
+mod context;
+mod cooldown;
+mod cue;
+#[cfg(feature = "mdns")]
+mod discovery;
+mod events;
+mod freeze;
+mod graph;
+#[cfg(feature = "http")]
+mod health;
+mod hot_reload;
+mod identity;
+mod import;
+mod inactivity;
+#[cfg(feature = "link")]
+mod link;
+mod logging;
+mod loop_guard;
+mod ordering;
+#[cfg(feature = "osc-input")]
 mod osc_listener;
+#[cfg(feature = "osc-input")]
+mod osc_match;
+mod osc_pool;
+mod osc_queue;
+mod overload;
+mod pacing;
+mod placeholder;
+mod plugins;
 mod processor;
+mod random;
+mod remote_health;
 mod router;
+mod selftest;
+mod sequence;
+mod sequencer;
+mod service;
 mod session_manager;
+mod settle;
+mod sinks;
+mod sniff;
+mod status;
+mod supervisor;
+mod tempo;
+#[cfg(any(feature = "http", feature = "osc-input"))]
+mod tempo_stats;
+mod time_source;
+mod timed;
+mod trigger;
+#[cfg(feature = "tui")]
+mod tui;
+mod validate;
+mod variables;
+mod virtual_ports;
+mod wire;
 
+use midi_router::{
+    command_dsl, config, device, mapping, midi_fields, plugin, secrets, sysex, validator,
+};
+
+use crate::cli::{Cli, CliCommand, EncryptSecretArgs, RunArgs};
 use crate::config::ConfigLoader;
-use crate::device::DeviceConfig;
-use crate::mapping::MapConfig;
-use crate::osc_listener::OscListener;
-use crate::processor::MidiProcessor;
-use crate::router::MidiRouter;
-use crate::session_manager::SessionManager;
+use crate::context::{ContextManager, DEFAULT_CONTEXT, RoutingContext};
+#[cfg(feature = "http")]
+use crate::health::HealthServer;
+use crate::status::{ReloadResult, RouterStatus};
+#[cfg(feature = "http")]
+use anyhow::Context;
 use anyhow::Result;
+use clap::Parser;
 use std::sync::Arc;
-use tokio::sync::RwLock;
 use tracing::{error, info};
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Initialize logging
-    tracing_subscriber::fmt::init();
-
-    info!("Starting MIDI Router application");
+    let cli = Cli::parse();
 
-    // Load configurations
-    let device_config = load_or_create_device_config().await?;
-    let map_config = load_or_create_map_config().await?;
+    // Initialize logging; the guard flushes file logs on exit. The terminal display
+    // owns the console, so it only logs to files.
+    #[cfg(feature = "tui")]
+    let console = !matches!(cli.command, Some(CliCommand::Tui));
+    #[cfg(not(feature = "tui"))]
+    let console = true;
+    let _log_guard = logging::init(&cli.logging, console)?;
 
-    // Wrap in Arc<RwLock> for shared access
-    let device_config = Arc::new(RwLock::new(device_config));
-    let map_config = Arc::new(RwLock::new(map_config));
+    match cli.command {
+        Some(CliCommand::InstallService(args)) => service::install(&args),
+        #[cfg(windows)]
+        Some(CliCommand::RunService(args)) => {
+            let run_args = cli.run.clone();
+            tokio::task::spawn_blocking(move || service::run_windows_service(&args, run_args))
+                .await?
+        }
+        #[cfg(feature = "http")]
+        Some(CliCommand::Healthcheck) => {
+            let port = cli
+                .run
+                .health_port
+                .context("--health-port (or MIDI_ROUTER_HEALTH_PORT) must be set")?;
+            health::check(port).await
+        }
+        Some(CliCommand::DiffConfig(args)) => config_diff::run(&args, &cli.run),
+        Some(CliCommand::Graph(args)) => graph::run(&args, &cli.run),
+        #[cfg(feature = "mdns")]
+        Some(CliCommand::Discover(args)) => discovery::run(&args).await,
+        Some(CliCommand::ImportPrograms(args)) => import::run(&args, &cli.run),
+        Some(CliCommand::EncryptSecret(args)) => encrypt_secret(&args),
+        Some(CliCommand::Bundle(args)) => bundle::run(&args, &cli.run),
+        Some(CliCommand::Validate) => validate::run(&cli.run),
+        Some(CliCommand::Selftest) => selftest::run(&cli.run).await,
+        #[cfg(feature = "tui")]
+        Some(CliCommand::Tui) => {
+            let status = Arc::new(RouterStatus::new());
+            status.set_console(false);
+            run_with_status(&cli.run, status.clone(), tui::run(status)).await
+        }
+        None => run(&cli.run, shutdown_signal()).await,
+    }
+}
 
-    // Create MIDI processor
-    let mut processor = MidiProcessor::new(device_config.clone(), map_config.clone())?;
+/// Run the router until the shutdown future completes
+async fn run(args: &RunArgs, shutdown: impl Future<Output = ()>) -> Result<()> {
+    run_with_status(args, Arc::new(RouterStatus::new()), shutdown).await
+}
 
-    // Create session manager
-    let session_manager = SessionManager::new();
+/// Run the router, reporting its state through `status`, until the shutdown future completes
+async fn run_with_status(
+    args: &RunArgs,
+    status: Arc<RouterStatus>,
+    shutdown: impl Future<Output = ()>,
+) -> Result<()> {
+    info!("Starting MIDI Router application");
+    if args.read_only {
+        info!("Read-only mode: control requests will be rejected");
+        status.set_read_only(true);
+    }
 
-    // Set up processor with session manager
-    processor.set_session_manager(session_manager.clone());
+    // Either one context from the config flags, or every context in the contexts file
+    let mut context = match args.contexts {
+        Some(ref contexts_path) => {
+            status
+                .set_config_hash(ConfigLoader::content_hash(&[contexts_path])?)
+                .await;
+            let mut manager = ContextManager::new(contexts_path, status.clone(), args.hot_reload);
+            manager.sync().await?;
+            manager.watch();
+            None
+        }
+        None => {
+            status
+                .set_config_hash(ConfigLoader::content_hash(&[
+                    &args.device_config,
+                    &args.map_config,
+                ])?)
+                .await;
+            Some(
+                RoutingContext::start(
+                    DEFAULT_CONTEXT,
+                    &args.device_config,
+                    &args.map_config,
+                    status.clone(),
+                    args.hot_reload,
+                )
+                .await?,
+            )
+        }
+    };
+    status
+        .set_last_reload(ReloadResult::new(true, "Loaded configs"))
+        .await;
 
-    let processor = Arc::new(processor);
+    // Serve the health endpoint
+    #[cfg(feature = "http")]
+    if let Some(port) = args.health_port {
+        HealthServer::new(port, status.clone(), args.health_token.clone())
+            .start()
+            .await?;
+        if status.prints_to_console() {
+            println!("  health    {:>5}/tcp              /healthz", port);
+        }
+    }
+    #[cfg(not(feature = "http"))]
+    if args.health_port.is_some() {
+        tracing::warn!("--health-port ignored: built without the `http` feature");
+    }
 
-    // Create MIDI router
-    let mut router = MidiRouter::new(processor.clone(), session_manager);
+    // SIGUSR1 freezes or thaws every output
+    #[cfg(unix)]
+    tokio::spawn(toggle_freeze_on_signal(status.clone()));
 
-    // Initialize RTP MIDI sessions
-    {
-        let map_config_read = map_config.read().await;
-        router.initialize_sessions(&map_config_read).await?;
-    }
+    info!("MIDI Router ready");
 
-    // Initialize OSC listeners
-    {
-        let map_config_read = map_config.read().await;
-        if !map_config_read.osc_sources.is_empty() {
-            let osc_listener = OscListener::new(processor.clone());
-            osc_listener
-                .start_listeners(&map_config_read.osc_sources)
-                .await?;
-            info!(
-                "Started {} OSC listeners",
-                map_config_read.osc_sources.len()
-            );
+    // Keep the application running, restarting the context when a reload is requested
+    tokio::pin!(shutdown);
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => break,
+            // A contexts file reloads its own contexts
+            _ = status.reload_requested(), if args.contexts.is_none() => {
+                if let Some(running) = context.take() {
+                    running.stop().await;
+                }
+                let result = match RoutingContext::start(
+                    DEFAULT_CONTEXT,
+                    &args.device_config,
+                    &args.map_config,
+                    status.clone(),
+                    args.hot_reload,
+                )
+                .await
+                {
+                    Ok(running) => {
+                        context = Some(running);
+                        if let Ok(hash) =
+                            ConfigLoader::content_hash(&[&args.device_config, &args.map_config])
+                        {
+                            status.set_config_hash(hash).await;
+                        }
+                        ReloadResult::new(true, "Reloaded configs")
+                    }
+                    Err(e) => {
+                        error!("Failed to reload configs: {}", e);
+                        ReloadResult::new(false, e.to_string())
+                    }
+                };
+                status.set_last_reload(result).await;
+            }
         }
     }
-
-    let session_count = router.get_session_names().await.len();
-    info!("MIDI Router ready with {session_count} sessions");
-
-    // Keep the application running
-    tokio::signal::ctrl_c().await?;
     info!("Shutting down MIDI Router");
 
+    if let Some(context) = context {
+        context.stop().await;
+    }
+
     Ok(())
 }
 
-async fn load_or_create_device_config() -> Result<DeviceConfig> {
-    let path = "config/devices.json";
+/// Encrypt the value on stdin and print the config string for it
+fn encrypt_secret(args: &EncryptSecretArgs) -> Result<()> {
+    let mut plaintext = String::new();
+    std::io::stdin().read_line(&mut plaintext)?;
+    let plaintext = plaintext.trim_end_matches(['\r', '\n']);
+    println!("{}", secrets::encrypt(&args.recipient, plaintext)?);
+    Ok(())
+}
 
-    match ConfigLoader::load_device_config(path) {
-        Ok(config) => {
-            info!("Loaded device configuration from {}", path);
-            Ok(config)
-        }
+/// Freeze every output on SIGUSR1, or thaw everything if output is frozen
+#[cfg(unix)]
+async fn toggle_freeze_on_signal(status: Arc<RouterStatus>) {
+    use tokio::signal::unix::{SignalKind, signal};
+    let mut sigusr1 = match signal(SignalKind::user_defined1()) {
+        Ok(sigusr1) => sigusr1,
         Err(e) => {
-            error!("Failed to load device config: {e}.");
-            Err(e)
+            error!("Failed to listen for SIGUSR1: {e}");
+            return;
         }
+    };
+    while sigusr1.recv().await.is_some() {
+        status.output_freeze().toggle();
     }
 }
 
-async fn load_or_create_map_config() -> Result<MapConfig> {
-    let path = "config/map.json";
-
-    match ConfigLoader::load_map_config(path) {
-        Ok(config) => {
-            info!("Loaded map configuration from {}", path);
-            Ok(config)
+/// Resolve when Ctrl-C or (on Unix) SIGTERM is received
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("Failed to listen for Ctrl-C: {e}");
         }
-        Err(e) => {
-            error!("Failed to load map config: {e}");
-            Err(e)
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                error!("Failed to listen for SIGTERM: {e}");
+                std::future::pending::<()>().await;
+            }
         }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
     }
 }
@@ -1,17 +1,196 @@
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use crate::command_dsl;
+use crate::config::KnownKeys;
+use crate::device::{Command, DisplayProtocol, OscArg};
+use crate::midi_fields::{self, MidiFields, MidiMessageKind};
+use crate::sysex::SysExPattern;
+use anyhow::Result;
+use serde::de::value::MapAccessDeserializer;
+use serde::de::{MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
 
 /// RTP MIDI session configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RtpMidiSession {
     /// Session name
     pub name: String,
-    /// Port to listen on
+    /// Alternative names that destinations can use to refer to this session
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// Port to listen on (required unless `send_only` is set)
+    #[serde(default)]
     pub port: u16,
     /// Whether this session should be created as a listener
+    #[serde(default)]
     pub listen: bool,
+    /// Only drive remote sessions: use any free local ports and never process input
+    #[serde(default)]
+    pub send_only: bool,
     /// Remote sessions to connect to (if any)
+    #[serde(default)]
     pub connect_to: Vec<RtpMidiRemote>,
+    /// What to do with outgoing messages while no participant is connected
+    #[serde(default)]
+    pub offline_policy: OfflinePolicy,
+    /// Minimum time between outgoing messages, for gear that drops messages sent back-to-back
+    #[serde(default)]
+    pub min_message_gap_ms: u64,
+    /// Incoming messages buffered for processing before new ones are dropped
+    #[serde(default = "default_ingest_capacity")]
+    pub ingest_capacity: usize,
+    /// Seconds without a successful clock sync before a remote is reported as dead
+    #[serde(default = "default_clock_sync_timeout_secs")]
+    pub clock_sync_timeout_secs: u64,
+    /// Send the last Program Change, Control Change values and tempo again when a
+    /// participant rejoins after dropping out
+    #[serde(default)]
+    pub resend_on_rejoin: bool,
+    /// Which end starts CK (clock sync) exchanges with participants
+    #[serde(default)]
+    pub clock_sync_role: ClockSyncRole,
+    /// Remote to leave clock sync to: a `connect_to` name or a `host[:port]` address
+    #[serde(default)]
+    pub clock_master: Option<String>,
+    /// Retrying invitations to remotes that are not connected
+    #[serde(default)]
+    pub invite_retry: InviteRetry,
+    /// Log the raw bytes of every message sent and received at debug level from startup
+    #[serde(default)]
+    pub sniff: bool,
+    /// Fixed SSRC for the session (persisted or random if unset)
+    #[serde(default)]
+    pub ssrc: Option<u32>,
+    /// Channels of incoming messages treated as other channels, e.g. `{ "1": 7 }` (1-16)
+    #[serde(default)]
+    pub receive_channel_map: BTreeMap<u8, u8>,
+    /// Whether the session is created; disabled sessions keep their config but don't start
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Limits on controller values sent, whatever the mappings compute
+    #[serde(default)]
+    pub value_clamps: Vec<ValueClamp>,
+    /// Limit on the rate of outgoing packets, for weak network links (optional)
+    #[serde(default)]
+    pub shaping: Option<OutboundShaping>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// Retry and circuit breaker settings for invitations to remotes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InviteRetry {
+    /// Delay before the first retry; doubled after every failure
+    pub initial_backoff_ms: u64,
+    /// Longest delay between retries
+    pub max_backoff_ms: u64,
+    /// Failed invitations in a row before invitations are paused
+    pub failure_threshold: u32,
+    /// Seconds to pause invitations before a single trial invitation
+    pub cooldown_secs: u64,
+}
+
+impl Default for InviteRetry {
+    fn default() -> Self {
+        Self {
+            initial_backoff_ms: 1000,
+            max_backoff_ms: 60_000,
+            failure_threshold: 5,
+            cooldown_secs: 300,
+        }
+    }
+}
+
+fn default_clock_sync_timeout_secs() -> u64 {
+    30
+}
+
+fn default_ingest_capacity() -> usize {
+    1024
+}
+
+/// Safe range of a controller's values, enforced on every Control Change sent to a
+/// session or virtual port
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValueClamp {
+    /// Controller limited
+    pub controller: u8,
+    /// MIDI channel limited (1-16, every channel if unset)
+    #[serde(default)]
+    pub channel: Option<u8>,
+    /// Lowest value sent
+    #[serde(default)]
+    pub min: u8,
+    /// Highest value sent
+    #[serde(default = "default_clamp_max")]
+    pub max: u8,
+}
+
+fn default_clamp_max() -> u8 {
+    127
+}
+
+impl ValueClamp {
+    /// Whether the clamp limits a controller on a channel (1-16)
+    pub fn covers(&self, channel: u8, controller: u8) -> bool {
+        self.controller == controller && self.channel.is_none_or(|c| c == channel)
+    }
+
+    /// Value sent in place of a computed value
+    pub fn apply(&self, value: u8) -> u8 {
+        value.clamp(self.min, self.max.max(self.min))
+    }
+}
+
+/// Rate limit on the packets sent through a session, so dense automation doesn't
+/// overrun a consumer-grade WiFi link
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboundShaping {
+    /// Packets sent per second on average
+    pub max_packets_per_sec: u32,
+    /// Packets that can go out back-to-back after a quiet spell
+    #[serde(default = "default_max_burst")]
+    pub max_burst: u32,
+    /// Longest a packet waits for its turn before it is dropped instead
+    #[serde(default = "default_max_shaping_delay_ms")]
+    pub max_delay_ms: u64,
+}
+
+fn default_max_burst() -> u32 {
+    1
+}
+
+fn default_max_shaping_delay_ms() -> u64 {
+    250
+}
+
+/// Role of the router in a session's CK (clock sync) exchanges
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClockSyncRole {
+    /// Whoever sent the invitation starts clock sync
+    #[default]
+    Auto,
+    /// Start clock sync with every participant, including ones that invited the router
+    Master,
+    /// Never start clock sync, only answer it
+    Slave,
+}
+
+/// Handling of outgoing messages for a session with no connected participants
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OfflinePolicy {
+    /// Send anyway
+    Send,
+    /// Discard the message
+    Drop,
+    /// Keep the latest Program Change per channel and Control Change per controller,
+    /// and send them when a participant connects
+    #[default]
+    QueueLatest,
 }
 
 /// Remote RTP MIDI session to connect to
@@ -32,6 +211,124 @@ pub struct OscDestination {
     pub host: String,
     /// Destination port
     pub port: u16,
+    /// Buffer messages while the destination is unreachable (optional)
+    pub offline_queue: Option<OscQueueConfig>,
+    /// Further endpoints that make this destination a pool with `host`/`port` first
+    #[serde(default)]
+    pub endpoints: Vec<OscEndpoint>,
+    /// How messages are spread across the pool's endpoints
+    #[serde(default)]
+    pub delivery: DeliveryPolicy,
+    /// Prepended to the address of every message sent here (e.g. `/console/2`)
+    #[serde(default)]
+    pub address_prefix: Option<String>,
+    /// Arguments inserted before the command's own arguments (e.g. a device ID)
+    #[serde(default)]
+    pub args_before: Vec<OscArg>,
+    /// Arguments appended after the command's own arguments
+    #[serde(default)]
+    pub args_after: Vec<OscArg>,
+    /// Send messages in bundles timetagged this far ahead, so peers act on them together (optional)
+    #[serde(default)]
+    pub bundle_latency_ms: Option<u64>,
+}
+
+impl OscDestination {
+    /// Address and arguments of a message after applying this destination's template
+    pub fn apply_template(&self, address: &str, args: &[OscArg]) -> (String, Vec<OscArg>) {
+        let address = match self.address_prefix {
+            Some(ref prefix) => format!("{}{}", prefix.trim_end_matches('/'), address),
+            None => address.to_string(),
+        };
+        let args = self
+            .args_before
+            .iter()
+            .chain(args)
+            .chain(&self.args_after)
+            .cloned()
+            .collect();
+        (address, args)
+    }
+
+    /// Every endpoint of the destination, starting with `host`/`port`
+    pub fn addresses(&self) -> Vec<String> {
+        std::iter::once(format!("{}:{}", self.host, self.port))
+            .chain(
+                self.endpoints
+                    .iter()
+                    .map(|endpoint| format!("{}:{}", endpoint.host, endpoint.port)),
+            )
+            .collect()
+    }
+}
+
+/// Additional endpoint of an OSC destination pool
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OscEndpoint {
+    /// Endpoint host
+    pub host: String,
+    /// Endpoint port
+    pub port: u16,
+}
+
+/// Delivery of messages to the endpoints of an OSC destination
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryPolicy {
+    /// Send every message to every endpoint (e.g. primary and backup playback)
+    #[default]
+    All,
+    /// Send each message to the next endpoint in turn
+    RoundRobin,
+    /// Send to the first endpoint that accepts the message
+    Failover,
+}
+
+/// Offline queue settings for an OSC destination
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OscQueueConfig {
+    /// Maximum number of queued messages (one per OSC address)
+    #[serde(default = "default_osc_queue_max_messages")]
+    pub max_messages: usize,
+    /// Queued messages older than this are discarded instead of replayed
+    #[serde(default = "default_osc_queue_max_age_ms")]
+    pub max_age_ms: u64,
+    /// OSC address of a query the destination answers (e.g. `/xinfo` on an X32), sent
+    /// every second to tell whether it is reachable
+    pub heartbeat_address: String,
+    /// The destination is unreachable once it hasn't sent anything back for this long
+    #[serde(default = "default_osc_queue_heartbeat_timeout_ms")]
+    pub heartbeat_timeout_ms: u64,
+}
+
+fn default_osc_queue_max_messages() -> usize {
+    32
+}
+
+fn default_osc_queue_max_age_ms() -> u64 {
+    60_000
+}
+
+fn default_osc_queue_heartbeat_timeout_ms() -> u64 {
+    3000
+}
+
+/// Virtual MIDI ports that local applications such as DAWs connect to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VirtualPort {
+    /// Name used in destinations and as the source of incoming messages; the
+    /// ports appear to other applications as "<name> In" and "<name> Out"
+    pub name: String,
+    /// Whether messages sent to the "In" port are processed
+    #[serde(default = "default_virtual_port_listen")]
+    pub listen: bool,
+    /// Limits on controller values sent, whatever the mappings compute
+    #[serde(default)]
+    pub value_clamps: Vec<ValueClamp>,
+}
+
+fn default_virtual_port_listen() -> bool {
+    true
 }
 
 /// OSC listening source configuration
@@ -41,11 +338,23 @@ pub struct OscSource {
     pub name: String,
     /// Port to listen on for incoming OSC messages
     pub port: u16,
+    /// Sender IPs or CIDR ranges accepted by this source (any sender if empty)
+    #[serde(default)]
+    pub allowed_senders: Vec<String>,
+    /// OSC address prefixes accepted by this source (any address if empty)
+    #[serde(default)]
+    pub allowed_addresses: Vec<String>,
+    /// Ports after `port` to try in turn if it is already in use (fail if 0)
+    #[serde(default)]
+    pub port_fallback: u16,
+    /// Whether the source is listened on
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
 }
 
 /// Destination for commands
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(tag = "type")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", remote = "Self")]
 pub enum Destination {
     /// Send to RTP MIDI session
     #[serde(rename = "rtp_midi")]
@@ -53,6 +362,52 @@ pub enum Destination {
     /// Send to OSC destination (by name reference)
     #[serde(rename = "osc")]
     Osc { destination_name: String },
+    /// Send to every member of a destination group (by name reference)
+    #[serde(rename = "group")]
+    Group { group_name: String },
+}
+
+impl Serialize for Destination {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Destination::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Destination {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct DestinationVisitor;
+
+        impl<'de> Visitor<'de> for DestinationVisitor {
+            type Value = Destination;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a destination object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Destination, A::Error> {
+                let map = KnownKeys::new(map, |key| Destination::FIELDS.contains(&key));
+                Destination::deserialize(MapAccessDeserializer::new(map))
+            }
+        }
+
+        deserializer.deserialize_map(DestinationVisitor)
+    }
+}
+
+impl Destination {
+    /// Names of the fields of every destination type, as they appear in a destination object
+    const FIELDS: &[&str] = &["type", "session_name", "destination_name", "group_name"];
+
+    /// Whether a command can be sent here: OSC commands to OSC destinations and
+    /// everything else to RTP MIDI sessions. Groups pass each command to the members
+    /// that can send it.
+    pub fn carries(&self, command: &Command) -> bool {
+        match self {
+            Destination::RtpMidi { .. } => !matches!(command, Command::Osc { .. }),
+            Destination::Osc { .. } => matches!(command, Command::Osc { .. }),
+            Destination::Group { .. } => true,
+        }
+    }
 }
 
 /// Device mapping - associates a device with input channel and output destination
@@ -66,17 +421,1365 @@ pub struct DeviceMapping {
     pub send_channel: Option<u8>,
     /// Destination for commands from this device
     pub destination: Destination,
+    /// Whether activations are written to the audit log
+    #[serde(default)]
+    pub audit: bool,
+    /// Bank this mapping belongs to (active in every bank if unset)
+    pub bank: Option<String>,
+    /// Show the selected program's name on a controller display (optional)
+    pub display: Option<ProgramDisplay>,
+    /// Ignore repeat activations within this many milliseconds (optional)
+    #[serde(default)]
+    pub cooldown_ms: Option<u64>,
+    /// Wait this many milliseconds after a Program Change and run only the last
+    /// program selected in the meantime (optional)
+    #[serde(default)]
+    pub settle_ms: Option<u64>,
+    /// Rank among mappings matching the same Program Change, for the `first` and `priority` match
+    /// policies (higher first)
+    #[serde(default)]
+    pub priority: i32,
+}
+
+/// Where to show the name of a selected program
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProgramDisplay {
+    /// Display protocol of the controller
+    pub protocol: DisplayProtocol,
+    /// Destination of the controller
+    pub destination: Destination,
+}
+
+/// A single input condition that participates in a combinatorial trigger
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Trigger {
+    /// Note held down on a channel
+    #[serde(rename = "note")]
+    Note { channel: u8, note: u8 },
+    /// Control Change at or above a threshold on a channel
+    #[serde(rename = "control_change")]
+    ControlChange {
+        channel: u8,
+        controller: u8,
+        threshold: u8,
+    },
+}
+
+/// Note mapping - runs a device program or commands when a note is played
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NoteMapping {
+    /// Unique name for the note mapping
+    pub name: String,
+    /// MIDI channel to listen on (1-16)
+    pub listen_channel: u8,
+    /// Note number that triggers the mapping
+    pub note: u8,
+    /// Fire on Note Off instead of Note On
+    #[serde(default)]
+    pub on_release: bool,
+    /// What the note runs
+    pub action: NoteAction,
+    /// MIDI channel to send commands on (1-16) for MIDI destinations
+    pub send_channel: Option<u8>,
+    /// Destination for the commands
+    pub destination: Destination,
+    /// Whether activations are written to the audit log
+    #[serde(default)]
+    pub audit: bool,
+    /// Bank this mapping belongs to (active in every bank if unset)
+    pub bank: Option<String>,
+    /// Ignore repeat activations within this many milliseconds (optional)
+    #[serde(default)]
+    pub cooldown_ms: Option<u64>,
+    /// Rank among mappings matching the same note, for the `first` and `priority` match
+    /// policies (higher first)
+    #[serde(default)]
+    pub priority: i32,
+}
+
+/// What a note mapping runs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum NoteAction {
+    /// Run the commands of a device's program
+    #[serde(rename = "program")]
+    Program { device_id: String, program: u8 },
+    /// Run a list of commands
+    #[serde(rename = "commands")]
+    Commands { commands: Vec<Command> },
+}
+
+/// Note program rule - converts notes in a range into Program Changes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NoteProgramRule {
+    /// Unique name for the rule
+    pub name: String,
+    /// MIDI channel to listen on (1-16)
+    pub listen_channel: u8,
+    /// Lowest note converted
+    pub first_note: u8,
+    /// Highest note converted
+    pub last_note: u8,
+    /// Subtracted from the note number to get the program (`first_note` if unset)
+    pub offset: Option<u8>,
+    /// MIDI channel to send the Program Change on (1-16)
+    pub send_channel: Option<u8>,
+    /// Destination for the Program Change
+    pub destination: Destination,
+    /// Bank this rule belongs to (active in every bank if unset)
+    pub bank: Option<String>,
+}
+
+impl NoteProgramRule {
+    /// Program a note converts to, if the note is in range
+    pub fn program_for(&self, note: u8) -> Option<u8> {
+        if !(self.first_note..=self.last_note).contains(&note) {
+            return None;
+        }
+        note.checked_sub(self.offset.unwrap_or(self.first_note))
+    }
+}
+
+/// Note forwarding rule - passes notes in a range through to a destination
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NoteForwardRule {
+    /// Unique name for the rule
+    pub name: String,
+    /// MIDI channel to listen on (1-16)
+    pub listen_channel: u8,
+    /// Lowest note forwarded
+    #[serde(default)]
+    pub first_note: u8,
+    /// Highest note forwarded
+    #[serde(default = "default_last_note")]
+    pub last_note: u8,
+    /// Send every Note On with this velocity instead of the played one (optional)
+    #[serde(default)]
+    pub fixed_velocity: Option<u8>,
+    /// Raise played velocities below this to it (optional)
+    #[serde(default)]
+    pub min_velocity: Option<u8>,
+    /// MIDI channel to send the notes on (1-16)
+    pub send_channel: Option<u8>,
+    /// Destination for the notes
+    pub destination: Destination,
+    /// Bank this rule belongs to (active in every bank if unset)
+    pub bank: Option<String>,
+}
+
+fn default_last_note() -> u8 {
+    127
+}
+
+impl NoteForwardRule {
+    /// Whether the rule forwards a note
+    pub fn covers(&self, note: u8) -> bool {
+        (self.first_note..=self.last_note).contains(&note)
+    }
+
+    /// Velocity a forwarded Note On is sent with
+    pub fn velocity(&self, velocity: u8) -> u8 {
+        match self.fixed_velocity {
+            Some(fixed) => fixed,
+            None => velocity.max(self.min_velocity.unwrap_or(0)),
+        }
+    }
+}
+
+/// Control Change forwarding rule - passes a controller through to a destination,
+/// converting between absolute values and relative encoder formats
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ControlForwardRule {
+    /// Unique name for the rule
+    pub name: String,
+    /// MIDI channel to listen on (1-16)
+    pub listen_channel: u8,
+    /// Controller forwarded
+    pub controller: u8,
+    /// Controller to send on (`controller` if unset)
+    #[serde(default)]
+    pub send_controller: Option<u8>,
+    /// Format of incoming values
+    #[serde(default)]
+    pub input_mode: ControllerMode,
+    /// Format of sent values
+    #[serde(default)]
+    pub output_mode: ControllerMode,
+    /// MIDI channel to send the Control Change on (1-16)
+    pub send_channel: Option<u8>,
+    /// Destination for the Control Changes
+    pub destination: Destination,
+    /// Bank this rule belongs to (active in every bank if unset)
+    pub bank: Option<String>,
+}
+
+impl ControlForwardRule {
+    /// Value to send for an incoming value (`None` if there is nothing to send) and the
+    /// new absolute position, given the position after the previous value (if any)
+    pub fn convert(&self, value: u8, position: Option<u8>) -> (Option<u8>, u8) {
+        let (delta, position) = match self.input_mode.decode(value) {
+            None => (position.map(|p| i16::from(value) - i16::from(p)), value),
+            Some(delta) => {
+                let start = i16::from(position.unwrap_or(64));
+                (Some(delta), (start + delta).clamp(0, 127) as u8)
+            }
+        };
+        let output = match self.output_mode {
+            ControllerMode::Absolute => Some(position),
+            mode => delta
+                .filter(|delta| *delta != 0)
+                .map(|delta| mode.encode(delta)),
+        };
+        (output, position)
+    }
+}
+
+/// Controller value format
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ControllerMode {
+    /// Absolute value 0-127
+    #[default]
+    Absolute,
+    /// Relative, 1-63 up and 127 (-1) down to 65 (-63)
+    TwosComplement,
+    /// Relative, 1-63 up and 65 (-1) to 127 (-63) down
+    SignedBit,
+    /// Relative, 65-127 up and 63 (-1) down to 1 (-63) around 64
+    Offset64,
+}
+
+impl ControllerMode {
+    /// Change encoded by a relative value, or `None` for absolute values
+    pub fn decode(self, value: u8) -> Option<i16> {
+        let value = i16::from(value & 0x7F);
+        match self {
+            ControllerMode::Absolute => None,
+            ControllerMode::TwosComplement if value >= 64 => Some(value - 128),
+            ControllerMode::TwosComplement => Some(value),
+            ControllerMode::SignedBit if value >= 64 => Some(64 - value),
+            ControllerMode::SignedBit => Some(value),
+            ControllerMode::Offset64 => Some(value - 64),
+        }
+    }
+
+    /// Relative value encoding a change, limited to 63 steps either way
+    pub fn encode(self, delta: i16) -> u8 {
+        let delta = delta.clamp(-63, 63);
+        let value = match self {
+            ControllerMode::Absolute => delta.max(0),
+            ControllerMode::TwosComplement if delta < 0 => 128 + delta,
+            ControllerMode::SignedBit if delta < 0 => 64 - delta,
+            ControllerMode::Offset64 => 64 + delta,
+            ControllerMode::TwosComplement | ControllerMode::SignedBit => delta,
+        };
+        value as u8
+    }
+}
+
+/// Banked program rule - a bank Control Change and a program select a patch beyond 128
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BankedProgramRule {
+    /// Unique name for the rule
+    pub name: String,
+    /// MIDI channel to listen on (1-16)
+    pub listen_channel: u8,
+    /// Controller whose value selects the bank
+    pub bank_controller: u8,
+    /// Message that selects the program within the bank and sends the patch
+    pub program_source: ProgramSource,
+    /// Programs in each incoming bank
+    #[serde(default = "default_bank_size")]
+    pub bank_size: u16,
+    /// Send the patch as Bank Select (CC 0) and Program Change instead of only a Program Change
+    #[serde(default = "default_bank_select")]
+    pub bank_select: bool,
+    /// Programs in each bank of the destination device
+    #[serde(default = "default_bank_size")]
+    pub output_bank_size: u16,
+    /// MIDI channel to send on (1-16)
+    pub send_channel: Option<u8>,
+    /// Destination for the composed program
+    pub destination: Destination,
+}
+
+impl BankedProgramRule {
+    /// Bank Select value and program to send for a bank and a program within it
+    pub fn compose(&self, bank: u8, program: u8) -> Option<(Option<u8>, u8)> {
+        if u16::from(program) >= self.bank_size {
+            return None;
+        }
+        let patch = u32::from(bank) * u32::from(self.bank_size) + u32::from(program);
+        if !self.bank_select {
+            return u8::try_from(patch)
+                .ok()
+                .filter(|patch| *patch < 128)
+                .map(|patch| (None, patch));
+        }
+        let output_bank_size = u32::from(self.output_bank_size.clamp(1, 128));
+        let output_bank = u8::try_from(patch / output_bank_size)
+            .ok()
+            .filter(|bank| *bank < 128)?;
+        Some((Some(output_bank), (patch % output_bank_size) as u8))
+    }
+}
+
+fn default_bank_size() -> u16 {
+    128
+}
+
+fn default_bank_select() -> bool {
+    true
+}
+
+/// Message that selects the program of a banked program rule
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ProgramSource {
+    /// A Program Change on the listen channel
+    #[serde(rename = "program_change")]
+    ProgramChange,
+    /// A Control Change on the listen channel
+    #[serde(rename = "control_change")]
+    ControlChange { controller: u8 },
+}
+
+/// Combo mapping - runs commands when several triggers are active at once
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComboMapping {
+    /// Human-readable name for the combo
+    pub name: String,
+    /// Triggers that must all be active
+    pub triggers: Vec<Trigger>,
+    /// Maximum time between the first and last trigger activation
+    #[serde(default = "default_combo_window_ms")]
+    pub window_ms: u64,
+    /// MIDI channel to send commands on (1-16) for MIDI destinations
+    pub send_channel: Option<u8>,
+    /// Commands to execute when the combo fires
+    pub commands: Vec<Command>,
+    /// Destination for the commands
+    pub destination: Destination,
+    /// Whether activations are written to the audit log
+    #[serde(default)]
+    pub audit: bool,
+    /// Bank this mapping belongs to (active in every bank if unset)
+    pub bank: Option<String>,
+    /// Ignore repeat activations within this many milliseconds (optional)
+    #[serde(default)]
+    pub cooldown_ms: Option<u64>,
+}
+
+fn default_combo_window_ms() -> u64 {
+    50
+}
+
+/// Feedback sent back to a controller (e.g. to light LEDs) when router state changes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Feedback {
+    /// MIDI channel to send commands on (1-16) for MIDI destinations
+    pub send_channel: Option<u8>,
+    /// Commands to send
+    pub commands: Vec<Command>,
+    /// Destination for the commands, usually the controller's session
+    pub destination: Destination,
+}
+
+/// One step of a step mapping
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Step {
+    /// Human-readable name for the step
+    pub name: String,
+    /// Commands to execute when this step is reached
+    pub commands: Vec<Command>,
+    /// Feedback to send when this step is reached (optional)
+    pub feedback: Option<Feedback>,
+}
+
+/// Step mapping - each activation of the trigger runs the next step in order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepMapping {
+    /// Unique name for the step mapping
+    pub name: String,
+    /// Trigger that advances to the next step
+    pub trigger: Trigger,
+    /// Trigger that returns the sequence to the first step (optional)
+    pub reset_trigger: Option<Trigger>,
+    /// Whether to return to the first step after the last one
+    #[serde(default = "default_wrap")]
+    pub wrap: bool,
+    /// MIDI channel to send commands on (1-16) for MIDI destinations
+    pub send_channel: Option<u8>,
+    /// Ordered steps to cycle through
+    pub steps: Vec<Step>,
+    /// Destination for the commands
+    pub destination: Destination,
+    /// Whether activations are written to the audit log
+    #[serde(default)]
+    pub audit: bool,
+    /// Bank this mapping belongs to (active in every bank if unset)
+    pub bank: Option<String>,
+    /// Ignore repeat activations within this many milliseconds (optional)
+    #[serde(default)]
+    pub cooldown_ms: Option<u64>,
+}
+
+/// OSC mapping - runs commands when a matching OSC message is received
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OscMapping {
+    /// Unique name for the OSC mapping
+    pub name: String,
+    /// OSC address the message must have, or an address pattern with `*`, `?`, `[]`
+    /// and `{}` wildcards
+    pub address: String,
+    /// Conditions on the message's arguments, by position
+    #[serde(default)]
+    pub args: Vec<OscArgMatch>,
+    /// MIDI channel to send commands on (1-16) for MIDI destinations
+    pub send_channel: Option<u8>,
+    /// Commands to execute when a message matches
+    pub commands: Vec<Command>,
+    /// Destination for the commands
+    pub destination: Destination,
+    /// Whether activations are written to the audit log
+    #[serde(default)]
+    pub audit: bool,
+    /// Bank this mapping belongs to (active in every bank if unset)
+    pub bank: Option<String>,
+    /// Ignore repeat activations within this many milliseconds (optional)
+    #[serde(default)]
+    pub cooldown_ms: Option<u64>,
+    /// Rank among mappings matching the same OSC message, for the `first` and `priority` match
+    /// policies (higher first)
+    #[serde(default)]
+    pub priority: i32,
+}
+
+/// SysEx mapping - runs commands when a matching SysEx message is received
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SysExMapping {
+    /// Unique name for the SysEx mapping
+    pub name: String,
+    /// Byte pattern the message must match, e.g. `"F0 00 21 24 ?? 01 *"`
+    pub pattern: SysExPattern,
+    /// RTP MIDI session the message must arrive on (any if unset)
+    #[serde(default)]
+    pub session: Option<String>,
+    /// MIDI channel to send commands on (1-16) for MIDI destinations
+    pub send_channel: Option<u8>,
+    /// Commands to execute when a message matches
+    pub commands: Vec<Command>,
+    /// Destination for the commands
+    pub destination: Destination,
+    /// Whether activations are written to the audit log
+    #[serde(default)]
+    pub audit: bool,
+    /// Bank this mapping belongs to (active in every bank if unset)
+    pub bank: Option<String>,
+    /// Ignore repeat activations within this many milliseconds (optional)
+    #[serde(default)]
+    pub cooldown_ms: Option<u64>,
+    /// Rank among mappings matching the same SysEx message, for the `first` and `priority` match
+    /// policies (higher first)
+    #[serde(default)]
+    pub priority: i32,
+}
+
+/// Which of the mappings matching one message run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchPolicy {
+    /// Every matching mapping, in config order
+    #[default]
+    All,
+    /// Only the matching mapping with the highest priority, the earliest in config order
+    /// on a tie
+    First,
+    /// Every matching mapping, highest priority first and in config order on a tie
+    Priority,
+}
+
+impl MatchPolicy {
+    /// The matches that run, in the order they run
+    pub fn select<T>(
+        self,
+        matches: impl IntoIterator<Item = T>,
+        priority: impl Fn(&T) -> i32,
+    ) -> Vec<T> {
+        let mut matches: Vec<T> = matches.into_iter().collect();
+        if self == MatchPolicy::All {
+            return matches;
+        }
+        // Stable, so ties keep config order
+        matches.sort_by_key(|m| std::cmp::Reverse(priority(m)));
+        if self == MatchPolicy::First {
+            matches.truncate(1);
+        }
+        matches
+    }
+}
+
+/// Match policy of each kind of mapping
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MatchPolicies {
+    /// Device mappings matching a Program Change
+    pub program_change: MatchPolicy,
+    /// Note mappings matching a note
+    pub note: MatchPolicy,
+    /// OSC mappings matching an OSC message
+    pub osc: MatchPolicy,
+    /// SysEx mappings matching a SysEx message
+    pub sysex: MatchPolicy,
+}
+
+/// Condition on one OSC argument; without `equals`, `min` or `max` any value matches
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OscArgMatch {
+    /// Exact value of the argument
+    pub equals: Option<OscValue>,
+    /// Lowest accepted value of a numeric argument
+    pub min: Option<f64>,
+    /// Highest accepted value of a numeric argument
+    pub max: Option<f64>,
+}
+
+/// A number, string or boolean OSC argument value
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum OscValue {
+    Bool(bool),
+    Number(f64),
+    String(String),
+}
+
+/// OSC control rule - converts a numeric OSC argument into a Control Change
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OscControlRule {
+    /// Unique name for the rule
+    pub name: String,
+    /// OSC address the message must have, or an address pattern with `*`, `?`, `[]`
+    /// and `{}` wildcards
+    pub address: String,
+    /// Position of the argument converted
+    #[serde(default)]
+    pub arg: usize,
+    /// Argument value sent as 0
+    #[serde(default)]
+    pub min: f64,
+    /// Argument value sent as 127
+    #[serde(default = "default_osc_control_max")]
+    pub max: f64,
+    /// Controller to send
+    pub controller: u8,
+    /// MIDI channel to send the Control Change on (1-16)
+    pub send_channel: u8,
+    /// RTP MIDI session or virtual port to send the Control Change to
+    pub session_name: String,
+    /// Bank this rule belongs to (active in every bank if unset)
+    pub bank: Option<String>,
+}
+
+impl OscControlRule {
+    /// Controller value for an argument value, scaled from `min`-`max` to 0-127.
+    /// Values outside the range are clamped, and `min` above `max` inverts the scale.
+    pub fn scale(&self, value: f64) -> u8 {
+        let position = (value - self.min) / (self.max - self.min);
+        (position * 127.0).round().clamp(0.0, 127.0) as u8
+    }
+}
+
+fn default_osc_control_max() -> f64 {
+    1.0
+}
+
+/// OSC forwarding rule - converts incoming MIDI messages into OSC messages whose
+/// address and arguments are templates over the message's fields
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OscForwardRule {
+    /// Unique name for the rule
+    pub name: String,
+    /// Kinds of message forwarded (every channel message if empty)
+    #[serde(default)]
+    pub messages: Vec<MidiMessageKind>,
+    /// MIDI channel to listen on (1-16, every channel if unset)
+    #[serde(default)]
+    pub listen_channel: Option<u8>,
+    /// Note, controller or program forwarded (every one if unset)
+    #[serde(default)]
+    pub number: Option<u8>,
+    /// OSC destination to send to
+    pub destination_name: String,
+    /// OSC address, with `{field}` placeholders
+    pub address: String,
+    /// Typed arguments such as `f:{value}`, with `{field}` placeholders
+    #[serde(default = "default_osc_forward_args")]
+    pub args: Vec<String>,
+    /// Bank this rule belongs to (active in every bank if unset)
+    pub bank: Option<String>,
+}
+
+fn default_osc_forward_args() -> Vec<String> {
+    vec!["f:{value}".to_string()]
+}
+
+impl OscForwardRule {
+    /// Whether the rule forwards a message
+    pub fn covers(&self, fields: &MidiFields) -> bool {
+        (self.messages.is_empty() || self.messages.contains(&fields.kind))
+            && self.listen_channel.is_none_or(|c| c == fields.channel)
+            && self.number.is_none_or(|n| fields.number == Some(n))
+    }
+
+    /// Address and arguments of the OSC message for a MIDI message
+    pub fn render(&self, fields: &MidiFields) -> Result<(String, Vec<OscArg>)> {
+        let address = midi_fields::expand(&self.address, fields);
+        let args = self
+            .args
+            .iter()
+            .map(|arg| command_dsl::parse_osc_arg(&midi_fields::expand(arg, fields)))
+            .collect::<Result<_>>()?;
+        Ok((address, args))
+    }
+}
+
+fn default_wrap() -> bool {
+    true
+}
+
+/// Capture - stores values of incoming messages in a named variable for later commands
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Capture {
+    /// Variable the value is stored in
+    pub variable: String,
+    /// Message the value is taken from
+    pub source: CaptureSource,
+    /// Value of the variable until one has been captured (optional)
+    #[serde(default)]
+    pub initial: Option<f64>,
+}
+
+/// Incoming message a capture takes its value from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum CaptureSource {
+    /// Value of a Control Change on a channel
+    #[serde(rename = "control_change")]
+    ControlChange { channel: u8, controller: u8 },
+    /// Numeric argument of an OSC message, by position
+    #[serde(rename = "osc")]
+    Osc {
+        address: String,
+        #[serde(default)]
+        arg: usize,
+    },
+}
+
+/// Inactivity rule - runs commands when no input has been received for a while
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InactivityRule {
+    /// Human-readable name for the rule
+    pub name: String,
+    /// Seconds without input before the rule fires
+    pub timeout_secs: u64,
+    /// MIDI channel to send commands on (1-16) for MIDI destinations
+    pub send_channel: Option<u8>,
+    /// Commands to execute when the timeout elapses
+    pub commands: Vec<Command>,
+    /// Destination for the commands
+    pub destination: Destination,
+}
+
+/// Tempo-synced automation - runs a sequence of timed steps once started
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Automation {
+    /// Unique name for the automation
+    pub name: String,
+    /// Trigger that starts the automation (and stops it if no stop trigger is set)
+    pub start_trigger: Trigger,
+    /// Trigger that stops the automation (optional)
+    pub stop_trigger: Option<Trigger>,
+    /// Whether to start again from the first step after the last one
+    #[serde(default = "default_wrap")]
+    pub repeat: bool,
+    /// MIDI channel to send commands on (1-16) for MIDI destinations
+    pub send_channel: Option<u8>,
+    /// Ordered steps to run
+    pub steps: Vec<AutomationStep>,
+    /// Destination for the commands
+    pub destination: Destination,
+    /// Bank this automation's triggers belong to (active in every bank if unset)
+    pub bank: Option<String>,
+    /// Ignore repeated start/stop activations within this many milliseconds (optional)
+    #[serde(default)]
+    pub cooldown_ms: Option<u64>,
+}
+
+/// A single automation step
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutomationStep {
+    /// Beats to wait before the next step (fractions allowed)
+    pub beats: f64,
+    /// Commands to execute at the start of the step
+    pub commands: Vec<Command>,
+}
+
+/// Cue list - theatre-style ordered cues, each fired by GO
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CueList {
+    /// Unique name for the cue list
+    pub name: String,
+    /// Trigger that fires the next cue (optional; GO can also come from OSC or HTTP)
+    #[serde(default)]
+    pub go_trigger: Option<Trigger>,
+    /// MIDI channel to send commands on (1-16) for MIDI destinations
+    pub send_channel: Option<u8>,
+    /// Ordered cues
+    pub cues: Vec<Cue>,
+    /// Destination for the commands
+    pub destination: Destination,
+    /// Whether fired cues are written to the audit log
+    #[serde(default)]
+    pub audit: bool,
+    /// Bank this cue list's GO trigger belongs to (active in every bank if unset)
+    pub bank: Option<String>,
+    /// Ignore repeat GO triggers within this many milliseconds (optional)
+    #[serde(default)]
+    pub cooldown_ms: Option<u64>,
+}
+
+/// A single cue
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Cue {
+    /// Human-readable name for the cue
+    pub name: String,
+    /// Milliseconds to wait after GO before the commands run
+    #[serde(default)]
+    pub wait_ms: u64,
+    /// Commands to execute when the cue fires
+    pub commands: Vec<Command>,
+    /// Fire the next cue automatically once this one has fired
+    #[serde(default)]
+    pub follow: bool,
+}
+
+/// Input that changes the active bank
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum BankSelector {
+    /// Program Change on a channel selects the bank with that index
+    #[serde(rename = "program_change")]
+    ProgramChange { channel: u8 },
+    /// Control Change value on a channel/controller selects the bank with that index
+    #[serde(rename = "control_change")]
+    ControlChange { channel: u8, controller: u8 },
+    /// Trigger that moves to the next bank
+    #[serde(rename = "next")]
+    Next { trigger: Trigger },
+    /// Trigger that moves to the previous bank
+    #[serde(rename = "previous")]
+    Previous { trigger: Trigger },
+}
+
+/// Bank configuration - mappings with a `bank` are only active while that bank is selected
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BankConfig {
+    /// Bank names, in order; the first bank is active on startup
+    pub names: Vec<String>,
+    /// Inputs that change the active bank
+    #[serde(default)]
+    pub selectors: Vec<BankSelector>,
+    /// Feedback to send when a bank is selected, keyed by bank name
+    #[serde(default)]
+    pub feedback: HashMap<String, Feedback>,
+    /// Song details for each bank, keyed by bank name, used as `{song.<field>}` placeholders
+    #[serde(default)]
+    pub metadata: HashMap<String, SongMetadata>,
+}
+
+/// Details of the song played in a bank
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SongMetadata {
+    /// Song title, available as `{song.title}`
+    pub title: Option<String>,
+    /// Tempo set when the bank is selected, available as `{song.bpm}`
+    pub bpm: Option<f64>,
+    /// Musical key, available as `{song.key}`
+    pub key: Option<String>,
+    /// Further values such as patch names, available as `{song.<name>}`
+    #[serde(default)]
+    pub values: HashMap<String, String>,
+}
+
+impl SongMetadata {
+    /// Value of a placeholder field, if the song defines it
+    pub fn value(&self, field: &str) -> Option<String> {
+        match field {
+            "title" => self.title.clone(),
+            "bpm" => self.bpm.map(|bpm| format!("{bpm}")),
+            "key" => self.key.clone(),
+            _ => self.values.get(field).cloned(),
+        }
+    }
+}
+
+/// Audit log configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditLogConfig {
+    /// Path of the current audit log file
+    pub path: String,
+    /// Size in bytes at which the file is rotated
+    #[serde(default = "default_audit_max_bytes")]
+    pub max_bytes: u64,
+    /// Number of rotated files to keep
+    #[serde(default = "default_audit_max_files")]
+    pub max_files: usize,
+}
+
+fn default_audit_max_bytes() -> u64 {
+    10 * 1024 * 1024
+}
+
+fn default_audit_max_files() -> usize {
+    5
+}
+
+/// Sink that receives every input and mapping activation as a JSON event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventSink {
+    /// Append events to a JSON Lines file, rotated by size
+    File {
+        /// Path of the current file
+        path: String,
+        /// Size in bytes at which the file is rotated
+        #[serde(default = "default_audit_max_bytes")]
+        max_bytes: u64,
+        /// Number of rotated files to keep
+        #[serde(default = "default_audit_max_files")]
+        max_files: usize,
+    },
+    /// Send events to a syslog server as RFC 5424 messages over UDP
+    Syslog {
+        /// Syslog server host
+        host: String,
+        /// Syslog server port
+        #[serde(default = "default_syslog_port")]
+        port: u16,
+        /// Syslog facility (0-23, default 16 for local0)
+        #[serde(default = "default_syslog_facility")]
+        facility: u8,
+    },
+    /// Send each event as a JSON string argument to an OSC destination
+    Osc {
+        /// Name of the destination in `osc_destinations`
+        destination_name: String,
+        /// OSC address of the messages
+        #[serde(default = "default_event_sink_address")]
+        address: String,
+    },
+}
+
+fn default_syslog_port() -> u16 {
+    514
+}
+
+fn default_syslog_facility() -> u8 {
+    16
+}
+
+fn default_event_sink_address() -> String {
+    "/midi-router/event".to_string()
+}
+
+/// Bounds on incoming tempo changes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TempoLimits {
+    /// Lowest accepted tempo; lower values are rejected
+    pub min_bpm: f64,
+    /// Highest accepted tempo; higher values are rejected
+    pub max_bpm: f64,
+    /// Largest change in BPM per second (unlimited if unset)
+    pub max_change_per_sec: Option<f64>,
+    /// Hold OSC tempo until it has stayed put this long, so a moving fader only
+    /// updates the devices once it stops
+    pub settle_ms: Option<u64>,
+    /// Ignore OSC tempo within this many BPM of the current tempo
+    pub min_change_bpm: Option<f64>,
+}
+
+impl Default for TempoLimits {
+    fn default() -> Self {
+        Self {
+            min_bpm: 20.0,
+            max_bpm: 400.0,
+            max_change_per_sec: None,
+            settle_ms: None,
+            min_change_bpm: None,
+        }
+    }
+}
+
+/// Window of the tempo statistics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TempoStatsConfig {
+    /// How many minutes of tempo changes and incoming tempo messages the statistics cover
+    pub window_mins: u64,
+}
+
+impl Default for TempoStatsConfig {
+    fn default() -> Self {
+        Self { window_mins: 10 }
+    }
+}
+
+/// Tempo followed by devices not assigned to a tempo domain
+pub const MAIN_TEMPO_DOMAIN: &str = "main";
+
+/// Tempo domain - a tempo of its own for a group of devices, e.g. a click tempo
+/// that differs from the front-of-house tempo
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TempoDomain {
+    /// Unique name, used in `/tempo/<name>` and the control API
+    pub name: String,
+    /// IDs of the devices that follow this tempo instead of the main one
+    pub devices: Vec<String>,
+    /// OSC sources whose `/tempo/raw` messages set this tempo instead of the main one
+    #[serde(default)]
+    pub sources: Vec<String>,
+}
+
+/// Tap input - a note or controller whose taps set a tempo, for foot controllers with
+/// only a tap button
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TapInput {
+    /// Unique name for the tap input
+    pub name: String,
+    /// Note or controller that taps
+    pub trigger: Trigger,
+    /// Tempo domain the taps set (defaults to the main tempo)
+    #[serde(default = "default_tempo_domain")]
+    pub tempo_domain: String,
+    /// Number of most recent tap intervals averaged
+    #[serde(default = "default_tap_average")]
+    pub average: usize,
+    /// Taps further apart than this start a new measurement
+    #[serde(default = "default_tap_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+fn default_tap_average() -> usize {
+    4
+}
+
+fn default_tap_timeout_ms() -> u64 {
+    2000
+}
+
+/// MIDI clock output - Timing Clock at 24 pulses per quarter note for devices that
+/// only sync to MIDI clock
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClockOutput {
+    /// RTP MIDI session or virtual port to send the clock to
+    pub session_name: String,
+    /// Tempo domain the clock follows (defaults to the main tempo)
+    #[serde(default = "default_tempo_domain")]
+    pub tempo_domain: String,
+    /// Whether Start and Stop are sent when the clock starts and stops
+    #[serde(default = "default_enabled")]
+    pub transport: bool,
+}
+
+fn default_tempo_domain() -> String {
+    MAIN_TEMPO_DOMAIN.to_string()
+}
+
+/// Ableton Link tempo sync - the Link session tempo and a router tempo follow each other
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkConfig {
+    /// Tempo domain kept in sync with Link (defaults to the main tempo)
+    #[serde(default = "default_tempo_domain")]
+    pub tempo_domain: String,
+}
+
+/// Tempo display - sends the tempo as text to OSC labels or scribble strips when it changes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TempoDisplay {
+    /// OSC destination of the display
+    pub destination_name: String,
+    /// OSC addresses the text is sent to
+    pub addresses: Vec<String>,
+    /// Text sent, with `{bpm}`, `{source}` and `{domain}` replaced
+    #[serde(default = "default_tempo_display_format")]
+    pub format: String,
+    /// Decimal places of `{bpm}`
+    #[serde(default)]
+    pub decimals: usize,
+    /// Tempo domain shown (defaults to the main tempo)
+    #[serde(default = "default_tempo_domain")]
+    pub tempo_domain: String,
+}
+
+fn default_tempo_display_format() -> String {
+    "{bpm} BPM".to_string()
+}
+
+impl TempoDisplay {
+    /// Text showing a tempo and what set it
+    pub fn text(&self, bpm: f64, source: &str) -> String {
+        self.format
+            .replace("{bpm}", &format!("{bpm:.*}", self.decimals))
+            .replace("{source}", source)
+            .replace("{domain}", &self.tempo_domain)
+    }
+}
+
+/// Show lock - while engaged, only operator sources can change tempo and scenes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShowLock {
+    /// Code required to lock or unlock
+    pub code: String,
+    /// RTP MIDI session and OSC source names that stay in control while locked
+    #[serde(default)]
+    pub operator_sources: Vec<String>,
+}
+
+/// A plugin library to load
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginConfig {
+    /// Path of the plugin's shared library
+    pub path: String,
+    /// Whether the plugin is loaded
+    #[serde(default = "default_wrap")]
+    pub enabled: bool,
+}
+
+/// Suppression of messages looped back by cross-connected routers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoopSuppression {
+    /// How long after sending a message to a session an identical one from it counts as an echo
+    #[serde(default = "default_loop_window_ms")]
+    pub window_ms: u64,
+}
+
+fn default_loop_window_ms() -> u64 {
+    100
+}
+
+/// Clock used for absolute timestamps sent to peers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimeSourceConfig {
+    pub source: ClockSource,
+    /// Added to the clock, e.g. to line up with peers whose clocks are known to differ
+    pub offset_ms: i64,
+    /// Wall-clock jumps at least this large are logged
+    pub step_threshold_ms: u64,
+}
+
+impl Default for TimeSourceConfig {
+    fn default() -> Self {
+        Self {
+            source: ClockSource::System,
+            offset_ms: 0,
+            step_threshold_ms: 100,
+        }
+    }
+}
+
+/// Where the wall-clock time comes from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClockSource {
+    /// The system clock, following NTP corrections as they happen
+    #[default]
+    System,
+    /// The system clock at startup advanced by a monotonic clock, so NTP steps are ignored
+    Monotonic,
+}
+
+/// Detection of sustained overload in incoming message processing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OverloadConfig {
+    /// Queued incoming messages at which a session counts as over the limit
+    pub max_queue_depth: usize,
+    /// Time from receipt to processing at which a session counts as over the limit
+    pub max_latency_ms: u64,
+    /// How long a session must be over (or back under) the limits for overload to start (or end)
+    pub sustain_ms: u64,
+    /// Drop Control Change, pitch bend and aftertouch messages while overloaded
+    pub shed: bool,
+}
+
+impl Default for OverloadConfig {
+    fn default() -> Self {
+        Self {
+            max_queue_depth: 256,
+            max_latency_ms: 50,
+            sustain_ms: 1000,
+            shed: true,
+        }
+    }
+}
+
+/// Subsystems that can be switched off for troubleshooting without deleting their config
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Subsystems {
+    /// Start the listeners in `osc_sources`
+    pub osc_listener: bool,
+    /// Apply incoming tempos and send them to devices
+    pub tempo: bool,
+}
+
+impl Default for Subsystems {
+    fn default() -> Self {
+        Self {
+            osc_listener: true,
+            tempo: true,
+        }
+    }
 }
 
 /// Complete mapping configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MapConfig {
+    /// Schema version of the file
+    #[serde(default)]
+    pub version: u64,
+    /// Subsystems to start
+    #[serde(default)]
+    pub subsystems: Subsystems,
     /// RTP MIDI sessions to create
     pub rtp_midi_sessions: Vec<RtpMidiSession>,
     /// OSC destinations (for reference)
     pub osc_destinations: HashMap<String, OscDestination>,
+    /// Named groups of RTP MIDI and OSC destinations that receive commands together
+    #[serde(default)]
+    pub destination_groups: HashMap<String, Vec<Destination>>,
     /// OSC listening sources (for incoming tempo and other messages)
     pub osc_sources: Vec<OscSource>,
+    /// Virtual MIDI ports for applications on the same machine
+    #[serde(default)]
+    pub virtual_ports: Vec<VirtualPort>,
     /// Device mappings
     pub device_mappings: Vec<DeviceMapping>,
+    /// Mappings triggered by single notes
+    #[serde(default)]
+    pub note_mappings: Vec<NoteMapping>,
+    /// Rules converting notes into Program Changes
+    #[serde(default)]
+    pub note_program_rules: Vec<NoteProgramRule>,
+    /// Rules passing notes through to a destination
+    #[serde(default)]
+    pub note_forward_rules: Vec<NoteForwardRule>,
+    /// Rules passing Control Changes through to a destination, converting value formats
+    #[serde(default)]
+    pub control_forward_rules: Vec<ControlForwardRule>,
+    /// Rules composing banked program selections
+    #[serde(default)]
+    pub banked_program_rules: Vec<BankedProgramRule>,
+    /// Combinatorial trigger mappings
+    #[serde(default)]
+    pub combo_mappings: Vec<ComboMapping>,
+    /// Step sequencer mappings
+    #[serde(default)]
+    pub step_mappings: Vec<StepMapping>,
+    /// Mappings triggered by incoming OSC messages
+    #[serde(default)]
+    pub osc_mappings: Vec<OscMapping>,
+    /// Rules converting numeric OSC arguments into Control Changes
+    #[serde(default)]
+    pub osc_control_rules: Vec<OscControlRule>,
+    /// Rules converting incoming MIDI messages into OSC messages
+    #[serde(default)]
+    pub osc_forward_rules: Vec<OscForwardRule>,
+    /// Mappings triggered by incoming SysEx messages
+    #[serde(default)]
+    pub sysex_mappings: Vec<SysExMapping>,
+    /// Which mappings run when several match one message
+    #[serde(default)]
+    pub match_policies: MatchPolicies,
+    /// Rules that fire after a period without input
+    #[serde(default)]
+    pub inactivity_rules: Vec<InactivityRule>,
+    /// Tempo-synced automation sequences
+    #[serde(default)]
+    pub automations: Vec<Automation>,
+    /// Theatre-style cue lists
+    #[serde(default)]
+    pub cue_lists: Vec<CueList>,
+    /// Values of incoming messages stored in variables for later commands
+    #[serde(default)]
+    pub captures: Vec<Capture>,
+    /// Accepted tempo range and rate of change
+    #[serde(default)]
+    pub tempo_limits: TempoLimits,
+    /// Window of the tempo statistics
+    #[serde(default)]
+    pub tempo_stats: TempoStatsConfig,
+    /// Tempos kept separately from the main tempo for groups of devices
+    #[serde(default)]
+    pub tempo_domains: Vec<TempoDomain>,
+    /// Notes or controllers that set a tempo by tapping
+    #[serde(default)]
+    pub tap_inputs: Vec<TapInput>,
+    /// MIDI clock sent to sessions at the current tempo
+    #[serde(default)]
+    pub clock_outputs: Vec<ClockOutput>,
+    /// Ableton Link tempo sync (optional, requires the `link` feature)
+    #[serde(default)]
+    pub link: Option<LinkConfig>,
+    /// OSC text displays that show the current tempo
+    #[serde(default)]
+    pub tempo_displays: Vec<TempoDisplay>,
+    /// Lock that restricts tempo and scene changes to operator sources (optional)
+    #[serde(default)]
+    pub show_lock: Option<ShowLock>,
+    /// Plugins loaded into the router (requires the `plugins` feature)
+    #[serde(default)]
+    pub plugins: Vec<PluginConfig>,
+    /// File that keeps session SSRCs across restarts (optional)
+    #[serde(default)]
+    pub identity_file: Option<String>,
+    /// Drop incoming messages that echo messages this router sent (optional)
+    #[serde(default)]
+    pub loop_suppression: Option<LoopSuppression>,
+    /// Clock for timestamps sent to peers
+    #[serde(default)]
+    pub time_source: TimeSourceConfig,
+    /// Overload warnings and load shedding for incoming messages (optional)
+    #[serde(default)]
+    pub overload: Option<OverloadConfig>,
+    /// Seed for randomized command values, for repeatable sequences (optional)
+    #[serde(default)]
+    pub random_seed: Option<u64>,
+    /// Bank switching (optional)
+    pub banks: Option<BankConfig>,
+    /// Audit log for mappings marked with `audit` (optional)
+    #[serde(default)]
+    pub audit_log: Option<AuditLogConfig>,
+    /// External sinks that receive every routed event
+    #[serde(default)]
+    pub event_sinks: Vec<EventSink>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(input_mode: ControllerMode, output_mode: ControllerMode) -> ControlForwardRule {
+        ControlForwardRule {
+            name: "encoder".to_string(),
+            listen_channel: 1,
+            controller: 16,
+            send_controller: None,
+            input_mode,
+            output_mode,
+            send_channel: None,
+            destination: Destination::RtpMidi {
+                session_name: "Main".to_string(),
+            },
+            bank: None,
+        }
+    }
+
+    #[test]
+    fn relative_decoding() {
+        use ControllerMode::*;
+        let cases = [
+            (TwosComplement, 1, 1),
+            (TwosComplement, 63, 63),
+            (TwosComplement, 127, -1),
+            (TwosComplement, 65, -63),
+            (SignedBit, 1, 1),
+            (SignedBit, 63, 63),
+            (SignedBit, 65, -1),
+            (SignedBit, 127, -63),
+            (Offset64, 65, 1),
+            (Offset64, 127, 63),
+            (Offset64, 63, -1),
+            (Offset64, 1, -63),
+            (Offset64, 64, 0),
+        ];
+        for (mode, value, delta) in cases {
+            assert_eq!(mode.decode(value), Some(delta), "{mode:?} {value}");
+        }
+        assert_eq!(Absolute.decode(100), None);
+    }
+
+    #[test]
+    fn relative_encoding() {
+        use ControllerMode::*;
+        for mode in [TwosComplement, SignedBit, Offset64] {
+            for delta in (-63..=63).filter(|delta| *delta != 0) {
+                assert_eq!(mode.decode(mode.encode(delta)), Some(delta), "{mode:?}");
+            }
+        }
+        assert_eq!(TwosComplement.encode(-1), 127);
+        assert_eq!(SignedBit.encode(-1), 65);
+        assert_eq!(Offset64.encode(-1), 63);
+        assert_eq!(Offset64.encode(1), 65);
+    }
+
+    #[test]
+    fn relative_encoding_clamps_to_63_steps() {
+        use ControllerMode::*;
+        assert_eq!(TwosComplement.encode(100), 63);
+        assert_eq!(TwosComplement.encode(-100), 65);
+        assert_eq!(SignedBit.encode(100), 63);
+        assert_eq!(SignedBit.encode(-100), 127);
+        assert_eq!(Offset64.encode(100), 127);
+        assert_eq!(Offset64.encode(-100), 1);
+        assert_eq!(Absolute.encode(-5), 0);
+    }
+
+    #[test]
+    fn absolute_passes_through() {
+        let rule = rule(ControllerMode::Absolute, ControllerMode::Absolute);
+        assert_eq!(rule.convert(42, None), (Some(42), 42));
+        assert_eq!(rule.convert(0, Some(127)), (Some(0), 0));
+    }
+
+    #[test]
+    fn absolute_to_relative() {
+        let rule = rule(ControllerMode::Absolute, ControllerMode::TwosComplement);
+        // The first value has no prior position to move from
+        assert_eq!(rule.convert(70, None), (None, 70));
+        assert_eq!(rule.convert(73, Some(70)), (Some(3), 73));
+        assert_eq!(rule.convert(68, Some(70)), (Some(126), 68));
+        // An unchanged value sends nothing
+        assert_eq!(rule.convert(70, Some(70)), (None, 70));
+        // Jumps beyond 63 steps are sent as 63
+        assert_eq!(rule.convert(127, Some(0)), (Some(63), 127));
+        assert_eq!(rule.convert(0, Some(127)), (Some(65), 0));
+    }
+
+    #[test]
+    fn relative_to_absolute() {
+        let rule = rule(ControllerMode::SignedBit, ControllerMode::Absolute);
+        // The first value moves from the center
+        assert_eq!(rule.convert(3, None), (Some(67), 67));
+        assert_eq!(rule.convert(66, Some(67)), (Some(65), 65));
+        // Positions stop at 0 and 127
+        assert_eq!(rule.convert(63, Some(100)), (Some(127), 127));
+        assert_eq!(rule.convert(127, Some(10)), (Some(0), 0));
+        assert_eq!(rule.convert(1, Some(127)), (Some(127), 127));
+    }
+
+    #[test]
+    fn relative_to_relative() {
+        let rule = rule(ControllerMode::Offset64, ControllerMode::TwosComplement);
+        assert_eq!(rule.convert(66, None), (Some(2), 66));
+        assert_eq!(rule.convert(62, Some(66)), (Some(126), 64));
+        // No change sends nothing
+        assert_eq!(rule.convert(64, Some(64)), (None, 64));
+        // The change is passed on even where the position stops
+        assert_eq!(rule.convert(70, Some(127)), (Some(6), 127));
+    }
 }
@@ -0,0 +1,202 @@
+//! Fields of incoming MIDI messages, used as `{field}` placeholders in OSC forwarding
+//! rules.
+
+use midi_types::MidiMessage;
+use serde::{Deserialize, Serialize};
+
+/// Kind of channel message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MidiMessageKind {
+    NoteOn,
+    NoteOff,
+    KeyPressure,
+    ControlChange,
+    ProgramChange,
+    ChannelPressure,
+    PitchBend,
+}
+
+impl MidiMessageKind {
+    /// Every kind of channel message
+    pub const ALL: &[MidiMessageKind] = &[
+        Self::NoteOn,
+        Self::NoteOff,
+        Self::KeyPressure,
+        Self::ControlChange,
+        Self::ProgramChange,
+        Self::ChannelPressure,
+        Self::PitchBend,
+    ];
+
+    /// Name used in configs and the `{type}` placeholder
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::NoteOn => "note_on",
+            Self::NoteOff => "note_off",
+            Self::KeyPressure => "key_pressure",
+            Self::ControlChange => "control_change",
+            Self::ProgramChange => "program_change",
+            Self::ChannelPressure => "channel_pressure",
+            Self::PitchBend => "pitch_bend",
+        }
+    }
+
+    /// Placeholder naming the message's number, besides `{number}`
+    fn number_name(self) -> Option<&'static str> {
+        match self {
+            Self::NoteOn | Self::NoteOff | Self::KeyPressure => Some("note"),
+            Self::ControlChange => Some("controller"),
+            Self::ProgramChange => Some("program"),
+            Self::ChannelPressure | Self::PitchBend => None,
+        }
+    }
+
+    /// Largest raw value
+    fn max_value(self) -> u16 {
+        match self {
+            Self::PitchBend => 16383,
+            _ => 127,
+        }
+    }
+}
+
+/// Placeholders every message has
+pub const COMMON_PLACEHOLDERS: &[&str] = &["type", "channel", "value", "raw"];
+
+/// Placeholders only some messages have
+pub const NUMBER_PLACEHOLDERS: &[&str] = &["number", "note", "controller", "program"];
+
+/// The fields of a channel message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MidiFields {
+    pub kind: MidiMessageKind,
+    /// MIDI channel (1-16)
+    pub channel: u8,
+    /// Note, controller or program
+    pub number: Option<u8>,
+    /// Velocity, controller value, pressure, program or pitch bend (0-16383)
+    pub raw: u16,
+}
+
+impl MidiFields {
+    /// Fields of a channel message, or `None` for system messages. A Note On with
+    /// velocity 0 is a Note Off.
+    pub fn from_message(message: &MidiMessage) -> Option<Self> {
+        let fields = |kind, channel: u8, number: Option<u8>, raw: u16| MidiFields {
+            kind,
+            channel: channel + 1,
+            number,
+            raw,
+        };
+        Some(match *message {
+            MidiMessage::NoteOn(channel, note, velocity) if u8::from(velocity) > 0 => fields(
+                MidiMessageKind::NoteOn,
+                channel.into(),
+                Some(note.into()),
+                u8::from(velocity).into(),
+            ),
+            MidiMessage::NoteOn(channel, note, velocity)
+            | MidiMessage::NoteOff(channel, note, velocity) => fields(
+                MidiMessageKind::NoteOff,
+                channel.into(),
+                Some(note.into()),
+                u8::from(velocity).into(),
+            ),
+            MidiMessage::KeyPressure(channel, note, pressure) => fields(
+                MidiMessageKind::KeyPressure,
+                channel.into(),
+                Some(note.into()),
+                u8::from(pressure).into(),
+            ),
+            MidiMessage::ControlChange(channel, controller, value) => fields(
+                MidiMessageKind::ControlChange,
+                channel.into(),
+                Some(controller.into()),
+                u8::from(value).into(),
+            ),
+            MidiMessage::ProgramChange(channel, program) => fields(
+                MidiMessageKind::ProgramChange,
+                channel.into(),
+                Some(program.into()),
+                u8::from(program).into(),
+            ),
+            MidiMessage::ChannelPressure(channel, pressure) => fields(
+                MidiMessageKind::ChannelPressure,
+                channel.into(),
+                None,
+                u8::from(pressure).into(),
+            ),
+            MidiMessage::PitchBendChange(channel, bend) => fields(
+                MidiMessageKind::PitchBend,
+                channel.into(),
+                None,
+                bend.into(),
+            ),
+            _ => return None,
+        })
+    }
+
+    /// Fields of a message of a kind, for checking templates
+    pub fn sample(kind: MidiMessageKind, channel: u8, number: Option<u8>) -> Self {
+        Self {
+            kind,
+            channel,
+            number: kind.number_name().map(|_| number.unwrap_or(0)),
+            raw: 0,
+        }
+    }
+
+    /// Value of a placeholder field, if the message has it
+    pub fn value(&self, field: &str) -> Option<String> {
+        match field {
+            "type" => Some(self.kind.name().to_string()),
+            "channel" => Some(self.channel.to_string()),
+            "value" => Some((f64::from(self.raw) / f64::from(self.kind.max_value())).to_string()),
+            "raw" => Some(self.raw.to_string()),
+            "number" => self.number.map(|n| n.to_string()),
+            name if self.kind.number_name() == Some(name) => self.number.map(|n| n.to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// Replace `{field}` placeholders with the fields of a message.
+///
+/// Placeholders for fields the message doesn't have are left as they are.
+pub fn expand(text: &str, fields: &MidiFields) -> String {
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('{') {
+        expanded.push_str(&rest[..start]);
+        let placeholder = &rest[start..];
+        let Some(end) = placeholder.find('}') else {
+            break;
+        };
+
+        match fields.value(&placeholder[1..end]) {
+            Some(value) => expanded.push_str(&value),
+            None => expanded.push_str(&placeholder[..=end]),
+        }
+        rest = &placeholder[end + 1..];
+    }
+
+    expanded.push_str(rest);
+    expanded
+}
+
+/// Names of the placeholders in a template
+pub fn placeholders(text: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        let placeholder = &rest[start + 1..];
+        let Some(end) = placeholder.find('}') else {
+            break;
+        };
+        names.push(&placeholder[..end]);
+        rest = &placeholder[end + 1..];
+    }
+    names
+}
@@ -0,0 +1,98 @@
+use anyhow::{Context, Result, bail};
+use serde_json::{Map, Value};
+use tracing::info;
+
+/// Current schema version of `devices.json`
+pub const DEVICE_CONFIG_VERSION: u64 = 1;
+/// Current schema version of `map.json`
+pub const MAP_CONFIG_VERSION: u64 = 1;
+
+/// A single schema upgrade from one version to the next
+struct Migration {
+    /// Version this migration upgrades from
+    from: u64,
+    /// Human-readable description of the change
+    description: &'static str,
+    /// Upgrade the JSON document in place
+    apply: fn(&mut Map<String, Value>) -> Result<()>,
+}
+
+/// Upgrades for `devices.json`, in version order
+const DEVICE_MIGRATIONS: &[Migration] = &[Migration {
+    from: 0,
+    description: "add schema version",
+    apply: |_| Ok(()),
+}];
+
+/// Upgrades for `map.json`, in version order
+const MAP_MIGRATIONS: &[Migration] = &[Migration {
+    from: 0,
+    description: "default missing `osc_destinations` and `osc_sources` to empty",
+    apply: |config| {
+        config
+            .entry("osc_destinations")
+            .or_insert_with(|| Value::Object(Map::new()));
+        config
+            .entry("osc_sources")
+            .or_insert_with(|| Value::Array(Vec::new()));
+        Ok(())
+    },
+}];
+
+/// Upgrade a device config document to the current schema version, returning whether it changed
+pub fn migrate_device_config(config: &mut Value) -> Result<bool> {
+    migrate(
+        "device config",
+        config,
+        DEVICE_MIGRATIONS,
+        DEVICE_CONFIG_VERSION,
+    )
+}
+
+/// Upgrade a map config document to the current schema version, returning whether it changed
+pub fn migrate_map_config(config: &mut Value) -> Result<bool> {
+    migrate("map config", config, MAP_MIGRATIONS, MAP_CONFIG_VERSION)
+}
+
+fn migrate(kind: &str, config: &mut Value, migrations: &[Migration], current: u64) -> Result<bool> {
+    let object = config
+        .as_object_mut()
+        .with_context(|| format!("The {kind} must be a JSON object"))?;
+
+    // Files written before versioning was introduced have no version field
+    let mut version = match object.get("version") {
+        None => 0,
+        Some(value) => value
+            .as_u64()
+            .with_context(|| format!("The {kind} `version` must be a non-negative integer"))?,
+    };
+
+    if version > current {
+        bail!(
+            "The {kind} is schema version {version}, but this build only supports up to version {current}; upgrade midi-router"
+        );
+    }
+
+    let migrated = version < current;
+    while version < current {
+        let migration = migrations
+            .iter()
+            .find(|migration| migration.from == version)
+            .with_context(|| format!("No migration for {kind} from version {version}"))?;
+
+        info!(
+            "Upgrading {} from version {} to {}: {}",
+            kind,
+            version,
+            version + 1,
+            migration.description
+        );
+        (migration.apply)(object)
+            .with_context(|| format!("Failed to upgrade {kind} from version {version}"))?;
+
+        version += 1;
+        object.insert("version".to_string(), Value::from(version));
+    }
+
+    Ok(migrated)
+}
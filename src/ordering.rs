@@ -0,0 +1,29 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{Mutex, OwnedMutexGuard};
+
+/// Serializes batches of commands per destination.
+///
+/// Commands from one trigger are sent while holding the destination's lock, so
+/// batches from concurrent triggers (tap tempo, automations, feedback) never
+/// interleave and each batch arrives in config order.
+pub struct DestinationLocks {
+    locks: Mutex<HashMap<String, Arc<Mutex<()>>>>,
+}
+
+impl DestinationLocks {
+    pub fn new() -> Self {
+        Self {
+            locks: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Wait for exclusive use of a destination; it is released when the guard is dropped
+    pub async fn lock(&self, destination: &str) -> OwnedMutexGuard<()> {
+        let lock = {
+            let mut locks = self.locks.lock().await;
+            Arc::clone(locks.entry(destination.to_string()).or_default())
+        };
+        lock.lock_owned().await
+    }
+}
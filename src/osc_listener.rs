@@ -1,70 +1,137 @@
+use crate::cue::CueAction;
 use crate::mapping::OscSource;
 use crate::processor::MidiProcessor;
+use crate::status::{OscListenerStatus, RouterStatus};
+use crate::supervisor::supervise;
+use crate::tempo_stats::TempoReport;
 use anyhow::Result;
-use rosc::{OscPacket, OscType, decoder};
-use std::net::UdpSocket;
+use rosc::{OscMessage, OscPacket, OscType, decoder, encoder};
+use std::net::{IpAddr, SocketAddr, UdpSocket};
 use std::sync::Arc;
-use tokio::task;
+use tokio::task::JoinHandle;
 use tracing::{debug, error, info, warn};
 
 /// OSC listener that handles incoming OSC messages
 pub struct OscListener {
     processor: Arc<MidiProcessor>,
+    status: Arc<RouterStatus>,
 }
 
 impl OscListener {
-    pub fn new(processor: Arc<MidiProcessor>) -> Self {
-        Self { processor }
+    pub fn new(processor: Arc<MidiProcessor>, status: Arc<RouterStatus>) -> Self {
+        Self { processor, status }
     }
 
     /// Start listening for OSC messages on the specified sources
-    pub async fn start_listeners(&self, osc_sources: &[OscSource]) -> Result<()> {
+    pub async fn start_listeners(&self, osc_sources: &[OscSource]) -> Result<Vec<JoinHandle<()>>> {
+        let mut tasks = Vec::new();
         for source in osc_sources {
-            self.start_listener(source).await?;
+            tasks.push(self.start_listener(source).await?);
         }
-        Ok(())
+        Ok(tasks)
     }
 
     /// Start a single OSC listener
-    async fn start_listener(&self, source: &OscSource) -> Result<()> {
+    async fn start_listener(&self, source: &OscSource) -> Result<JoinHandle<()>> {
         info!(
             "Starting OSC listener '{}' on port {}",
             source.name, source.port
         );
 
-        let socket = UdpSocket::bind(format!("0.0.0.0:{}", source.port))?;
+        let (socket, port) = match bind(source) {
+            Ok(bound) => bound,
+            Err(e) => {
+                self.record_status(source, None).await;
+                return Err(e.into());
+            }
+        };
+        if port != source.port {
+            warn!(
+                "OSC source '{}': port {} is in use, listening on port {} instead",
+                source.name, source.port, port
+            );
+        }
         socket.set_nonblocking(true)?;
+        self.record_status(source, Some(port)).await;
 
+        let socket = Arc::new(tokio::net::UdpSocket::from_std(socket)?);
         let processor = Arc::clone(&self.processor);
+        let status = Arc::clone(&self.status);
         let source_name = source.name.clone();
+        let filter = Arc::new(SourceFilter::new(source));
 
-        task::spawn(async move {
-            let mut buf = [0u8; 1024];
-            let socket = tokio::net::UdpSocket::from_std(socket).expect("Failed to convert socket");
+        let task = supervise(format!("OSC listener '{}'", source.name), move || {
+            let socket = Arc::clone(&socket);
+            let processor = Arc::clone(&processor);
+            let status = Arc::clone(&status);
+            let source_name = source_name.clone();
+            let filter = Arc::clone(&filter);
+            async move {
+                let mut buf = [0u8; 1024];
 
-            loop {
-                match socket.recv_from(&mut buf).await {
-                    Ok((size, _addr)) => {
-                        if let Err(e) = Self::handle_osc_packet(&processor, &buf[..size]).await {
-                            error!("Error handling OSC packet on '{}': {}", source_name, e);
+                loop {
+                    match socket.recv_from(&mut buf).await {
+                        Ok((size, addr)) => {
+                            if !filter.allows_sender(addr.ip()) {
+                                debug!(
+                                    "Ignoring OSC packet on '{}' from {}: sender not allowed",
+                                    source_name, addr
+                                );
+                                continue;
+                            }
+                            let reply = Reply {
+                                socket: &socket,
+                                sender: addr,
+                            };
+                            if let Err(e) = Self::handle_osc_packet(
+                                &processor,
+                                &status,
+                                &source_name,
+                                &filter,
+                                &reply,
+                                &buf[..size],
+                            )
+                            .await
+                            {
+                                error!("Error handling OSC packet on '{}': {}", source_name, e);
+                            }
+                        }
+                        Err(e) => {
+                            error!("Error receiving OSC data on '{}': {}", source_name, e);
+                            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
                         }
-                    }
-                    Err(e) => {
-                        error!("Error receiving OSC data on '{}': {}", source_name, e);
-                        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
                     }
                 }
             }
         });
 
-        Ok(())
+        Ok(task)
+    }
+
+    /// Record whether a listener is bound
+    async fn record_status(&self, source: &OscSource, bound_port: Option<u16>) {
+        self.status
+            .set_osc_listener(OscListenerStatus {
+                name: source.name.clone(),
+                port: source.port,
+                bound: bound_port.is_some(),
+                bound_port,
+            })
+            .await;
     }
 
     /// Handle an incoming OSC packet
-    async fn handle_osc_packet(processor: &Arc<MidiProcessor>, data: &[u8]) -> Result<()> {
+    async fn handle_osc_packet(
+        processor: &Arc<MidiProcessor>,
+        status: &RouterStatus,
+        source: &str,
+        filter: &SourceFilter,
+        reply: &Reply<'_>,
+        data: &[u8],
+    ) -> Result<()> {
         match decoder::decode_udp(data) {
             Ok((_, packet)) => {
-                Self::process_osc_packet(processor, packet).await?;
+                Self::process_osc_packet(processor, status, source, filter, reply, packet).await?;
             }
             Err(e) => {
                 warn!("Failed to decode OSC packet: {}", e);
@@ -74,30 +141,361 @@ impl OscListener {
     }
 
     /// Process a decoded OSC packet
-    async fn process_osc_packet(processor: &Arc<MidiProcessor>, packet: OscPacket) -> Result<()> {
+    async fn process_osc_packet(
+        processor: &Arc<MidiProcessor>,
+        status: &RouterStatus,
+        source: &str,
+        filter: &SourceFilter,
+        reply: &Reply<'_>,
+        packet: OscPacket,
+    ) -> Result<()> {
         match packet {
             OscPacket::Message(msg) => {
                 debug!("Received OSC message: {} {:?}", msg.addr, msg.args);
 
-                // Handle tempo messages
-                if msg.addr == "/tempo/raw" {
-                    if let Some(OscType::Float(bpm)) = msg.args.first() {
-                        processor.handle_osc_tempo(*bpm as f64).await?;
-                    } else if let Some(OscType::Int(bpm)) = msg.args.first() {
-                        processor.handle_osc_tempo(*bpm as f64).await?;
-                    } else {
-                        warn!("Invalid argument type for /tempo/raw: {:?}", msg.args);
+                if !filter.allows_address(&msg.addr) {
+                    debug!("Ignoring OSC message {}: address not allowed", msg.addr);
+                    return Ok(());
+                }
+
+                match msg.addr.as_str() {
+                    address if status.is_read_only() && is_control_address(address) => {
+                        info!(
+                            "Ignoring {} from '{}': router is in read-only mode",
+                            msg.addr, source
+                        );
+                    }
+                    // Show lock
+                    "/lock" | "/unlock" => {
+                        if let Some(OscType::String(code)) = msg.args.first() {
+                            processor.set_show_lock(code, msg.addr == "/lock").await?;
+                        } else {
+                            warn!("Invalid arguments for {}: {:?}", msg.addr, msg.args);
+                        }
+                    }
+                    address
+                        if (address.starts_with("/tempo/")
+                            || matches!(
+                                address,
+                                "/bank/select" | "/bank/next" | "/bank/previous" | "/cue/go"
+                            )
+                            || program_device(address).is_some())
+                            && processor.is_locked_out(source).await =>
+                    {
+                        info!(
+                            "Ignoring {} from '{}' while the show is locked",
+                            msg.addr, source
+                        );
+                    }
+                    // Handle tempo messages
+                    "/tempo/raw" => {
+                        if let Some(OscType::Float(bpm)) = msg.args.first() {
+                            processor
+                                .handle_osc_tempo(source, None, *bpm as f64)
+                                .await?;
+                        } else if let Some(OscType::Int(bpm)) = msg.args.first() {
+                            processor
+                                .handle_osc_tempo(source, None, *bpm as f64)
+                                .await?;
+                        } else {
+                            warn!("Invalid argument type for /tempo/raw: {:?}", msg.args);
+                        }
+                    }
+                    // Tempo of a named tempo domain
+                    address if address.starts_with("/tempo/") => {
+                        let domain = &address["/tempo/".len()..];
+                        match msg.args.first() {
+                            Some(OscType::Float(bpm)) => {
+                                processor
+                                    .handle_osc_tempo(source, Some(domain), *bpm as f64)
+                                    .await?;
+                            }
+                            Some(OscType::Int(bpm)) => {
+                                processor
+                                    .handle_osc_tempo(source, Some(domain), *bpm as f64)
+                                    .await?;
+                            }
+                            _ => warn!("Invalid argument type for {}: {:?}", address, msg.args),
+                        }
                     }
+                    // Session management
+                    "/session/rename" => {
+                        if let [OscType::String(old_name), OscType::String(new_name)] =
+                            msg.args.as_slice()
+                        {
+                            if !status.rename_session(old_name, new_name).await? {
+                                warn!("Session '{}' not found", old_name);
+                            }
+                        } else {
+                            warn!("Invalid arguments for /session/rename: {:?}", msg.args);
+                        }
+                    }
+                    "/session/alias" => {
+                        if let [OscType::String(session_name), OscType::String(alias)] =
+                            msg.args.as_slice()
+                        {
+                            if !status.add_session_alias(session_name, alias).await {
+                                warn!("Session '{}' not found", session_name);
+                            }
+                        } else {
+                            warn!("Invalid arguments for /session/alias: {:?}", msg.args);
+                        }
+                    }
+                    // Bank switching
+                    "/bank/select" => match msg.args.first() {
+                        Some(OscType::String(bank)) => processor.select_bank(bank).await?,
+                        Some(OscType::Int(index)) => {
+                            processor.select_bank(&index.to_string()).await?
+                        }
+                        _ => warn!("Invalid arguments for /bank/select: {:?}", msg.args),
+                    },
+                    "/bank/next" => processor.step_bank(true).await?,
+                    "/bank/previous" => processor.step_bank(false).await?,
+                    // Cue lists
+                    "/cue/go" | "/cue/stop" | "/cue/reset" => {
+                        let action = CueAction::from_name(msg.addr.trim_start_matches("/cue/"));
+                        match (action, msg.args.first()) {
+                            (Some(action), Some(OscType::String(name))) => {
+                                if !processor.cue(name, action).await {
+                                    warn!("Cue list '{}' not found", name);
+                                }
+                            }
+                            _ => warn!("Invalid arguments for {}: {:?}", msg.addr, msg.args),
+                        }
+                    }
+                    // Dropping outgoing messages
+                    "/freeze" | "/unfreeze" => {
+                        let destination = match msg.args.first() {
+                            Some(OscType::String(destination)) => Some(destination.as_str()),
+                            _ => None,
+                        };
+                        processor.set_output_frozen(destination, msg.addr == "/freeze");
+                    }
+                    // Device programs, as if their Program Change had been received
+                    address if let Some(device_id) = program_device(address) => {
+                        let program = match msg.args.first() {
+                            Some(OscType::Int(program)) => u8::try_from(*program).ok(),
+                            Some(OscType::Float(program)) => u8::try_from(*program as i32).ok(),
+                            _ => None,
+                        };
+                        match program.filter(|program| *program <= 127) {
+                            Some(program) => {
+                                if !processor
+                                    .run_device_program("OSC", device_id, program, None, None)
+                                    .await?
+                                {
+                                    warn!("Program {} not found on '{}'", program, device_id);
+                                }
+                            }
+                            None => warn!("Invalid arguments for {}: {:?}", address, msg.args),
+                        }
+                    }
+                    // MIDI clock outputs
+                    "/clock/start" => processor.set_clock_running(true),
+                    "/clock/stop" => processor.set_clock_running(false),
+                    // Tempo statistics, for one domain or all of them
+                    "/stats/tempo" => {
+                        let domain = match msg.args.first() {
+                            Some(OscType::String(domain)) => Some(domain.as_str()),
+                            _ => None,
+                        };
+                        let report = processor.tempo_stats().await;
+                        reply.send(tempo_stats_messages(&report, domain)).await;
+                    }
+                    // Anything else may trigger OSC mappings
+                    _ => processor.handle_osc_message(&msg.addr, &msg.args).await?,
                 }
-                // Add more OSC message handlers here as needed
             }
             OscPacket::Bundle(bundle) => {
                 // Handle OSC bundles by processing each packet
                 for packet in bundle.content {
-                    Box::pin(Self::process_osc_packet(processor, packet)).await?;
+                    Box::pin(Self::process_osc_packet(
+                        processor, status, source, filter, reply, packet,
+                    ))
+                    .await?;
                 }
             }
         }
         Ok(())
     }
 }
+
+/// Where answers to queries go: back to the sender, from the listening socket
+struct Reply<'a> {
+    socket: &'a tokio::net::UdpSocket,
+    sender: SocketAddr,
+}
+
+impl Reply<'_> {
+    async fn send(&self, messages: Vec<OscMessage>) {
+        for message in messages {
+            let result = match encoder::encode(&OscPacket::Message(message)) {
+                Ok(data) => self.socket.send_to(&data, self.sender).await.map(drop),
+                Err(e) => Err(std::io::Error::other(e)),
+            };
+            if let Err(e) = result {
+                warn!("Failed to reply to {}: {}", self.sender, e);
+                return;
+            }
+        }
+    }
+}
+
+/// Replies to `/stats/tempo`: one `/stats/tempo` message per domain and one
+/// `/stats/tempo/source` message per tempo source, limited to `domain` if given
+fn tempo_stats_messages(report: &TempoReport, domain: Option<&str>) -> Vec<OscMessage> {
+    let wanted = |name: &str| domain.is_none_or(|domain| domain == name);
+    let domains = report
+        .domains
+        .iter()
+        .filter(|stats| wanted(&stats.domain))
+        .map(|stats| OscMessage {
+            addr: "/stats/tempo".to_string(),
+            args: vec![
+                OscType::String(stats.domain.clone()),
+                OscType::Float(stats.bpm as f32),
+                OscType::Float(stats.min_bpm as f32),
+                OscType::Float(stats.max_bpm as f32),
+                OscType::Int(stats.changes as i32),
+            ],
+        });
+    let sources = report
+        .sources
+        .iter()
+        .filter(|stats| wanted(&stats.domain))
+        .map(|stats| OscMessage {
+            addr: "/stats/tempo/source".to_string(),
+            args: vec![
+                OscType::String(stats.source.clone()),
+                OscType::String(stats.domain.clone()),
+                OscType::Int(stats.messages as i32),
+                OscType::Float(stats.last_bpm as f32),
+                OscType::Float(stats.bpm_jitter as f32),
+                OscType::Float(stats.interval_ms as f32),
+                OscType::Float(stats.interval_jitter_ms as f32),
+            ],
+        });
+    domains.chain(sources).collect()
+}
+
+/// Whether an address changes the router's control state, which read-only mode refuses.
+/// `/freeze` and `/unfreeze` are left out so monitors can still stop the output.
+fn is_control_address(address: &str) -> bool {
+    matches!(
+        address,
+        "/session/rename"
+            | "/session/alias"
+            | "/lock"
+            | "/unlock"
+            | "/bank/select"
+            | "/bank/next"
+            | "/bank/previous"
+            | "/cue/go"
+            | "/cue/stop"
+            | "/cue/reset"
+    ) || program_device(address).is_some()
+}
+
+/// Device id of a `/device/{device_id}/program` address
+fn program_device(address: &str) -> Option<&str> {
+    address
+        .strip_prefix("/device/")?
+        .strip_suffix("/program")
+        .filter(|device_id| !device_id.is_empty() && !device_id.contains('/'))
+}
+
+/// Allow-lists of senders and OSC addresses for one OSC source
+struct SourceFilter {
+    senders: Vec<(IpAddr, u8)>,
+    addresses: Vec<String>,
+}
+
+impl SourceFilter {
+    fn new(source: &OscSource) -> Self {
+        let senders = source
+            .allowed_senders
+            .iter()
+            .filter_map(|rule| match parse_ip_range(rule) {
+                Some(range) => Some(range),
+                None => {
+                    warn!(
+                        "Ignoring invalid allowed sender '{}' for OSC source '{}'",
+                        rule, source.name
+                    );
+                    None
+                }
+            })
+            .collect();
+
+        Self {
+            senders,
+            addresses: source.allowed_addresses.clone(),
+        }
+    }
+
+    fn allows_sender(&self, ip: IpAddr) -> bool {
+        self.senders.is_empty()
+            || self
+                .senders
+                .iter()
+                .any(|(network, prefix)| in_range(ip, *network, *prefix))
+    }
+
+    fn allows_address(&self, address: &str) -> bool {
+        self.addresses.is_empty()
+            || self
+                .addresses
+                .iter()
+                .any(|prefix| address.starts_with(prefix.as_str()))
+    }
+}
+
+/// Parse an IP address or CIDR range such as `192.168.1.0/24`
+fn parse_ip_range(rule: &str) -> Option<(IpAddr, u8)> {
+    match rule.split_once('/') {
+        Some((ip, prefix)) => {
+            let ip: IpAddr = ip.trim().parse().ok()?;
+            let prefix: u8 = prefix.trim().parse().ok()?;
+            let max = if ip.is_ipv4() { 32 } else { 128 };
+            (prefix <= max).then_some((ip, prefix))
+        }
+        None => {
+            let ip: IpAddr = rule.trim().parse().ok()?;
+            Some((ip, if ip.is_ipv4() { 32 } else { 128 }))
+        }
+    }
+}
+
+/// Whether an address is within a network of the given prefix length
+fn in_range(ip: IpAddr, network: IpAddr, prefix: u8) -> bool {
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(network) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V4(_)) => ip
+            .to_ipv4_mapped()
+            .is_some_and(|ip| in_range(IpAddr::V4(ip), network, prefix)),
+        _ => false,
+    }
+}
+
+/// Bind a source's port, or the first free one of its fallback ports
+pub fn bind(source: &OscSource) -> std::io::Result<(UdpSocket, u16)> {
+    let last = source.port.saturating_add(source.port_fallback);
+    let mut port = source.port;
+    loop {
+        match UdpSocket::bind(("0.0.0.0", port)) {
+            Ok(socket) => return Ok((socket, port)),
+            Err(e) if e.kind() == std::io::ErrorKind::AddrInUse && port < last => {
+                debug!("OSC port {} is in use, trying {}", port, port + 1);
+                port += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
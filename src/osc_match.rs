@@ -0,0 +1,220 @@
+use crate::mapping::{OscArgMatch, OscControlRule, OscMapping, OscValue};
+use rosc::OscType;
+
+/// Matching of config conditions against incoming OSC messages
+pub trait OscMatch {
+    /// Whether a message with this address and arguments matches
+    fn matches(&self, address: &str, args: &[OscType]) -> bool;
+}
+
+impl OscMatch for OscMapping {
+    fn matches(&self, address: &str, args: &[OscType]) -> bool {
+        // Every condition needs an argument to test
+        address_matches(&self.address, address)
+            && self.args.len() <= args.len()
+            && self
+                .args
+                .iter()
+                .zip(args)
+                .all(|(condition, arg)| arg_matches(condition, arg))
+    }
+}
+
+impl OscMatch for OscControlRule {
+    fn matches(&self, address: &str, args: &[OscType]) -> bool {
+        address_matches(&self.address, address) && args.get(self.arg).and_then(number).is_some()
+    }
+}
+
+/// Whether an address matches an OSC address pattern. Within one part of the address,
+/// `*` matches any run of characters and `?` any single character, `[a-z]` matches a
+/// character in the set (`[!a-z]` one outside it), and `{left,right}` any of the
+/// listed strings. A pattern without these characters matches only itself, and one
+/// with an unclosed `[` or `{` matches nothing.
+pub fn address_matches(pattern: &str, address: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let address: Vec<char> = address.chars().collect();
+    matches_from(&pattern, &address)
+}
+
+fn matches_from(pattern: &[char], address: &[char]) -> bool {
+    let Some((&first, rest)) = pattern.split_first() else {
+        return address.is_empty();
+    };
+    match first {
+        // Try every run that stays within the current part
+        '*' => (0..=address.len())
+            .take_while(|&len| len == 0 || address[len - 1] != '/')
+            .any(|len| matches_from(rest, &address[len..])),
+        '?' => matches!(
+            address.split_first(),
+            Some((&c, tail)) if c != '/' && matches_from(rest, tail)
+        ),
+        '[' => {
+            let Some(end) = rest.iter().position(|&c| c == ']') else {
+                return false;
+            };
+            matches!(
+                address.split_first(),
+                Some((&c, tail)) if c != '/' && in_set(&rest[..end], c) && matches_from(&rest[end + 1..], tail)
+            )
+        }
+        '{' => {
+            let Some(end) = rest.iter().position(|&c| c == '}') else {
+                return false;
+            };
+            rest[..end].split(|&c| c == ',').any(|choice| {
+                address.starts_with(choice)
+                    && matches_from(&rest[end + 1..], &address[choice.len()..])
+            })
+        }
+        literal => matches!(
+            address.split_first(),
+            Some((&c, tail)) if c == literal && matches_from(rest, tail)
+        ),
+    }
+}
+
+/// Whether a character is in a `[]` set such as `abc`, `0-9` or `!a-z`
+fn in_set(set: &[char], c: char) -> bool {
+    let (negated, set) = match set.split_first() {
+        Some((&'!', rest)) => (true, rest),
+        _ => (false, set),
+    };
+    let mut found = false;
+    let mut i = 0;
+    while i < set.len() {
+        if i + 2 < set.len() && set[i + 1] == '-' {
+            found |= (set[i]..=set[i + 2]).contains(&c);
+            i += 3;
+        } else {
+            found |= set[i] == c;
+            i += 1;
+        }
+    }
+    found != negated
+}
+
+/// Whether one argument satisfies a condition
+fn arg_matches(condition: &OscArgMatch, arg: &OscType) -> bool {
+    let number = number(arg);
+    let equals = match condition.equals {
+        None => true,
+        Some(OscValue::Number(expected)) => number == Some(expected),
+        Some(OscValue::String(ref expected)) => {
+            matches!(arg, OscType::String(value) if value == expected)
+        }
+        Some(OscValue::Bool(expected)) => match *arg {
+            OscType::Bool(value) => value == expected,
+            // Many controllers send buttons as 0/1 integers
+            _ => number.is_some_and(|value| (value != 0.0) == expected),
+        },
+    };
+    let in_range = match (condition.min, condition.max) {
+        (None, None) => true,
+        (min, max) => number.is_some_and(|value| {
+            min.is_none_or(|min| value >= min) && max.is_none_or(|max| value <= max)
+        }),
+    };
+    equals && in_range
+}
+
+/// Value of a numeric argument
+pub fn number(arg: &OscType) -> Option<f64> {
+    match *arg {
+        OscType::Int(value) => Some(value as f64),
+        OscType::Long(value) => Some(value as f64),
+        OscType::Float(value) => Some(value as f64),
+        OscType::Double(value) => Some(value),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn address_patterns() {
+        let cases = [
+            // Literal addresses match only themselves, case-sensitively
+            ("/mixer/fader", "/mixer/fader", true),
+            ("/mixer/fader", "/mixer/fader2", false),
+            ("/mixer/fader", "/mixer", false),
+            ("/mixer/fader", "/Mixer/fader", false),
+            // `?` matches any single character within a part
+            ("/fader?", "/fader1", true),
+            ("/fader?", "/fader", false),
+            ("/fader?", "/fader12", false),
+            ("/a?b", "/a/b", false),
+            // `*` matches any run of characters within a part, including none
+            ("/fader*", "/fader", true),
+            ("/fader*", "/fader12", true),
+            ("/*", "/a/b", false),
+            ("/*/volume", "/channel1/volume", true),
+            ("/*/volume", "/channel1/pan", false),
+            ("/*/*", "/a/b", true),
+            ("/a*c", "/abbbc", true),
+            ("/a*c", "/abbbd", false),
+            ("/*c*", "/abcd", true),
+            ("/**", "/abc", true),
+            // `[]` matches one character of a set, range or negated set
+            ("/fader[123]", "/fader2", true),
+            ("/fader[123]", "/fader4", false),
+            ("/fader[1-3]", "/fader3", true),
+            ("/fader[1-3]", "/fader0", false),
+            ("/fader[a-cx]", "/faderx", true),
+            ("/fader[!1-3]", "/fader4", true),
+            ("/fader[!1-3]", "/fader2", false),
+            ("/fader[1-3]", "/fader12", false),
+            ("/a[/]b", "/a/b", false),
+            ("/a[!x]b", "/a/b", false),
+            // `-` at either end of a set and `!` after the start are literal
+            ("/a[b-]", "/a-", true),
+            ("/a[-b]", "/a-", true),
+            ("/a[-b]", "/ab", true),
+            ("/a[b!]", "/a!", true),
+            ("/a[b!]", "/ac", false),
+            // `{}` matches any of the listed strings
+            ("/scene/{up,down}", "/scene/up", true),
+            ("/scene/{up,down}", "/scene/down", true),
+            ("/scene/{up,down}", "/scene/left", false),
+            ("/scene/{up,down}", "/scene/updown", false),
+            ("/{a,ab}c", "/abc", true),
+            ("/a{,s}", "/a", true),
+            ("/a{,s}", "/as", true),
+            ("/{fader,knob}[0-9]/*", "/knob7/value", true),
+            // Malformed patterns match nothing, not even themselves
+            ("/fader[12", "/fader[12", false),
+            ("/fader[12", "/fader1", false),
+            ("/scene/{up,down", "/scene/{up,down", false),
+            ("/scene/{up,down", "/scene/up", false),
+            // Unpaired closing brackets are literal
+            ("/a]", "/a]", true),
+            ("/a}", "/a}", true),
+        ];
+        for (pattern, address, expected) in cases {
+            assert_eq!(
+                address_matches(pattern, address),
+                expected,
+                "{pattern} against {address}"
+            );
+        }
+    }
+
+    #[test]
+    fn character_sets() {
+        let set = |text: &str| text.chars().collect::<Vec<_>>();
+        assert!(in_set(&set("abc"), 'b'));
+        assert!(!in_set(&set("abc"), 'd'));
+        assert!(in_set(&set("a-z"), 'm'));
+        assert!(in_set(&set("a-z0-9"), '5'));
+        assert!(!in_set(&set("a-z0-9"), 'A'));
+        assert!(!in_set(&set("!a-z"), 'm'));
+        assert!(in_set(&set("!a-z"), 'M'));
+        // A reversed range is empty
+        assert!(!in_set(&set("z-a"), 'm'));
+        // An empty set matches nothing
+        assert!(!in_set(&[], 'a'));
+    }
+}
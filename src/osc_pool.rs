@@ -0,0 +1,68 @@
+use crate::mapping::{DeliveryPolicy, OscDestination};
+use std::collections::HashMap;
+use std::io;
+use tokio::net::UdpSocket;
+use tokio::sync::Mutex;
+use tracing::warn;
+
+/// Sends OSC packets to the endpoints of destination pools according to their policy
+pub struct OscPools {
+    /// Next round-robin endpoint index keyed by destination name
+    next: Mutex<HashMap<String, usize>>,
+}
+
+impl OscPools {
+    pub fn new() -> Self {
+        Self {
+            next: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Send a packet to the destination, returning the endpoints that received it.
+    ///
+    /// Fails only if no endpoint accepted the packet.
+    pub async fn send(
+        &self,
+        socket: &UdpSocket,
+        destination_name: &str,
+        destination: &OscDestination,
+        packet: &[u8],
+    ) -> io::Result<Vec<String>> {
+        let addresses = destination.addresses();
+
+        let mut order: Vec<&String> = addresses.iter().collect();
+        if destination.delivery == DeliveryPolicy::RoundRobin {
+            let mut next = self.next.lock().await;
+            let index = next.entry(destination_name.to_string()).or_default();
+            order.rotate_left(*index % addresses.len());
+            *index = (*index + 1) % addresses.len();
+        }
+
+        let mut sent = Vec::new();
+        let mut last_error = None;
+        for addr in order {
+            match socket.send_to(packet, addr.as_str()).await {
+                Ok(_) => {
+                    sent.push(addr.clone());
+                    if destination.delivery != DeliveryPolicy::All {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    if addresses.len() > 1 {
+                        warn!(
+                            "Failed to send OSC message to {} ({}): {}",
+                            destination_name, addr, e
+                        );
+                    }
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        match last_error {
+            Some(e) if sent.is_empty() => Err(e),
+            _ => Ok(sent),
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use crate::mapping::OscQueueConfig;
+use crate::processor::MidiProcessor;
+use crate::supervisor::supervise;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// How often queued OSC destinations are sent their heartbeat query
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// An encoded OSC message waiting for its destination to become reachable
+#[derive(Debug, Clone)]
+pub struct QueuedOscMessage {
+    /// OSC address, used to supersede older messages to the same address
+    pub address: String,
+    /// Encoded packet
+    pub packet: Vec<u8>,
+    queued_at: Instant,
+}
+
+/// Buffers state-relevant OSC messages per destination while it is unreachable
+pub struct OscOfflineQueue {
+    unreachable: HashSet<String>,
+    messages: HashMap<String, VecDeque<QueuedOscMessage>>,
+    /// Destination of each resolved endpoint address, for matching replies
+    endpoints: HashMap<SocketAddr, String>,
+    /// Time each destination last replied, or since when it has been watched
+    last_reply: HashMap<String, Instant>,
+}
+
+impl OscOfflineQueue {
+    pub fn new() -> Self {
+        Self {
+            unreachable: HashSet::new(),
+            messages: HashMap::new(),
+            endpoints: HashMap::new(),
+            last_reply: HashMap::new(),
+        }
+    }
+
+    /// Whether the destination is replying to its heartbeats
+    pub fn is_reachable(&self, destination: &str) -> bool {
+        !self.unreachable.contains(destination)
+    }
+
+    /// Match replies from an endpoint address to the destination
+    pub fn set_endpoint(&mut self, addr: SocketAddr, destination: &str) {
+        self.endpoints.insert(addr, destination.to_string());
+    }
+
+    /// Record a packet received from an endpoint address
+    pub fn record_reply(&mut self, from: SocketAddr) {
+        if let Some(destination) = self.endpoints.get(&from) {
+            self.last_reply.insert(destination.clone(), Instant::now());
+        }
+    }
+
+    /// Whether the destination replied within the timeout; a destination that was just
+    /// added gets the timeout to send its first reply
+    pub fn replied_within(&mut self, destination: &str, timeout: Duration) -> bool {
+        self.last_reply
+            .entry(destination.to_string())
+            .or_insert_with(Instant::now)
+            .elapsed()
+            <= timeout
+    }
+
+    /// Record whether the destination is reachable
+    pub fn set_reachable(&mut self, destination: &str, reachable: bool) {
+        if reachable {
+            if self.unreachable.remove(destination) {
+                info!("OSC destination '{}' is reachable again", destination);
+            }
+        } else if self.unreachable.insert(destination.to_string()) {
+            warn!(
+                "OSC destination '{}' is unreachable, queueing messages",
+                destination
+            );
+        }
+    }
+
+    /// Queue a message, replacing any older message to the same address
+    pub fn push(
+        &mut self,
+        destination: &str,
+        config: &OscQueueConfig,
+        address: &str,
+        packet: Vec<u8>,
+    ) {
+        let queue = self.messages.entry(destination.to_string()).or_default();
+        queue.retain(|message| message.address != address);
+        queue.push_back(QueuedOscMessage {
+            address: address.to_string(),
+            packet,
+            queued_at: Instant::now(),
+        });
+
+        while queue.len() > config.max_messages {
+            if let Some(dropped) = queue.pop_front() {
+                warn!(
+                    "OSC queue for '{}' is full, dropping {}",
+                    destination, dropped.address
+                );
+            }
+        }
+    }
+
+    /// Take every queued message for the destination that has not gone stale, oldest first
+    pub fn take(&mut self, destination: &str, config: &OscQueueConfig) -> Vec<QueuedOscMessage> {
+        let max_age = Duration::from_millis(config.max_age_ms);
+        self.messages
+            .remove(destination)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|message| {
+                let fresh = message.queued_at.elapsed() <= max_age;
+                if !fresh {
+                    info!(
+                        "Discarding stale queued OSC message {} for '{}'",
+                        message.address, destination
+                    );
+                }
+                fresh
+            })
+            .collect()
+    }
+
+    /// Mark the destination reachable if nothing is left to replay, so messages queued
+    /// during a replay are replayed before new ones are sent directly
+    pub fn finish_replay(&mut self, destination: &str) -> bool {
+        let done = self
+            .messages
+            .get(destination)
+            .is_none_or(|queue| queue.is_empty());
+        if done {
+            self.set_reachable(destination, true);
+        }
+        done
+    }
+
+    /// Put messages that could not be delivered back at the front of the queue
+    pub fn restore(&mut self, destination: &str, messages: Vec<QueuedOscMessage>) {
+        let queue = self.messages.entry(destination.to_string()).or_default();
+        for message in messages.into_iter().rev() {
+            if !queue.iter().any(|queued| queued.address == message.address) {
+                queue.push_front(message);
+            }
+        }
+    }
+}
+
+/// Sends heartbeat queries to OSC destinations with an offline queue and watches for
+/// their replies, replaying the queue once a destination answers again
+pub struct OscQueueMonitor {
+    processor: Arc<MidiProcessor>,
+}
+
+impl OscQueueMonitor {
+    pub fn new(processor: Arc<MidiProcessor>) -> Self {
+        Self { processor }
+    }
+
+    /// Start the heartbeats in the background
+    pub fn start(&self) -> JoinHandle<()> {
+        let processor = Arc::clone(&self.processor);
+
+        supervise("OSC queue heartbeats", move || {
+            let processor = Arc::clone(&processor);
+            async move {
+                let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+                loop {
+                    tokio::select! {
+                        _ = interval.tick() => processor.check_osc_destinations().await,
+                        reply = processor.receive_osc_reply() => match reply {
+                            Ok(from) => processor.record_osc_reply(from).await,
+                            Err(e) => {
+                                debug!("Failed to receive OSC reply: {}", e);
+                                tokio::time::sleep(Duration::from_millis(100)).await;
+                            }
+                        },
+                    }
+                }
+            }
+        })
+    }
+}
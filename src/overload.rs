@@ -0,0 +1,155 @@
+use crate::mapping::OverloadConfig;
+use midi_types::MidiMessage;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// How often a summary is logged while a session stays overloaded
+const REPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Overload state of a session, shared with status reporting
+#[derive(Debug, Default)]
+pub struct OverloadStats {
+    pub overloaded: AtomicBool,
+    /// Incoming messages dropped by the shed policy
+    pub shed: AtomicU64,
+}
+
+/// Watches the ingestion queue of one session for sustained overload.
+///
+/// A session becomes overloaded once its queue depth or the time from receipt to
+/// processing stays over the limits for `sustain_ms`, and recovers once neither has
+/// been over them for `sustain_ms`. While overloaded, continuous controller messages
+/// can be shed so program changes, notes and clock keep flowing.
+pub struct OverloadMonitor {
+    session: String,
+    config: OverloadConfig,
+    stats: Arc<OverloadStats>,
+    /// Start of the current run of observations over the limits
+    over_since: Option<Instant>,
+    /// Most recent observation over the limits
+    last_over: Option<Instant>,
+    last_report: Instant,
+    /// Worst values and shed messages since the last report
+    peak_depth: usize,
+    peak_latency: Duration,
+    shed_since_report: u64,
+}
+
+impl OverloadMonitor {
+    pub fn new(session: &str, config: OverloadConfig, stats: Arc<OverloadStats>) -> Self {
+        Self {
+            session: session.to_string(),
+            config,
+            stats,
+            over_since: None,
+            last_over: None,
+            last_report: Instant::now(),
+            peak_depth: 0,
+            peak_latency: Duration::ZERO,
+            shed_since_report: 0,
+        }
+    }
+
+    /// Whether a message should be dropped instead of processed
+    pub fn should_shed(&mut self, message: &MidiMessage) -> bool {
+        self.update_recovery();
+        if !self.config.shed || !self.stats.overloaded.load(Ordering::Relaxed) {
+            return false;
+        }
+        let sheddable = matches!(
+            message,
+            MidiMessage::ControlChange(..)
+                | MidiMessage::PitchBendChange(..)
+                | MidiMessage::ChannelPressure(..)
+                | MidiMessage::KeyPressure(..)
+        );
+        if sheddable {
+            self.stats.shed.fetch_add(1, Ordering::Relaxed);
+            self.shed_since_report += 1;
+        }
+        sheddable
+    }
+
+    /// Record the queue depth and latency seen when a message was processed
+    pub fn observe(&mut self, queue_depth: usize, latency: Duration) {
+        let now = Instant::now();
+        let over = queue_depth >= self.config.max_queue_depth
+            || latency >= Duration::from_millis(self.config.max_latency_ms);
+        self.peak_depth = self.peak_depth.max(queue_depth);
+        self.peak_latency = self.peak_latency.max(latency);
+
+        if over {
+            self.last_over = Some(now);
+            let since = *self.over_since.get_or_insert(now);
+            if !self.stats.overloaded.load(Ordering::Relaxed) && now - since >= self.sustain() {
+                self.stats.overloaded.store(true, Ordering::Relaxed);
+                warn!(
+                    session = %self.session,
+                    queue_depth,
+                    latency_ms = latency.as_millis() as u64,
+                    shedding = self.config.shed,
+                    "Session '{}' is overloaded{}",
+                    self.session,
+                    if self.config.shed {
+                        ", shedding controller messages"
+                    } else {
+                        ""
+                    }
+                );
+                self.reset_report(now);
+            }
+        } else {
+            self.over_since = None;
+        }
+        self.update_recovery();
+
+        if self.stats.overloaded.load(Ordering::Relaxed)
+            && now - self.last_report >= REPORT_INTERVAL
+        {
+            warn!(
+                session = %self.session,
+                peak_queue_depth = self.peak_depth,
+                peak_latency_ms = self.peak_latency.as_millis() as u64,
+                shed = self.shed_since_report,
+                "Session '{}' still overloaded",
+                self.session
+            );
+            self.reset_report(now);
+        }
+    }
+
+    /// End the overload once nothing has been over the limits for the sustain time
+    fn update_recovery(&mut self) {
+        if !self.stats.overloaded.load(Ordering::Relaxed) {
+            return;
+        }
+        if self
+            .last_over
+            .is_some_and(|last_over| last_over.elapsed() < self.sustain())
+        {
+            return;
+        }
+        self.stats.overloaded.store(false, Ordering::Relaxed);
+        self.over_since = None;
+        info!(
+            session = %self.session,
+            shed = self.shed_since_report,
+            "Session '{}' recovered from overload",
+            self.session
+        );
+        self.reset_report(Instant::now());
+    }
+
+    fn reset_report(&mut self, now: Instant) {
+        self.last_report = now;
+        self.peak_depth = 0;
+        self.peak_latency = Duration::ZERO;
+        self.shed_since_report = 0;
+    }
+
+    fn sustain(&self) -> Duration {
+        Duration::from_millis(self.config.sustain_ms)
+    }
+}
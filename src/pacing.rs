@@ -0,0 +1,105 @@
+use crate::mapping::OutboundShaping;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// Keeps a minimum gap between messages sent to one destination, and limits their
+/// rate if shaping is configured.
+///
+/// Some vintage gear behind a network MIDI bridge drops messages that arrive
+/// back-to-back faster than its serial port can take them, and dense automation can
+/// overrun a consumer-grade WiFi link.
+#[derive(Debug)]
+pub struct Pacer {
+    gap: Duration,
+    shaping: Option<OutboundShaping>,
+    state: Mutex<PacerState>,
+    /// Messages that waited for the rate limit
+    pub delayed: AtomicU64,
+    /// Messages dropped because they would have waited longer than `max_delay_ms`
+    pub dropped: AtomicU64,
+}
+
+#[derive(Debug)]
+struct PacerState {
+    /// When the previous message was sent
+    last_sent: Option<Instant>,
+    /// Packets that can be sent without waiting, as of `refilled`
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl Pacer {
+    pub fn new(gap: Duration, shaping: Option<OutboundShaping>) -> Self {
+        let tokens = shaping
+            .as_ref()
+            .map_or(0.0, |shaping| f64::from(shaping.max_burst.max(1)));
+        Self {
+            gap,
+            shaping,
+            state: Mutex::new(PacerState {
+                last_sent: None,
+                tokens,
+                refilled: Instant::now(),
+            }),
+            delayed: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Run a send once the gap since the previous one has passed and the rate limit
+    /// allows it, however long that takes
+    pub async fn send<F: Future>(&self, send: F) -> F::Output {
+        match self.pace(send, false).await {
+            Some(output) => output,
+            None => unreachable!("sends that wait are never dropped"),
+        }
+    }
+
+    /// Like [`Pacer::send`], but a send that would wait longer than the shaping's
+    /// `max_delay_ms` is dropped without running and `None` is returned
+    pub async fn try_send<F: Future>(&self, send: F) -> Option<F::Output> {
+        self.pace(send, true).await
+    }
+
+    /// Sends wait in order for the gap and the rate limit
+    async fn pace<F: Future>(&self, send: F, droppable: bool) -> Option<F::Output> {
+        if self.gap.is_zero() && self.shaping.is_none() {
+            return Some(send.await);
+        }
+        let requested = Instant::now();
+        let mut state = self.state.lock().await;
+
+        let now = Instant::now();
+        let mut ready = state
+            .last_sent
+            .map_or(now, |last_sent| (last_sent + self.gap).max(now));
+        if let Some(ref shaping) = self.shaping {
+            let rate = f64::from(shaping.max_packets_per_sec.max(1));
+            let elapsed = ready.duration_since(state.refilled).as_secs_f64();
+            let tokens = (state.tokens + elapsed * rate).min(f64::from(shaping.max_burst.max(1)));
+            if tokens < 1.0 {
+                ready += Duration::from_secs_f64((1.0 - tokens) / rate);
+            }
+            if droppable
+                && ready > now
+                && ready.duration_since(requested) > Duration::from_millis(shaping.max_delay_ms)
+            {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                return None;
+            }
+            if tokens < 1.0 {
+                self.delayed.fetch_add(1, Ordering::Relaxed);
+            }
+            state.tokens = tokens.max(1.0) - 1.0;
+            state.refilled = ready;
+        }
+
+        tokio::time::sleep_until(ready).await;
+        let output = send.await;
+        state.last_sent = Some(Instant::now());
+        Some(output)
+    }
+}
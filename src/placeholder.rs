@@ -0,0 +1,40 @@
+use crate::device::OscArg;
+use crate::mapping::SongMetadata;
+
+/// Replace `{song.<field>}` placeholders with values from the selected song's metadata.
+///
+/// Placeholders for fields the song doesn't define are left as they are.
+pub fn expand(text: &str, song: &SongMetadata) -> String {
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("{song.") {
+        expanded.push_str(&rest[..start]);
+        let placeholder = &rest[start..];
+        let Some(end) = placeholder.find('}') else {
+            break;
+        };
+
+        let field = &placeholder["{song.".len()..end];
+        match song.value(field) {
+            Some(value) => expanded.push_str(&value),
+            None => expanded.push_str(&placeholder[..=end]),
+        }
+        rest = &placeholder[end + 1..];
+    }
+
+    expanded.push_str(rest);
+    expanded
+}
+
+/// Expand placeholders in the string arguments of an OSC command
+pub fn expand_args(args: &[OscArg], song: &SongMetadata) -> Vec<OscArg> {
+    args.iter()
+        .map(|arg| match arg {
+            OscArg::String { value } => OscArg::String {
+                value: expand(value, song),
+            },
+            other => other.clone(),
+        })
+        .collect()
+}
@@ -0,0 +1,62 @@
+//! Plugin API for custom message transforms and protocol adapters.
+//!
+//! A plugin is a `cdylib` crate that depends on `midi-router`, implements [`Plugin`]
+//! and exports it with [`declare_plugin!`]. The router loads it from the map config's
+//! `plugins` list when built with the `plugins` feature.
+//!
+//! Plugins are passed between the router and the library as Rust trait objects, so
+//! they must be built with the same Rust toolchain and `midi-router` version as the
+//! router; [`PLUGIN_API_VERSION`] is checked on load to catch mismatches.
+
+use midi_types::MidiMessage;
+use std::sync::Arc;
+
+/// Version of the plugin API; bumped whenever [`Plugin`] or [`PluginHost`] change
+pub const PLUGIN_API_VERSION: u32 = 1;
+
+/// Symbol exporting the plugin's API version
+pub const API_VERSION_SYMBOL: &[u8] = b"midi_router_plugin_api_version";
+/// Symbol exporting the plugin constructor
+pub const CREATE_SYMBOL: &[u8] = b"midi_router_plugin_create";
+
+/// Services the router offers to plugins
+pub trait PluginHost: Send + Sync {
+    /// Feed a MIDI message into routing as if it was received from `source`
+    fn inject_midi(&self, source: &str, message: MidiMessage);
+}
+
+/// A plugin loaded into the router.
+///
+/// Every method has a default, so a plugin only implements what it needs.
+pub trait Plugin: Send + Sync {
+    /// Name used in logs
+    fn name(&self) -> &str;
+
+    /// Called once after loading; protocol adapters keep the host to inject messages
+    fn start(&self, _host: Arc<dyn PluginHost>) {}
+
+    /// Transform an incoming MIDI message before routing, or return `None` to drop it
+    fn on_midi(&self, _source: &str, message: MidiMessage) -> Option<MidiMessage> {
+        Some(message)
+    }
+
+    /// Called before the plugin is unloaded
+    fn stop(&self) {}
+}
+
+/// Export a plugin from a `cdylib`, given an expression that constructs it
+#[macro_export]
+macro_rules! declare_plugin {
+    ($constructor:expr) => {
+        #[unsafe(no_mangle)]
+        pub extern "C" fn midi_router_plugin_api_version() -> u32 {
+            $crate::plugin::PLUGIN_API_VERSION
+        }
+
+        #[unsafe(no_mangle)]
+        pub extern "C" fn midi_router_plugin_create() -> *mut Box<dyn $crate::plugin::Plugin> {
+            let plugin: Box<dyn $crate::plugin::Plugin> = Box::new($constructor);
+            Box::into_raw(Box::new(plugin))
+        }
+    };
+}
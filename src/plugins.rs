@@ -0,0 +1,119 @@
+use crate::mapping::PluginConfig;
+use crate::plugin::{Plugin, PluginHost};
+use anyhow::Result;
+use midi_types::MidiMessage;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tracing::info;
+
+/// A loaded plugin, dropped before the library that contains its code
+#[cfg_attr(not(feature = "plugins"), allow(dead_code))]
+struct LoadedPlugin {
+    plugin: Box<dyn Plugin>,
+    #[cfg(feature = "plugins")]
+    _library: libloading::Library,
+}
+
+/// Host handed to plugins; injected messages are routed by the routing context
+struct ChannelHost {
+    tx: mpsc::UnboundedSender<(String, MidiMessage)>,
+}
+
+impl PluginHost for ChannelHost {
+    fn inject_midi(&self, source: &str, message: MidiMessage) {
+        // The receiver only goes away when the context stops
+        let _ = self.tx.send((source.to_string(), message));
+    }
+}
+
+/// Plugins loaded for one routing context
+pub struct PluginRegistry {
+    plugins: Vec<LoadedPlugin>,
+}
+
+impl PluginRegistry {
+    /// Load and start the configured plugins, returning the messages they inject
+    pub fn load(
+        configs: &[PluginConfig],
+    ) -> Result<(Self, mpsc::UnboundedReceiver<(String, MidiMessage)>)> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let host: Arc<dyn PluginHost> = Arc::new(ChannelHost { tx });
+
+        let mut plugins = Vec::new();
+        for config in configs {
+            if !config.enabled {
+                continue;
+            }
+            if let Some(loaded) = load_plugin(config)? {
+                info!(
+                    "Loaded plugin '{}' from {}",
+                    loaded.plugin.name(),
+                    config.path
+                );
+                loaded.plugin.start(Arc::clone(&host));
+                plugins.push(loaded);
+            }
+        }
+
+        Ok((Self { plugins }, rx))
+    }
+
+    /// Pass a message through every plugin in order, returning `None` if one drops it
+    pub fn transform(&self, source: &str, message: MidiMessage) -> Option<MidiMessage> {
+        self.plugins.iter().try_fold(message, |message, loaded| {
+            loaded.plugin.on_midi(source, message)
+        })
+    }
+
+    /// Stop every plugin
+    pub fn stop(&self) {
+        for loaded in &self.plugins {
+            loaded.plugin.stop();
+        }
+    }
+}
+
+#[cfg(feature = "plugins")]
+fn load_plugin(config: &PluginConfig) -> Result<Option<LoadedPlugin>> {
+    use crate::plugin::{API_VERSION_SYMBOL, CREATE_SYMBOL, PLUGIN_API_VERSION};
+    use anyhow::{Context, bail};
+
+    // SAFETY: loading runs the library's initializers; plugins are trusted code
+    // named in the config, built against this crate's plugin API
+    unsafe {
+        let library = libloading::Library::new(&config.path)
+            .with_context(|| format!("Failed to load plugin {}", config.path))?;
+
+        let api_version: libloading::Symbol<extern "C" fn() -> u32> = library
+            .get(API_VERSION_SYMBOL)
+            .with_context(|| format!("{} is not a midi-router plugin", config.path))?;
+        let version = api_version();
+        if version != PLUGIN_API_VERSION {
+            bail!(
+                "Plugin {} uses API version {} (router supports {})",
+                config.path,
+                version,
+                PLUGIN_API_VERSION
+            );
+        }
+
+        let create: libloading::Symbol<extern "C" fn() -> *mut Box<dyn Plugin>> = library
+            .get(CREATE_SYMBOL)
+            .with_context(|| format!("{} is not a midi-router plugin", config.path))?;
+        let plugin = *Box::from_raw(create());
+
+        Ok(Some(LoadedPlugin {
+            plugin,
+            _library: library,
+        }))
+    }
+}
+
+#[cfg(not(feature = "plugins"))]
+fn load_plugin(config: &PluginConfig) -> Result<Option<LoadedPlugin>> {
+    tracing::warn!(
+        "Plugin {} not loaded: built without the `plugins` feature",
+        config.path
+    );
+    Ok(None)
+}
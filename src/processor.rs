@@ -1,25 +1,142 @@
-use crate::device::{Command, DeviceConfig, OscArg, TempoDataType, TempoSpec};
-use crate::mapping::{Destination, MapConfig};
+use crate::audit::AuditLog;
+use crate::automation::{AutomationState, DEFAULT_BPM};
+use crate::bank::{self, BankState};
+use crate::command_dsl::MAX_SONG_POSITION;
+use crate::cooldown::Cooldowns;
+#[cfg(any(feature = "http", feature = "osc-input"))]
+use crate::cue::CueAction;
+use crate::cue::CueState;
+#[cfg(feature = "http")]
+use crate::device::Device;
+use crate::device::{
+    Command, CommandBatch, CommandValue, DeviceConfig, DisplayProtocol, OscArg, Program,
+    SequenceStep, TempoDataType, TempoSpec,
+};
+use crate::events::EventPublisher;
+use crate::freeze::OutputFreeze;
+#[cfg(feature = "osc-input")]
+use crate::mapping::OscControlRule;
+use crate::mapping::{
+    BankSelector, CueList, Destination, DeviceMapping, InactivityRule, MAIN_TEMPO_DOMAIN,
+    MapConfig, NoteAction, OscDestination, ProgramSource, SongMetadata, TimeSourceConfig,
+};
+use crate::midi_fields::MidiFields;
+use crate::ordering::DestinationLocks;
+#[cfg(feature = "osc-input")]
+use crate::osc_match::{self, OscMatch};
+use crate::osc_pool::OscPools;
+use crate::osc_queue::OscOfflineQueue;
+use crate::placeholder;
+use crate::plugins::PluginRegistry;
+use crate::random::Randomizer;
+use crate::sequence::Sequences;
+use crate::sequencer::StepSequencer;
 use crate::session_manager::SessionManager;
+use crate::settle::{PendingProgram, PendingPrograms, PendingTempos};
+use crate::sysex;
+use crate::tempo::{self, TapTimes, TapToken, TempoDomains};
+#[cfg(any(feature = "http", feature = "osc-input"))]
+use crate::tempo_stats::{TempoReport, TempoStats};
+use crate::time_source::TimeSource;
+use crate::timed::{TimedPrograms, TimedRun};
+use crate::trigger::{Activation, TriggerEvent, TriggerTracker};
+use crate::variables::Variables;
 use anyhow::Result;
 use midi_types::MidiMessage;
-use rosc::{OscMessage, OscPacket, OscType};
-use std::net::UdpSocket;
+use rosc::{OscBundle, OscMessage, OscPacket, OscType};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::net::SocketAddr;
 use std::sync::Arc;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
 use tokio::sync::RwLock;
 use tracing::{debug, error, info, warn};
 
+/// Number of recent inputs kept for status displays
+const RECENT_INPUTS: usize = 50;
+
+/// Control Change number of the All Notes Off channel mode message
+#[cfg(feature = "tui")]
+const ALL_NOTES_OFF: u8 = 123;
+
+/// Control Change numbers of Bank Select MSB and LSB
+const BANK_SELECT_MSB: u8 = 0;
+const BANK_SELECT_LSB: u8 = 32;
+
 /// MIDI event processor that handles incoming MIDI events and routes commands
 pub struct MidiProcessor {
     device_config: Arc<RwLock<DeviceConfig>>,
     map_config: Arc<RwLock<MapConfig>>,
     osc_socket: Option<UdpSocket>,
     session_manager: Option<SessionManager>,
-    current_bpm: Arc<tokio::sync::RwLock<Option<f64>>>,
-    // Cancellation token for tap tempo operations
-    tap_tempo_cancel_tx: tokio::sync::watch::Sender<u64>,
-    tap_tempo_cancel_rx: tokio::sync::watch::Receiver<u64>,
+    // Current tempo of each tempo domain
+    tempos: Arc<tokio::sync::Mutex<TempoDomains>>,
+    // Recent tempo changes and tempo messages, for statistics
+    #[cfg(any(feature = "http", feature = "osc-input"))]
+    tempo_stats: Arc<tokio::sync::Mutex<TempoStats>>,
+    // Held notes and controller values for combinatorial triggers
+    trigger_tracker: Arc<tokio::sync::Mutex<TriggerTracker>>,
+    // Current position of each step mapping
+    step_sequencer: Arc<tokio::sync::Mutex<StepSequencer>>,
+    // Time of the most recent MIDI or OSC input
+    last_input: Arc<tokio::sync::RwLock<Instant>>,
+    audit_log: Option<AuditLog>,
+    events: Option<EventPublisher>,
+    plugins: Option<Arc<PluginRegistry>>,
+    // Messages buffered for unreachable OSC destinations
+    osc_queue: Arc<tokio::sync::Mutex<OscOfflineQueue>>,
+    // Endpoint selection for OSC destination pools
+    osc_pools: Arc<OscPools>,
+    // Clock for OSC bundle timetags
+    time_source: Arc<TimeSource>,
+    // Selected bank of each banked program rule
+    banked_selections: Arc<tokio::sync::Mutex<HashMap<String, u8>>>,
+    // Last Bank Select MSB (CC0) and LSB (CC32) received per input channel
+    bank_selects: Arc<tokio::sync::Mutex<HashMap<u8, (u8, u8)>>>,
+    // Currently selected bank
+    bank_state: Arc<tokio::sync::RwLock<BankState>>,
+    // Whether tempo and scene changes are restricted to operator sources
+    show_locked: AtomicBool,
+    // Most recent inputs, newest last, for status displays
+    recent_inputs: Arc<tokio::sync::Mutex<VecDeque<String>>>,
+    // Last program executed on each device, keyed by device name
+    active_programs: Arc<tokio::sync::RwLock<BTreeMap<String, String>>>,
+    // Metadata of the selected bank's song, for command placeholders
+    song: Arc<tokio::sync::RwLock<SongMetadata>>,
+    // Running automations and their next steps
+    automations: Arc<tokio::sync::Mutex<AutomationState>>,
+    // Position of each cue list and its waiting cues
+    cues: Arc<tokio::sync::Mutex<CueState>>,
+    // Program sequences waiting at their barriers
+    sequences: Arc<tokio::sync::Mutex<Sequences>>,
+    // Programs paused between their commands, one per mapping
+    timed_programs: Arc<tokio::sync::Mutex<TimedPrograms>>,
+    // Values captured from incoming messages
+    variables: Arc<tokio::sync::Mutex<Variables>>,
+    // Source of randomized command values
+    randomizer: Arc<tokio::sync::Mutex<Randomizer>>,
+    // Keeps batches of commands to the same destination from interleaving
+    destination_locks: Arc<DestinationLocks>,
+    // Last activation of each trigger with a cooldown
+    cooldowns: Arc<tokio::sync::Mutex<Cooldowns>>,
+    // Programs waiting for a burst of program changes to settle, keyed by mapping
+    pending_programs: Arc<tokio::sync::Mutex<PendingPrograms>>,
+    // OSC tempos waiting for their source to stop changing them, keyed by tempo domain
+    pending_tempos: Arc<tokio::sync::Mutex<PendingTempos>>,
+    // Recent taps of each tap input
+    tap_times: Arc<tokio::sync::Mutex<TapTimes>>,
+    // Last text sent to each tempo display address, keyed by destination and address
+    tempo_display_texts: Arc<tokio::sync::Mutex<HashMap<String, String>>>,
+    // Absolute position of each control forwarding rule, keyed by rule name
+    control_positions: Arc<tokio::sync::Mutex<HashMap<String, u8>>>,
+    // Last value sent by each OSC control rule, keyed by rule name
+    #[cfg(feature = "osc-input")]
+    osc_control_values: Arc<tokio::sync::Mutex<HashMap<String, u8>>>,
+    // Switch that drops outgoing messages
+    output_freeze: OutputFreeze,
+    // Whether the MIDI clock outputs are running
+    clock_running: tokio::sync::watch::Sender<bool>,
 }
 
 impl MidiProcessor {
@@ -28,112 +145,2132 @@ impl MidiProcessor {
         map_config: Arc<RwLock<MapConfig>>,
     ) -> Result<Self> {
         // Create a UDP socket for OSC messages
-        let osc_socket = UdpSocket::bind("0.0.0.0:0").ok();
+        let osc_socket = std::net::UdpSocket::bind("0.0.0.0:0")
+            .and_then(|socket| {
+                socket.set_nonblocking(true)?;
+                UdpSocket::from_std(socket)
+            })
+            .ok();
+
+        Ok(Self {
+            device_config,
+            map_config,
+            osc_socket,
+            session_manager: None,
+            tempos: Arc::new(tokio::sync::Mutex::new(TempoDomains::new())),
+            #[cfg(any(feature = "http", feature = "osc-input"))]
+            tempo_stats: Arc::new(tokio::sync::Mutex::new(TempoStats::default())),
+            trigger_tracker: Arc::new(tokio::sync::Mutex::new(TriggerTracker::new())),
+            step_sequencer: Arc::new(tokio::sync::Mutex::new(StepSequencer::new())),
+            last_input: Arc::new(tokio::sync::RwLock::new(Instant::now())),
+            audit_log: None,
+            events: None,
+            plugins: None,
+            osc_queue: Arc::new(tokio::sync::Mutex::new(OscOfflineQueue::new())),
+            osc_pools: Arc::new(OscPools::new()),
+            time_source: Arc::new(TimeSource::new(TimeSourceConfig::default())),
+            banked_selections: Arc::new(tokio::sync::Mutex::new(HashMap::new())),
+            bank_selects: Arc::new(tokio::sync::Mutex::new(HashMap::new())),
+            bank_state: Arc::new(tokio::sync::RwLock::new(BankState::new())),
+            show_locked: AtomicBool::new(false),
+            recent_inputs: Arc::new(tokio::sync::Mutex::new(VecDeque::new())),
+            active_programs: Arc::new(tokio::sync::RwLock::new(BTreeMap::new())),
+            song: Arc::new(tokio::sync::RwLock::new(SongMetadata::default())),
+            automations: Arc::new(tokio::sync::Mutex::new(AutomationState::new())),
+            cues: Arc::new(tokio::sync::Mutex::new(CueState::new())),
+            sequences: Arc::new(tokio::sync::Mutex::new(Sequences::default())),
+            timed_programs: Arc::new(tokio::sync::Mutex::new(TimedPrograms::default())),
+            variables: Arc::new(tokio::sync::Mutex::new(Variables::new())),
+            randomizer: Arc::new(tokio::sync::Mutex::new(Randomizer::new(None))),
+            destination_locks: Arc::new(DestinationLocks::new()),
+            cooldowns: Arc::new(tokio::sync::Mutex::new(Cooldowns::new())),
+            pending_programs: Arc::new(tokio::sync::Mutex::new(PendingPrograms::default())),
+            pending_tempos: Arc::new(tokio::sync::Mutex::new(PendingTempos::default())),
+            tap_times: Arc::new(tokio::sync::Mutex::new(TapTimes::default())),
+            tempo_display_texts: Arc::new(tokio::sync::Mutex::new(HashMap::new())),
+            control_positions: Arc::new(tokio::sync::Mutex::new(HashMap::new())),
+            #[cfg(feature = "osc-input")]
+            osc_control_values: Arc::new(tokio::sync::Mutex::new(HashMap::new())),
+            output_freeze: OutputFreeze::default(),
+            clock_running: tokio::sync::watch::Sender::new(true),
+        })
+    }
 
-        // Create cancellation channel for tap tempo operations
-        let (tap_tempo_cancel_tx, tap_tempo_cancel_rx) = tokio::sync::watch::channel(0u64);
+    /// Publish routed events to monitors
+    pub fn set_event_publisher(&mut self, events: EventPublisher) {
+        self.events = Some(events);
+    }
+
+    /// Set the session manager after construction
+    pub fn set_session_manager(&mut self, session_manager: SessionManager) {
+        self.session_manager = Some(session_manager);
+    }
+
+    /// Set the audit log after construction
+    pub fn set_audit_log(&mut self, audit_log: AuditLog) {
+        self.audit_log = Some(audit_log);
+    }
+
+    /// Pass incoming MIDI through plugins after construction
+    pub fn set_plugins(&mut self, plugins: Arc<PluginRegistry>) {
+        self.plugins = Some(plugins);
+    }
+
+    /// Share the switch that drops outgoing messages
+    pub fn set_output_freeze(&mut self, output_freeze: OutputFreeze) {
+        self.output_freeze = output_freeze;
+    }
+
+    /// Freeze or thaw one destination, or every output if `destination` is `None`
+    #[cfg(feature = "osc-input")]
+    pub fn set_output_frozen(&self, destination: Option<&str>, frozen: bool) {
+        self.output_freeze.set(destination, frozen);
+    }
+
+    /// Set the clock used for timestamps sent to peers
+    pub fn set_time_source(&mut self, time_source: Arc<TimeSource>) {
+        self.time_source = time_source;
+    }
+
+    /// Seed randomized command values so they repeat across runs
+    pub fn set_random_seed(&mut self, seed: u64) {
+        self.randomizer = Arc::new(tokio::sync::Mutex::new(Randomizer::new(Some(seed))));
+    }
+
+    /// Process an incoming MIDI message received from the named source
+    pub async fn process_midi_message(&self, source: &str, message: MidiMessage) -> Result<()> {
+        self.record_input().await;
+        self.record_recent(source, format!("{message:?}")).await;
+
+        let message = match self.plugins {
+            Some(ref plugins) => match plugins.transform(source, message) {
+                Some(message) => message,
+                None => {
+                    debug!("MIDI message from '{}' dropped by a plugin", source);
+                    return Ok(());
+                }
+            },
+            None => message,
+        };
+        self.evaluate_osc_forwards(&message).await?;
+        let locked = self.is_locked_out(source).await;
+
+        match message {
+            MidiMessage::ProgramChange(msg_channel, program) => {
+                if locked {
+                    info!(
+                        "Ignoring program change from '{}' while the show is locked",
+                        source
+                    );
+                } else {
+                    self.handle_program_change(msg_channel.into(), program.into())
+                        .await?;
+                }
+            }
+            MidiMessage::NoteOn(msg_channel, note, velocity) => {
+                self.handle_note(msg_channel.into(), note.into(), velocity.into(), locked)
+                    .await?;
+            }
+            MidiMessage::NoteOff(msg_channel, note, _velocity) => {
+                self.handle_note(msg_channel.into(), note.into(), 0, locked)
+                    .await?;
+            }
+            MidiMessage::ControlChange(msg_channel, controller, value) => {
+                self.handle_control_change(
+                    msg_channel.into(),
+                    controller.into(),
+                    value.into(),
+                    locked,
+                )
+                .await?;
+            }
+            _ => {
+                debug!("Ignoring MIDI message: {:?}", message);
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle OSC tempo message, for the given domain or the one the source sets
+    #[cfg(feature = "osc-input")]
+    pub async fn handle_osc_tempo(
+        &self,
+        source: &str,
+        domain: Option<&str>,
+        bpm: f64,
+    ) -> Result<()> {
+        self.record_input().await;
+        let (domain, limits, window) = {
+            let map_config = self.map_config.read().await;
+            let domain = match domain {
+                Some(domain) => domain.to_string(),
+                None => tempo::source_domain(&map_config, source).to_string(),
+            };
+            (
+                domain,
+                map_config.tempo_limits.clone(),
+                stats_window(&map_config),
+            )
+        };
+        self.record_recent("OSC", tempo_input(&domain, bpm)).await;
+        self.tempo_stats
+            .lock()
+            .await
+            .record_source(source, &domain, bpm, window);
+
+        // Drop jitter around the current tempo, along with any tempo still settling,
+        // since the source has come back to where it was
+        if let Some(min_change) = limits.min_change_bpm {
+            let current = self.tempos.lock().await.bpm(&domain);
+            if current.is_some_and(|current| (current - bpm).abs() <= min_change) {
+                self.pending_tempos.lock().await.cancel(&domain);
+                debug!(
+                    "Ignoring tempo {} BPM via {} within {} BPM of the current tempo",
+                    bpm, source, min_change
+                );
+                return Ok(());
+            }
+        }
+
+        if let Some(settle_ms) = limits.settle_ms {
+            self.pending_tempos.lock().await.hold(
+                &domain,
+                bpm,
+                source,
+                Duration::from_millis(settle_ms),
+                limits.min_change_bpm.unwrap_or(0.0),
+            );
+            return Ok(());
+        }
+
+        self.set_tempo(&domain, bpm, source).await
+    }
+
+    /// Apply the OSC tempos that have stopped changing
+    pub async fn apply_settled_tempos(&self) {
+        let settled = self.pending_tempos.lock().await.take_due(Instant::now());
+        for (domain, pending) in settled {
+            if let Err(e) = self.set_tempo(&domain, pending.bpm, &pending.source).await {
+                error!("Error applying settled tempo: {}", e);
+            }
+        }
+    }
+
+    /// Handle a tempo set through the control API
+    #[cfg(feature = "http")]
+    pub async fn handle_api_tempo(&self, domain: &str, bpm: f64) -> Result<()> {
+        self.record_input().await;
+        self.record_recent("API", tempo_input(domain, bpm)).await;
+        self.set_tempo(domain, bpm, "API").await
+    }
+
+    /// Handle a tempo from the Ableton Link session
+    #[cfg(feature = "link")]
+    pub async fn handle_link_tempo(&self, domain: &str, bpm: f64) -> Result<()> {
+        self.record_input().await;
+        self.record_recent("Link", tempo_input(domain, bpm)).await;
+        self.set_tempo(domain, bpm, "Link").await
+    }
+
+    /// Whether a tempo domain is configured
+    #[cfg(feature = "http")]
+    pub async fn has_tempo_domain(&self, domain: &str) -> bool {
+        tempo::has_domain(&*self.map_config.read().await, domain)
+    }
+
+    /// Apply a new tempo to a domain within the configured limits and send it to its devices
+    async fn set_tempo(&self, domain: &str, bpm: f64, source: &str) -> Result<()> {
+        let (limits, known, enabled) = {
+            let map_config = self.map_config.read().await;
+            (
+                map_config.tempo_limits.clone(),
+                tempo::has_domain(&map_config, domain),
+                map_config.subsystems.tempo,
+            )
+        };
+        if !enabled {
+            debug!("Ignoring tempo via {} while tempo is disabled", source);
+            return Ok(());
+        }
+        if !known {
+            warn!("Ignoring tempo for unknown tempo domain '{}'", domain);
+            return Ok(());
+        }
+        if !bpm.is_finite() || bpm < limits.min_bpm || bpm > limits.max_bpm {
+            warn!(
+                "Rejecting tempo {} BPM outside {}-{} BPM",
+                bpm, limits.min_bpm, limits.max_bpm
+            );
+            return Ok(());
+        }
+
+        // Update the domain's BPM, limiting how fast it can change
+        let (bpm, taps) = self
+            .tempos
+            .lock()
+            .await
+            .set(domain, bpm, limits.max_change_per_sec);
+        info!("Tempo '{}' updated via {}: {:.1} BPM", domain, source, bpm);
+        #[cfg(any(feature = "http", feature = "osc-input"))]
+        {
+            let window = stats_window(&*self.map_config.read().await);
+            self.tempo_stats
+                .lock()
+                .await
+                .record_tempo(domain, bpm, window);
+        }
+
+        // Update tempo on the domain's devices that support it
+        self.update_device_tempos(domain, bpm, taps).await?;
+        self.update_tempo_displays(domain, bpm, source).await
+    }
+
+    /// Send the tempo to the domain's tempo displays whose text changed
+    async fn update_tempo_displays(&self, domain: &str, bpm: f64, source: &str) -> Result<()> {
+        let displays = self.map_config.read().await.tempo_displays.clone();
+        for display in displays.iter().filter(|d| d.tempo_domain == domain) {
+            let text = display.text(bpm, source);
+            let destination = Destination::Osc {
+                destination_name: display.destination_name.clone(),
+            };
+            for address in &display.addresses {
+                let key = format!("{}{}", display.destination_name, address);
+                if self
+                    .tempo_display_texts
+                    .lock()
+                    .await
+                    .insert(key, text.clone())
+                    == Some(text.clone())
+                {
+                    continue;
+                }
+                let command = Command::Osc {
+                    address: address.clone(),
+                    args: vec![OscArg::String {
+                        value: text.clone(),
+                    }],
+                };
+                self.execute_commands(&[command], &destination, None)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Lock or unlock tempo and scene changes from non-operator sources
+    #[cfg(any(feature = "http", feature = "osc-input"))]
+    pub async fn set_show_lock(&self, code: &str, locked: bool) -> Result<()> {
+        {
+            let map_config = self.map_config.read().await;
+            let Some(ref show_lock) = map_config.show_lock else {
+                anyhow::bail!("No show lock configured");
+            };
+            if show_lock.code != code {
+                anyhow::bail!("Wrong show lock code");
+            }
+        }
+
+        self.show_locked.store(locked, Ordering::Relaxed);
+        info!("Show {}", if locked { "locked" } else { "unlocked" });
+        Ok(())
+    }
+
+    /// Whether the show lock is engaged
+    pub fn is_show_locked(&self) -> bool {
+        self.show_locked.load(Ordering::Relaxed)
+    }
+
+    /// Whether tempo and scene changes from a source are currently refused
+    pub async fn is_locked_out(&self, source: &str) -> bool {
+        if !self.is_show_locked() {
+            return false;
+        }
+        self.map_config
+            .read()
+            .await
+            .show_lock
+            .as_ref()
+            .is_some_and(|show_lock| !show_lock.operator_sources.iter().any(|s| s == source))
+    }
+
+    /// Start or stop the MIDI clock outputs
+    #[cfg(feature = "osc-input")]
+    pub fn set_clock_running(&self, running: bool) {
+        if self.clock_running.send_replace(running) != running {
+            info!("MIDI clock {}", if running { "started" } else { "stopped" });
+        }
+    }
+
+    /// Receiver that changes when the MIDI clock outputs start or stop
+    pub fn watch_clock(&self) -> tokio::sync::watch::Receiver<bool> {
+        self.clock_running.subscribe()
+    }
+
+    /// Send a MIDI clock or transport message straight to a session or virtual port
+    pub async fn send_clock(&self, session_name: &str, message: MidiMessage) -> Result<()> {
+        match self.session_manager {
+            Some(ref session_manager) => {
+                session_manager
+                    .send_midi_to_session(session_name, message)
+                    .await
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Name of the active bank, if banks are configured
+    pub async fn active_bank_name(&self) -> Option<String> {
+        let map_config = self.map_config.read().await;
+        let banks = map_config.banks.as_ref()?;
+        let active = self.bank_state.read().await.active();
+        banks.names.get(active).cloned()
+    }
+
+    /// Select a bank by name or index
+    #[cfg(feature = "osc-input")]
+    pub async fn select_bank(&self, bank: &str) -> Result<()> {
+        {
+            let map_config = self.map_config.read().await;
+            let Some(ref banks) = map_config.banks else {
+                anyhow::bail!("No banks configured");
+            };
+            let index = banks
+                .names
+                .iter()
+                .position(|name| name == bank)
+                .or_else(|| bank.parse().ok())
+                .ok_or_else(|| anyhow::anyhow!("Bank '{}' not found", bank))?;
+
+            if !self.bank_state.write().await.select(banks, index) {
+                anyhow::bail!("Bank index {} out of range", index);
+            }
+        }
+
+        self.bank_selected().await
+    }
+
+    /// Move to the next (or previous) bank
+    #[cfg(any(feature = "osc-input", feature = "tui"))]
+    pub async fn step_bank(&self, forward: bool) -> Result<()> {
+        {
+            let map_config = self.map_config.read().await;
+            let Some(ref banks) = map_config.banks else {
+                return Ok(());
+            };
+            let mut bank_state = self.bank_state.write().await;
+            if forward {
+                bank_state.next(banks);
+            } else {
+                bank_state.previous(banks);
+            }
+        }
+
+        self.bank_selected().await
+    }
+
+    /// Log the newly selected bank, apply its song metadata and send its feedback
+    async fn bank_selected(&self) -> Result<()> {
+        let Some(name) = self.active_bank_name().await else {
+            return Ok(());
+        };
+        info!("Selected bank '{}'", name);
+
+        let (feedback, song) = {
+            let map_config = self.map_config.read().await;
+            let banks = map_config.banks.as_ref();
+            (
+                banks.and_then(|banks| banks.feedback.get(&name).cloned()),
+                banks
+                    .and_then(|banks| banks.metadata.get(&name).cloned())
+                    .unwrap_or_default(),
+            )
+        };
+
+        let bpm = song.bpm;
+        *self.song.write().await = song;
+        if let Some(bpm) = bpm {
+            self.set_tempo(MAIN_TEMPO_DOMAIN, bpm, &format!("bank '{name}'"))
+                .await?;
+        }
+
+        if let Some(feedback) = feedback {
+            self.execute_commands(
+                &feedback.commands,
+                &feedback.destination,
+                feedback.send_channel,
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Handle a Program Change that selects a bank, returning true if it was consumed
+    async fn select_bank_from_program_change(&self, midi_channel: u8, program: u8) -> Result<bool> {
+        {
+            let map_config = self.map_config.read().await;
+            let Some(ref banks) = map_config.banks else {
+                return Ok(false);
+            };
+
+            let selects_bank = banks.selectors.iter().any(|selector| {
+                matches!(selector, BankSelector::ProgramChange { channel } if *channel == midi_channel)
+            });
+            if !selects_bank {
+                return Ok(false);
+            }
+            if !self
+                .bank_state
+                .write()
+                .await
+                .select(banks, program as usize)
+            {
+                warn!("Bank {} not configured", program);
+                return Ok(true);
+            }
+        }
+
+        self.bank_selected().await?;
+        Ok(true)
+    }
+
+    /// Handle a note or Control Change that selects a bank, returning true if it was consumed
+    async fn select_bank_from_trigger(&self, event: &TriggerEvent, locked: bool) -> Result<bool> {
+        let selected = {
+            let map_config = self.map_config.read().await;
+            let Some(ref banks) = map_config.banks else {
+                return Ok(false);
+            };
+            let mut bank_state = self.bank_state.write().await;
+
+            let mut selected = None;
+            for selector in &banks.selectors {
+                selected = match (selector, event) {
+                    (
+                        BankSelector::ControlChange {
+                            channel,
+                            controller,
+                        },
+                        TriggerEvent::ControlChange {
+                            channel: event_channel,
+                            controller: event_controller,
+                            value,
+                            ..
+                        },
+                    ) if channel == event_channel && controller == event_controller => {
+                        if locked {
+                            Some(false)
+                        } else if bank_state.select(banks, *value as usize) {
+                            Some(true)
+                        } else {
+                            warn!("Bank {} not configured", value);
+                            Some(false)
+                        }
+                    }
+                    (BankSelector::Next { trigger }, _) if trigger.is_activated_by(event) => {
+                        if !locked {
+                            bank_state.next(banks);
+                        }
+                        Some(!locked)
+                    }
+                    (BankSelector::Previous { trigger }, _) if trigger.is_activated_by(event) => {
+                        if !locked {
+                            bank_state.previous(banks);
+                        }
+                        Some(!locked)
+                    }
+                    _ => None,
+                };
+                if selected.is_some() {
+                    break;
+                }
+            }
+            selected
+        };
+
+        if locked && selected.is_some() {
+            info!("Ignoring bank change while the show is locked");
+        }
+        match selected {
+            Some(true) => {
+                self.bank_selected().await?;
+                Ok(true)
+            }
+            Some(false) => Ok(true),
+            None => Ok(false),
+        }
+    }
+
+    /// Time elapsed since the most recent input
+    pub async fn idle_duration(&self) -> Duration {
+        self.last_input.read().await.elapsed()
+    }
+
+    /// Get the configured inactivity rules
+    pub async fn inactivity_rules(&self) -> Vec<InactivityRule> {
+        self.map_config.read().await.inactivity_rules.clone()
+    }
+
+    /// Execute a list of commands to the specified destination
+    pub async fn execute_commands(
+        &self,
+        commands: &[Command],
+        destination: &Destination,
+        channel: Option<u8>,
+    ) -> Result<()> {
+        // Each member of a group gets the whole batch of commands it can send in turn
+        if let Destination::Group { group_name } = destination {
+            for member in self.group_members(group_name).await {
+                let commands: Vec<_> = commands
+                    .iter()
+                    .filter(|command| member.carries(command))
+                    .cloned()
+                    .collect();
+                Box::pin(self.execute_commands(&commands, &member, channel)).await?;
+            }
+            return Ok(());
+        }
+
+        // Hold the destination for the whole batch so commands arrive in config order
+        let _order = self.lock_destination(destination).await;
+        for command in commands {
+            self.execute_command(command, destination, channel).await?;
+        }
+        Ok(())
+    }
+
+    /// Wait for exclusive use of a destination, resolving session aliases
+    async fn lock_destination(
+        &self,
+        destination: &Destination,
+    ) -> tokio::sync::OwnedMutexGuard<()> {
+        let key = match destination {
+            Destination::RtpMidi { session_name } => match self.session_manager {
+                Some(ref session_manager) => {
+                    format!("rtp_midi:{}", session_manager.resolve(session_name).await)
+                }
+                None => format!("rtp_midi:{session_name}"),
+            },
+            Destination::Osc { destination_name } => format!("osc:{destination_name}"),
+            Destination::Group { group_name } => format!("group:{group_name}"),
+        };
+        self.destination_locks.lock(&key).await
+    }
+
+    /// Members of a destination group; nested groups are skipped
+    async fn group_members(&self, group_name: &str) -> Vec<Destination> {
+        let map_config = self.map_config.read().await;
+        let Some(members) = map_config.destination_groups.get(group_name) else {
+            warn!(
+                "Destination group '{}' not found in configuration",
+                group_name
+            );
+            return Vec::new();
+        };
+        members
+            .iter()
+            .filter(|member| !matches!(member, Destination::Group { .. }))
+            .cloned()
+            .collect()
+    }
+
+    /// Report a mapping activation to event monitors, and to the audit log if enabled for the mapping
+    async fn audit(
+        &self,
+        enabled: bool,
+        mapping: &str,
+        trigger: &str,
+        commands: &[Command],
+        destination: &Destination,
+    ) {
+        if let Some(ref events) = self.events {
+            events.mapping(mapping, trigger, commands, destination);
+        }
+        if !enabled {
+            return;
+        }
+
+        if let Some(ref audit_log) = self.audit_log {
+            if let Err(e) = audit_log.record(mapping, trigger, commands, destination) {
+                error!("Failed to write audit log entry for '{}': {}", mapping, e);
+            }
+        } else {
+            warn!(
+                "Mapping '{}' is audited but no audit log is configured",
+                mapping
+            );
+        }
+    }
+
+    /// Record that input was received
+    async fn record_input(&self) {
+        *self.last_input.write().await = Instant::now();
+    }
+
+    /// Remember an input for status displays, keeping the most recent ones
+    async fn record_recent(&self, source: &str, message: String) {
+        if let Some(ref events) = self.events {
+            events.input(source, &message);
+        }
+        let mut recent = self.recent_inputs.lock().await;
+        if recent.len() == RECENT_INPUTS {
+            recent.pop_front();
+        }
+        recent.push_back(format!("{source}: {message}"));
+    }
+
+    /// Most recent inputs, oldest first
+    #[cfg(any(feature = "http", feature = "tui"))]
+    pub async fn recent_inputs(&self) -> Vec<String> {
+        self.recent_inputs.lock().await.iter().cloned().collect()
+    }
+
+    /// Last program executed on each device
+    #[cfg(any(feature = "http", feature = "tui"))]
+    pub async fn active_programs(&self) -> BTreeMap<String, String> {
+        self.active_programs.read().await.clone()
+    }
+
+    /// Current main tempo, if one has been received
+    pub async fn current_bpm(&self) -> Option<f64> {
+        self.tempo(MAIN_TEMPO_DOMAIN).await
+    }
+
+    /// Current tempo of a tempo domain, if one has been received
+    pub async fn tempo(&self, domain: &str) -> Option<f64> {
+        self.tempos.lock().await.bpm(domain)
+    }
+
+    /// Current tempo of every tempo domain that has received one
+    #[cfg(any(feature = "http", feature = "osc-input"))]
+    pub async fn tempos(&self) -> BTreeMap<String, f64> {
+        self.tempos.lock().await.all()
+    }
+
+    /// Tempo statistics over the configured window
+    #[cfg(any(feature = "http", feature = "osc-input"))]
+    pub async fn tempo_stats(&self) -> TempoReport {
+        let window = stats_window(&*self.map_config.read().await);
+        let current = self.tempos().await;
+        self.tempo_stats.lock().await.report(window, &current)
+    }
+
+    /// Configured devices, ordered by ID
+    #[cfg(feature = "http")]
+    pub async fn devices(&self) -> Vec<Device> {
+        let mut devices: Vec<Device> = self
+            .device_config
+            .read()
+            .await
+            .devices
+            .values()
+            .cloned()
+            .collect();
+        devices.sort_by(|a, b| a.id.cmp(&b.id));
+        devices
+    }
+
+    /// Stop paused programs and send All Notes Off on every channel of every RTP MIDI
+    /// session
+    #[cfg(feature = "tui")]
+    pub async fn panic(&self) -> Result<()> {
+        let stopped = self.timed_programs.lock().await.cancel_all();
+        if stopped > 0 {
+            warn!("Panic: stopping {} paused programs", stopped);
+        }
+        let Some(ref session_manager) = self.session_manager else {
+            return Ok(());
+        };
+        warn!("Panic: sending All Notes Off to every session");
+        for session_name in session_manager.get_session_names().await {
+            let destination = Destination::RtpMidi { session_name };
+            let _order = self.lock_destination(&destination).await;
+            for channel in 1..=16 {
+                self.send_midi_control_change(&destination, channel, ALL_NOTES_OFF, 0)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle MIDI Program Change messages
+    async fn handle_program_change(&self, midi_channel: u8, program: u8) -> Result<()> {
+        info!(
+            "Program change received: channel {}, program {}",
+            midi_channel, program
+        );
+
+        if self
+            .select_bank_from_program_change(midi_channel, program)
+            .await?
+        {
+            return Ok(());
+        }
+
+        self.evaluate_banked_programs(midi_channel, None, program)
+            .await?;
+
+        let (bank_msb, bank_lsb) = self
+            .bank_selects
+            .lock()
+            .await
+            .get(&midi_channel)
+            .copied()
+            .unwrap_or_default();
+
+        // Find device mappings that match the input channel while holding locks briefly
+        let active_bank = self.active_bank_name().await;
+        let matched = {
+            let map_config = self.map_config.read().await;
+            let device_config = self.device_config.read().await;
+            let mut cooldowns = self.cooldowns.lock().await;
+
+            let candidates = map_config.match_policies.program_change.select(
+                map_config
+                    .device_mappings
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, mapping)| {
+                        mapping.listen_channel == midi_channel
+                            && bank::in_bank(&mapping.bank, active_bank.as_deref())
+                    }),
+                |(_, mapping)| mapping.priority,
+            );
+
+            let mut matched = Vec::new();
+            for (index, mapping) in candidates {
+                if !cooldowns.try_fire(&format!("mapping:{index}"), mapping.cooldown_ms) {
+                    debug!(
+                        "Ignoring program change for device '{}' during cooldown",
+                        mapping.device_id
+                    );
+                    continue;
+                }
+
+                let Some(device) = device_config.get_device(&mapping.device_id) else {
+                    warn!("Device '{}' not found in configuration", mapping.device_id);
+                    continue;
+                };
+                // Find the program in the device, preferring one tied to the selected bank
+                let selected = |p: &&Program| p.is_selected_by(program, bank_msb, bank_lsb);
+                let Some(device_program) = device
+                    .programs
+                    .iter()
+                    .filter(selected)
+                    .find(|p| p.has_bank())
+                    .or_else(|| device.programs.iter().find(selected))
+                else {
+                    warn!(
+                        "Program {} (bank {}/{}) not found on device '{}'",
+                        program, bank_msb, bank_lsb, device.name
+                    );
+                    continue;
+                };
+                matched.push((
+                    index,
+                    mapping.clone(),
+                    device.name.clone(),
+                    device_program.clone(),
+                ));
+            }
+            matched
+        }; // Locks are released here, so a config reload can't stall behind command execution
+
+        for (index, mapping, device_name, device_program) in matched {
+            let trigger = format!("program change {program} on channel {midi_channel}");
+            if mapping.settle_ms.is_some() {
+                // Hold the program so a later one in the same burst can replace it
+                let pending = PendingProgram::new(mapping, device_name, device_program, trigger);
+                if let Some(superseded) = self
+                    .pending_programs
+                    .lock()
+                    .await
+                    .hold(format!("mapping:{index}"), pending)
+                {
+                    debug!(
+                        "Skipping program '{}' on device '{}', superseded by a newer program change",
+                        superseded.program.name, superseded.device_name
+                    );
+                }
+                continue;
+            }
+            self.run_program(&mapping, &device_name, &device_program, &trigger)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Run the programs whose burst of program changes has settled
+    pub async fn run_settled_programs(&self) {
+        let settled = self.pending_programs.lock().await.take_due(Instant::now());
+        for pending in settled {
+            if let Err(e) = self
+                .run_program(
+                    &pending.mapping,
+                    &pending.device_name,
+                    &pending.program,
+                    &pending.trigger,
+                )
+                .await
+            {
+                error!("Error running settled program: {}", e);
+            }
+        }
+    }
+
+    /// Run a program requested over the control API or OSC (`source`) on every active
+    /// mapping of a device, returning whether the device and program were found
+    #[cfg(any(feature = "http", feature = "osc-input"))]
+    pub async fn run_device_program(
+        &self,
+        source: &str,
+        device_id: &str,
+        program: u8,
+        bank_msb: Option<u8>,
+        bank_lsb: Option<u8>,
+    ) -> Result<bool> {
+        self.record_input().await;
+        self.record_recent(source, format!("program {program} on {device_id}"))
+            .await;
+        self.run_program_on_device(
+            device_id,
+            program,
+            bank_msb,
+            bank_lsb,
+            &format!("{source} program {program}"),
+        )
+        .await
+    }
+
+    /// Run a program on every active mapping of a device, returning whether the device
+    /// and program were found
+    async fn run_program_on_device(
+        &self,
+        device_id: &str,
+        program: u8,
+        bank_msb: Option<u8>,
+        bank_lsb: Option<u8>,
+        trigger: &str,
+    ) -> Result<bool> {
+        // Collect the device's active mappings while holding locks briefly
+        let active_bank = self.active_bank_name().await;
+        let matched = {
+            let map_config = self.map_config.read().await;
+            let device_config = self.device_config.read().await;
+
+            let Some(device) = device_config.get_device(device_id) else {
+                return Ok(false);
+            };
+            let Some(device_program) = device.programs.iter().find(|p| {
+                p.number == program
+                    && bank_msb.is_none_or(|msb| p.bank_msb == Some(msb))
+                    && bank_lsb.is_none_or(|lsb| p.bank_lsb == Some(lsb))
+            }) else {
+                return Ok(false);
+            };
+
+            map_config
+                .device_mappings
+                .iter()
+                .filter(|mapping| {
+                    mapping.device_id == device_id
+                        && bank::in_bank(&mapping.bank, active_bank.as_deref())
+                })
+                .map(|mapping| (mapping.clone(), device.name.clone(), device_program.clone()))
+                .collect::<Vec<_>>()
+        };
+
+        if matched.is_empty() {
+            warn!("Device '{}' has no active mapping", device_id);
+        }
+        for (mapping, device_name, device_program) in matched {
+            self.run_program(&mapping, &device_name, &device_program, trigger)
+                .await?;
+        }
+
+        Ok(true)
+    }
+
+    /// Execute a device program through a mapping, recording it as the device's active program
+    async fn run_program(
+        &self,
+        mapping: &DeviceMapping,
+        device_name: &str,
+        device_program: &Program,
+        trigger: &str,
+    ) -> Result<()> {
+        info!(
+            "Executing program '{}' on device '{}'",
+            device_program.name, device_name
+        );
+
+        // Commands with their own destination or a pause split the program into batches
+        let batches = device_program.batches(&mapping.destination);
+
+        // A transactional program that won't be sent is neither audited nor active
+        if device_program.transactional
+            && let Err(reason) = self.validate_batches(&batches, mapping.send_channel).await
+        {
+            error!(
+                "Aborting transactional program '{}' on device '{}': {}",
+                device_program.name, device_name, reason
+            );
+            return Ok(());
+        }
+
+        self.active_programs.write().await.insert(
+            device_name.to_string(),
+            format!("{} {}", device_program.number, device_program.name),
+        );
+        for batch in &batches {
+            self.audit(
+                mapping.audit,
+                &mapping.device_id,
+                trigger,
+                &batch.commands,
+                &batch.destination,
+            )
+            .await;
+        }
+
+        if let Some(ref display) = mapping.display {
+            self.send_display_text(
+                &display.destination,
+                &display.protocol,
+                &device_program.name,
+            )
+            .await?;
+        }
+
+        // Execute all commands for this program, then start its sequence
+        let program = format!("'{}' on device '{}'", device_program.name, device_name);
+        let key = format!(
+            "device:{}:{}:{:?}",
+            mapping.device_id, mapping.listen_channel, mapping.destination
+        );
+        self.execute_batches(
+            &key,
+            &program,
+            batches,
+            mapping.send_channel,
+            Some((&mapping.device_id, &device_program.sequence)),
+        )
+        .await
+    }
+
+    /// Send a program's batches up to its first pause and leave the rest to follow in
+    /// the background, stopping the unfinished commands of the program previously run
+    /// under the same key. A sequence given starts once the last batch has been sent.
+    async fn execute_batches(
+        &self,
+        key: &str,
+        program: &str,
+        mut batches: Vec<CommandBatch>,
+        channel: Option<u8>,
+        sequence: Option<(&str, &[SequenceStep])>,
+    ) -> Result<()> {
+        if let Some(cancelled) = self.timed_programs.lock().await.cancel(key) {
+            info!(
+                "Running {} stops the unfinished commands of {}",
+                program, cancelled
+            );
+        }
+
+        let paused = batches
+            .iter()
+            .position(|batch| batch.delay_ms > 0)
+            .unwrap_or(batches.len());
+        let rest = batches.split_off(paused);
+        for batch in &batches {
+            self.execute_commands(&batch.commands, &batch.destination, channel)
+                .await?;
+        }
+
+        if !rest.is_empty() {
+            let mut run = TimedRun::new(program.to_string(), rest, channel);
+            if let Some((device_id, steps)) = sequence {
+                run = run.then_sequence(device_id, steps);
+            }
+            self.timed_programs
+                .lock()
+                .await
+                .start(key, run, Instant::now());
+        } else if let Some((device_id, steps)) = sequence {
+            self.start_sequence(device_id, program, steps).await;
+        }
+        Ok(())
+    }
+
+    /// Send the batches of paused programs whose pause has passed
+    pub async fn run_due_timed_batches(&self) {
+        let due = self.timed_programs.lock().await.take_due(Instant::now());
+        for due in due {
+            debug!(
+                "Sending {} delayed commands of program {}",
+                due.batch.commands.len(),
+                due.program
+            );
+            if let Err(e) = self
+                .execute_commands(&due.batch.commands, &due.batch.destination, due.channel)
+                .await
+            {
+                error!(
+                    "Error sending delayed commands of program {}: {}",
+                    due.program, e
+                );
+            }
+            let finished =
+                self.timed_programs
+                    .lock()
+                    .await
+                    .sent(&due.key, &due.program, Instant::now());
+            if let Some(finished) = finished {
+                self.start_sequence(&finished.device_id, &finished.program, &finished.steps)
+                    .await;
+            }
+        }
+    }
+
+    /// Start the sequence of a program that has been sent
+    async fn start_sequence(&self, device_id: &str, program: &str, steps: &[SequenceStep]) {
+        if steps.is_empty() {
+            return;
+        }
+        let replaced = self
+            .sequences
+            .lock()
+            .await
+            .start(device_id, program, steps, Instant::now());
+        if let Some(replaced) = replaced {
+            info!(
+                "Sequence of {} replaces the unfinished sequence of {}",
+                program, replaced
+            );
+        }
+    }
+
+    /// Run the sequence steps whose barriers have passed
+    pub async fn run_due_sequence_steps(&self) {
+        let due = self.sequences.lock().await.take_due(Instant::now());
+        for due in due {
+            info!(
+                "Sequence of {} at step {}: program {} on device '{}'",
+                due.program,
+                due.index + 1,
+                due.step.program,
+                due.step.device_id
+            );
+            let trigger = format!("step {} of the sequence of {}", due.index + 1, due.program);
+            match self
+                .run_program_on_device(&due.step.device_id, due.step.program, None, None, &trigger)
+                .await
+            {
+                Ok(true) => {}
+                Ok(false) => warn!(
+                    "Program {} not found on '{}'",
+                    due.step.program, due.step.device_id
+                ),
+                Err(e) => error!("Error running sequence step: {}", e),
+            }
+            self.sequences.lock().await.sent(&due.key, Instant::now());
+        }
+    }
+
+    /// Handle MIDI Note On/Off messages; Note Off has velocity 0
+    async fn handle_note(
+        &self,
+        midi_channel: u8,
+        note: u8,
+        velocity: u8,
+        locked: bool,
+    ) -> Result<()> {
+        let pressed = velocity > 0;
+        debug!(
+            "Note {} received: channel {}, note {}",
+            if pressed { "on" } else { "off" },
+            midi_channel,
+            note
+        );
+
+        self.trigger_tracker
+            .lock()
+            .await
+            .note(midi_channel, note, pressed);
+        self.evaluate_combos().await?;
+
+        if pressed {
+            let event = TriggerEvent::NotePressed {
+                channel: midi_channel,
+                note,
+            };
+            if self.select_bank_from_trigger(&event, locked).await? {
+                return Ok(());
+            }
+            self.evaluate_steps(&event).await?;
+            self.evaluate_automations(&event).await;
+            self.evaluate_cue_triggers(&event, locked).await;
+            self.evaluate_tap_inputs(&event, locked).await?;
+            self.evaluate_note_programs(midi_channel, note).await?;
+        }
+
+        self.evaluate_note_forwards(midi_channel, note, velocity)
+            .await?;
+        self.evaluate_note_mappings(midi_channel, note, pressed)
+            .await
+    }
+
+    /// Send the Program Changes of note program rules covering a pressed note
+    async fn evaluate_note_programs(&self, midi_channel: u8, note: u8) -> Result<()> {
+        let active_bank = self.active_bank_name().await;
+        let conversions = {
+            let map_config = self.map_config.read().await;
+            map_config
+                .note_program_rules
+                .iter()
+                .filter(|rule| {
+                    rule.listen_channel == midi_channel
+                        && bank::in_bank(&rule.bank, active_bank.as_deref())
+                })
+                .filter_map(|rule| Some((rule.clone(), rule.program_for(note)?)))
+                .collect::<Vec<_>>()
+        };
+
+        for (rule, program) in conversions {
+            info!(
+                "Note {} converted to program {} by rule '{}'",
+                note, program, rule.name
+            );
+            let commands = [Command::ProgramChange {
+                program: program.into(),
+                bank_msb: None,
+                bank_lsb: None,
+            }];
+            self.audit(
+                false,
+                &rule.name,
+                &format!("note {note} on channel {midi_channel}"),
+                &commands,
+                &rule.destination,
+            )
+            .await;
+            self.execute_commands(&commands, &rule.destination, rule.send_channel)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Pass a note through the note forwarding rules covering it, adjusting Note On velocity
+    async fn evaluate_note_forwards(&self, midi_channel: u8, note: u8, velocity: u8) -> Result<()> {
+        let active_bank = self.active_bank_name().await;
+        let rules = {
+            let map_config = self.map_config.read().await;
+            map_config
+                .note_forward_rules
+                .iter()
+                .filter(|rule| {
+                    rule.listen_channel == midi_channel
+                        && rule.covers(note)
+                        && bank::in_bank(&rule.bank, active_bank.as_deref())
+                })
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+
+        for rule in rules {
+            let command = match velocity {
+                0 => Command::NoteOff { note },
+                velocity => Command::NoteOn {
+                    note,
+                    velocity: rule.velocity(velocity).into(),
+                },
+            };
+            debug!("Note {} forwarded by rule '{}'", note, rule.name);
+            self.execute_commands(&[command], &rule.destination, rule.send_channel)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Run the note mappings for a note press or release
+    async fn evaluate_note_mappings(
+        &self,
+        midi_channel: u8,
+        note: u8,
+        pressed: bool,
+    ) -> Result<()> {
+        // Resolve the commands to run while holding locks briefly
+        let active_bank = self.active_bank_name().await;
+        let matched = {
+            let map_config = self.map_config.read().await;
+            let device_config = self.device_config.read().await;
+            let mut cooldowns = self.cooldowns.lock().await;
+
+            let candidates = map_config.match_policies.note.select(
+                map_config.note_mappings.iter().filter(|mapping| {
+                    mapping.listen_channel == midi_channel
+                        && mapping.note == note
+                        && mapping.on_release != pressed
+                        && bank::in_bank(&mapping.bank, active_bank.as_deref())
+                }),
+                |mapping| mapping.priority,
+            );
+
+            let mut matched = Vec::new();
+            for mapping in candidates {
+                if !cooldowns.try_fire(&format!("note:{}", mapping.name), mapping.cooldown_ms) {
+                    debug!("Ignoring note mapping '{}' during cooldown", mapping.name);
+                    continue;
+                }
+
+                let (batches, program) = match mapping.action {
+                    NoteAction::Commands { ref commands } => (
+                        vec![CommandBatch {
+                            delay_ms: 0,
+                            destination: mapping.destination.clone(),
+                            commands: commands.clone(),
+                        }],
+                        None,
+                    ),
+                    NoteAction::Program {
+                        ref device_id,
+                        program,
+                    } => {
+                        let Some(device) = device_config.get_device(device_id) else {
+                            warn!("Device '{}' not found in configuration", device_id);
+                            continue;
+                        };
+                        let Some(device_program) =
+                            device.programs.iter().find(|p| p.number == program)
+                        else {
+                            warn!("Program {} not found on device '{}'", program, device.name);
+                            continue;
+                        };
+                        (
+                            device_program.batches(&mapping.destination),
+                            Some((
+                                device.name.clone(),
+                                format!("{} {}", device_program.number, device_program.name),
+                                device_program.transactional,
+                            )),
+                        )
+                    }
+                };
+                matched.push((mapping.clone(), batches, program));
+            }
+            matched
+        };
+
+        for (mapping, batches, program) in matched {
+            info!("Note mapping '{}' triggered", mapping.name);
+            let label = match program {
+                Some((device_name, program, transactional)) => {
+                    let label = format!("'{program}' on device '{device_name}'");
+                    if transactional
+                        && let Err(reason) =
+                            self.validate_batches(&batches, mapping.send_channel).await
+                    {
+                        error!("Aborting transactional program {}: {}", label, reason);
+                        continue;
+                    }
+                    self.active_programs
+                        .write()
+                        .await
+                        .insert(device_name, program);
+                    label
+                }
+                None => format!("note mapping '{}'", mapping.name),
+            };
+            let trigger = format!(
+                "note {} {note} on channel {midi_channel}",
+                if pressed { "on" } else { "off" }
+            );
+            for batch in &batches {
+                self.audit(
+                    mapping.audit,
+                    &mapping.name,
+                    &trigger,
+                    &batch.commands,
+                    &batch.destination,
+                )
+                .await;
+            }
+            self.execute_batches(
+                &format!("note:{}", mapping.name),
+                &label,
+                batches,
+                mapping.send_channel,
+                None,
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Handle MIDI Control Change messages
+    async fn handle_control_change(
+        &self,
+        midi_channel: u8,
+        controller: u8,
+        value: u8,
+        locked: bool,
+    ) -> Result<()> {
+        debug!(
+            "Control change received: channel {}, controller {}, value {}",
+            midi_channel, controller, value
+        );
+
+        let previous =
+            self.trigger_tracker
+                .lock()
+                .await
+                .control_change(midi_channel, controller, value);
+        {
+            let map_config = self.map_config.read().await;
+            self.variables.lock().await.capture_control_change(
+                &map_config.captures,
+                midi_channel,
+                controller,
+                value,
+            );
+        }
+
+        self.evaluate_control_forwards(midi_channel, controller, value)
+            .await?;
+
+        if controller == BANK_SELECT_MSB || controller == BANK_SELECT_LSB {
+            let mut bank_selects = self.bank_selects.lock().await;
+            let bank = bank_selects.entry(midi_channel).or_default();
+            if controller == BANK_SELECT_MSB {
+                bank.0 = value;
+            } else {
+                bank.1 = value;
+            }
+        }
+
+        let event = TriggerEvent::ControlChange {
+            channel: midi_channel,
+            controller,
+            previous,
+            value,
+        };
+        if self.select_bank_from_trigger(&event, locked).await? {
+            return Ok(());
+        }
+
+        self.evaluate_combos().await?;
+        self.evaluate_steps(&event).await?;
+        self.evaluate_automations(&event).await;
+        self.evaluate_cue_triggers(&event, locked).await;
+        self.evaluate_tap_inputs(&event, locked).await?;
+
+        // Banked selections are program changes, which the show lock holds back
+        if locked {
+            return Ok(());
+        }
+        self.evaluate_banked_programs(midi_channel, Some(controller), value)
+            .await
+    }
+
+    /// Pass a Control Change through the control forwarding rules for it, converting
+    /// between absolute and relative values
+    async fn evaluate_control_forwards(
+        &self,
+        midi_channel: u8,
+        controller: u8,
+        value: u8,
+    ) -> Result<()> {
+        let active_bank = self.active_bank_name().await;
+        let sends = {
+            let map_config = self.map_config.read().await;
+            let mut positions = self.control_positions.lock().await;
+            map_config
+                .control_forward_rules
+                .iter()
+                .filter(|rule| {
+                    rule.listen_channel == midi_channel
+                        && rule.controller == controller
+                        && bank::in_bank(&rule.bank, active_bank.as_deref())
+                })
+                .filter_map(|rule| {
+                    let position = positions.get(&rule.name).copied();
+                    let (output, position) = rule.convert(value, position);
+                    positions.insert(rule.name.clone(), position);
+                    Some((rule.clone(), output?))
+                })
+                .collect::<Vec<_>>()
+        };
 
-        Ok(Self {
-            device_config,
-            map_config,
-            osc_socket,
-            session_manager: None,
-            current_bpm: Arc::new(tokio::sync::RwLock::new(None)),
-            tap_tempo_cancel_tx,
-            tap_tempo_cancel_rx,
-        })
+        for (rule, output) in sends {
+            debug!(
+                "Controller {} value {} forwarded as {} by rule '{}'",
+                controller, value, output, rule.name
+            );
+            let command = Command::ControlChange {
+                controller: rule.send_controller.unwrap_or(controller),
+                value: output.into(),
+            };
+            self.execute_commands(&[command], &rule.destination, rule.send_channel)
+                .await?;
+        }
+        Ok(())
     }
 
-    /// Set the session manager after construction
-    pub fn set_session_manager(&mut self, session_manager: SessionManager) {
-        self.session_manager = Some(session_manager);
+    /// Send the OSC messages of OSC forwarding rules covering an incoming message
+    async fn evaluate_osc_forwards(&self, message: &MidiMessage) -> Result<()> {
+        let Some(fields) = MidiFields::from_message(message) else {
+            return Ok(());
+        };
+        let active_bank = self.active_bank_name().await;
+        let rules = {
+            let map_config = self.map_config.read().await;
+            map_config
+                .osc_forward_rules
+                .iter()
+                .filter(|rule| {
+                    rule.covers(&fields) && bank::in_bank(&rule.bank, active_bank.as_deref())
+                })
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+
+        for rule in rules {
+            let (address, args) = match rule.render(&fields) {
+                Ok(message) => message,
+                Err(e) => {
+                    warn!("OSC forwarding rule '{}' failed: {:#}", rule.name, e);
+                    continue;
+                }
+            };
+            debug!(
+                "{} forwarded as OSC {} by rule '{}'",
+                fields.kind.name(),
+                address,
+                rule.name
+            );
+            let destination = Destination::Osc {
+                destination_name: rule.destination_name.clone(),
+            };
+            self.send_osc_command(&destination, &address, &args).await?;
+        }
+        Ok(())
     }
 
-    /// Process an incoming MIDI message
-    pub async fn process_midi_message(&self, message: MidiMessage) -> Result<()> {
-        match message {
-            MidiMessage::ProgramChange(msg_channel, program) => {
-                self.handle_program_change(msg_channel.into(), program.into())
-                    .await?;
-            }
-            _ => {
-                debug!("Ignoring MIDI message: {:?}", message);
+    /// Track bank selections of banked program rules and send composed programs.
+    ///
+    /// `controller` is the Control Change's controller, or `None` for a Program Change.
+    async fn evaluate_banked_programs(
+        &self,
+        midi_channel: u8,
+        controller: Option<u8>,
+        value: u8,
+    ) -> Result<()> {
+        let sends = {
+            let map_config = self.map_config.read().await;
+            let mut selections = self.banked_selections.lock().await;
+
+            let mut sends = Vec::new();
+            for rule in &map_config.banked_program_rules {
+                if rule.listen_channel != midi_channel {
+                    continue;
+                }
+                if controller == Some(rule.bank_controller) {
+                    debug!("Rule '{}' selected bank {}", rule.name, value);
+                    selections.insert(rule.name.clone(), value);
+                    continue;
+                }
+                let selects_program = match rule.program_source {
+                    ProgramSource::ProgramChange => controller.is_none(),
+                    ProgramSource::ControlChange {
+                        controller: program_controller,
+                    } => controller == Some(program_controller),
+                };
+                if !selects_program {
+                    continue;
+                }
+
+                let bank = selections.get(&rule.name).copied().unwrap_or(0);
+                match rule.compose(bank, value) {
+                    Some((bank_select, program)) => {
+                        sends.push((rule.clone(), bank, bank_select, program))
+                    }
+                    None => warn!(
+                        "Rule '{}' can't send program {} of bank {}",
+                        rule.name, value, bank
+                    ),
+                }
             }
+            sends
+        };
+
+        for (rule, bank, bank_select, program) in sends {
+            info!(
+                "Rule '{}' sending program {} of bank {} as {}program {}",
+                rule.name,
+                value,
+                bank,
+                bank_select
+                    .map(|bank| format!("bank {bank} "))
+                    .unwrap_or_default(),
+                program
+            );
+            let commands = [Command::ProgramChange {
+                program: program.into(),
+                bank_msb: bank_select,
+                bank_lsb: None,
+            }];
+            self.audit(
+                false,
+                &rule.name,
+                &format!("program {value} of bank {bank}"),
+                &commands,
+                &rule.destination,
+            )
+            .await;
+            self.execute_commands(&commands, &rule.destination, rule.send_channel)
+                .await?;
         }
         Ok(())
     }
 
-    /// Handle OSC tempo message
-    pub async fn handle_osc_tempo(&self, bpm: f64) -> Result<()> {
-        info!("Tempo updated via OSC: {:.1} BPM", bpm);
+    /// Execute any combo mappings whose triggers have just been satisfied
+    async fn evaluate_combos(&self) -> Result<()> {
+        // Collect fired combos while holding locks briefly
+        let active_bank = self.active_bank_name().await;
+        let fired = {
+            let map_config = self.map_config.read().await;
+            let mut tracker = self.trigger_tracker.lock().await;
+            tracker
+                .evaluate(&map_config.combo_mappings)
+                .into_iter()
+                .filter_map(|index| map_config.combo_mappings.get(index).cloned())
+                .filter(|combo| bank::in_bank(&combo.bank, active_bank.as_deref()))
+                .collect::<Vec<_>>()
+        };
+
+        let fired = {
+            let mut cooldowns = self.cooldowns.lock().await;
+            fired
+                .into_iter()
+                .filter(|combo| {
+                    let ready =
+                        cooldowns.try_fire(&format!("combo:{}", combo.name), combo.cooldown_ms);
+                    if !ready {
+                        debug!("Ignoring combo '{}' during cooldown", combo.name);
+                    }
+                    ready
+                })
+                .collect::<Vec<_>>()
+        };
 
-        // Update current BPM
-        {
-            let mut current_bpm = self.current_bpm.write().await;
-            *current_bpm = Some(bpm);
+        for combo in fired {
+            info!("Combo '{}' triggered", combo.name);
+            self.audit(
+                combo.audit,
+                &combo.name,
+                "combo",
+                &combo.commands,
+                &combo.destination,
+            )
+            .await;
+            self.execute_commands(&combo.commands, &combo.destination, combo.send_channel)
+                .await?;
         }
 
-        // Update tempo on all devices that support it
-        self.update_device_tempos(bpm).await?;
         Ok(())
     }
 
-    /// Handle MIDI Program Change messages
-    async fn handle_program_change(&self, midi_channel: u8, program: u8) -> Result<()> {
-        info!(
-            "Program change received: channel {}, program {}",
-            midi_channel, program
-        );
+    /// Run the OSC mappings matched by an incoming OSC message
+    #[cfg(feature = "osc-input")]
+    pub async fn handle_osc_message(&self, address: &str, args: &[OscType]) -> Result<()> {
+        self.sequences.lock().await.confirm(address);
+
+        // Collect matched mappings while holding locks briefly
+        let active_bank = self.active_bank_name().await;
+        let matched = {
+            let map_config = self.map_config.read().await;
+            self.variables
+                .lock()
+                .await
+                .capture_osc(&map_config.captures, address, args);
+            let mut cooldowns = self.cooldowns.lock().await;
+            map_config
+                .match_policies
+                .osc
+                .select(
+                    map_config.osc_mappings.iter().filter(|mapping| {
+                        mapping.matches(address, args)
+                            && bank::in_bank(&mapping.bank, active_bank.as_deref())
+                    }),
+                    |mapping| mapping.priority,
+                )
+                .into_iter()
+                .filter(|mapping| {
+                    let ready =
+                        cooldowns.try_fire(&format!("osc:{}", mapping.name), mapping.cooldown_ms);
+                    if !ready {
+                        debug!("Ignoring OSC mapping '{}' during cooldown", mapping.name);
+                    }
+                    ready
+                })
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        let controls = self.evaluate_osc_controls(address, args).await;
+        if matched.is_empty() && controls.is_empty() {
+            return Ok(());
+        }
+
+        self.record_input().await;
+        self.record_recent("OSC", format!("{address} {args:?}"))
+            .await;
+        for (rule, value) in controls {
+            debug!(
+                "OSC {} sent as controller {} value {} by rule '{}'",
+                address, rule.controller, value, rule.name
+            );
+            let command = Command::ControlChange {
+                controller: rule.controller,
+                value: value.into(),
+            };
+            let destination = Destination::RtpMidi {
+                session_name: rule.session_name.clone(),
+            };
+            self.execute_commands(&[command], &destination, Some(rule.send_channel))
+                .await?;
+        }
+        for mapping in matched {
+            info!("OSC mapping '{}' triggered by {}", mapping.name, address);
+            self.audit(
+                mapping.audit,
+                &mapping.name,
+                &format!("OSC {address}"),
+                &mapping.commands,
+                &mapping.destination,
+            )
+            .await;
+            self.execute_commands(
+                &mapping.commands,
+                &mapping.destination,
+                mapping.send_channel,
+            )
+            .await?;
+        }
+        Ok(())
+    }
 
+    /// Controller values of the OSC control rules for a message, skipping rules whose
+    /// value hasn't changed since they last sent
+    #[cfg(feature = "osc-input")]
+    async fn evaluate_osc_controls(
+        &self,
+        address: &str,
+        args: &[OscType],
+    ) -> Vec<(OscControlRule, u8)> {
+        let active_bank = self.active_bank_name().await;
         let map_config = self.map_config.read().await;
-        let device_config = self.device_config.read().await;
-
-        // Find device mappings that match the input channel
-        for mapping in &map_config.device_mappings {
-            if mapping.listen_channel == midi_channel {
-                if let Some(device) = device_config.get_device(&mapping.device_id) {
-                    // Find the program in the device
-                    if let Some(device_program) =
-                        device.programs.iter().find(|p| p.number == program)
-                    {
-                        info!(
-                            "Executing program '{}' on device '{}'",
-                            device_program.name, device.name
-                        );
+        let mut values = self.osc_control_values.lock().await;
+        map_config
+            .osc_control_rules
+            .iter()
+            .filter(|rule| {
+                rule.matches(address, args) && bank::in_bank(&rule.bank, active_bank.as_deref())
+            })
+            .filter_map(|rule| {
+                let value = rule.scale(osc_match::number(&args[rule.arg])?);
+                let previous = values.insert(rule.name.clone(), value);
+                (previous != Some(value)).then(|| (rule.clone(), value))
+            })
+            .collect()
+    }
 
-                        // Execute all commands for this program
-                        for command in &device_program.commands {
-                            self.execute_command(
-                                command,
-                                &mapping.destination,
-                                mapping.send_channel,
-                            )
-                            .await?;
-                        }
-                    } else {
-                        warn!("Program {} not found on device '{}'", program, device.name);
+    /// Run the SysEx mappings matched by an incoming SysEx message
+    pub async fn handle_sysex(&self, source: &str, data: &[u8]) -> Result<()> {
+        // Collect matched mappings while holding locks briefly
+        let active_bank = self.active_bank_name().await;
+        let matched = {
+            let map_config = self.map_config.read().await;
+            let mut cooldowns = self.cooldowns.lock().await;
+            map_config
+                .match_policies
+                .sysex
+                .select(
+                    map_config.sysex_mappings.iter().filter(|mapping| {
+                        mapping
+                            .session
+                            .as_ref()
+                            .is_none_or(|session| session == source)
+                            && mapping.pattern.matches(data)
+                            && bank::in_bank(&mapping.bank, active_bank.as_deref())
+                    }),
+                    |mapping| mapping.priority,
+                )
+                .into_iter()
+                .filter(|mapping| {
+                    let ready =
+                        cooldowns.try_fire(&format!("sysex:{}", mapping.name), mapping.cooldown_ms);
+                    if !ready {
+                        debug!("Ignoring SysEx mapping '{}' during cooldown", mapping.name);
                     }
-                } else {
-                    warn!("Device '{}' not found in configuration", mapping.device_id);
+                    ready
+                })
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+
+        self.record_input().await;
+        self.record_recent(source, format!("SysEx ({} bytes)", data.len()))
+            .await;
+        for mapping in matched {
+            info!(
+                "SysEx mapping '{}' triggered by a message from '{}'",
+                mapping.name, source
+            );
+            self.audit(
+                mapping.audit,
+                &mapping.name,
+                &format!("SysEx from {source}"),
+                &mapping.commands,
+                &mapping.destination,
+            )
+            .await;
+            self.execute_commands(
+                &mapping.commands,
+                &mapping.destination,
+                mapping.send_channel,
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Advance or reset any step mappings activated by the event
+    async fn evaluate_steps(&self, event: &TriggerEvent) -> Result<()> {
+        // Resolve the steps to run while holding locks briefly
+        let active_bank = self.active_bank_name().await;
+        let steps = {
+            let map_config = self.map_config.read().await;
+            let mut sequencer = self.step_sequencer.lock().await;
+            let mut cooldowns = self.cooldowns.lock().await;
+
+            let mut steps = Vec::new();
+            for mapping in &map_config.step_mappings {
+                if !bank::in_bank(&mapping.bank, active_bank.as_deref()) {
+                    continue;
+                }
+
+                if let Some(ref reset_trigger) = mapping.reset_trigger
+                    && reset_trigger.is_activated_by(event)
+                {
+                    info!("Step mapping '{}' reset", mapping.name);
+                    sequencer.reset(&mapping.name);
+                    continue;
                 }
+
+                if mapping.trigger.is_activated_by(event) {
+                    if !cooldowns.try_fire(&format!("step:{}", mapping.name), mapping.cooldown_ms) {
+                        debug!("Ignoring step mapping '{}' during cooldown", mapping.name);
+                        continue;
+                    }
+                    if let Some(index) = sequencer.advance(mapping) {
+                        steps.push((mapping.clone(), index));
+                    }
+                }
+            }
+            steps
+        };
+
+        for (mapping, index) in steps {
+            let step = &mapping.steps[index];
+            info!(
+                "Step mapping '{}' at step {} ('{}')",
+                mapping.name,
+                index + 1,
+                step.name
+            );
+            self.audit(
+                mapping.audit,
+                &mapping.name,
+                &format!("step {}", index + 1),
+                &step.commands,
+                &mapping.destination,
+            )
+            .await;
+            self.execute_commands(&step.commands, &mapping.destination, mapping.send_channel)
+                .await?;
+            if let Some(ref feedback) = step.feedback {
+                self.execute_commands(
+                    &feedback.commands,
+                    &feedback.destination,
+                    feedback.send_channel,
+                )
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Start or stop any automations activated by the event
+    async fn evaluate_automations(&self, event: &TriggerEvent) {
+        let active_bank = self.active_bank_name().await;
+        let map_config = self.map_config.read().await;
+        let mut automations = self.automations.lock().await;
+        let mut cooldowns = self.cooldowns.lock().await;
+
+        for automation in &map_config.automations {
+            if !bank::in_bank(&automation.bank, active_bank.as_deref()) {
+                continue;
+            }
+
+            let running = automations.is_running(&automation.name);
+            let stop_trigger = automation
+                .stop_trigger
+                .as_ref()
+                .unwrap_or(&automation.start_trigger);
+
+            let activated = if running {
+                stop_trigger.is_activated_by(event)
+            } else {
+                automation.start_trigger.is_activated_by(event)
+            };
+            if !activated {
+                continue;
+            }
+            if !cooldowns.try_fire(
+                &format!("automation:{}", automation.name),
+                automation.cooldown_ms,
+            ) {
+                debug!("Ignoring automation '{}' during cooldown", automation.name);
+                continue;
+            }
+
+            if running {
+                info!("Automation '{}' stopped", automation.name);
+                automations.stop(&automation.name);
+            } else {
+                info!("Automation '{}' started", automation.name);
+                automations.start(&automation.name);
+            }
+        }
+    }
+
+    /// Run the steps of any automations that are due
+    pub async fn run_due_automations(&self) {
+        let bpm = self.current_bpm().await.unwrap_or(DEFAULT_BPM);
+        let now = Instant::now();
+
+        // Resolve the due steps while holding locks briefly
+        let due = {
+            let map_config = self.map_config.read().await;
+            let mut automations = self.automations.lock().await;
+            map_config
+                .automations
+                .iter()
+                .filter_map(|automation| {
+                    automations
+                        .take_due(automation, bpm, now)
+                        .map(|index| (automation.clone(), index))
+                })
+                .collect::<Vec<_>>()
+        };
+
+        for (automation, index) in due {
+            debug!("Automation '{}' at step {}", automation.name, index + 1);
+            self.audit(
+                false,
+                &automation.name,
+                &format!("step {}", index + 1),
+                &automation.steps[index].commands,
+                &automation.destination,
+            )
+            .await;
+            if let Err(e) = self
+                .execute_commands(
+                    &automation.steps[index].commands,
+                    &automation.destination,
+                    automation.send_channel,
+                )
+                .await
+            {
+                error!("Error running automation '{}': {}", automation.name, e);
             }
         }
+    }
 
+    /// Measure the tempo of tap inputs the event taps, setting it once there are two taps
+    async fn evaluate_tap_inputs(&self, event: &TriggerEvent, locked: bool) -> Result<()> {
+        let tempos = {
+            let map_config = self.map_config.read().await;
+            let mut tap_times = self.tap_times.lock().await;
+            let now = Instant::now();
+            map_config
+                .tap_inputs
+                .iter()
+                .filter(|input| input.trigger.is_activated_by(event))
+                .filter_map(|input| Some((input.clone(), tap_times.tap(input, now)?)))
+                .collect::<Vec<_>>()
+        };
+
+        for (input, bpm) in tempos {
+            if locked {
+                info!(
+                    "Ignoring tap tempo from '{}' while the show is locked",
+                    input.name
+                );
+                continue;
+            }
+            info!("Tap input '{}' measured {:.1} BPM", input.name, bpm);
+            self.set_tempo(
+                &input.tempo_domain,
+                bpm,
+                &format!("tap input '{}'", input.name),
+            )
+            .await?;
+        }
         Ok(())
     }
 
-    /// Update tempo on all devices that have tempo specifications
-    async fn update_device_tempos(&self, bpm: f64) -> Result<()> {
-        // Cancel any ongoing tap tempo operations first
-        let cancel_signal = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_nanos() as u64;
+    /// Fire the next cue of any cue lists whose GO trigger the event activates
+    async fn evaluate_cue_triggers(&self, event: &TriggerEvent, locked: bool) {
+        let active_bank = self.active_bank_name().await;
+        let fired = {
+            let map_config = self.map_config.read().await;
+            let mut cues = self.cues.lock().await;
+            let mut cooldowns = self.cooldowns.lock().await;
+
+            let mut fired = false;
+            for cue_list in &map_config.cue_lists {
+                let Some(ref go_trigger) = cue_list.go_trigger else {
+                    continue;
+                };
+                if !bank::in_bank(&cue_list.bank, active_bank.as_deref())
+                    || !go_trigger.is_activated_by(event)
+                {
+                    continue;
+                }
+                if locked {
+                    info!(
+                        "Ignoring GO for cue list '{}' while the show is locked",
+                        cue_list.name
+                    );
+                    continue;
+                }
+                if !cooldowns.try_fire(&format!("cue:{}", cue_list.name), cue_list.cooldown_ms) {
+                    debug!(
+                        "Ignoring GO for cue list '{}' during cooldown",
+                        cue_list.name
+                    );
+                    continue;
+                }
+                fired |= Self::go(&mut cues, cue_list);
+            }
+            fired
+        };
+
+        if fired {
+            self.run_due_cues().await;
+        }
+    }
+
+    /// Apply an operator action to a cue list, returning whether the list exists
+    #[cfg(any(feature = "http", feature = "osc-input"))]
+    pub async fn cue(&self, name: &str, action: CueAction) -> bool {
+        self.record_input().await;
+        self.record_recent("Cue", format!("{action:?} '{name}'"))
+            .await;
+
+        {
+            let map_config = self.map_config.read().await;
+            let Some(cue_list) = map_config.cue_lists.iter().find(|c| c.name == name) else {
+                return false;
+            };
+            let mut cues = self.cues.lock().await;
+            match action {
+                CueAction::Go => {
+                    Self::go(&mut cues, cue_list);
+                }
+                CueAction::Stop => {
+                    info!("Cue list '{}' stopped", name);
+                    cues.stop(name);
+                }
+                CueAction::Reset => {
+                    info!("Cue list '{}' reset", name);
+                    cues.reset(name);
+                }
+            }
+        }
+
+        self.run_due_cues().await;
+        true
+    }
+
+    /// Fire the next cue of a list, returning whether there was one
+    fn go(cues: &mut CueState, cue_list: &CueList) -> bool {
+        match cues.go(cue_list, Instant::now()) {
+            Some(index) => {
+                info!(
+                    "Cue list '{}' GO: cue {} ('{}')",
+                    cue_list.name,
+                    index + 1,
+                    cue_list.cues[index].name
+                );
+                true
+            }
+            None => {
+                info!("Cue list '{}' is at its end", cue_list.name);
+                false
+            }
+        }
+    }
+
+    /// Run any cues whose wait has elapsed
+    pub async fn run_due_cues(&self) {
+        let now = Instant::now();
+
+        // Resolve the due cues while holding locks briefly
+        let due = {
+            let map_config = self.map_config.read().await;
+            let mut cues = self.cues.lock().await;
+            map_config
+                .cue_lists
+                .iter()
+                .flat_map(|cue_list| {
+                    cues.take_due(cue_list, now)
+                        .into_iter()
+                        .map(|index| (cue_list.clone(), index))
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        };
 
-        if self.tap_tempo_cancel_tx.send(cancel_signal).is_err() {
-            warn!("Failed to send tap tempo cancellation signal");
+        for (cue_list, index) in due {
+            let cue = &cue_list.cues[index];
+            info!(
+                "Cue list '{}' running cue {} ('{}')",
+                cue_list.name,
+                index + 1,
+                cue.name
+            );
+            self.audit(
+                cue_list.audit,
+                &cue_list.name,
+                &format!("cue {}", index + 1),
+                &cue.commands,
+                &cue_list.destination,
+            )
+            .await;
+            if let Err(e) = self
+                .execute_commands(&cue.commands, &cue_list.destination, cue_list.send_channel)
+                .await
+            {
+                error!("Error running cue list '{}': {}", cue_list.name, e);
+            }
         }
+    }
 
+    /// Update tempo on a domain's devices that have tempo specifications
+    async fn update_device_tempos(&self, domain: &str, bpm: f64, taps: TapToken) -> Result<()> {
         // Collect tempo update tasks while holding locks briefly
         let tempo_updates = {
             let map_config = self.map_config.read().await;
@@ -141,40 +2278,97 @@ impl MidiProcessor {
 
             let mut updates = Vec::new();
             for mapping in &map_config.device_mappings {
-                if let Some(device) = device_config.get_device(&mapping.device_id) {
-                    if let Some(ref tempo_spec) = device.tempo_spec {
-                        info!(
-                            "Updating tempo for device '{}' to {:.1} BPM",
-                            device.name, bpm
-                        );
-                        updates.push((
-                            tempo_spec.clone(),
-                            mapping.destination.clone(),
-                            mapping.send_channel,
-                        ));
-                    }
+                if tempo::device_domain(&map_config, &mapping.device_id) != domain {
+                    continue;
+                }
+                if let Some(device) = device_config.get_device(&mapping.device_id)
+                    && let Some(ref tempo_spec) = device.tempo_spec
+                {
+                    info!(
+                        "Updating tempo for device '{}' to {:.1} BPM",
+                        device.name, bpm
+                    );
+                    updates.push((
+                        tempo_spec.clone(),
+                        mapping.destination.clone(),
+                        mapping.send_channel,
+                    ));
                 }
             }
             updates
         }; // Locks are released here
 
         // Execute tempo updates without holding any locks
-        // Use a different ID for the new operations
-        let operation_id = cancel_signal + 1; // Use a different ID for new operations
-
-        // Set the channel to the new operation ID so new operations know they should continue
-        if self.tap_tempo_cancel_tx.send(operation_id).is_err() {
-            warn!("Failed to send new operation ID");
-        }
-
         for (tempo_spec, destination, channel) in tempo_updates {
-            self.send_tempo_update(&tempo_spec, bpm, &destination, channel, operation_id)
+            self.send_tempo_update(&tempo_spec, bpm, &destination, channel, taps.clone())
                 .await?;
         }
 
         Ok(())
     }
 
+    /// Send the current tempo again to devices behind a session, e.g. after one rejoined
+    pub async fn resend_tempo(&self, session: &str) -> Result<()> {
+        let Some(ref session_manager) = self.session_manager else {
+            return Ok(());
+        };
+
+        let tempo_updates: Vec<_> = {
+            let map_config = self.map_config.read().await;
+            let device_config = self.device_config.read().await;
+            map_config
+                .device_mappings
+                .iter()
+                .filter_map(|mapping| {
+                    let device = device_config.get_device(&mapping.device_id)?;
+                    Some((mapping, device.tempo_spec.clone()?))
+                })
+                .flat_map(|(mapping, tempo_spec)| {
+                    // Only the sessions of a group are resent to, not the whole group
+                    let destinations = match mapping.destination {
+                        Destination::Group { ref group_name } => map_config
+                            .destination_groups
+                            .get(group_name)
+                            .cloned()
+                            .unwrap_or_default(),
+                        ref destination => vec![destination.clone()],
+                    };
+                    let domain = tempo::device_domain(&map_config, &mapping.device_id);
+                    destinations
+                        .into_iter()
+                        .filter_map(|destination| match destination {
+                            Destination::RtpMidi { ref session_name } => Some((
+                                session_name.clone(),
+                                domain.to_string(),
+                                tempo_spec.clone(),
+                                destination.clone(),
+                                mapping.send_channel,
+                            )),
+                            Destination::Osc { .. } | Destination::Group { .. } => None,
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect()
+        };
+
+        for (session_name, domain, tempo_spec, destination, channel) in tempo_updates {
+            if session_manager.resolve(&session_name).await != session {
+                continue;
+            }
+            let (bpm, taps) = {
+                let mut tempos = self.tempos.lock().await;
+                (tempos.bpm(&domain), tempos.tap_token(&domain))
+            };
+            let Some(bpm) = bpm else {
+                continue;
+            };
+            info!("Resending tempo {:.1} BPM to session '{}'", bpm, session);
+            self.send_tempo_update(&tempo_spec, bpm, &destination, channel, taps)
+                .await?;
+        }
+        Ok(())
+    }
+
     /// Send tempo update to a device
     async fn send_tempo_update(
         &self,
@@ -182,16 +2376,22 @@ impl MidiProcessor {
         bpm: f64,
         destination: &Destination,
         channel: Option<u8>,
-        cancel_id: u64,
+        taps: TapToken,
     ) -> Result<()> {
+        let device_bpm = tempo_spec.device_bpm(bpm);
+        if device_bpm != bpm {
+            debug!("Tempo {:.1} BPM sent as {:.1} BPM", bpm, device_bpm);
+        }
+        let bpm = device_bpm;
         match tempo_spec {
-            TempoSpec::TapTempo { commands } => {
-                self.send_tap_tempo(commands, bpm, destination, channel, cancel_id)
+            TempoSpec::TapTempo { .. } => {
+                self.send_tap_tempo(tempo_spec, bpm, destination, channel, taps)
                     .await?;
             }
             TempoSpec::RawTempo {
                 commands,
                 data_type,
+                ..
             } => {
                 self.send_raw_tempo(commands, data_type, bpm, destination, channel)
                     .await?;
@@ -200,54 +2400,69 @@ impl MidiProcessor {
         Ok(())
     }
 
-    /// Send tap tempo (4 quarter note taps using the specified commands)
+    /// Send the taps of a tap tempo spec
     async fn send_tap_tempo(
         &self,
-        commands: &[Command],
+        tempo_spec: &TempoSpec,
         bpm: f64,
         destination: &Destination,
         channel: Option<u8>,
-        cancel_id: u64,
+        mut taps: TapToken,
     ) -> Result<()> {
-        // Calculate interval between taps (quarter note duration in milliseconds)
-        let quarter_note_ms = (60.0 / bpm * 1000.0) as u64;
+        let TempoSpec::TapTempo {
+            commands,
+            taps: count,
+            subdivision,
+            jitter_ms,
+            repeats,
+            repeat_gap_ms,
+            ..
+        } = tempo_spec
+        else {
+            return Ok(());
+        };
+
+        // Calculate interval between taps in milliseconds
+        let interval_ms = 60.0 / bpm * 1000.0 * subdivision.quarter_notes();
+        let cancel_id = taps.id();
 
         info!(
-            "Sending tap tempo: 4 taps with {}ms intervals using {} commands (cancel_id: {})",
-            quarter_note_ms,
+            "Sending tap tempo: {} taps with {:.0}ms intervals using {} commands, {} repeats (cancel_id: {})",
+            count,
+            interval_ms,
             commands.len(),
+            repeats,
             cancel_id
         );
 
-        // Send 4 taps, each a quarter note apart
-        for i in 0..4 {
-            // Check if we've been cancelled
-            if *self.tap_tempo_cancel_rx.borrow() != cancel_id {
-                info!("Tap tempo cancelled (cancel_id: {})", cancel_id);
-                return Ok(());
-            }
+        for sequence in 0..=*repeats {
+            for i in 0..*count {
+                // Check if a newer tempo replaced this one
+                if taps.is_cancelled() {
+                    info!("Tap tempo cancelled (cancel_id: {})", cancel_id);
+                    return Ok(());
+                }
 
-            // Execute all commands for this tap
-            for command in commands {
-                self.execute_command(command, destination, channel).await?;
-            }
+                // Execute all commands for this tap
+                self.execute_commands(commands, destination, channel)
+                    .await?;
 
-            // Wait for the next tap (except after the last one)
-            if i < 3 {
-                // Use a cancellable sleep
-                let sleep_future = tokio::time::sleep(Duration::from_millis(quarter_note_ms));
-                let mut cancel_rx = self.tap_tempo_cancel_rx.clone();
+                // Wait for the next tap, or the next sequence after the last tap
+                let wait_ms = if i + 1 < *count {
+                    let jitter = self.randomizer.lock().await.jitter(*jitter_ms);
+                    (interval_ms + jitter as f64).max(0.0) as u64
+                } else if sequence < *repeats {
+                    repeat_gap_ms.unwrap_or(interval_ms as u64)
+                } else {
+                    break;
+                };
 
+                // Use a cancellable sleep
                 tokio::select! {
-                    _ = sleep_future => {
-                        // Sleep completed normally
-                    }
-                    _ = cancel_rx.changed() => {
-                        // We've been cancelled
-                        if *cancel_rx.borrow() != cancel_id {
-                            info!("Tap tempo cancelled during sleep (cancel_id: {})", cancel_id);
-                            return Ok(());
-                        }
+                    _ = tokio::time::sleep(Duration::from_millis(wait_ms)) => {}
+                    _ = taps.cancelled() => {
+                        info!("Tap tempo cancelled during sleep (cancel_id: {})", cancel_id);
+                        return Ok(());
                     }
                 }
             }
@@ -285,7 +2500,8 @@ impl MidiProcessor {
             bpm
         );
 
-        // Execute all specified commands with the calculated value
+        // Execute all specified commands with the calculated value, in order
+        let _order = self.lock_destination(destination).await;
         for command in commands {
             match command {
                 Command::Osc { address, args } => {
@@ -305,35 +2521,143 @@ impl MidiProcessor {
                             other => other.clone(),
                         })
                         .collect();
-
-                    let osc_cmd = Command::Osc {
-                        address: address.clone(),
-                        args: modified_args,
-                    };
-                    self.execute_command(&osc_cmd, destination, channel).await?;
+
+                    let osc_cmd = Command::Osc {
+                        address: address.clone(),
+                        args: modified_args,
+                    };
+                    self.execute_command(&osc_cmd, destination, channel).await?;
+                }
+                Command::ControlChange {
+                    controller,
+                    value: _,
+                } => {
+                    // Map value to MIDI CC range (0-127)
+                    let cc_value = if matches!(data_type, TempoDataType::Tempo) {
+                        // BPM range: assume 60-180 BPM maps to 0-127
+                        ((value - 60.0) / 120.0 * 127.0).clamp(0.0, 127.0) as u8
+                    } else {
+                        // Time range: assume 333ms-1000ms (180-60 BPM) maps to 0-127
+                        ((1000.0 - value) / 667.0 * 127.0).clamp(0.0, 127.0) as u8
+                    };
+
+                    let cc_cmd = Command::ControlChange {
+                        controller: *controller,
+                        value: cc_value.into(),
+                    };
+                    self.execute_command(&cc_cmd, destination, channel).await?;
+                }
+                _ => {
+                    // Execute other commands as-is
+                    self.execute_command(command, destination, channel).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that every batch of a program can be delivered without sending anything
+    async fn validate_batches(
+        &self,
+        batches: &[CommandBatch],
+        channel: Option<u8>,
+    ) -> std::result::Result<(), String> {
+        for batch in batches {
+            self.validate_commands(&batch.commands, &batch.destination, channel)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Check that every command can be delivered to the destination without sending anything
+    async fn validate_commands(
+        &self,
+        commands: &[Command],
+        destination: &Destination,
+        channel: Option<u8>,
+    ) -> std::result::Result<(), String> {
+        match destination {
+            Destination::RtpMidi { session_name } => {
+                let known = match self.session_manager {
+                    Some(ref session_manager) => session_manager.has_session(session_name).await,
+                    None => false,
+                };
+                if !known {
+                    return Err(format!(
+                        "RTP MIDI session '{session_name}' is not available"
+                    ));
+                }
+            }
+            Destination::Osc { destination_name } => {
+                if !self
+                    .map_config
+                    .read()
+                    .await
+                    .osc_destinations
+                    .contains_key(destination_name)
+                {
+                    return Err(format!(
+                        "OSC destination '{destination_name}' not found in configuration"
+                    ));
+                }
+                if self.osc_socket.is_none() {
+                    return Err("OSC socket not available".to_string());
+                }
+            }
+            Destination::Group { group_name } => {
+                if !self
+                    .map_config
+                    .read()
+                    .await
+                    .destination_groups
+                    .contains_key(group_name)
+                {
+                    return Err(format!(
+                        "destination group '{group_name}' not found in configuration"
+                    ));
+                }
+                // Each member checks the commands it sends
+                for member in self.group_members(group_name).await {
+                    let commands: Vec<_> = commands
+                        .iter()
+                        .filter(|command| member.carries(command))
+                        .cloned()
+                        .collect();
+                    Box::pin(self.validate_commands(&commands, &member, channel)).await?;
                 }
-                Command::ControlChange {
-                    controller,
-                    value: _,
-                } => {
-                    // Map value to MIDI CC range (0-127)
-                    let cc_value = if matches!(data_type, TempoDataType::Tempo) {
-                        // BPM range: assume 60-180 BPM maps to 0-127
-                        ((value - 60.0) / 120.0 * 127.0).clamp(0.0, 127.0) as u8
-                    } else {
-                        // Time range: assume 333ms-1000ms (180-60 BPM) maps to 0-127
-                        ((1000.0 - value) / 667.0 * 127.0).clamp(0.0, 127.0) as u8
-                    };
+                return Ok(());
+            }
+        }
 
-                    let cc_cmd = Command::ControlChange {
-                        controller: *controller,
-                        value: cc_value,
-                    };
-                    self.execute_command(&cc_cmd, destination, channel).await?;
+        for command in commands {
+            match (command, destination) {
+                (
+                    Command::ProgramChange { .. }
+                    | Command::ControlChange { .. }
+                    | Command::NoteOn { .. }
+                    | Command::NoteOff { .. },
+                    Destination::RtpMidi { .. },
+                ) => {
+                    if channel.is_none() {
+                        return Err(format!("no channel specified for MIDI command {command:?}"));
+                    }
                 }
-                _ => {
-                    // Execute other commands as-is
-                    self.execute_command(command, destination, channel).await?;
+                (
+                    Command::Start
+                    | Command::Stop
+                    | Command::Continue
+                    | Command::SongSelect { .. }
+                    | Command::SongPosition { .. }
+                    | Command::DisplayText { .. }
+                    | Command::SysEx { .. },
+                    Destination::RtpMidi { .. },
+                ) => {}
+                (Command::Osc { .. }, Destination::Osc { .. }) => {}
+                (command, destination) => {
+                    return Err(format!(
+                        "command {command:?} cannot be sent to destination {destination:?}"
+                    ));
                 }
             }
         }
@@ -341,6 +2665,27 @@ impl MidiProcessor {
         Ok(())
     }
 
+    /// Pick the value to send for a command value, nudging captured variables
+    async fn resolve_value(&self, value: &CommandValue) -> Result<u8> {
+        if let CommandValue::Variable { variable, offset } = value {
+            let map_config = self.map_config.read().await;
+            let value = self.variables.lock().await.nudge(
+                &map_config.captures,
+                variable,
+                f64::from(*offset),
+                0.0,
+                127.0,
+            )?;
+            return Ok(value.round() as u8);
+        }
+        Ok(self
+            .randomizer
+            .lock()
+            .await
+            .resolve(value)
+            .unwrap_or_default())
+    }
+
     /// Execute a command to the specified destination
     async fn execute_command(
         &self,
@@ -348,24 +2693,129 @@ impl MidiProcessor {
         destination: &Destination,
         channel: Option<u8>,
     ) -> Result<()> {
+        if let Destination::Group { group_name } = destination {
+            for member in self.group_members(group_name).await {
+                if member.carries(command) {
+                    Box::pin(self.execute_command(command, &member, channel)).await?;
+                }
+            }
+            return Ok(());
+        }
+
         match command {
-            Command::ProgramChange { program } => {
+            Command::ProgramChange {
+                program,
+                bank_msb,
+                bank_lsb,
+            } => {
                 if let Some(ch) = channel {
-                    self.send_midi_command(destination, ch, *program).await?;
+                    let program = self.resolve_value(program).await?;
+                    if let Some(msb) = bank_msb {
+                        self.send_midi_control_change(destination, ch, BANK_SELECT_MSB, *msb)
+                            .await?;
+                    }
+                    if let Some(lsb) = bank_lsb {
+                        self.send_midi_control_change(destination, ch, BANK_SELECT_LSB, *lsb)
+                            .await?;
+                    }
+                    self.send_midi_command(destination, ch, program).await?;
                 } else {
                     warn!("No channel specified for MIDI Program Change command");
                 }
             }
             Command::ControlChange { controller, value } => {
                 if let Some(ch) = channel {
-                    self.send_midi_control_change(destination, ch, *controller, *value)
+                    let value = self.resolve_value(value).await?;
+                    self.send_midi_control_change(destination, ch, *controller, value)
                         .await?;
                 } else {
                     warn!("No channel specified for MIDI Control Change command");
                 }
             }
+            Command::NoteOn { note, velocity } => {
+                if let Some(ch) = channel {
+                    let velocity = self.resolve_value(velocity).await?;
+                    self.send_midi_note(destination, ch, *note, Some(velocity))
+                        .await?;
+                } else {
+                    warn!("No channel specified for MIDI Note On command");
+                }
+            }
+            Command::NoteOff { note } => {
+                if let Some(ch) = channel {
+                    self.send_midi_note(destination, ch, *note, None).await?;
+                } else {
+                    warn!("No channel specified for MIDI Note Off command");
+                }
+            }
+            Command::Start => {
+                self.send_midi_system(destination, MidiMessage::Start)
+                    .await?;
+            }
+            Command::Stop => {
+                self.send_midi_system(destination, MidiMessage::Stop)
+                    .await?;
+            }
+            Command::Continue => {
+                self.send_midi_system(destination, MidiMessage::Continue)
+                    .await?;
+            }
+            Command::SongSelect { song } => {
+                let song = midi_types::Value7::new(song & 0x7F);
+                self.send_midi_system(destination, MidiMessage::SongSelect(song))
+                    .await?;
+            }
+            Command::SongPosition { position } => {
+                let position = midi_types::Value14::from((*position).min(MAX_SONG_POSITION));
+                self.send_midi_system(destination, MidiMessage::SongPositionPointer(position))
+                    .await?;
+            }
+            Command::DisplayText { protocol, text } => {
+                let text = placeholder::expand(text, &*self.song.read().await);
+                self.send_display_text(destination, protocol, &text).await?;
+            }
+            Command::SysEx { data } => {
+                self.send_sysex(destination, data).await?;
+            }
             Command::Osc { address, args } => {
-                self.send_osc_command(destination, address, args).await?;
+                let args = placeholder::expand_args(args, &*self.song.read().await);
+                self.send_osc_command(destination, address, &args).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Send a MIDI transport or song message, which has no channel
+    async fn send_midi_system(
+        &self,
+        destination: &Destination,
+        message: MidiMessage,
+    ) -> Result<()> {
+        match destination {
+            Destination::RtpMidi { session_name } => {
+                info!("Sending MIDI {:?} to session '{}'", message, session_name);
+                if let Some(ref session_manager) = self.session_manager {
+                    session_manager
+                        .send_midi_to_session(session_name, message)
+                        .await?;
+                } else {
+                    warn!(
+                        "No session manager available for session '{}'",
+                        session_name
+                    );
+                }
+            }
+            Destination::Osc { destination_name } => {
+                warn!(
+                    "Cannot send MIDI command to OSC destination '{}'",
+                    destination_name
+                );
+            }
+            Destination::Group { group_name } => {
+                warn!(
+                    "Cannot send to destination group '{}' without resolving its members",
+                    group_name
+                );
             }
         }
         Ok(())
@@ -406,8 +2856,9 @@ impl MidiProcessor {
                 let map_config = self.map_config.read().await;
                 if let Some(osc_dest) = map_config.osc_destinations.get(destination_name) {
                     warn!(
-                        "Cannot send MIDI command to OSC destination '{}' ({}:{})",
-                        destination_name, osc_dest.host, osc_dest.port
+                        "Cannot send MIDI command to OSC destination '{}' ({})",
+                        destination_name,
+                        osc_dest.addresses().join(", ")
                     );
                 } else {
                     warn!(
@@ -416,6 +2867,12 @@ impl MidiProcessor {
                     );
                 }
             }
+            Destination::Group { group_name } => {
+                warn!(
+                    "Cannot send to destination group '{}' without resolving its members",
+                    group_name
+                );
+            }
         }
         Ok(())
     }
@@ -458,8 +2915,9 @@ impl MidiProcessor {
                 let map_config = self.map_config.read().await;
                 if let Some(osc_dest) = map_config.osc_destinations.get(destination_name) {
                     warn!(
-                        "Cannot send MIDI command to OSC destination '{}' ({}:{})",
-                        destination_name, osc_dest.host, osc_dest.port
+                        "Cannot send MIDI command to OSC destination '{}' ({})",
+                        destination_name,
+                        osc_dest.addresses().join(", ")
                     );
                 } else {
                     warn!(
@@ -468,6 +2926,150 @@ impl MidiProcessor {
                     );
                 }
             }
+            Destination::Group { group_name } => {
+                warn!(
+                    "Cannot send to destination group '{}' without resolving its members",
+                    group_name
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Send MIDI Note On (with a velocity) or Note Off (without) command
+    async fn send_midi_note(
+        &self,
+        destination: &Destination,
+        channel: u8,
+        note: u8,
+        velocity: Option<u8>,
+    ) -> Result<()> {
+        match destination {
+            Destination::RtpMidi { session_name } => {
+                info!(
+                    "Sending MIDI Note {} to session '{}': channel {}, note {}, velocity {}",
+                    if velocity.is_some() { "On" } else { "Off" },
+                    session_name,
+                    channel,
+                    note,
+                    velocity.unwrap_or(0)
+                );
+
+                if let Some(ref session_manager) = self.session_manager {
+                    use midi_types::{Channel, Note, Value7};
+                    let midi_channel = Channel::new(channel.saturating_sub(1) & 0x0F);
+                    let midi_note = Note::new(note & 0x7F);
+                    let message = match velocity {
+                        Some(velocity) => MidiMessage::NoteOn(
+                            midi_channel,
+                            midi_note,
+                            Value7::new(velocity & 0x7F),
+                        ),
+                        None => MidiMessage::NoteOff(midi_channel, midi_note, Value7::new(0)),
+                    };
+
+                    session_manager
+                        .send_midi_to_session(session_name, message)
+                        .await?;
+                } else {
+                    warn!(
+                        "No session manager available for session '{}'",
+                        session_name
+                    );
+                }
+            }
+            Destination::Osc { destination_name } => {
+                warn!(
+                    "Cannot send MIDI command to OSC destination '{}'",
+                    destination_name
+                );
+            }
+            Destination::Group { group_name } => {
+                warn!(
+                    "Cannot send to destination group '{}' without resolving its members",
+                    group_name
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Send text to a controller display via SysEx
+    async fn send_display_text(
+        &self,
+        destination: &Destination,
+        protocol: &DisplayProtocol,
+        text: &str,
+    ) -> Result<()> {
+        match destination {
+            Destination::RtpMidi { session_name } => {
+                info!(
+                    "Sending display text to session '{}': {:?}",
+                    session_name, text
+                );
+
+                if let Some(ref session_manager) = self.session_manager {
+                    let message = sysex::encode_display_text(protocol, text);
+                    session_manager
+                        .send_sysex_to_session(session_name, &message)
+                        .await?;
+                } else {
+                    warn!(
+                        "No session manager available for session '{}'",
+                        session_name
+                    );
+                }
+            }
+            Destination::Osc { destination_name } => {
+                warn!(
+                    "Cannot send display text to OSC destination '{}'",
+                    destination_name
+                );
+            }
+            Destination::Group { group_name } => {
+                for member in self.group_members(group_name).await {
+                    if let Destination::RtpMidi { .. } = member {
+                        Box::pin(self.send_display_text(&member, protocol, text)).await?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Send a raw SysEx message
+    async fn send_sysex(&self, destination: &Destination, data: &[u8]) -> Result<()> {
+        match destination {
+            Destination::RtpMidi { session_name } => {
+                info!(
+                    "Sending SysEx ({} bytes) to session '{}'",
+                    data.len(),
+                    session_name
+                );
+
+                if let Some(ref session_manager) = self.session_manager {
+                    session_manager
+                        .send_sysex_to_session(session_name, &sysex::frame(data))
+                        .await?;
+                } else {
+                    warn!(
+                        "No session manager available for session '{}'",
+                        session_name
+                    );
+                }
+            }
+            Destination::Osc { destination_name } => {
+                warn!(
+                    "Cannot send SysEx to OSC destination '{}'",
+                    destination_name
+                );
+            }
+            Destination::Group { group_name } => {
+                warn!(
+                    "Cannot send to destination group '{}' without resolving its members",
+                    group_name
+                );
+            }
         }
         Ok(())
     }
@@ -485,9 +3087,11 @@ impl MidiProcessor {
                 let map_config = self.map_config.read().await;
                 if let Some(osc_dest) = map_config.osc_destinations.get(destination_name) {
                     if let Some(ref socket) = self.osc_socket {
-                        let osc_args: Vec<OscType> = args
-                            .iter()
-                            .map(|arg| match arg {
+                        let (address, args) = osc_dest.apply_template(address, args);
+                        let address = address.as_str();
+                        let mut osc_args = Vec::with_capacity(args.len());
+                        for arg in &args {
+                            osc_args.push(match arg {
                                 OscArg::Int { value } => OscType::Int(*value),
                                 OscArg::Float { value } => OscType::Float(*value),
                                 OscArg::String { value } => OscType::String(value.clone()),
@@ -495,24 +3099,43 @@ impl MidiProcessor {
                                 OscArg::Normalized { value, min, max } => {
                                     OscType::Float((value - min) / (max - min))
                                 }
-                            })
-                            .collect();
+                                OscArg::Variable {
+                                    name,
+                                    offset,
+                                    min,
+                                    max,
+                                } => {
+                                    let value = self.variables.lock().await.nudge(
+                                        &map_config.captures,
+                                        name,
+                                        f64::from(*offset),
+                                        min.map_or(f64::MIN, f64::from),
+                                        max.map_or(f64::MAX, f64::from),
+                                    )?;
+                                    OscType::Float(value as f32)
+                                }
+                            });
+                        }
 
                         let msg = OscMessage {
                             addr: address.to_string(),
                             args: osc_args,
                         };
 
-                        let packet = OscPacket::Message(msg);
+                        let mut packet = OscPacket::Message(msg);
+                        if let Some(latency_ms) = osc_dest.bundle_latency_ms {
+                            packet = OscPacket::Bundle(OscBundle {
+                                timetag: self
+                                    .time_source
+                                    .timetag_in(Duration::from_millis(latency_ms)),
+                                content: vec![packet],
+                            });
+                        }
                         let msg_buf = rosc::encoder::encode(&packet)?;
 
-                        let addr = format!("{}:{}", osc_dest.host, osc_dest.port);
-                        socket.send_to(&msg_buf, &addr)?;
-
-                        info!(
-                            "Sent OSC message to {} ({}): {} {:?}",
-                            destination_name, addr, address, args
-                        );
+                        self.deliver_osc(socket, destination_name, osc_dest, address, msg_buf)
+                            .await?;
+                        debug!("OSC message args: {:?}", args);
                     } else {
                         error!("OSC socket not available");
                     }
@@ -529,7 +3152,226 @@ impl MidiProcessor {
                     session_name
                 );
             }
+            Destination::Group { group_name } => {
+                warn!(
+                    "Cannot send to destination group '{}' without resolving its members",
+                    group_name
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Send an encoded OSC message, queueing it if the destination is unreachable
+    async fn deliver_osc(
+        &self,
+        socket: &UdpSocket,
+        destination_name: &str,
+        osc_dest: &OscDestination,
+        address: &str,
+        msg_buf: Vec<u8>,
+    ) -> Result<()> {
+        if self.output_freeze.is_frozen(destination_name) {
+            info!(
+                "Output frozen, not sending OSC message to {}: {}",
+                destination_name, address
+            );
+            return Ok(());
+        }
+        let Some(ref queue_config) = osc_dest.offline_queue else {
+            let sent = self
+                .osc_pools
+                .send(socket, destination_name, osc_dest, &msg_buf)
+                .await?;
+            info!(
+                "Sent OSC message to {} ({}): {}",
+                destination_name,
+                sent.join(", "),
+                address
+            );
+            return Ok(());
+        };
+
+        {
+            // Keep ordering: queue behind earlier messages until the replay catches up
+            let mut queue = self.osc_queue.lock().await;
+            if !queue.is_reachable(destination_name) {
+                queue.push(destination_name, queue_config, address, msg_buf);
+                return Ok(());
+            }
+        }
+
+        match self
+            .osc_pools
+            .send(socket, destination_name, osc_dest, &msg_buf)
+            .await
+        {
+            Ok(sent) => {
+                info!(
+                    "Sent OSC message to {} ({}): {}",
+                    destination_name,
+                    sent.join(", "),
+                    address
+                );
+            }
+            Err(e) => {
+                warn!("Failed to send OSC message to {}: {}", destination_name, e);
+                let mut queue = self.osc_queue.lock().await;
+                queue.set_reachable(destination_name, false);
+                queue.push(destination_name, queue_config, address, msg_buf);
+            }
         }
         Ok(())
     }
+
+    /// Replay the queued messages of a destination that replies again, oldest first,
+    /// stopping at the first failure
+    async fn flush_osc_queue(
+        &self,
+        socket: &UdpSocket,
+        destination_name: &str,
+        osc_dest: &OscDestination,
+    ) {
+        let Some(ref queue_config) = osc_dest.offline_queue else {
+            return;
+        };
+        if self.output_freeze.is_frozen(destination_name) {
+            return;
+        }
+
+        // Messages queued while replaying are picked up by the next round
+        loop {
+            let pending = {
+                let mut queue = self.osc_queue.lock().await;
+                if queue.finish_replay(destination_name) {
+                    return;
+                }
+                queue.take(destination_name, queue_config)
+            };
+
+            for (index, message) in pending.iter().enumerate() {
+                let sent = match self
+                    .osc_pools
+                    .send(socket, destination_name, osc_dest, &message.packet)
+                    .await
+                {
+                    Ok(sent) => sent,
+                    Err(e) => {
+                        warn!(
+                            "Failed to replay OSC messages to {}: {}",
+                            destination_name, e
+                        );
+                        let mut queue = self.osc_queue.lock().await;
+                        queue.restore(destination_name, pending[index..].to_vec());
+                        queue.set_reachable(destination_name, false);
+                        return;
+                    }
+                };
+                info!(
+                    "Replayed queued OSC message to {} ({}): {}",
+                    destination_name,
+                    sent.join(", "),
+                    message.address
+                );
+            }
+        }
+    }
+
+    /// Send the heartbeat query to every OSC destination with an offline queue, mark
+    /// those that stopped replying unreachable and replay the queue of those that reply
+    pub async fn check_osc_destinations(&self) {
+        let Some(ref socket) = self.osc_socket else {
+            return;
+        };
+
+        let destinations: Vec<(String, OscDestination)> = self
+            .map_config
+            .read()
+            .await
+            .osc_destinations
+            .iter()
+            .filter(|(_, destination)| destination.offline_queue.is_some())
+            .map(|(name, destination)| (name.clone(), destination.clone()))
+            .collect();
+        for (name, destination) in destinations {
+            let Some(ref queue_config) = destination.offline_queue else {
+                continue;
+            };
+
+            let heartbeat = OscPacket::Message(OscMessage {
+                addr: queue_config.heartbeat_address.clone(),
+                args: Vec::new(),
+            });
+            let packet = match rosc::encoder::encode(&heartbeat) {
+                Ok(packet) => packet,
+                Err(e) => {
+                    warn!("Failed to encode OSC heartbeat for '{}': {:?}", name, e);
+                    continue;
+                }
+            };
+            for addr in destination.addresses() {
+                let resolved = match tokio::net::lookup_host(addr.as_str()).await {
+                    Ok(mut resolved) => resolved.next(),
+                    Err(e) => {
+                        debug!(
+                            "Failed to resolve OSC endpoint {} of '{}': {}",
+                            addr, name, e
+                        );
+                        None
+                    }
+                };
+                let Some(resolved) = resolved else {
+                    continue;
+                };
+                self.osc_queue.lock().await.set_endpoint(resolved, &name);
+                if let Err(e) = socket.send_to(&packet, resolved).await {
+                    debug!("Failed to send OSC heartbeat to {} ({}): {}", name, addr, e);
+                }
+            }
+
+            let timeout = Duration::from_millis(queue_config.heartbeat_timeout_ms);
+            let replying = {
+                let mut queue = self.osc_queue.lock().await;
+                let replying = queue.replied_within(&name, timeout);
+                if !replying {
+                    queue.set_reachable(&name, false);
+                }
+                replying
+            };
+            if replying {
+                self.flush_osc_queue(socket, &name, &destination).await;
+            }
+        }
+    }
+
+    /// Wait for a packet on the OSC socket, returning who sent it
+    pub async fn receive_osc_reply(&self) -> std::io::Result<SocketAddr> {
+        let Some(ref socket) = self.osc_socket else {
+            return std::future::pending().await;
+        };
+        let mut buf = [0u8; rosc::decoder::MTU];
+        let (_, from) = socket.recv_from(&mut buf).await?;
+        Ok(from)
+    }
+
+    /// Count a packet from an OSC destination as a reply to its heartbeat
+    pub async fn record_osc_reply(&self, from: SocketAddr) {
+        self.osc_queue.lock().await.record_reply(from);
+    }
+}
+
+/// Recent input entry for a tempo change, naming the domain unless it is the main one
+#[cfg(any(feature = "http", feature = "osc-input", feature = "link"))]
+fn tempo_input(domain: &str, bpm: f64) -> String {
+    if domain == MAIN_TEMPO_DOMAIN {
+        format!("tempo {bpm:.1}")
+    } else {
+        format!("tempo {domain} {bpm:.1}")
+    }
+}
+
+/// Time covered by the tempo statistics
+#[cfg(any(feature = "http", feature = "osc-input"))]
+fn stats_window(map_config: &MapConfig) -> Duration {
+    Duration::from_secs(map_config.tempo_stats.window_mins * 60)
 }
@@ -0,0 +1,41 @@
+use crate::device::CommandValue;
+use rand::rngs::StdRng;
+use rand::seq::IndexedRandom;
+use rand::{Rng, SeedableRng};
+
+/// Resolves randomized command values, optionally from a fixed seed
+pub struct Randomizer {
+    rng: StdRng,
+}
+
+impl Randomizer {
+    /// Create a randomizer; the same seed always produces the same sequence of values
+    pub fn new(seed: Option<u64>) -> Self {
+        let rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_os_rng(),
+        };
+        Self { rng }
+    }
+
+    /// Pick the value to send for a command value, or `None` for a variable,
+    /// which only the processor can resolve
+    pub fn resolve(&mut self, value: &CommandValue) -> Option<u8> {
+        match value {
+            CommandValue::Fixed(value) => Some(*value),
+            CommandValue::Range { min, max } => {
+                Some(self.rng.random_range(*min.min(max)..=*min.max(max)))
+            }
+            CommandValue::Choice { choices } => {
+                Some(choices.choose(&mut self.rng).copied().unwrap_or(0))
+            }
+            CommandValue::Variable { .. } => None,
+        }
+    }
+
+    /// Random offset of up to `max` either way
+    pub fn jitter(&mut self, max: u64) -> i64 {
+        let max = i64::try_from(max).unwrap_or(i64::MAX);
+        self.rng.random_range(-max..=max)
+    }
+}
@@ -0,0 +1,226 @@
+use crate::mapping::InviteRetry;
+use serde::Serialize;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Clock sync state of one remote a session was configured to connect to
+#[derive(Debug, Clone)]
+pub struct RemoteHealth {
+    name: String,
+    #[cfg(any(feature = "http", feature = "tui"))]
+    host: String,
+    #[cfg(any(feature = "http", feature = "tui"))]
+    port: u16,
+    /// Resolved address of the remote, if the host could be resolved
+    addr: Option<SocketAddr>,
+    /// Whether the remote is in the session's participant list
+    connected: bool,
+    /// Time of the most recent successful CK (clock sync) exchange
+    last_sync: Option<Instant>,
+    /// Whether clock sync is currently considered healthy
+    synced: bool,
+    /// Invitation retries while the remote is not connected
+    invite: InviteState,
+}
+
+/// Circuit breaker state for invitations to a remote
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// Invitations are retried with backoff
+    Closed,
+    /// Too many invitations failed; waiting out the cooldown
+    Open,
+    /// Cooldown over; one trial invitation is outstanding
+    HalfOpen,
+}
+
+/// Invitation retry tracking for one remote
+#[derive(Debug, Clone)]
+struct InviteState {
+    /// Consecutive invitations that did not lead to a connection
+    failures: u32,
+    /// When the outstanding invitation is considered failed and the next may be sent
+    next_attempt: Instant,
+    circuit: CircuitState,
+}
+
+/// Snapshot of a remote's health for status reporting
+#[cfg(any(feature = "http", feature = "tui"))]
+#[derive(Debug, Clone, Serialize)]
+pub struct RemoteStatus {
+    /// Remote name from the config
+    pub name: String,
+    /// Remote host and port
+    pub address: String,
+    /// Whether the remote is a participant of the session
+    pub connected: bool,
+    /// Whether CK exchanges with the remote are succeeding
+    pub clock_synced: bool,
+    /// Milliseconds since the last successful CK exchange
+    pub last_sync_ms_ago: Option<u64>,
+    /// Consecutive invitations that did not lead to a connection
+    pub invite_failures: u32,
+    /// Circuit breaker state for invitations
+    pub invite_circuit: CircuitState,
+}
+
+impl RemoteHealth {
+    /// Track a remote that was just invited
+    pub fn new(name: &str, host: &str, port: u16, retry: &InviteRetry) -> Self {
+        let addr = (host, port)
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addrs| addrs.next());
+        Self {
+            name: name.to_string(),
+            #[cfg(any(feature = "http", feature = "tui"))]
+            host: host.to_string(),
+            #[cfg(any(feature = "http", feature = "tui"))]
+            port,
+            addr,
+            connected: false,
+            last_sync: None,
+            synced: false,
+            invite: InviteState {
+                failures: 0,
+                next_attempt: Instant::now() + Duration::from_millis(retry.initial_backoff_ms),
+                circuit: CircuitState::Closed,
+            },
+        }
+    }
+
+    /// Whether a participant address belongs to this remote (control or data port)
+    pub fn matches(&self, participant: &SocketAddr) -> bool {
+        self.addr.is_some_and(|addr| is_peer(addr, participant))
+    }
+
+    /// Update from the current participant list, logging when clock sync starts or stops
+    pub fn update(
+        &mut self,
+        session_name: &str,
+        sync_age: Option<Option<Duration>>,
+        timeout: Duration,
+    ) {
+        self.connected = sync_age.is_some();
+        if let Some(Some(age)) = sync_age {
+            self.last_sync = Instant::now().checked_sub(age);
+        }
+
+        let synced = self.connected
+            && self
+                .last_sync
+                .is_some_and(|last_sync| last_sync.elapsed() <= timeout);
+
+        if synced && !self.synced {
+            info!(
+                "Clock sync with remote '{}' of session '{}' is healthy",
+                self.name, session_name
+            );
+        } else if !synced && self.synced {
+            warn!(
+                "Clock sync with remote '{}' of session '{}' stopped (last sync {})",
+                self.name,
+                session_name,
+                self.last_sync
+                    .map(|last_sync| format!("{}s ago", last_sync.elapsed().as_secs()))
+                    .unwrap_or_else(|| "never".to_string())
+            );
+        }
+        self.synced = synced;
+    }
+
+    /// Address to invite again if the remote is not connected and a retry is due
+    pub fn invite_due(&mut self, session_name: &str, retry: &InviteRetry) -> Option<SocketAddr> {
+        let now = Instant::now();
+        let invite = &mut self.invite;
+
+        if self.connected {
+            if invite.failures > 0 || invite.circuit != CircuitState::Closed {
+                info!(
+                    "Remote '{}' of session '{}' connected, invitations reset",
+                    self.name, session_name
+                );
+            }
+            invite.failures = 0;
+            invite.circuit = CircuitState::Closed;
+            // If the remote drops, wait the initial backoff before inviting again
+            invite.next_attempt = now + Duration::from_millis(retry.initial_backoff_ms);
+            return None;
+        }
+        if now < invite.next_attempt {
+            return None;
+        }
+
+        match invite.circuit {
+            CircuitState::Open => {
+                info!(
+                    "Retrying invitation to remote '{}' of session '{}' after cooldown",
+                    self.name, session_name
+                );
+                invite.circuit = CircuitState::HalfOpen;
+                invite.next_attempt = now + Duration::from_millis(retry.initial_backoff_ms);
+            }
+            CircuitState::HalfOpen => {
+                // The trial invitation failed too
+                invite.failures += 1;
+                self.open_circuit(session_name, retry, now);
+                return None;
+            }
+            CircuitState::Closed => {
+                invite.failures += 1;
+                if invite.failures >= retry.failure_threshold {
+                    self.open_circuit(session_name, retry, now);
+                    return None;
+                }
+                let backoff = retry
+                    .initial_backoff_ms
+                    .saturating_mul(1 << invite.failures.min(16))
+                    .min(retry.max_backoff_ms);
+                info!(
+                    "Remote '{}' of session '{}' not connected, inviting again (attempt {}, next in {}ms)",
+                    self.name,
+                    session_name,
+                    invite.failures + 1,
+                    backoff
+                );
+                invite.next_attempt = now + Duration::from_millis(backoff);
+            }
+        }
+
+        self.addr
+    }
+
+    /// Stop inviting the remote until the cooldown has passed
+    fn open_circuit(&mut self, session_name: &str, retry: &InviteRetry, now: Instant) {
+        warn!(
+            "Remote '{}' of session '{}' refused {} invitations, pausing invitations for {}s",
+            self.name, session_name, self.invite.failures, retry.cooldown_secs
+        );
+        self.invite.circuit = CircuitState::Open;
+        self.invite.next_attempt = now + Duration::from_secs(retry.cooldown_secs);
+    }
+
+    #[cfg(any(feature = "http", feature = "tui"))]
+    pub fn status(&self) -> RemoteStatus {
+        RemoteStatus {
+            name: self.name.clone(),
+            address: format!("{}:{}", self.host, self.port),
+            connected: self.connected,
+            clock_synced: self.synced,
+            last_sync_ms_ago: self
+                .last_sync
+                .map(|last_sync| last_sync.elapsed().as_millis() as u64),
+            invite_failures: self.invite.failures,
+            invite_circuit: self.invite.circuit,
+        }
+    }
+}
+
+/// Whether a participant address is the control or data port of a remote's address
+pub fn is_peer(remote: SocketAddr, participant: &SocketAddr) -> bool {
+    remote.ip() == participant.ip()
+        && (participant.port() == remote.port()
+            || participant.port() == remote.port().wrapping_add(1))
+}
@@ -1,14 +1,21 @@
-use crate::mapping::{MapConfig, RtpMidiSession};
+use crate::identity::SessionIdentities;
+use crate::mapping::{MapConfig, OverloadConfig, RtpMidiSession};
+use crate::overload::{OverloadMonitor, OverloadStats};
 use crate::processor::MidiProcessor;
 use crate::session_manager::SessionManager;
-use anyhow::Result;
-use rand::RngCore;
-use rtpmidi::sessions::events::event_handling::MidiMessageEvent;
+use crate::supervisor::supervise;
+use anyhow::{Result, bail};
+use midi_types::{Channel, MidiMessage};
+use rtpmidi::sessions::events::event_handling::{MidiMessageFromEvent, SysExPacketEvent};
 use rtpmidi::sessions::invite_responder::InviteResponder;
 use rtpmidi::sessions::rtp_midi_session::RtpMidiSession as AppleMidiSession;
-use std::net::ToSocketAddrs;
+use std::collections::BTreeMap;
+use std::net::{ToSocketAddrs, UdpSocket};
 use std::sync::Arc;
-use tracing::info;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+use tokio::sync::mpsc;
+use tracing::{debug, error, info, warn};
 
 /// Manages RTP MIDI sessions and routes messages to the processor
 pub struct MidiRouter {
@@ -26,38 +33,180 @@ impl MidiRouter {
 
     /// Initialize RTP MIDI sessions based on configuration
     pub async fn initialize_sessions(&mut self, map_config: &MapConfig) -> Result<()> {
+        let mut identities = SessionIdentities::load(map_config.identity_file.as_deref());
         for session_config in &map_config.rtp_midi_sessions {
-            self.create_session(session_config).await?;
+            if !session_config.enabled {
+                info!(
+                    "Session '{}' is disabled, not starting it",
+                    session_config.name
+                );
+                continue;
+            }
+            let ssrc = identities.ssrc(&session_config.name, session_config.ssrc);
+            self.create_session(session_config, ssrc, map_config.overload.as_ref())
+                .await?;
         }
         Ok(())
     }
 
     /// Create and start a single RTP MIDI session
-    async fn create_session(&mut self, config: &RtpMidiSession) -> Result<()> {
+    async fn create_session(
+        &mut self,
+        config: &RtpMidiSession,
+        ssrc: u32,
+        overload: Option<&OverloadConfig>,
+    ) -> Result<()> {
+        let mut config = config.clone();
+        if config.send_only {
+            if config.listen {
+                warn!("Session '{}' is send-only, ignoring `listen`", config.name);
+                config.listen = false;
+            }
+            if config.port == 0 {
+                config.port = free_port_pair()?;
+            }
+        } else if config.port == 0 {
+            bail!(
+                "Session '{}' needs a `port` unless it is `send_only`",
+                config.name
+            );
+        }
+        let config = &config;
+
         info!(
-            "Creating RTP MIDI session '{}' on port {}",
-            config.name, config.port
+            "Creating RTP MIDI session '{}' on port {}{}",
+            config.name,
+            config.port,
+            if config.send_only { " (send-only)" } else { "" }
         );
 
         // Create the Apple MIDI session
-        let ssrc: u32 = rand::rng().next_u32();
         let session =
-            AppleMidiSession::start(config.port, &config.name, ssrc, InviteResponder::Accept)
-                .await?;
+            match AppleMidiSession::start(config.port, &config.name, ssrc, InviteResponder::Accept)
+                .await
+            {
+                Ok(session) => session,
+                Err(e) => {
+                    #[cfg(any(feature = "http", feature = "tui"))]
+                    self.session_manager.record_unbound(config, ssrc).await;
+                    return Err(e.into());
+                }
+            };
 
+        let dropped = Arc::new(AtomicU64::new(0));
+        let overload_stats = Arc::new(OverloadStats::default());
         if config.listen {
             info!("Starting listener for session '{}'", config.name);
+
+            // A single consumer per session keeps messages in order; the bounded
+            // channel drops new messages instead of growing without limit
+            let (tx, rx) =
+                mpsc::channel::<(MidiMessage, u32, Instant)>(config.ingest_capacity.max(1));
             let processor = Arc::clone(&self.processor);
+            let session_manager = self.session_manager.clone();
+            let session_name = config.name.clone();
+            let channel_map = config.receive_channel_map.clone();
+            let overload = overload.map(|overload| {
+                OverloadMonitor::new(&config.name, overload.clone(), overload_stats.clone())
+            });
+            // Shared so a restarted consumer picks up where the last one stopped
+            let input = Arc::new(tokio::sync::Mutex::new((rx, overload)));
+            supervise(format!("session '{}' input", config.name), move || {
+                let input = Arc::clone(&input);
+                let processor = Arc::clone(&processor);
+                let session_manager = session_manager.clone();
+                let session_name = session_name.clone();
+                let channel_map = channel_map.clone();
+                async move {
+                    let mut input = input.lock().await;
+                    let (rx, overload) = &mut *input;
+                    while let Some((message, ssrc, received_at)) = rx.recv().await {
+                        if let Some(overload) = overload.as_mut()
+                            && overload.should_shed(&message)
+                        {
+                            debug!("Shedding MIDI message during overload {message:?}");
+                            continue;
+                        }
+                        session_manager
+                            .record_received(&session_name, &message)
+                            .await;
+                        if session_manager.is_echo(&session_name, ssrc, &message).await {
+                            debug!("Dropping looped MIDI message in session {message:?}");
+                            continue;
+                        }
+                        debug!("Received MIDI message in session {message:?}");
+                        let message = remap_channel(message, &channel_map);
+                        if let Err(e) = processor.process_midi_message(&session_name, message).await
+                        {
+                            error!("Error processing MIDI message: {}", e);
+                        }
+                        if let Some(overload) = overload.as_mut() {
+                            overload.observe(rx.len(), received_at.elapsed());
+                        }
+                    }
+                }
+            });
+
+            let session_name = config.name.clone();
+            let overflow = Arc::clone(&dropped);
             session
-                .add_listener(MidiMessageEvent, move |(message, _timestamp)| {
-                    // Spawn a task to handle incoming MIDI messages asynchronously
+                .add_listener(MidiMessageFromEvent, move |(message, _timestamp, ssrc)| {
+                    if tx.try_send((message, ssrc, Instant::now())).is_err() {
+                        // Warn on the first drop and then every 1000 drops
+                        let count = overflow.fetch_add(1, Ordering::Relaxed);
+                        if count.is_multiple_of(1000) {
+                            warn!(
+                                "Ingestion channel for session '{}' is full, dropped {} messages",
+                                session_name,
+                                count + 1
+                            );
+                        }
+                    }
+                })
+                .await;
+
+            // SysEx is rare and matched separately, so it gets its own consumer
+            let (sysex_tx, sysex_rx) = mpsc::channel::<Vec<u8>>(config.ingest_capacity.max(1));
+            let processor = Arc::clone(&self.processor);
+            let session_manager = self.session_manager.clone();
+            let session_name = config.name.clone();
+            let sysex_rx = Arc::new(tokio::sync::Mutex::new(sysex_rx));
+            supervise(
+                format!("session '{}' SysEx input", config.name),
+                move || {
+                    let sysex_rx = Arc::clone(&sysex_rx);
                     let processor = Arc::clone(&processor);
-                    tokio::spawn(async move {
-                        tracing::debug!("Received MIDI message in session {message:?}");
-                        if let Err(e) = processor.process_midi_message(message).await {
-                            tracing::error!("Error processing MIDI message: {}", e);
+                    let session_manager = session_manager.clone();
+                    let session_name = session_name.clone();
+                    async move {
+                        let mut sysex_rx = sysex_rx.lock().await;
+                        while let Some(data) = sysex_rx.recv().await {
+                            session_manager
+                                .record_received_sysex(&session_name, &data)
+                                .await;
+                            debug!("Received SysEx message in session ({} bytes)", data.len());
+                            if let Err(e) = processor.handle_sysex(&session_name, &data).await {
+                                error!("Error processing SysEx message: {}", e);
+                            }
+                        }
+                    }
+                },
+            );
+
+            let session_name = config.name.clone();
+            let overflow = Arc::clone(&dropped);
+            session
+                .add_listener(SysExPacketEvent, move |data: &[u8]| {
+                    if sysex_tx.try_send(data.to_vec()).is_err() {
+                        let count = overflow.fetch_add(1, Ordering::Relaxed);
+                        if count.is_multiple_of(1000) {
+                            warn!(
+                                "SysEx channel for session '{}' is full, dropped {} messages",
+                                session_name,
+                                count + 1
+                            );
                         }
-                    });
+                    }
                 })
                 .await;
         }
@@ -79,8 +228,13 @@ impl MidiRouter {
             session.invite_participant(addr).await;
         }
 
+        #[cfg(any(feature = "http", feature = "tui"))]
         self.session_manager
-            .add_session(config.name.clone(), session)
+            .add_session(config, session, ssrc, dropped, overload_stats)
+            .await;
+        #[cfg(not(any(feature = "http", feature = "tui")))]
+        self.session_manager
+            .add_session(config, session, ssrc)
             .await;
         Ok(())
     }
@@ -90,3 +244,48 @@ impl MidiRouter {
         self.session_manager.get_session_names().await
     }
 }
+
+/// Find two consecutive free UDP ports for the control and data channels of a session
+/// Move a channel message to the channel its channel is mapped to (both 1-16)
+fn remap_channel(message: MidiMessage, channel_map: &BTreeMap<u8, u8>) -> MidiMessage {
+    let remap = |channel: Channel| match channel_map.get(&(u8::from(channel) + 1)) {
+        Some(to) => Channel::new(to.saturating_sub(1) & 0x0F),
+        None => channel,
+    };
+    match message {
+        MidiMessage::NoteOff(channel, note, velocity) => {
+            MidiMessage::NoteOff(remap(channel), note, velocity)
+        }
+        MidiMessage::NoteOn(channel, note, velocity) => {
+            MidiMessage::NoteOn(remap(channel), note, velocity)
+        }
+        MidiMessage::KeyPressure(channel, note, pressure) => {
+            MidiMessage::KeyPressure(remap(channel), note, pressure)
+        }
+        MidiMessage::ControlChange(channel, controller, value) => {
+            MidiMessage::ControlChange(remap(channel), controller, value)
+        }
+        MidiMessage::ProgramChange(channel, program) => {
+            MidiMessage::ProgramChange(remap(channel), program)
+        }
+        MidiMessage::ChannelPressure(channel, pressure) => {
+            MidiMessage::ChannelPressure(remap(channel), pressure)
+        }
+        MidiMessage::PitchBendChange(channel, value) => {
+            MidiMessage::PitchBendChange(remap(channel), value)
+        }
+        other => other,
+    }
+}
+
+/// Lowest port of a free pair of consecutive UDP ports, for a session's control and MIDI ports
+pub fn free_port_pair() -> Result<u16> {
+    for _ in 0..20 {
+        let control = UdpSocket::bind("0.0.0.0:0")?;
+        let port = control.local_addr()?.port();
+        if port < u16::MAX && UdpSocket::bind(("0.0.0.0", port + 1)).is_ok() {
+            return Ok(port);
+        }
+    }
+    bail!("No free pair of consecutive UDP ports found")
+}
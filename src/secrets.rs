@@ -0,0 +1,117 @@
+use anyhow::{Context, Result, anyhow, bail};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde_json::Value;
+use std::io::Read;
+use std::str::FromStr;
+
+/// Prefix of a value encrypted to an age recipient, followed by base64 ciphertext
+const AGE_PREFIX: &str = "secret:age:";
+/// Prefix of a value stored in the OS keyring, followed by `<service>/<user>`
+const KEYRING_PREFIX: &str = "secret:keyring:";
+/// Environment variable naming the age identity file used to decrypt secrets
+pub const IDENTITY_ENV: &str = "MIDI_ROUTER_AGE_IDENTITY";
+
+/// Replace every secret reference in a config document with its plaintext.
+///
+/// Returns whether any value was replaced. Identities are only loaded once an
+/// age secret is found, so configs without secrets need no key.
+pub fn decrypt_values(value: &mut Value) -> Result<bool> {
+    let mut identities = None;
+    decrypt_in_place(value, "", &mut identities)
+}
+
+/// Whether a config document contains secret references
+pub fn contains_secrets(value: &Value) -> bool {
+    match value {
+        Value::String(text) => text.starts_with(AGE_PREFIX) || text.starts_with(KEYRING_PREFIX),
+        Value::Array(items) => items.iter().any(contains_secrets),
+        Value::Object(map) => map.values().any(contains_secrets),
+        _ => false,
+    }
+}
+
+/// Encrypt a value to an age recipient (`age1...`), returning the config string
+pub fn encrypt(recipient: &str, plaintext: &str) -> Result<String> {
+    let recipient = age::x25519::Recipient::from_str(recipient)
+        .map_err(|e| anyhow!("Invalid age recipient: {e}"))?;
+    let ciphertext = age::encrypt(&recipient, plaintext.as_bytes())?;
+    Ok(format!("{AGE_PREFIX}{}", STANDARD.encode(ciphertext)))
+}
+
+fn decrypt_in_place(
+    value: &mut Value,
+    path: &str,
+    identities: &mut Option<Vec<Box<dyn age::Identity>>>,
+) -> Result<bool> {
+    match value {
+        Value::String(text) => {
+            let plaintext = if let Some(ciphertext) = text.strip_prefix(AGE_PREFIX) {
+                let identities = match identities {
+                    Some(identities) => identities,
+                    None => identities.insert(load_identities()?),
+                };
+                decrypt_age(ciphertext, identities)
+            } else if let Some(entry) = text.strip_prefix(KEYRING_PREFIX) {
+                read_keyring(entry)
+            } else {
+                return Ok(false);
+            };
+            *text = plaintext.with_context(|| format!("Failed to decrypt secret at `{path}`"))?;
+            Ok(true)
+        }
+        Value::Array(items) => {
+            let mut replaced = false;
+            for (index, item) in items.iter_mut().enumerate() {
+                replaced |= decrypt_in_place(item, &format!("{path}[{index}]"), identities)?;
+            }
+            Ok(replaced)
+        }
+        Value::Object(map) => {
+            let mut replaced = false;
+            for (key, item) in map.iter_mut() {
+                replaced |= decrypt_in_place(item, &format!("{path}.{key}"), identities)?;
+            }
+            Ok(replaced)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Load the age identities named by the identity environment variable
+fn load_identities() -> Result<Vec<Box<dyn age::Identity>>> {
+    let path = std::env::var(IDENTITY_ENV)
+        .with_context(|| format!("Config contains age secrets but {IDENTITY_ENV} is not set"))?;
+    age::IdentityFile::from_file(path.clone())
+        .with_context(|| format!("Failed to read age identity file: {path:?}"))?
+        .into_identities()
+        .with_context(|| format!("Invalid age identity file: {path:?}"))
+}
+
+fn decrypt_age(ciphertext: &str, identities: &[Box<dyn age::Identity>]) -> Result<String> {
+    let ciphertext = STANDARD
+        .decode(ciphertext)
+        .context("Secret is not valid base64")?;
+    let decryptor = age::Decryptor::new(&ciphertext[..])?;
+    let mut reader = decryptor.decrypt(
+        identities
+            .iter()
+            .map(|identity| identity.as_ref() as &dyn age::Identity),
+    )?;
+    let mut plaintext = String::new();
+    reader.read_to_string(&mut plaintext)?;
+    Ok(plaintext)
+}
+
+#[cfg(feature = "keyring")]
+fn read_keyring(entry: &str) -> Result<String> {
+    let Some((service, user)) = entry.split_once('/') else {
+        bail!("Keyring secret must be `{KEYRING_PREFIX}<service>/<user>`");
+    };
+    Ok(keyring::Entry::new(service, user)?.get_password()?)
+}
+
+#[cfg(not(feature = "keyring"))]
+fn read_keyring(entry: &str) -> Result<String> {
+    bail!("Keyring secret '{entry}' can't be read: built without the `keyring` feature")
+}
@@ -0,0 +1,261 @@
+use crate::cli::RunArgs;
+use crate::config::ConfigLoader;
+#[cfg(feature = "osc-input")]
+use crate::context;
+use crate::mapping::MapConfig;
+#[cfg(feature = "osc-input")]
+use crate::osc_listener;
+use crate::router::free_port_pair;
+use crate::validate;
+use anyhow::{Context, Result, anyhow, bail};
+use midi_types::{Channel, MidiMessage, Note, Value7};
+use rosc::{OscMessage, OscPacket, OscType, decoder, encoder};
+use rtpmidi::packets::midi_packets::rtp_midi_message::RtpMidiMessage;
+use rtpmidi::sessions::events::event_handling::MidiMessageEvent;
+use rtpmidi::sessions::invite_responder::InviteResponder;
+use rtpmidi::sessions::rtp_midi_session::RtpMidiSession as AppleMidiSession;
+use std::fmt::Display;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// How long a remote's host name may take to resolve
+const RESOLVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long the loopback sessions may take to connect and deliver the test message
+const LOOPBACK_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Check that the configured ports can be bound, the remotes resolve, and RTP MIDI
+/// and OSC work on this machine, printing a pass/fail line per check
+pub async fn run(current: &RunArgs) -> Result<()> {
+    let mut report = Report::default();
+
+    for (device_path, map_path) in validate::config_pairs(current)? {
+        println!(
+            "Configs {} and {}:",
+            device_path.display(),
+            map_path.display()
+        );
+        let problems = validate::check(&device_path, &map_path);
+        report.record(
+            "configs load and are valid",
+            match problems.len() {
+                0 => Ok(()),
+                count => Err(anyhow!("{count} problem(s), see `validate`")),
+            },
+        );
+        if let Ok(map_config) = ConfigLoader::load_map_config(&map_path) {
+            check_ports(&mut report, &map_config);
+            check_remotes(&mut report, &map_config).await;
+        }
+    }
+
+    println!("Router:");
+    if let Some(port) = current.health_port {
+        report.record(
+            format!("health endpoint binds port {port}"),
+            std::net::TcpListener::bind(("0.0.0.0", port))
+                .map(drop)
+                .map_err(Into::into),
+        );
+    }
+    report.record(
+        "RTP MIDI loopback delivers a message",
+        rtp_midi_loopback().await,
+    );
+    report.record("OSC messages survive encode and decode", osc_round_trip());
+
+    println!("{} passed, {} failed", report.passed, report.failed);
+    if report.failed > 0 {
+        bail!("Self-test failed");
+    }
+    Ok(())
+}
+
+/// Counts of passed and failed checks
+#[derive(Default)]
+struct Report {
+    passed: usize,
+    failed: usize,
+}
+
+impl Report {
+    fn record(&mut self, check: impl Display, result: Result<()>) {
+        match result {
+            Ok(()) => {
+                self.passed += 1;
+                println!("  PASS  {check}");
+            }
+            Err(e) => {
+                self.failed += 1;
+                println!("  FAIL  {check}: {e:#}");
+            }
+        }
+    }
+}
+
+/// Bind the ports of the sessions and OSC sources that would be started
+fn check_ports(report: &mut Report, map_config: &MapConfig) {
+    for session in map_config.rtp_midi_sessions.iter().filter(|s| s.enabled) {
+        if session.send_only && session.port == 0 {
+            continue;
+        }
+        let port = session.port;
+        report.record(
+            format!(
+                "RTP MIDI session '{}' binds ports {}-{}",
+                session.name,
+                port,
+                port.saturating_add(1)
+            ),
+            bind_pair(port),
+        );
+    }
+
+    // OSC sources aren't started without the listeners
+    #[cfg(feature = "osc-input")]
+    for source in context::started_osc_sources(map_config) {
+        report.record(
+            format!("OSC source '{}' binds port {}", source.name, source.port),
+            osc_listener::bind(&source)
+                .map_err(Into::into)
+                .and_then(|(_, port)| match port {
+                    port if port == source.port => Ok(()),
+                    port => Err(anyhow!("port in use, would fall back to {port}")),
+                }),
+        );
+    }
+}
+
+/// Bind a session's control and MIDI ports
+fn bind_pair(port: u16) -> Result<()> {
+    if port == 0 {
+        bail!("no port configured");
+    }
+    for port in [port, port.saturating_add(1)] {
+        UdpSocket::bind(("0.0.0.0", port)).with_context(|| format!("port {port}"))?;
+    }
+    Ok(())
+}
+
+/// Resolve the RTP MIDI remotes and OSC destinations
+async fn check_remotes(report: &mut Report, map_config: &MapConfig) {
+    for session in map_config.rtp_midi_sessions.iter().filter(|s| s.enabled) {
+        for remote in &session.connect_to {
+            let address = format!("{}:{}", remote.host, remote.port);
+            report.record(
+                format!(
+                    "remote '{}' of session '{}' resolves ({})",
+                    remote.name, session.name, address
+                ),
+                resolve(&address).await,
+            );
+        }
+    }
+
+    let mut destinations: Vec<_> = map_config.osc_destinations.iter().collect();
+    destinations.sort_by_key(|(name, _)| *name);
+    for (name, destination) in destinations {
+        for address in destination.addresses() {
+            report.record(
+                format!("OSC destination '{name}' resolves ({address})"),
+                resolve(&address).await,
+            );
+        }
+    }
+}
+
+async fn resolve(address: &str) -> Result<()> {
+    let mut addrs = tokio::time::timeout(RESOLVE_TIMEOUT, tokio::net::lookup_host(address))
+        .await
+        .map_err(|_| anyhow!("timed out"))??;
+    addrs
+        .next()
+        .map(drop)
+        .ok_or_else(|| anyhow!("no addresses found"))
+}
+
+/// Connect two sessions on free local ports and send a Note On from one to the other
+async fn rtp_midi_loopback() -> Result<()> {
+    let receiver_port = free_port_pair()?;
+    let receiver = AppleMidiSession::start(
+        receiver_port,
+        "Self-test In",
+        0x5E1F_7E51,
+        InviteResponder::Accept,
+    )
+    .await?;
+    let sender = AppleMidiSession::start(
+        free_port_pair()?,
+        "Self-test Out",
+        0x5E1F_7E52,
+        InviteResponder::Accept,
+    )
+    .await?;
+
+    let result = loopback(&receiver, receiver_port, &sender).await;
+    sender.stop_gracefully().await;
+    receiver.stop_gracefully().await;
+    result
+}
+
+async fn loopback(
+    receiver: &AppleMidiSession,
+    receiver_port: u16,
+    sender: &AppleMidiSession,
+) -> Result<()> {
+    let (tx, mut rx) = mpsc::channel(16);
+    receiver
+        .add_listener(MidiMessageEvent, move |(message, _timestamp)| {
+            let _ = tx.try_send(message);
+        })
+        .await;
+
+    sender
+        .invite_participant(SocketAddr::from((Ipv4Addr::LOCALHOST, receiver_port)))
+        .await;
+
+    let expected = MidiMessage::NoteOn(Channel::new(15), Note::new(60), Value7::new(1));
+    tokio::time::timeout(LOOPBACK_TIMEOUT, async {
+        while sender.participants().await.is_empty() {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        sender
+            .send_midi(&RtpMidiMessage::MidiMessage(expected))
+            .await?;
+        while let Some(message) = rx.recv().await {
+            if message == expected {
+                return Ok(());
+            }
+        }
+        bail!("receiving session stopped")
+    })
+    .await
+    .map_err(|_| anyhow!("timed out"))?
+}
+
+/// Send a message with every argument type the router uses through a local socket
+/// and check it decodes to the same message
+fn osc_round_trip() -> Result<()> {
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/selftest/round-trip".to_string(),
+        args: vec![
+            OscType::Int(-42),
+            OscType::Float(120.5),
+            OscType::String("Self-test".to_string()),
+            OscType::Bool(true),
+            OscType::Double(0.25),
+        ],
+    });
+
+    let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))?;
+    socket.set_read_timeout(Some(LOOPBACK_TIMEOUT))?;
+    socket.send_to(&encoder::encode(&packet)?, socket.local_addr()?)?;
+    let mut buf = [0u8; decoder::MTU];
+    let size = socket.recv(&mut buf).context("no packet received")?;
+    let (_, decoded) = decoder::decode_udp(&buf[..size])?;
+    if decoded != packet {
+        bail!("sent {packet:?} but decoded {decoded:?}");
+    }
+    Ok(())
+}
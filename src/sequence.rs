@@ -0,0 +1,149 @@
+use crate::device::SequenceStep;
+#[cfg(feature = "osc-input")]
+use crate::osc_match::address_matches;
+use crate::processor::MidiProcessor;
+use crate::supervisor::supervise;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+
+/// How often running sequences are checked for steps whose barrier has passed
+const TICK_INTERVAL: Duration = Duration::from_millis(5);
+
+/// A program's sequence in progress
+struct RunningSequence {
+    /// Program that started the sequence, for logs and the audit log
+    program: String,
+    steps: Vec<SequenceStep>,
+    /// Index of the step waiting at its barrier
+    next: usize,
+    /// When the barrier times out; `None` while the previous step is being sent
+    due: Option<Instant>,
+    /// Whether the confirmation of the previous step has arrived
+    confirmed: bool,
+}
+
+/// A sequence step whose barrier has passed
+pub struct DueStep {
+    /// Device whose program started the sequence
+    pub key: String,
+    /// Program that started the sequence
+    pub program: String,
+    /// Position of the step in the sequence
+    pub index: usize,
+    pub step: SequenceStep,
+}
+
+/// Sequences of programs waiting at their barriers, one per device that started one
+#[derive(Default)]
+pub struct Sequences {
+    running: HashMap<String, RunningSequence>,
+}
+
+impl Sequences {
+    /// Start a sequence once the program that declares it has been sent, returning the
+    /// name of the program whose unfinished sequence it replaces
+    pub fn start(
+        &mut self,
+        key: &str,
+        program: &str,
+        steps: &[SequenceStep],
+        now: Instant,
+    ) -> Option<String> {
+        let first = steps.first()?;
+        let sequence = RunningSequence {
+            program: program.to_string(),
+            steps: steps.to_vec(),
+            next: 0,
+            due: Some(now + Duration::from_millis(first.barrier.timeout_ms)),
+            confirmed: false,
+        };
+        // Each mapping of the device runs the program, so only report other sequences
+        self.running
+            .insert(key.to_string(), sequence)
+            .filter(|replaced| replaced.program != program || replaced.next > 0)
+            .map(|replaced| replaced.program)
+    }
+
+    /// Release the barriers waiting for a confirmation with this address
+    #[cfg(feature = "osc-input")]
+    pub fn confirm(&mut self, address: &str) {
+        for sequence in self.running.values_mut() {
+            let barrier = &sequence.steps[sequence.next].barrier;
+            if barrier
+                .confirm_address
+                .as_ref()
+                .is_some_and(|pattern| address_matches(pattern, address))
+            {
+                sequence.confirmed = true;
+            }
+        }
+    }
+
+    /// Remove and return the steps whose barrier has passed. Their sequences wait for
+    /// `sent` before the next barrier starts.
+    pub fn take_due(&mut self, now: Instant) -> Vec<DueStep> {
+        let mut due = Vec::new();
+        for (key, sequence) in &mut self.running {
+            let Some(at) = sequence.due else {
+                continue;
+            };
+            if !sequence.confirmed && at > now {
+                continue;
+            }
+            due.push(DueStep {
+                key: key.clone(),
+                program: sequence.program.clone(),
+                index: sequence.next,
+                step: sequence.steps[sequence.next].clone(),
+            });
+            sequence.next += 1;
+            sequence.due = None;
+            // A confirmation may arrive while the step is still being sent
+            sequence.confirmed = false;
+        }
+        self.running
+            .retain(|_, sequence| sequence.next < sequence.steps.len());
+        due
+    }
+
+    /// Start the barrier of the step after one that has been sent
+    pub fn sent(&mut self, key: &str, now: Instant) {
+        if let Some(sequence) = self.running.get_mut(key)
+            && sequence.due.is_none()
+        {
+            let timeout = sequence.steps[sequence.next].barrier.timeout_ms;
+            sequence.due = Some(now + Duration::from_millis(timeout));
+        }
+    }
+}
+
+/// Runs sequence steps once their barriers have passed
+pub struct SequenceMonitor {
+    processor: Arc<MidiProcessor>,
+}
+
+impl SequenceMonitor {
+    pub fn new(processor: Arc<MidiProcessor>) -> Self {
+        Self { processor }
+    }
+
+    /// Start running sequence steps in the background
+    pub fn start(&self) -> JoinHandle<()> {
+        let processor = Arc::clone(&self.processor);
+
+        supervise("sequences", move || {
+            let processor = Arc::clone(&processor);
+            async move {
+                let mut interval = tokio::time::interval(TICK_INTERVAL);
+                interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+                loop {
+                    interval.tick().await;
+                    processor.run_due_sequence_steps().await;
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,39 @@
+use crate::mapping::StepMapping;
+use std::collections::HashMap;
+
+/// Tracks the current position of each step mapping
+pub struct StepSequencer {
+    /// Index of the last executed step keyed by step mapping name
+    positions: HashMap<String, usize>,
+}
+
+impl StepSequencer {
+    pub fn new() -> Self {
+        Self {
+            positions: HashMap::new(),
+        }
+    }
+
+    /// Move to the next step of a mapping and return its index
+    pub fn advance(&mut self, mapping: &StepMapping) -> Option<usize> {
+        if mapping.steps.is_empty() {
+            return None;
+        }
+
+        let last_step = mapping.steps.len() - 1;
+        let next = match self.positions.get(&mapping.name) {
+            None => 0,
+            Some(&current) if current < last_step => current + 1,
+            Some(_) if mapping.wrap => 0,
+            Some(_) => last_step,
+        };
+
+        self.positions.insert(mapping.name.clone(), next);
+        Some(next)
+    }
+
+    /// Return a mapping to its first step on the next activation
+    pub fn reset(&mut self, name: &str) {
+        self.positions.remove(name);
+    }
+}
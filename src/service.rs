@@ -0,0 +1,251 @@
+use anyhow::Result;
+use clap::Args;
+use std::path::PathBuf;
+#[cfg(windows)]
+use {crate::cli::RunArgs, std::sync::OnceLock};
+
+/// Service name used for launchd and the Windows service control manager
+#[cfg_attr(not(any(target_os = "macos", windows)), allow(dead_code))]
+const SERVICE_NAME: &str = "midi-router";
+
+/// Options for `install-service`
+#[derive(Debug, Clone, Args)]
+pub struct InstallServiceArgs {
+    /// Directory containing the `config` folder (defaults to the current directory)
+    #[arg(long)]
+    pub working_dir: Option<PathBuf>,
+    /// Write the launchd plist to this path instead of ~/Library/LaunchAgents
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+}
+
+/// Options for `run-service`
+#[cfg(windows)]
+#[derive(Debug, Clone, Args)]
+pub struct RunServiceArgs {
+    /// Directory containing the `config` folder
+    #[arg(long)]
+    pub working_dir: Option<PathBuf>,
+}
+
+/// Install the router as a boot-time service for the current platform
+pub fn install(args: &InstallServiceArgs) -> Result<()> {
+    let working_dir = match args.working_dir {
+        Some(ref dir) => dir.canonicalize()?,
+        None => std::env::current_dir()?,
+    };
+    let executable = std::env::current_exe()?;
+
+    platform::install(args, &executable, &working_dir)
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::{InstallServiceArgs, SERVICE_NAME};
+    use anyhow::{Context, Result};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use tracing::info;
+
+    /// launchd job label
+    const LAUNCHD_LABEL: &str = "com.ikadmium.midi-router";
+
+    pub fn install(args: &InstallServiceArgs, executable: &Path, working_dir: &Path) -> Result<()> {
+        let output = match args.output {
+            Some(ref path) => path.clone(),
+            None => {
+                let home = std::env::var("HOME").context("HOME is not set")?;
+                PathBuf::from(home)
+                    .join("Library/LaunchAgents")
+                    .join(format!("{LAUNCHD_LABEL}.plist"))
+            }
+        };
+
+        if let Some(parent) = output.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&output, launchd_plist(executable, working_dir))
+            .with_context(|| format!("Failed to write launchd plist: {output:?}"))?;
+
+        info!("Wrote launchd plist for '{}' to {:?}", SERVICE_NAME, output);
+        println!("Load it with: launchctl load -w {}", output.display());
+        Ok(())
+    }
+
+    fn launchd_plist(executable: &Path, working_dir: &Path) -> String {
+        let log_path = working_dir.join(format!("{SERVICE_NAME}.log"));
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{label}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{executable}</string>
+    </array>
+    <key>WorkingDirectory</key>
+    <string>{working_dir}</string>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>KeepAlive</key>
+    <true/>
+    <key>StandardOutPath</key>
+    <string>{log}</string>
+    <key>StandardErrorPath</key>
+    <string>{log}</string>
+</dict>
+</plist>
+"#,
+            label = LAUNCHD_LABEL,
+            executable = xml_escape(&executable.display().to_string()),
+            working_dir = xml_escape(&working_dir.display().to_string()),
+            log = xml_escape(&log_path.display().to_string()),
+        )
+    }
+
+    fn xml_escape(value: &str) -> String {
+        value
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::{InstallServiceArgs, SERVICE_NAME};
+    use anyhow::Result;
+    use std::ffi::OsString;
+    use std::path::Path;
+    use tracing::info;
+    use windows_service::service::{
+        ServiceAccess, ServiceErrorControl, ServiceInfo, ServiceStartType, ServiceType,
+    };
+    use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+
+    pub fn install(
+        _args: &InstallServiceArgs,
+        executable: &Path,
+        working_dir: &Path,
+    ) -> Result<()> {
+        let manager = ServiceManager::local_computer(
+            None::<&str>,
+            ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE,
+        )?;
+
+        let info = ServiceInfo {
+            name: OsString::from(SERVICE_NAME),
+            display_name: OsString::from("MIDI Router"),
+            service_type: ServiceType::OWN_PROCESS,
+            start_type: ServiceStartType::AutoStart,
+            error_control: ServiceErrorControl::Normal,
+            executable_path: executable.to_path_buf(),
+            launch_arguments: vec![
+                OsString::from("run-service"),
+                OsString::from("--working-dir"),
+                working_dir.as_os_str().to_os_string(),
+            ],
+            dependencies: vec![],
+            account_name: None,
+            account_password: None,
+        };
+        manager.create_service(&info, ServiceAccess::QUERY_STATUS)?;
+
+        info!("Installed Windows service '{}'", SERVICE_NAME);
+        println!("Start it with: sc start {SERVICE_NAME}");
+        Ok(())
+    }
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+mod platform {
+    use super::InstallServiceArgs;
+    use anyhow::{Result, bail};
+    use std::path::Path;
+
+    pub fn install(
+        _args: &InstallServiceArgs,
+        _executable: &Path,
+        _working_dir: &Path,
+    ) -> Result<()> {
+        bail!("install-service supports macOS and Windows; use a systemd unit on Linux")
+    }
+}
+
+/// Run options handed from `main` to the service entry point
+#[cfg(windows)]
+static SERVICE_RUN_ARGS: OnceLock<RunArgs> = OnceLock::new();
+
+/// Run the router under the Windows service control manager
+#[cfg(windows)]
+pub fn run_windows_service(args: &RunServiceArgs, run_args: RunArgs) -> Result<()> {
+    if let Some(ref dir) = args.working_dir {
+        std::env::set_current_dir(dir)?;
+    }
+    let _ = SERVICE_RUN_ARGS.set(run_args);
+    windows_service::service_dispatcher::start(SERVICE_NAME, ffi_service_main)?;
+    Ok(())
+}
+
+#[cfg(windows)]
+windows_service::define_windows_service!(ffi_service_main, service_main);
+
+#[cfg(windows)]
+fn service_main(_arguments: Vec<std::ffi::OsString>) {
+    if let Err(e) = run_service() {
+        tracing::error!("Windows service failed: {e}");
+    }
+}
+
+#[cfg(windows)]
+fn run_service() -> Result<()> {
+    use std::time::Duration;
+    use windows_service::service::{
+        ServiceControl, ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus,
+        ServiceType,
+    };
+    use windows_service::service_control_handler::{self, ServiceControlHandlerResult};
+
+    let (shutdown_tx, mut shutdown_rx) = tokio::sync::watch::channel(false);
+
+    let status_handle =
+        service_control_handler::register(SERVICE_NAME, move |control| match control {
+            ServiceControl::Stop | ServiceControl::Shutdown => {
+                let _ = shutdown_tx.send(true);
+                ServiceControlHandlerResult::NoError
+            }
+            ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+            _ => ServiceControlHandlerResult::NotImplemented,
+        })?;
+
+    let status = |state: ServiceState, controls: ServiceControlAccept| ServiceStatus {
+        service_type: ServiceType::OWN_PROCESS,
+        current_state: state,
+        controls_accepted: controls,
+        exit_code: ServiceExitCode::Win32(0),
+        checkpoint: 0,
+        wait_hint: Duration::default(),
+        process_id: None,
+    };
+
+    status_handle.set_service_status(status(
+        ServiceState::Running,
+        ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+    ))?;
+
+    let run_args = SERVICE_RUN_ARGS
+        .get()
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("Service started without run options"))?;
+    let runtime = tokio::runtime::Runtime::new()?;
+    let result = runtime.block_on(crate::run(&run_args, async move {
+        let _ = shutdown_rx.wait_for(|stop| *stop).await;
+    }));
+
+    status_handle
+        .set_service_status(status(ServiceState::Stopped, ServiceControlAccept::empty()))?;
+
+    result
+}
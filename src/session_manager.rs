@@ -1,27 +1,573 @@
+use crate::freeze::OutputFreeze;
+use crate::loop_guard::LoopGuard;
+use crate::mapping::{ClockSyncRole, InviteRetry, OfflinePolicy, RtpMidiSession, ValueClamp};
+#[cfg(any(feature = "http", feature = "tui"))]
+use crate::overload::OverloadStats;
+use crate::pacing::Pacer;
+#[cfg(any(feature = "http", feature = "tui"))]
+use crate::remote_health::RemoteStatus;
+use crate::remote_health::{self, RemoteHealth};
+use crate::sniff::{Direction, Sniffer};
+use crate::supervisor::supervise;
+use crate::virtual_ports::VirtualPort;
+use crate::wire;
 use anyhow::Result;
-use midi_types::MidiMessage;
+#[cfg(any(feature = "http", feature = "osc-input"))]
+use anyhow::bail;
+use midi_types::{MidiMessage, Value7};
 use rtpmidi::packets::midi_packets::rtp_midi_message::RtpMidiMessage;
 use rtpmidi::sessions::rtp_midi_session::RtpMidiSession as AppleMidiSession;
-use std::collections::HashMap;
+#[cfg(any(feature = "http", feature = "tui"))]
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
 use std::sync::Arc;
-use tokio::sync::RwLock;
-use tracing::{info, warn};
+#[cfg(any(feature = "http", feature = "tui"))]
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::{RwLock, broadcast};
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, trace, warn};
+
+/// How often participant counts are polled
+const PARTICIPANT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How often the router starts a CK exchange with a participant when it drives clock sync
+const CLOCK_SYNC_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Port assumed for a `clock_master` address without one
+const DEFAULT_RTP_MIDI_PORT: u16 = 5004;
+
+/// Key identifying which queued message a newer one supersedes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum QueueKey {
+    ProgramChange { channel: u8 },
+    ControlChange { channel: u8, controller: u8 },
+}
+
+impl QueueKey {
+    /// Queue key for state-relevant messages; other messages are not queued
+    fn for_message(message: &MidiMessage) -> Option<Self> {
+        match *message {
+            MidiMessage::ProgramChange(channel, _) => Some(QueueKey::ProgramChange {
+                channel: channel.into(),
+            }),
+            MidiMessage::ControlChange(channel, controller, _) => Some(QueueKey::ControlChange {
+                channel: channel.into(),
+                controller: controller.into(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// A message held back until it can be sent in order
+#[derive(Debug, Clone)]
+struct QueuedMidi {
+    /// Position in the session's send order
+    sequence: u64,
+    /// Key of state-relevant messages, which newer messages supersede
+    key: Option<QueueKey>,
+    message: MidiMessage,
+}
+
+/// Traffic and connection details tracked for a session
+#[derive(Debug, Clone)]
+struct SessionState {
+    #[cfg(any(feature = "http", feature = "tui"))]
+    port: u16,
+    #[cfg(any(feature = "http", feature = "tui"))]
+    listen: bool,
+    #[cfg(any(feature = "http", feature = "tui"))]
+    remotes: Vec<String>,
+    messages_sent: u64,
+    messages_received: u64,
+    last_received: Option<Instant>,
+    offline_policy: OfflinePolicy,
+    participants: usize,
+    queued: Vec<QueuedMidi>,
+    /// Sequence number of the next outgoing message
+    next_sequence: u64,
+    /// Whether queued messages are being replayed; new messages queue behind them
+    flushing: bool,
+    /// Incoming messages dropped because the ingestion channel was full
+    #[cfg(any(feature = "http", feature = "tui"))]
+    dropped: Arc<AtomicU64>,
+    /// Overload state and messages shed, if overload detection is configured
+    #[cfg(any(feature = "http", feature = "tui"))]
+    overload: Arc<OverloadStats>,
+    /// Minimum gap between outgoing messages
+    pacer: Arc<Pacer>,
+    /// Raw byte logging, toggled at runtime
+    sniffer: Arc<Sniffer>,
+    /// Synchronization source identifier of this end of the session
+    #[cfg(any(feature = "http", feature = "tui"))]
+    ssrc: u32,
+    /// Incoming messages dropped as echoes of messages sent by this router
+    echoes_dropped: u64,
+    /// Limits on controller values sent
+    value_clamps: Vec<ValueClamp>,
+    /// Control Changes sent with a clamped value
+    values_clamped: u64,
+    /// Clock sync health of each remote in `connect_to`
+    remote_health: Vec<RemoteHealth>,
+    /// Time without a CK exchange after which a remote is considered dead
+    clock_sync_timeout: Duration,
+    invite_retry: InviteRetry,
+    clock_sync_role: ClockSyncRole,
+    /// Configured preferred clock master and its resolved address
+    clock_master: Option<(String, Option<SocketAddr>)>,
+    /// Time the router last started a CK exchange with each participant
+    clock_sync_sent: HashMap<SocketAddr, Instant>,
+    resend_on_rejoin: bool,
+    /// Last Program Change per channel and Control Change per controller sent
+    sent_state: Vec<(QueueKey, MidiMessage)>,
+    /// Hosts that have been participants, and those that are now
+    known_hosts: HashSet<IpAddr>,
+    present_hosts: HashSet<IpAddr>,
+}
+
+impl SessionState {
+    /// Remember the latest state-relevant message for resending after a rejoin
+    fn record_state(&mut self, message: MidiMessage) {
+        if !self.resend_on_rejoin {
+            return;
+        }
+        if let Some(key) = QueueKey::for_message(&message) {
+            self.sent_state.retain(|(sent, _)| *sent != key);
+            self.sent_state.push((key, message));
+        }
+    }
+
+    /// Whether the router starts clock sync itself instead of leaving it to the library
+    fn drives_clock_sync(&self) -> bool {
+        self.clock_sync_role != ClockSyncRole::Auto || self.clock_master.is_some()
+    }
+
+    /// Whether the router should start CK exchanges with a participant
+    fn starts_clock_sync(&self, participant: &SocketAddr) -> bool {
+        let is_master = matches!(
+            self.clock_master,
+            Some((_, Some(master))) if remote_health::is_peer(master, participant)
+        );
+        if is_master {
+            return false;
+        }
+        match self.clock_sync_role {
+            ClockSyncRole::Master => true,
+            ClockSyncRole::Slave => false,
+            // The inviting end starts clock sync
+            ClockSyncRole::Auto => self
+                .remote_health
+                .iter()
+                .any(|remote| remote.matches(participant)),
+        }
+    }
+}
+
+/// Snapshot of a session's state for status reporting
+#[cfg(any(feature = "http", feature = "tui"))]
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SessionStatus {
+    /// Session name
+    pub name: String,
+    /// Local port the session is bound to
+    pub port: u16,
+    /// Synchronization source identifier of this router's end of the session
+    pub ssrc: u32,
+    /// Whether the session is bound
+    pub bound: bool,
+    /// Whether incoming messages are processed
+    pub listen: bool,
+    /// Remote sessions invited on startup
+    pub remotes: Vec<String>,
+    /// Connection and clock sync health of each invited remote
+    pub remote_health: Vec<RemoteStatus>,
+    /// Which end starts clock sync with participants
+    pub clock_sync_role: ClockSyncRole,
+    /// Remote clock sync is left to, if configured
+    pub clock_master: Option<String>,
+    /// Number of connected participants
+    pub participants: usize,
+    /// Messages waiting for a participant to connect
+    pub queued_messages: usize,
+    /// Messages sent through the session
+    pub messages_sent: u64,
+    /// Messages received on the session
+    pub messages_received: u64,
+    /// Incoming messages dropped because processing fell behind
+    pub messages_dropped: u64,
+    /// Incoming messages dropped as echoes of messages this router sent
+    pub echoes_dropped: u64,
+    /// Control Changes sent with a value clamped to the session's `value_clamps`
+    pub values_clamped: u64,
+    /// Outgoing messages that waited for the session's `shaping` rate limit
+    pub shaping_delayed: u64,
+    /// Outgoing messages dropped because `shaping` held them back too long
+    pub shaping_dropped: u64,
+    /// Whether raw bytes of the session's messages are being logged
+    pub sniff: bool,
+    /// Whether incoming message processing is overloaded
+    pub overloaded: bool,
+    /// Incoming messages dropped by the overload shed policy
+    pub messages_shed: u64,
+    /// Milliseconds since the last message was received
+    pub last_received_ms_ago: Option<u64>,
+}
 
 /// Shared session manager that can be used by both router and processor
 pub struct SessionManager {
     sessions: Arc<RwLock<HashMap<String, Arc<AppleMidiSession>>>>,
+    states: Arc<RwLock<HashMap<String, SessionState>>>,
+    // Alias name -> session name
+    aliases: Arc<RwLock<HashMap<String, String>>>,
+    // Own SSRCs and recently sent messages, when loop suppression is enabled
+    loop_guard: Arc<tokio::sync::Mutex<Option<LoopGuard>>>,
+    // Names of sessions a participant rejoined, for resending state
+    rejoins: broadcast::Sender<String>,
+    // Virtual MIDI ports, addressed like sessions
+    virtual_ports: Arc<RwLock<HashMap<String, Arc<VirtualPort>>>>,
+    // Switch that drops outgoing messages
+    output_freeze: OutputFreeze,
+    // Sessions whose ports could not be bound
+    #[cfg(any(feature = "http", feature = "tui"))]
+    unbound: Arc<RwLock<Vec<SessionStatus>>>,
 }
 
 impl SessionManager {
-    pub fn new() -> Self {
+    pub fn new(output_freeze: OutputFreeze) -> Self {
         Self {
             sessions: Arc::new(RwLock::new(HashMap::new())),
+            states: Arc::new(RwLock::new(HashMap::new())),
+            aliases: Arc::new(RwLock::new(HashMap::new())),
+            loop_guard: Arc::new(tokio::sync::Mutex::new(None)),
+            rejoins: broadcast::channel(16).0,
+            virtual_ports: Arc::new(RwLock::new(HashMap::new())),
+            output_freeze,
+            #[cfg(any(feature = "http", feature = "tui"))]
+            unbound: Arc::new(RwLock::new(Vec::new())),
+        }
+    }
+
+    /// Receive the names of sessions with `resend_on_rejoin` that a participant rejoined
+    pub fn subscribe_rejoins(&self) -> broadcast::Receiver<String> {
+        self.rejoins.subscribe()
+    }
+
+    /// Drop incoming messages from this router's own sessions, and ones that echo a
+    /// message sent to the same session within the window
+    pub async fn set_loop_suppression(&self, window: Duration) {
+        info!(
+            "Suppressing looped messages within {}ms",
+            window.as_millis()
+        );
+        *self.loop_guard.lock().await = Some(LoopGuard::new(window));
+    }
+
+    /// Whether an incoming message from the sender SSRC is one this router sent, counting it if so
+    pub async fn is_echo(&self, session_name: &str, ssrc: u32, message: &MidiMessage) -> bool {
+        let echo = match *self.loop_guard.lock().await {
+            Some(ref mut guard) => guard.is_own(ssrc) || guard.is_echo(session_name, message),
+            None => false,
+        };
+        if echo && let Some(state) = self.states.write().await.get_mut(session_name) {
+            state.echoes_dropped += 1;
+        }
+        echo
+    }
+
+    /// Remember a message sent to a session for loop suppression
+    async fn record_sent(&self, session_name: &str, message: MidiMessage) {
+        if let Some(ref mut guard) = *self.loop_guard.lock().await {
+            guard.record_sent(session_name, message);
+        }
+    }
+
+    pub async fn add_session(
+        &self,
+        config: &RtpMidiSession,
+        session: Arc<AppleMidiSession>,
+        ssrc: u32,
+        #[cfg(any(feature = "http", feature = "tui"))] dropped: Arc<AtomicU64>,
+        #[cfg(any(feature = "http", feature = "tui"))] overload: Arc<OverloadStats>,
+    ) {
+        let clock_master = config.clock_master.as_ref().map(|master| {
+            let addr = resolve_clock_master(config, master);
+            if addr.is_none() {
+                warn!(
+                    "Clock master '{}' of session '{}' could not be resolved",
+                    master, config.name
+                );
+            }
+            (master.clone(), addr)
+        });
+        if config.clock_sync_role != ClockSyncRole::Auto || clock_master.is_some() {
+            info!(
+                "Session '{}' clock sync role: {:?}{}",
+                config.name,
+                config.clock_sync_role,
+                clock_master
+                    .as_ref()
+                    .map(|(master, _)| format!(", clock master '{master}'"))
+                    .unwrap_or_default()
+            );
+            // CK exchanges are started by `poll_participants` instead
+            session.set_auto_clock_sync(false).await;
+        }
+
+        if let Some(ref mut guard) = *self.loop_guard.lock().await {
+            guard.add_own_ssrc(ssrc);
+        }
+        let mut sessions = self.sessions.write().await;
+        sessions.insert(config.name.clone(), session);
+
+        let state = SessionState {
+            #[cfg(any(feature = "http", feature = "tui"))]
+            port: config.port,
+            #[cfg(any(feature = "http", feature = "tui"))]
+            listen: config.listen,
+            #[cfg(any(feature = "http", feature = "tui"))]
+            remotes: config
+                .connect_to
+                .iter()
+                .map(|remote| format!("{} ({}:{})", remote.name, remote.host, remote.port))
+                .collect(),
+            messages_sent: 0,
+            messages_received: 0,
+            last_received: None,
+            offline_policy: config.offline_policy,
+            participants: 0,
+            queued: Vec::new(),
+            next_sequence: 0,
+            flushing: false,
+            #[cfg(any(feature = "http", feature = "tui"))]
+            dropped,
+            #[cfg(any(feature = "http", feature = "tui"))]
+            overload,
+            pacer: Arc::new(Pacer::new(
+                Duration::from_millis(config.min_message_gap_ms),
+                config.shaping.clone(),
+            )),
+            sniffer: Arc::new(Sniffer::new(config.sniff)),
+            #[cfg(any(feature = "http", feature = "tui"))]
+            ssrc,
+            echoes_dropped: 0,
+            value_clamps: config.value_clamps.clone(),
+            values_clamped: 0,
+            remote_health: config
+                .connect_to
+                .iter()
+                .map(|remote| {
+                    RemoteHealth::new(
+                        &remote.name,
+                        &remote.host,
+                        remote.port,
+                        &config.invite_retry,
+                    )
+                })
+                .collect(),
+            clock_sync_timeout: Duration::from_secs(config.clock_sync_timeout_secs),
+            invite_retry: config.invite_retry.clone(),
+            clock_sync_role: config.clock_sync_role,
+            clock_master,
+            clock_sync_sent: HashMap::new(),
+            resend_on_rejoin: config.resend_on_rejoin,
+            sent_state: Vec::new(),
+            known_hosts: HashSet::new(),
+            present_hosts: HashSet::new(),
+        };
+        self.states.write().await.insert(config.name.clone(), state);
+
+        let mut aliases = self.aliases.write().await;
+        for alias in &config.aliases {
+            aliases.insert(alias.clone(), config.name.clone());
         }
     }
 
-    pub async fn add_session(&self, name: String, session: Arc<AppleMidiSession>) {
+    /// Resolve a session name or alias to the session name
+    pub async fn resolve(&self, name: &str) -> String {
+        if self.sessions.read().await.contains_key(name) {
+            return name.to_string();
+        }
+        self.aliases
+            .read()
+            .await
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| name.to_string())
+    }
+
+    /// Whether a session or virtual port with the given name or alias exists
+    pub async fn has_session(&self, session_name: &str) -> bool {
+        let session_name = self.resolve(session_name).await;
+        self.sessions.read().await.contains_key(&session_name)
+            || self.virtual_ports.read().await.contains_key(&session_name)
+    }
+
+    /// Make a virtual port reachable under its name
+    #[cfg(all(unix, feature = "virtual-ports"))]
+    pub async fn add_virtual_port(&self, name: &str, port: VirtualPort) {
+        self.virtual_ports
+            .write()
+            .await
+            .insert(name.to_string(), Arc::new(port));
+    }
+
+    async fn virtual_port(&self, name: &str) -> Option<Arc<VirtualPort>> {
+        self.virtual_ports.read().await.get(name).cloned()
+    }
+
+    /// Rename a session at runtime, returning false if there is no such session; the
+    /// old name stays usable as an alias
+    #[cfg(any(feature = "http", feature = "osc-input"))]
+    pub async fn rename_session(&self, old_name: &str, new_name: &str) -> Result<bool> {
+        let old_name = self.resolve(old_name).await;
         let mut sessions = self.sessions.write().await;
-        sessions.insert(name, session);
+        if !sessions.contains_key(&old_name) {
+            return Ok(false);
+        }
+        if sessions.contains_key(new_name) {
+            bail!("A session named '{new_name}' already exists");
+        }
+        if let Some(session) = sessions.remove(&old_name) {
+            sessions.insert(new_name.to_string(), session);
+        }
+
+        let mut states = self.states.write().await;
+        if let Some(state) = states.remove(&old_name) {
+            states.insert(new_name.to_string(), state);
+        }
+
+        let mut aliases = self.aliases.write().await;
+        aliases.remove(new_name);
+        for target in aliases.values_mut() {
+            if *target == old_name {
+                *target = new_name.to_string();
+            }
+        }
+        aliases.insert(old_name.clone(), new_name.to_string());
+
+        info!("Renamed session '{}' to '{}'", old_name, new_name);
+        Ok(true)
+    }
+
+    /// Add an alias for a session at runtime, returning false if there is no such session
+    #[cfg(any(feature = "http", feature = "osc-input"))]
+    pub async fn add_alias(&self, session_name: &str, alias: &str) -> bool {
+        let session_name = self.resolve(session_name).await;
+        if !self.sessions.read().await.contains_key(&session_name) {
+            return false;
+        }
+        self.aliases
+            .write()
+            .await
+            .insert(alias.to_string(), session_name.clone());
+
+        info!("Added alias '{}' for session '{}'", alias, session_name);
+        true
+    }
+
+    /// Record that a message was received on a session
+    pub async fn record_received(&self, session_name: &str, message: &MidiMessage) {
+        let session_name = self.resolve(session_name).await;
+        if let Some(state) = self.states.write().await.get_mut(&session_name) {
+            state.messages_received += 1;
+            state.last_received = Some(Instant::now());
+            state
+                .sniffer
+                .midi(&session_name, Direction::Received, message);
+        }
+    }
+
+    /// Record that a SysEx message was received on a session
+    pub async fn record_received_sysex(&self, session_name: &str, data: &[u8]) {
+        let session_name = self.resolve(session_name).await;
+        if let Some(state) = self.states.write().await.get_mut(&session_name) {
+            state.messages_received += 1;
+            state.last_received = Some(Instant::now());
+            state
+                .sniffer
+                .sysex(&session_name, Direction::Received, data);
+        }
+    }
+
+    /// Start or stop logging a session's raw bytes, returning false if the session is unknown
+    #[cfg(feature = "http")]
+    pub async fn set_sniff(&self, session_name: &str, enabled: bool) -> bool {
+        let session_name = self.resolve(session_name).await;
+        match self.states.read().await.get(&session_name) {
+            Some(state) => {
+                info!(
+                    "{} byte sniffing on session '{}'",
+                    if enabled { "Started" } else { "Stopped" },
+                    session_name
+                );
+                state.sniffer.set_enabled(enabled);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Get a snapshot of every session's state
+    #[cfg(any(feature = "http", feature = "tui"))]
+    pub async fn session_statuses(&self) -> Vec<SessionStatus> {
+        let states = self.states.read().await;
+        let mut statuses: Vec<SessionStatus> = states
+            .iter()
+            .map(|(name, state)| SessionStatus {
+                name: name.clone(),
+                port: state.port,
+                ssrc: state.ssrc,
+                bound: true,
+                listen: state.listen,
+                remotes: state.remotes.clone(),
+                remote_health: state
+                    .remote_health
+                    .iter()
+                    .map(RemoteHealth::status)
+                    .collect(),
+                clock_sync_role: state.clock_sync_role,
+                clock_master: state
+                    .clock_master
+                    .as_ref()
+                    .map(|(master, _)| master.clone()),
+                participants: state.participants,
+                queued_messages: state.queued.len(),
+                messages_sent: state.messages_sent,
+                messages_received: state.messages_received,
+                messages_dropped: state.dropped.load(Ordering::Relaxed),
+                echoes_dropped: state.echoes_dropped,
+                values_clamped: state.values_clamped,
+                shaping_delayed: state.pacer.delayed.load(Ordering::Relaxed),
+                shaping_dropped: state.pacer.dropped.load(Ordering::Relaxed),
+                sniff: state.sniffer.is_enabled(),
+                overloaded: state.overload.overloaded.load(Ordering::Relaxed),
+                messages_shed: state.overload.shed.load(Ordering::Relaxed),
+                last_received_ms_ago: state
+                    .last_received
+                    .map(|time| time.elapsed().as_millis() as u64),
+            })
+            .collect();
+        statuses.extend(self.unbound.read().await.iter().cloned());
+        statuses.sort_by(|a, b| a.name.cmp(&b.name));
+        statuses
+    }
+
+    /// Report a session that failed to start because its ports could not be bound
+    #[cfg(any(feature = "http", feature = "tui"))]
+    pub async fn record_unbound(&self, config: &RtpMidiSession, ssrc: u32) {
+        let mut unbound = self.unbound.write().await;
+        unbound.retain(|status| status.name != config.name);
+        unbound.push(SessionStatus {
+            name: config.name.clone(),
+            port: config.port,
+            ssrc,
+            bound: false,
+            listen: config.listen,
+            clock_sync_role: config.clock_sync_role,
+            ..SessionStatus::default()
+        });
     }
 
     pub async fn send_midi_to_session(
@@ -29,16 +575,91 @@ impl SessionManager {
         session_name: &str,
         message: MidiMessage,
     ) -> Result<()> {
+        let session_name = &self.resolve(session_name).await;
+        if self.output_freeze.is_frozen(session_name) {
+            if is_clock(&message) {
+                trace!(
+                    "Output frozen, not sending MIDI clock to '{}': {:?}",
+                    session_name, message
+                );
+            } else {
+                info!(
+                    "Output frozen, not sending MIDI message to '{}': {:?}",
+                    session_name, message
+                );
+            }
+            return Ok(());
+        }
         let sessions = self.sessions.read().await;
         if let Some(session) = sessions.get(session_name) {
-            info!(
-                "Sending MIDI message to session '{}': {:?}",
-                session_name, message
-            );
+            let message = {
+                let mut states = self.states.write().await;
+                match states.get_mut(session_name) {
+                    Some(state) => {
+                        let clamped = clamp(session_name, &state.value_clamps, message);
+                        if clamped != message {
+                            state.values_clamped += 1;
+                        }
+                        clamped
+                    }
+                    None => message,
+                }
+            };
+            if self.gate_offline_send(session_name, message).await {
+                return Ok(());
+            }
+
+            if is_clock(&message) {
+                trace!(
+                    "Sending MIDI clock to session '{}': {:?}",
+                    session_name, message
+                );
+            } else {
+                info!(
+                    "Sending MIDI message to session '{}': {:?}",
+                    session_name, message
+                );
+            }
 
             let rtp_message = RtpMidiMessage::MidiMessage(message);
-            session.send_midi(&rtp_message).await?;
+            let Some(sent) = self
+                .pacer(session_name)
+                .await
+                .try_send(session.send_midi(&rtp_message))
+                .await
+            else {
+                info!(
+                    "Dropping MIDI message to session '{}' held back too long by shaping: {:?}",
+                    session_name, message
+                );
+                return Ok(());
+            };
+            sent?;
+            self.record_sent(session_name, message).await;
+            self.sniffer(session_name)
+                .await
+                .midi(session_name, Direction::Sent, &message);
 
+            if let Some(state) = self.states.write().await.get_mut(session_name) {
+                state.messages_sent += 1;
+                state.record_state(message);
+            }
+
+            Ok(())
+        } else if let Some(port) = self.virtual_port(session_name).await {
+            let message = clamp(session_name, &port.value_clamps, message);
+            if is_clock(&message) {
+                trace!(
+                    "Sending MIDI clock to virtual port '{}': {:?}",
+                    session_name, message
+                );
+            } else {
+                info!(
+                    "Sending MIDI message to virtual port '{}': {:?}",
+                    session_name, message
+                );
+            }
+            port.send(&wire::encode(&message))?;
             Ok(())
         } else {
             warn!("Session '{}' not found", session_name);
@@ -46,16 +667,425 @@ impl SessionManager {
         }
     }
 
+    /// Send a complete SysEx message (including F0/F7) to a session
+    pub async fn send_sysex_to_session(&self, session_name: &str, data: &[u8]) -> Result<()> {
+        let session_name = &self.resolve(session_name).await;
+        if self.output_freeze.is_frozen(session_name) {
+            info!(
+                "Output frozen, not sending SysEx to '{}': {} bytes",
+                session_name,
+                data.len()
+            );
+            return Ok(());
+        }
+        let sessions = self.sessions.read().await;
+        if let Some(session) = sessions.get(session_name) {
+            info!(
+                "Sending SysEx to session '{}': {} bytes",
+                session_name,
+                data.len()
+            );
+
+            let rtp_message = RtpMidiMessage::SysEx(data);
+            let Some(sent) = self
+                .pacer(session_name)
+                .await
+                .try_send(session.send_midi(&rtp_message))
+                .await
+            else {
+                warn!(
+                    "Dropping SysEx to session '{}' held back too long by shaping",
+                    session_name
+                );
+                return Ok(());
+            };
+            sent?;
+            self.sniffer(session_name)
+                .await
+                .sysex(session_name, Direction::Sent, data);
+
+            if let Some(state) = self.states.write().await.get_mut(session_name) {
+                state.messages_sent += 1;
+            }
+        } else if let Some(port) = self.virtual_port(session_name).await {
+            info!(
+                "Sending SysEx to virtual port '{}': {} bytes",
+                session_name,
+                data.len()
+            );
+            port.send(data)?;
+        } else {
+            warn!("Session '{}' not found", session_name);
+        }
+        Ok(())
+    }
+
+    /// Pacer keeping the session's minimum gap and rate limit on outgoing messages
+    async fn pacer(&self, session_name: &str) -> Arc<Pacer> {
+        match self.states.read().await.get(session_name) {
+            Some(state) => Arc::clone(&state.pacer),
+            None => Arc::new(Pacer::new(Duration::ZERO, None)),
+        }
+    }
+
+    /// Byte sniffer of the session
+    async fn sniffer(&self, session_name: &str) -> Arc<Sniffer> {
+        match self.states.read().await.get(session_name) {
+            Some(state) => Arc::clone(&state.sniffer),
+            None => Arc::new(Sniffer::new(false)),
+        }
+    }
+
+    /// Apply the session's offline policy, returning true if the message must not be sent now
+    async fn gate_offline_send(&self, session_name: &str, message: MidiMessage) -> bool {
+        let mut states = self.states.write().await;
+        let Some(state) = states.get_mut(session_name) else {
+            return false;
+        };
+        let sequence = state.next_sequence;
+        state.next_sequence += 1;
+
+        if state.flushing {
+            // Keep send order: replayed messages must go out before this one
+            debug!(
+                "Queueing message #{} for session '{}' behind replayed messages: {:?}",
+                sequence, session_name, message
+            );
+            state.queued.push(QueuedMidi {
+                sequence,
+                key: None,
+                message,
+            });
+            return true;
+        }
+        if state.participants > 0 {
+            return false;
+        }
+
+        match state.offline_policy {
+            OfflinePolicy::Send => false,
+            OfflinePolicy::Drop => {
+                debug!(
+                    "Dropping message for session '{}' with no participants: {:?}",
+                    session_name, message
+                );
+                true
+            }
+            OfflinePolicy::QueueLatest => {
+                if let Some(key) = QueueKey::for_message(&message) {
+                    debug!(
+                        "Queueing message #{} for session '{}' until a participant connects: {:?}",
+                        sequence, session_name, message
+                    );
+                    state.queued.retain(|queued| queued.key != Some(key));
+                    state.queued.push(QueuedMidi {
+                        sequence,
+                        key: Some(key),
+                        message,
+                    });
+                } else {
+                    debug!(
+                        "Dropping message for session '{}' with no participants: {:?}",
+                        session_name, message
+                    );
+                }
+                true
+            }
+        }
+    }
+
+    /// Poll participant counts in the background, flushing queued messages on connect
+    pub fn start_monitor(&self) -> JoinHandle<()> {
+        let manager = self.clone();
+
+        supervise("participant monitor", move || {
+            let manager = manager.clone();
+            async move {
+                let mut interval = tokio::time::interval(PARTICIPANT_POLL_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    manager.poll_participants().await;
+                }
+            }
+        })
+    }
+
+    /// Update participant counts and flush queues of sessions that gained a participant
+    async fn poll_participants(&self) {
+        let sessions = self.sessions.read().await;
+
+        for (name, session) in sessions.iter() {
+            let participants = participant_sync_ages(session).await;
+            let count = participants.len();
+            let mut invites = Vec::new();
+            let mut clock_syncs = Vec::new();
+            let mut resend = Vec::new();
+
+            let flush = {
+                let mut states = self.states.write().await;
+                let Some(state) = states.get_mut(name) else {
+                    continue;
+                };
+
+                let timeout = state.clock_sync_timeout;
+                let retry = state.invite_retry.clone();
+                for remote in &mut state.remote_health {
+                    let sync_age = participants
+                        .iter()
+                        .find(|(addr, _)| remote.matches(addr))
+                        .map(|(_, age)| *age);
+                    remote.update(name, sync_age, timeout);
+                    invites.extend(remote.invite_due(name, &retry));
+                }
+
+                if state.drives_clock_sync() {
+                    let now = Instant::now();
+                    state
+                        .clock_sync_sent
+                        .retain(|addr, _| participants.iter().any(|(p, _)| p == addr));
+                    for (addr, _) in &participants {
+                        let due = state
+                            .clock_sync_sent
+                            .get(addr)
+                            .is_none_or(|sent| now.duration_since(*sent) >= CLOCK_SYNC_INTERVAL);
+                        if due && state.starts_clock_sync(addr) {
+                            state.clock_sync_sent.insert(*addr, now);
+                            clock_syncs.push(*addr);
+                        }
+                    }
+                }
+
+                let present: HashSet<IpAddr> =
+                    participants.iter().map(|(addr, _)| addr.ip()).collect();
+                if state.resend_on_rejoin {
+                    let rejoined: Vec<IpAddr> = present
+                        .iter()
+                        .filter(|host| {
+                            state.known_hosts.contains(host) && !state.present_hosts.contains(host)
+                        })
+                        .copied()
+                        .collect();
+                    if !rejoined.is_empty() {
+                        info!(
+                            "{:?} rejoined session '{}', resending {} state messages",
+                            rejoined,
+                            name,
+                            state.sent_state.len()
+                        );
+                        resend = state
+                            .sent_state
+                            .iter()
+                            .map(|(_, message)| *message)
+                            .collect();
+                        let _ = self.rejoins.send(name.clone());
+                    }
+                }
+                state.known_hosts.extend(present.iter().copied());
+                state.present_hosts = present;
+
+                let connected = state.participants == 0 && count > 0;
+                if state.participants != count {
+                    info!(
+                        "Session '{}' participants: {} -> {}",
+                        name, state.participants, count
+                    );
+                }
+                state.participants = count;
+
+                let flush = connected && !state.queued.is_empty();
+                if flush {
+                    state.flushing = true;
+                }
+                flush
+            };
+
+            for addr in invites {
+                session.invite_participant(addr).await;
+            }
+            for addr in clock_syncs {
+                debug!("Starting clock sync with {} in session '{}'", addr, name);
+                session.start_clock_sync(addr).await;
+            }
+            let pacer = self.pacer(name).await;
+            let sniffer = self.sniffer(name).await;
+            for message in resend {
+                if self.output_freeze.is_frozen(name) {
+                    info!("Output frozen, not resending to '{}': {:?}", name, message);
+                    continue;
+                }
+                let rtp_message = RtpMidiMessage::MidiMessage(message);
+                match pacer.send(session.send_midi(&rtp_message)).await {
+                    Ok(_) => {
+                        self.record_sent(name, message).await;
+                        sniffer.midi(name, Direction::Sent, &message);
+                    }
+                    Err(e) => error!("Failed to resend state to session '{}': {}", name, e),
+                }
+            }
+            if flush {
+                self.flush_queue(name, session).await;
+            }
+        }
+    }
+
+    /// Replay queued messages in sequence order, including any queued during the replay
+    async fn flush_queue(&self, name: &str, session: &AppleMidiSession) {
+        loop {
+            let mut flush = {
+                let mut states = self.states.write().await;
+                let Some(state) = states.get_mut(name) else {
+                    return;
+                };
+                if state.queued.is_empty() {
+                    state.flushing = false;
+                    return;
+                }
+                std::mem::take(&mut state.queued)
+            };
+            flush.sort_by_key(|queued| queued.sequence);
+            let pacer = self.pacer(name).await;
+            let sniffer = self.sniffer(name).await;
+
+            for queued in flush {
+                if self.output_freeze.is_frozen(name) {
+                    info!(
+                        "Output frozen, not sending queued MIDI message #{} to '{}': {:?}",
+                        queued.sequence, name, queued.message
+                    );
+                    continue;
+                }
+                info!(
+                    "Sending queued MIDI message #{} to session '{}': {:?}",
+                    queued.sequence, name, queued.message
+                );
+                let rtp_message = RtpMidiMessage::MidiMessage(queued.message);
+                match pacer.send(session.send_midi(&rtp_message)).await {
+                    Ok(_) => {
+                        self.record_sent(name, queued.message).await;
+                        sniffer.midi(name, Direction::Sent, &queued.message);
+                        if let Some(state) = self.states.write().await.get_mut(name) {
+                            state.record_state(queued.message);
+                        }
+                    }
+                    Err(e) => {
+                        error!("Failed to send queued message to session '{}': {}", name, e)
+                    }
+                }
+            }
+        }
+    }
+
     pub async fn get_session_names(&self) -> Vec<String> {
         let sessions = self.sessions.read().await;
         sessions.keys().cloned().collect()
     }
+
+    /// Invite a remote participant to a session, returning false if the session is unknown
+    #[cfg(feature = "http")]
+    pub async fn invite(&self, session_name: &str, addr: SocketAddr) -> bool {
+        let session_name = self.resolve(session_name).await;
+        let sessions = self.sessions.read().await;
+        match sessions.get(&session_name) {
+            Some(session) => {
+                info!("Inviting {} to session '{}'", addr, session_name);
+                session.invite_participant(addr).await;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Drop every session, e.g. when its routing context is unloaded
+    pub async fn remove_all(&self) {
+        self.sessions.write().await.clear();
+        self.states.write().await.clear();
+        self.aliases.write().await.clear();
+        self.virtual_ports.write().await.clear();
+        #[cfg(any(feature = "http", feature = "tui"))]
+        self.unbound.write().await.clear();
+    }
 }
 
 impl Clone for SessionManager {
     fn clone(&self) -> Self {
         Self {
             sessions: Arc::clone(&self.sessions),
+            states: Arc::clone(&self.states),
+            aliases: Arc::clone(&self.aliases),
+            loop_guard: Arc::clone(&self.loop_guard),
+            rejoins: self.rejoins.clone(),
+            virtual_ports: Arc::clone(&self.virtual_ports),
+            output_freeze: self.output_freeze.clone(),
+            #[cfg(any(feature = "http", feature = "tui"))]
+            unbound: Arc::clone(&self.unbound),
         }
     }
 }
+
+/// Address of each participant with the time since its last successful CK exchange
+async fn participant_sync_ages(session: &AppleMidiSession) -> Vec<(SocketAddr, Option<Duration>)> {
+    session
+        .participants()
+        .await
+        .iter()
+        .map(|participant| {
+            (
+                participant.addr(),
+                participant
+                    .last_clock_sync()
+                    .map(|last_sync| last_sync.elapsed()),
+            )
+        })
+        .collect()
+}
+
+/// Address of a session's `clock_master`, by `connect_to` name or as `host[:port]`
+fn resolve_clock_master(config: &RtpMidiSession, master: &str) -> Option<SocketAddr> {
+    let resolved = match config
+        .connect_to
+        .iter()
+        .find(|remote| remote.name == master)
+    {
+        Some(remote) => (remote.host.as_str(), remote.port).to_socket_addrs(),
+        None => match master.rsplit_once(':') {
+            Some((_, port)) if port.parse::<u16>().is_ok() => master.to_socket_addrs(),
+            _ => (master, DEFAULT_RTP_MIDI_PORT).to_socket_addrs(),
+        },
+    };
+    resolved.ok().and_then(|mut addrs| addrs.next())
+}
+
+/// Limit a Control Change's value to the first clamp covering its controller, as the
+/// last step before sending
+fn clamp(destination: &str, clamps: &[ValueClamp], message: MidiMessage) -> MidiMessage {
+    let MidiMessage::ControlChange(channel, controller, value) = message else {
+        return message;
+    };
+    let Some(clamp) = clamps
+        .iter()
+        .find(|clamp| clamp.covers(u8::from(channel) + 1, controller.into()))
+    else {
+        return message;
+    };
+    let clamped = clamp.apply(value.into());
+    if clamped == u8::from(value) {
+        return message;
+    }
+    warn!(
+        "Clamped controller {} on channel {} for '{}' from {} to {}",
+        u8::from(controller),
+        u8::from(channel) + 1,
+        destination,
+        u8::from(value),
+        clamped
+    );
+    MidiMessage::ControlChange(channel, controller, Value7::new(clamped))
+}
+
+/// Whether a message is MIDI clock or transport, sent too often to log each one
+fn is_clock(message: &MidiMessage) -> bool {
+    matches!(
+        message,
+        MidiMessage::TimingClock | MidiMessage::Start | MidiMessage::Continue | MidiMessage::Stop
+    )
+}
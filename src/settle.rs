@@ -0,0 +1,159 @@
+use crate::device::Program;
+use crate::mapping::DeviceMapping;
+use crate::processor::MidiProcessor;
+use crate::supervisor::supervise;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+
+/// How often programs and tempos waiting to settle are checked
+const TICK_INTERVAL: Duration = Duration::from_millis(5);
+
+/// A program selected through a mapping with `settle_ms`, waiting to run
+pub struct PendingProgram {
+    pub mapping: DeviceMapping,
+    pub device_name: String,
+    pub program: Program,
+    /// What selected the program, for the audit log
+    pub trigger: String,
+    due: Instant,
+}
+
+impl PendingProgram {
+    pub fn new(
+        mapping: DeviceMapping,
+        device_name: String,
+        program: Program,
+        trigger: String,
+    ) -> Self {
+        let due = Instant::now() + Duration::from_millis(mapping.settle_ms.unwrap_or(0));
+        Self {
+            mapping,
+            device_name,
+            program,
+            trigger,
+            due,
+        }
+    }
+}
+
+/// Programs waiting for their mapping's burst of program changes to settle
+#[derive(Default)]
+pub struct PendingPrograms {
+    pending: HashMap<String, PendingProgram>,
+}
+
+impl PendingPrograms {
+    /// Hold a program until it settles, returning the program it supersedes
+    pub fn hold(&mut self, key: String, program: PendingProgram) -> Option<PendingProgram> {
+        self.pending.insert(key, program)
+    }
+
+    /// Remove and return the programs that have settled
+    pub fn take_due(&mut self, now: Instant) -> Vec<PendingProgram> {
+        let due: Vec<String> = self
+            .pending
+            .iter()
+            .filter(|(_, program)| program.due <= now)
+            .map(|(key, _)| key.clone())
+            .collect();
+        due.iter()
+            .filter_map(|key| self.pending.remove(key))
+            .collect()
+    }
+}
+
+/// OSC tempo waiting for its source to stop changing it
+pub struct PendingTempo {
+    pub bpm: f64,
+    /// OSC source that sent the tempo
+    pub source: String,
+    due: Instant,
+}
+
+/// OSC tempos waiting to settle, keyed by tempo domain
+#[derive(Default)]
+pub struct PendingTempos {
+    pending: HashMap<String, PendingTempo>,
+}
+
+impl PendingTempos {
+    /// Hold a tempo until it has stayed within `min_change` BPM for `settle`. A tempo
+    /// that moves further starts the wait over.
+    #[cfg(feature = "osc-input")]
+    pub fn hold(
+        &mut self,
+        domain: &str,
+        bpm: f64,
+        source: &str,
+        settle: Duration,
+        min_change: f64,
+    ) {
+        let now = Instant::now();
+        let due = match self.pending.get(domain) {
+            Some(pending) if (pending.bpm - bpm).abs() <= min_change => pending.due,
+            _ => now + settle,
+        };
+        self.pending.insert(
+            domain.to_string(),
+            PendingTempo {
+                bpm,
+                source: source.to_string(),
+                due,
+            },
+        );
+    }
+
+    /// Drop a domain's waiting tempo
+    #[cfg(feature = "osc-input")]
+    pub fn cancel(&mut self, domain: &str) -> Option<PendingTempo> {
+        self.pending.remove(domain)
+    }
+
+    /// Remove and return the tempos that have settled, with their domains
+    pub fn take_due(&mut self, now: Instant) -> Vec<(String, PendingTempo)> {
+        let due: Vec<String> = self
+            .pending
+            .iter()
+            .filter(|(_, tempo)| tempo.due <= now)
+            .map(|(domain, _)| domain.clone())
+            .collect();
+        due.into_iter()
+            .filter_map(|domain| {
+                let tempo = self.pending.remove(&domain)?;
+                Some((domain, tempo))
+            })
+            .collect()
+    }
+}
+
+/// Runs programs and OSC tempos once they have settled
+pub struct SettleMonitor {
+    processor: Arc<MidiProcessor>,
+}
+
+impl SettleMonitor {
+    pub fn new(processor: Arc<MidiProcessor>) -> Self {
+        Self { processor }
+    }
+
+    /// Start running settled programs and tempos in the background
+    pub fn start(&self) -> JoinHandle<()> {
+        let processor = Arc::clone(&self.processor);
+
+        supervise("settling", move || {
+            let processor = Arc::clone(&processor);
+            async move {
+                let mut interval = tokio::time::interval(TICK_INTERVAL);
+                interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+                loop {
+                    interval.tick().await;
+                    processor.run_settled_programs().await;
+                    processor.apply_settled_tempos().await;
+                }
+            }
+        })
+    }
+}
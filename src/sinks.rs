@@ -0,0 +1,240 @@
+use crate::events::{EventStream, RoutedEvent};
+use crate::logging;
+use crate::mapping::{EventSink as EventSinkConfig, MapConfig};
+use crate::supervisor::supervise;
+use anyhow::{Context, Result};
+use rosc::{OscMessage, OscPacket, OscType, encoder};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Syslog severity of every event (informational)
+const SYSLOG_SEVERITY: u8 = 6;
+
+/// Receives the structured events of a routing context, e.g. to archive every routed
+/// action outside the logs
+pub trait Sink: Send {
+    /// Where the events go, for log messages
+    fn describe(&self) -> String;
+
+    /// Deliver one event
+    fn send(&mut self, event: &RoutedEvent) -> Result<()>;
+}
+
+/// Create the sinks configured in `event_sinks`
+pub fn build(map_config: &MapConfig) -> Result<Vec<Box<dyn Sink>>> {
+    map_config
+        .event_sinks
+        .iter()
+        .map(|config| -> Result<Box<dyn Sink>> {
+            Ok(match config {
+                EventSinkConfig::File {
+                    path,
+                    max_bytes,
+                    max_files,
+                } => Box::new(FileSink::new(Path::new(path), *max_bytes, *max_files)?),
+                EventSinkConfig::Syslog {
+                    host,
+                    port,
+                    facility,
+                } => Box::new(SyslogSink::new(&format!("{host}:{port}"), *facility)?),
+                EventSinkConfig::Osc {
+                    destination_name,
+                    address,
+                } => {
+                    let destination = map_config
+                        .osc_destinations
+                        .get(destination_name)
+                        .with_context(|| {
+                            format!("Unknown OSC destination '{destination_name}' for event sink")
+                        })?;
+                    let target = format!("{}:{}", destination.host, destination.port);
+                    Box::new(OscSink::new(&target, address)?)
+                }
+            })
+        })
+        .collect()
+}
+
+/// Feed a routing context's events to each of its sinks in the background
+pub fn start(
+    context: &str,
+    sinks: Vec<Box<dyn Sink>>,
+    events: &EventStream,
+) -> Vec<JoinHandle<()>> {
+    sinks
+        .into_iter()
+        .map(|sink| {
+            info!("Sending events to {}", sink.describe());
+            let name = format!("event sink {}", sink.describe());
+            let context = context.to_string();
+            // Shared so a restarted task keeps the subscription and the open sink
+            let state = Arc::new(tokio::sync::Mutex::new((events.subscribe(), sink)));
+            supervise(name, move || {
+                let state = Arc::clone(&state);
+                let context = context.clone();
+                async move {
+                    let mut state = state.lock().await;
+                    let (events, sink) = &mut *state;
+                    let mut failing = false;
+                    loop {
+                        let event = match events.recv().await {
+                            Ok(event) => event,
+                            Err(RecvError::Lagged(missed)) => {
+                                warn!("{} missed {} events", sink.describe(), missed);
+                                continue;
+                            }
+                            Err(RecvError::Closed) => return,
+                        };
+                        if event.context() != context {
+                            continue;
+                        }
+                        // Warn only when sending starts or stops failing
+                        match sink.send(&event) {
+                            Ok(()) if failing => {
+                                failing = false;
+                                info!("Sending events to {} recovered", sink.describe());
+                            }
+                            Ok(()) => {}
+                            Err(e) if !failing => {
+                                failing = true;
+                                warn!("Failed to send event to {}: {:#}", sink.describe(), e);
+                            }
+                            Err(_) => {}
+                        }
+                    }
+                }
+            })
+        })
+        .collect()
+}
+
+/// Appends events to a JSON Lines file, rotated by size
+pub struct FileSink {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    file: File,
+}
+
+impl FileSink {
+    pub fn new(path: &Path, max_bytes: u64, max_files: usize) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create event sink directory: {parent:?}"))?;
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_bytes,
+            max_files,
+            file: Self::open(path)?,
+        })
+    }
+
+    fn open(path: &Path) -> Result<File> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open event sink file: {path:?}"))
+    }
+}
+
+impl Sink for FileSink {
+    fn describe(&self) -> String {
+        format!("file {}", self.path.display())
+    }
+
+    fn send(&mut self, event: &RoutedEvent) -> Result<()> {
+        writeln!(self.file, "{}", serde_json::to_string(event)?)
+            .context("Failed to write event")?;
+        if self.file.metadata()?.len() >= self.max_bytes {
+            logging::rotate_files(&self.path, self.max_files)
+                .context("Failed to rotate event sink file")?;
+            self.file = Self::open(&self.path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Sends events to a syslog server as RFC 5424 messages over UDP
+pub struct SyslogSink {
+    socket: UdpSocket,
+    target: SocketAddr,
+    facility: u8,
+}
+
+impl SyslogSink {
+    pub fn new(target: &str, facility: u8) -> Result<Self> {
+        Ok(Self {
+            socket: UdpSocket::bind("0.0.0.0:0")?,
+            target: resolve(target)?,
+            facility,
+        })
+    }
+}
+
+impl Sink for SyslogSink {
+    fn describe(&self) -> String {
+        format!("syslog {}", self.target)
+    }
+
+    fn send(&mut self, event: &RoutedEvent) -> Result<()> {
+        // The server stamps the time and host; the event carries its own timestamp
+        let message = format!(
+            "<{}>1 - - midi-router {} {} - {}",
+            u16::from(self.facility) * 8 + u16::from(SYSLOG_SEVERITY),
+            std::process::id(),
+            event.kind(),
+            serde_json::to_string(event)?
+        );
+        self.socket.send_to(message.as_bytes(), self.target)?;
+        Ok(())
+    }
+}
+
+/// Sends each event as a JSON string argument to an OSC destination
+pub struct OscSink {
+    socket: UdpSocket,
+    target: SocketAddr,
+    address: String,
+}
+
+impl OscSink {
+    pub fn new(target: &str, address: &str) -> Result<Self> {
+        Ok(Self {
+            socket: UdpSocket::bind("0.0.0.0:0")?,
+            target: resolve(target)?,
+            address: address.to_string(),
+        })
+    }
+}
+
+impl Sink for OscSink {
+    fn describe(&self) -> String {
+        format!("OSC {}{}", self.target, self.address)
+    }
+
+    fn send(&mut self, event: &RoutedEvent) -> Result<()> {
+        let packet = OscPacket::Message(OscMessage {
+            addr: self.address.clone(),
+            args: vec![OscType::String(serde_json::to_string(event)?)],
+        });
+        self.socket
+            .send_to(&encoder::encode(&packet)?, self.target)?;
+        Ok(())
+    }
+}
+
+fn resolve(target: &str) -> Result<SocketAddr> {
+    target
+        .to_socket_addrs()
+        .with_context(|| format!("Failed to resolve event sink address: {target}"))?
+        .next()
+        .with_context(|| format!("Failed to resolve event sink address: {target}"))
+}
@@ -0,0 +1,70 @@
+use crate::wire;
+use midi_types::MidiMessage;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::debug;
+
+/// Direction of a sniffed message
+#[derive(Debug, Clone, Copy)]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+impl Direction {
+    fn arrow(self) -> &'static str {
+        match self {
+            Direction::Sent => "->",
+            Direction::Received => "<-",
+        }
+    }
+}
+
+/// Logs the raw bytes of a session's messages at debug level while enabled.
+///
+/// Useful when a third-party RTP MIDI peer decodes what the router sends
+/// differently than expected.
+#[derive(Debug)]
+pub struct Sniffer {
+    enabled: AtomicBool,
+}
+
+impl Sniffer {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled: AtomicBool::new(enabled),
+        }
+    }
+
+    #[cfg(feature = "http")]
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Log a channel or system message as MIDI bytes
+    pub fn midi(&self, session_name: &str, direction: Direction, message: &MidiMessage) {
+        if self.is_enabled() {
+            self.log(session_name, direction, &wire::encode(message));
+        }
+    }
+
+    /// Log a complete SysEx message
+    pub fn sysex(&self, session_name: &str, direction: Direction, data: &[u8]) {
+        if self.is_enabled() {
+            self.log(session_name, direction, data);
+        }
+    }
+
+    fn log(&self, session_name: &str, direction: Direction, bytes: &[u8]) {
+        let hex: Vec<String> = bytes.iter().map(|byte| format!("{byte:02X}")).collect();
+        debug!(
+            "Sniff '{}' {} {}",
+            session_name,
+            direction.arrow(),
+            hex.join(" ")
+        );
+    }
+}
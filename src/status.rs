@@ -0,0 +1,512 @@
+#[cfg(feature = "http")]
+use crate::config_editor::ConfigEditor;
+#[cfg(feature = "http")]
+use crate::cue::CueAction;
+use crate::events::EventStream;
+use crate::freeze::OutputFreeze;
+use crate::processor::MidiProcessor;
+use crate::session_manager::SessionManager;
+#[cfg(any(feature = "http", feature = "tui"))]
+use crate::session_manager::SessionStatus;
+#[cfg(feature = "http")]
+use crate::tempo_stats::TempoReport;
+#[cfg(any(feature = "http", feature = "osc-input"))]
+use anyhow::Result;
+#[cfg(feature = "http")]
+use anyhow::bail;
+use serde::Serialize;
+#[cfg(feature = "http")]
+use std::collections::BTreeMap;
+#[cfg(feature = "http")]
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::{Notify, RwLock};
+#[cfg(feature = "http")]
+use tracing::debug;
+
+/// Observed state of an OSC listener
+#[derive(Debug, Clone, Serialize)]
+pub struct OscListenerStatus {
+    /// Name of the OSC source
+    pub name: String,
+    /// Port the listener was configured for
+    pub port: u16,
+    /// Whether the listener socket is bound
+    pub bound: bool,
+    /// Port the listener is bound to, which differs from `port` after a fallback
+    pub bound_port: Option<u16>,
+}
+
+/// Outcome of the most recent configuration reload
+#[derive(Debug, Clone, Serialize)]
+pub struct ReloadResult {
+    /// Time of the reload in milliseconds since the Unix epoch
+    pub timestamp_ms: u64,
+    /// Whether the reload succeeded
+    pub success: bool,
+    /// Description of the outcome
+    pub message: String,
+}
+
+impl ReloadResult {
+    pub fn new(success: bool, message: impl Into<String>) -> Self {
+        Self {
+            timestamp_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            success,
+            message: message.into(),
+        }
+    }
+}
+
+/// Source name the show lock checks for control API requests
+#[cfg(feature = "http")]
+pub const API_SOURCE: &str = "api";
+
+/// Outcome of a control request applied across routing contexts
+#[cfg(feature = "http")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlOutcome {
+    /// Applied in at least one context
+    Applied,
+    /// Refused by the show lock everywhere it would have applied
+    Locked,
+    /// Nothing it could apply to
+    NotFound,
+}
+
+/// A device and its programs, as listed by the control API
+#[cfg(feature = "http")]
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceSummary {
+    /// Routing context the device is configured in
+    pub context: String,
+    pub id: String,
+    pub name: String,
+    pub programs: Vec<ProgramSummary>,
+}
+
+/// A device program, as listed by the control API
+#[cfg(feature = "http")]
+#[derive(Debug, Clone, Serialize)]
+pub struct ProgramSummary {
+    pub number: u8,
+    pub bank_msb: Option<u8>,
+    pub bank_lsb: Option<u8>,
+    pub name: String,
+}
+
+/// Live state of a routing context
+#[cfg(feature = "http")]
+#[derive(Debug, Clone, Serialize)]
+pub struct ContextState {
+    pub context: String,
+    /// Current main tempo, if one has been received
+    pub bpm: Option<f64>,
+    /// Current tempo of every tempo domain that has received one, including the main one
+    pub tempos: BTreeMap<String, f64>,
+    /// Selected bank, if banks are configured
+    pub bank: Option<String>,
+    pub show_locked: bool,
+    /// Last program executed on each device
+    pub active_programs: BTreeMap<String, String>,
+    /// Most recent inputs, oldest first
+    pub recent_inputs: Vec<String>,
+}
+
+/// Tempo statistics of a routing context
+#[cfg(feature = "http")]
+#[derive(Debug, Clone, Serialize)]
+pub struct ContextTempoStats {
+    pub context: String,
+    #[serde(flatten)]
+    pub stats: TempoReport,
+}
+
+/// Router-wide state reported by the readiness endpoint
+pub struct RouterStatus {
+    config_hash: RwLock<String>,
+    last_reload: RwLock<Option<ReloadResult>>,
+    osc_listeners: RwLock<Vec<OscListenerStatus>>,
+    session_managers: RwLock<Vec<(String, SessionManager)>>,
+    processors: RwLock<Vec<(String, Arc<MidiProcessor>)>>,
+    #[cfg(feature = "http")]
+    config_editors: RwLock<Vec<(String, Arc<ConfigEditor>)>>,
+    read_only: AtomicBool,
+    /// Whether the port summary is printed to stdout, which the terminal display owns
+    console: AtomicBool,
+    reload: Notify,
+    events: Arc<EventStream>,
+    output_freeze: OutputFreeze,
+}
+
+impl RouterStatus {
+    pub fn new() -> Self {
+        Self {
+            config_hash: RwLock::new(String::new()),
+            last_reload: RwLock::new(None),
+            osc_listeners: RwLock::new(Vec::new()),
+            session_managers: RwLock::new(Vec::new()),
+            processors: RwLock::new(Vec::new()),
+            #[cfg(feature = "http")]
+            config_editors: RwLock::new(Vec::new()),
+            read_only: AtomicBool::new(false),
+            console: AtomicBool::new(true),
+            reload: Notify::new(),
+            events: Arc::new(EventStream::new()),
+            output_freeze: OutputFreeze::default(),
+        }
+    }
+
+    /// Reject control-plane requests from now on
+    pub fn set_read_only(&self, read_only: bool) {
+        self.read_only.store(read_only, Ordering::Relaxed);
+    }
+
+    #[cfg(any(feature = "http", feature = "osc-input", feature = "tui"))]
+    pub fn is_read_only(&self) -> bool {
+        self.read_only.load(Ordering::Relaxed)
+    }
+
+    #[cfg(feature = "tui")]
+    pub fn set_console(&self, console: bool) {
+        self.console.store(console, Ordering::Relaxed);
+    }
+
+    pub fn prints_to_console(&self) -> bool {
+        self.console.load(Ordering::Relaxed)
+    }
+
+    pub async fn set_config_hash(&self, hash: String) {
+        *self.config_hash.write().await = hash;
+    }
+
+    pub async fn config_hash(&self) -> String {
+        self.config_hash.read().await.clone()
+    }
+
+    pub async fn set_last_reload(&self, result: ReloadResult) {
+        *self.last_reload.write().await = Some(result);
+    }
+
+    #[cfg(feature = "http")]
+    pub async fn last_reload(&self) -> Option<ReloadResult> {
+        self.last_reload.read().await.clone()
+    }
+
+    /// Record the state of an OSC listener, replacing any previous entry with the same name
+    #[cfg(feature = "osc-input")]
+    pub async fn set_osc_listener(&self, listener: OscListenerStatus) {
+        let mut listeners = self.osc_listeners.write().await;
+        listeners.retain(|existing| existing.name != listener.name);
+        listeners.push(listener);
+    }
+
+    pub async fn osc_listeners(&self) -> Vec<OscListenerStatus> {
+        self.osc_listeners.read().await.clone()
+    }
+
+    /// Stream of routed events from every routing context
+    pub fn events(&self) -> Arc<EventStream> {
+        Arc::clone(&self.events)
+    }
+
+    /// Report the sessions of a routing context, replacing those of an earlier attempt
+    /// to start it
+    pub async fn add_session_manager(&self, context: &str, session_manager: SessionManager) {
+        let mut session_managers = self.session_managers.write().await;
+        session_managers.retain(|(name, _)| name != context);
+        session_managers.push((context.to_string(), session_manager));
+    }
+
+    /// Make a routing context's processor reachable from control requests
+    pub async fn add_processor(&self, context: &str, processor: Arc<MidiProcessor>) {
+        self.processors
+            .write()
+            .await
+            .push((context.to_string(), processor));
+    }
+
+    /// Make a routing context's config files editable from the web UI
+    #[cfg(feature = "http")]
+    pub async fn add_config_editor(&self, context: &str, editor: ConfigEditor) {
+        self.config_editors
+            .write()
+            .await
+            .push((context.to_string(), Arc::new(editor)));
+    }
+
+    /// Config editor of the named routing context
+    #[cfg(feature = "http")]
+    pub async fn config_editor(&self, context: &str) -> Option<Arc<ConfigEditor>> {
+        self.config_editors
+            .read()
+            .await
+            .iter()
+            .find(|(name, _)| name == context)
+            .map(|(_, editor)| Arc::clone(editor))
+    }
+
+    /// Processors of every routing context, with the context names
+    #[cfg(any(feature = "http", feature = "tui"))]
+    pub async fn processors(&self) -> Vec<(String, Arc<MidiProcessor>)> {
+        self.processors.read().await.clone()
+    }
+
+    /// Ask the running contexts to reload their configs
+    #[cfg(feature = "tui")]
+    pub fn request_reload(&self) {
+        self.reload.notify_one();
+    }
+
+    /// Wait until a reload is requested
+    pub async fn reload_requested(&self) {
+        self.reload.notified().await;
+    }
+
+    /// Stop reporting a routing context's sessions and OSC listeners
+    pub async fn remove_session_manager(&self, context: &str, osc_sources: &[String]) {
+        self.session_managers
+            .write()
+            .await
+            .retain(|(name, _)| name != context);
+        self.processors
+            .write()
+            .await
+            .retain(|(name, _)| name != context);
+        #[cfg(feature = "http")]
+        self.config_editors
+            .write()
+            .await
+            .retain(|(name, _)| name != context);
+        self.osc_listeners
+            .write()
+            .await
+            .retain(|listener| !osc_sources.contains(&listener.name));
+    }
+
+    /// Session states across all routing contexts
+    #[cfg(any(feature = "http", feature = "tui"))]
+    pub async fn session_statuses(&self) -> Vec<SessionStatus> {
+        let session_managers = self.session_managers.read().await.clone();
+        let mut statuses = Vec::new();
+        for (_, session_manager) in session_managers {
+            statuses.extend(session_manager.session_statuses().await);
+        }
+        statuses
+    }
+
+    /// Invite a remote to the named session in whichever context has it
+    #[cfg(feature = "http")]
+    pub async fn invite(&self, session_name: &str, addr: SocketAddr) -> bool {
+        let session_managers = self.session_managers.read().await.clone();
+        for (_, session_manager) in session_managers {
+            if session_manager.invite(session_name, addr).await {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Start or stop byte sniffing on the named session in whichever context has it
+    #[cfg(feature = "http")]
+    pub async fn set_sniff(&self, session_name: &str, enabled: bool) -> bool {
+        let session_managers = self.session_managers.read().await.clone();
+        for (_, session_manager) in session_managers {
+            if session_manager.set_sniff(session_name, enabled).await {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Rename an RTP MIDI session in whichever context has it, returning false if none
+    /// does; the old name stays usable as an alias
+    #[cfg(any(feature = "http", feature = "osc-input"))]
+    pub async fn rename_session(&self, old_name: &str, new_name: &str) -> Result<bool> {
+        let session_managers = self.session_managers.read().await.clone();
+        for (_, session_manager) in session_managers {
+            if session_manager.rename_session(old_name, new_name).await? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Add an alias for an RTP MIDI session in whichever context has it, returning false
+    /// if none does
+    #[cfg(any(feature = "http", feature = "osc-input"))]
+    pub async fn add_session_alias(&self, session_name: &str, alias: &str) -> bool {
+        let session_managers = self.session_managers.read().await.clone();
+        for (_, session_manager) in session_managers {
+            if session_manager.add_alias(session_name, alias).await {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Lock or unlock the show in every routing context that has a show lock
+    #[cfg(feature = "http")]
+    pub async fn set_show_lock(&self, code: &str, locked: bool) -> Result<()> {
+        let processors = self.processors.read().await.clone();
+        let mut applied = false;
+        for (context, processor) in processors {
+            match processor.set_show_lock(code, locked).await {
+                Ok(()) => applied = true,
+                Err(e) => debug!("Show lock not changed in '{}': {}", context, e),
+            }
+        }
+        if !applied {
+            bail!("Wrong code or no show lock configured");
+        }
+        Ok(())
+    }
+
+    /// Switch that drops outgoing messages of every routing context
+    pub fn output_freeze(&self) -> OutputFreeze {
+        self.output_freeze.clone()
+    }
+
+    /// Whether any routing context has its show lock engaged
+    #[cfg(any(feature = "http", feature = "tui"))]
+    pub async fn show_locked(&self) -> bool {
+        self.processors
+            .read()
+            .await
+            .iter()
+            .any(|(_, processor)| processor.is_show_locked())
+    }
+
+    /// Devices of every routing context
+    #[cfg(feature = "http")]
+    pub async fn devices(&self) -> Vec<DeviceSummary> {
+        let mut summaries = Vec::new();
+        for (context, processor) in self.processors().await {
+            for device in processor.devices().await {
+                summaries.push(DeviceSummary {
+                    context: context.clone(),
+                    id: device.id,
+                    name: device.name,
+                    programs: device
+                        .programs
+                        .into_iter()
+                        .map(|program| ProgramSummary {
+                            number: program.number,
+                            bank_msb: program.bank_msb,
+                            bank_lsb: program.bank_lsb,
+                            name: program.name,
+                        })
+                        .collect(),
+                });
+            }
+        }
+        summaries
+    }
+
+    /// Live state of every routing context
+    #[cfg(feature = "http")]
+    pub async fn context_states(&self) -> Vec<ContextState> {
+        let mut states = Vec::new();
+        for (context, processor) in self.processors().await {
+            states.push(ContextState {
+                context,
+                bpm: processor.current_bpm().await,
+                tempos: processor.tempos().await,
+                bank: processor.active_bank_name().await,
+                show_locked: processor.is_show_locked(),
+                active_programs: processor.active_programs().await,
+                recent_inputs: processor.recent_inputs().await,
+            });
+        }
+        states
+    }
+
+    /// Tempo statistics of every routing context
+    #[cfg(feature = "http")]
+    pub async fn tempo_stats(&self) -> Vec<ContextTempoStats> {
+        let mut stats = Vec::new();
+        for (context, processor) in self.processors().await {
+            stats.push(ContextTempoStats {
+                context,
+                stats: processor.tempo_stats().await,
+            });
+        }
+        stats
+    }
+
+    /// Set a domain's tempo in every routing context that has it and the show lock allows
+    #[cfg(feature = "http")]
+    pub async fn set_tempo(&self, domain: &str, bpm: f64) -> Result<ControlOutcome> {
+        let mut outcome = ControlOutcome::NotFound;
+        for (context, processor) in self.processors().await {
+            if !processor.has_tempo_domain(domain).await {
+                continue;
+            }
+            if processor.is_locked_out(API_SOURCE).await {
+                debug!(
+                    "Tempo not changed in '{}' while the show is locked",
+                    context
+                );
+                if outcome == ControlOutcome::NotFound {
+                    outcome = ControlOutcome::Locked;
+                }
+                continue;
+            }
+            processor.handle_api_tempo(domain, bpm).await?;
+            outcome = ControlOutcome::Applied;
+        }
+        Ok(outcome)
+    }
+
+    /// Run a device program in every routing context that has the device
+    #[cfg(feature = "http")]
+    pub async fn run_device_program(
+        &self,
+        device_id: &str,
+        program: u8,
+        bank_msb: Option<u8>,
+        bank_lsb: Option<u8>,
+    ) -> Result<ControlOutcome> {
+        let mut outcome = ControlOutcome::NotFound;
+        for (context, processor) in self.processors().await {
+            if processor.is_locked_out(API_SOURCE).await {
+                debug!("Program not run in '{}' while the show is locked", context);
+                if outcome == ControlOutcome::NotFound {
+                    outcome = ControlOutcome::Locked;
+                }
+                continue;
+            }
+            if processor
+                .run_device_program("API", device_id, program, bank_msb, bank_lsb)
+                .await?
+            {
+                outcome = ControlOutcome::Applied;
+            }
+        }
+        Ok(outcome)
+    }
+
+    /// Apply an operator action to the named cue list in every routing context that has it
+    #[cfg(feature = "http")]
+    pub async fn cue(&self, name: &str, action: CueAction) -> ControlOutcome {
+        let mut outcome = ControlOutcome::NotFound;
+        for (context, processor) in self.processors().await {
+            if action == CueAction::Go && processor.is_locked_out(API_SOURCE).await {
+                debug!("Cue not fired in '{}' while the show is locked", context);
+                if outcome == ControlOutcome::NotFound {
+                    outcome = ControlOutcome::Locked;
+                }
+                continue;
+            }
+            if processor.cue(name, action).await {
+                outcome = ControlOutcome::Applied;
+            }
+        }
+        outcome
+    }
+}
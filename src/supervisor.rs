@@ -0,0 +1,75 @@
+use std::any::Any;
+use std::future::Future;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tracing::{debug, error};
+
+/// Delay before the first restart of a task that panicked; doubled for every panic in a row
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Longest delay between restarts
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// How long a restarted task must run before its next panic starts the backoff over
+const HEALTHY_RUN: Duration = Duration::from_secs(60);
+
+/// Run a background task, restarting it with backoff whenever it panics so a bug hit
+/// mid-show costs a moment instead of a listener or scheduler for the rest of the show.
+///
+/// `start` creates a fresh run of the task each time. Aborting the returned handle
+/// aborts the running task too.
+pub fn supervise<F, Fut>(name: impl Into<String>, mut start: F) -> JoinHandle<()>
+where
+    F: FnMut() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let name = name.into();
+    tokio::spawn(async move {
+        let mut backoff = INITIAL_BACKOFF;
+        loop {
+            let started = Instant::now();
+            let mut task = AbortOnDrop(tokio::spawn(start()));
+            match (&mut task.0).await {
+                Ok(()) => {
+                    debug!("Task '{}' finished", name);
+                    return;
+                }
+                Err(e) if e.is_panic() => {
+                    if started.elapsed() >= HEALTHY_RUN {
+                        backoff = INITIAL_BACKOFF;
+                    }
+                    error!(
+                        "Task '{}' panicked: {}; restarting in {}ms",
+                        name,
+                        panic_message(e.into_panic()),
+                        backoff.as_millis()
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+                // Aborted from outside, e.g. while shutting down
+                Err(_) => return,
+            }
+        }
+    })
+}
+
+/// Aborts a task when the supervisor that awaits it is aborted
+struct AbortOnDrop(JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "unknown panic".to_string(),
+        },
+    }
+}
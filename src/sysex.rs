@@ -0,0 +1,179 @@
+use crate::device::DisplayProtocol;
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+
+/// Start of a System Exclusive message
+const SYSEX_START: u8 = 0xF0;
+/// End of a System Exclusive message
+const SYSEX_END: u8 = 0xF7;
+
+/// Encode text for a controller display as a complete SysEx message
+pub fn encode_display_text(protocol: &DisplayProtocol, text: &str) -> Vec<u8> {
+    match protocol {
+        DisplayProtocol::Mcu { offset } => {
+            // Mackie Control main unit LCD write: F0 00 00 66 14 12 <offset> <text> F7
+            let mut message = vec![SYSEX_START, 0x00, 0x00, 0x66, 0x14, 0x12, offset & 0x7F];
+            message.extend(ascii_bytes(text, 112usize.saturating_sub(*offset as usize)));
+            message.push(SYSEX_END);
+            message
+        }
+        DisplayProtocol::Sysex { prefix, max_length } => {
+            let mut message = Vec::with_capacity(prefix.len() + text.len() + 2);
+            if prefix.first() != Some(&SYSEX_START) {
+                message.push(SYSEX_START);
+            }
+            message.extend(prefix);
+            message.extend(ascii_bytes(text, *max_length));
+            message.push(SYSEX_END);
+            message
+        }
+    }
+}
+
+/// A SysEx message with F0/F7 framing, added where the data lacks it
+pub fn frame(data: &[u8]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(data.len() + 2);
+    if data.first() != Some(&SYSEX_START) {
+        message.push(SYSEX_START);
+    }
+    message.extend(data);
+    if message.last() != Some(&SYSEX_END) {
+        message.push(SYSEX_END);
+    }
+    message
+}
+
+/// Text as 7-bit ASCII, truncated to `max_length`, with other characters replaced by '?'
+fn ascii_bytes(text: &str, max_length: usize) -> impl Iterator<Item = u8> + '_ {
+    text.chars().take(max_length).map(|c| {
+        if c.is_ascii() && !c.is_ascii_control() {
+            c as u8
+        } else {
+            b'?'
+        }
+    })
+}
+
+/// Byte pattern for matching incoming SysEx messages, written as hex where `??`
+/// matches any byte and a trailing `*` any number of remaining bytes,
+/// e.g. `"F0 00 21 24 ?? 01 *"`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SysExPattern {
+    /// Expected bytes, `None` for a wildcard
+    bytes: Vec<Option<u8>>,
+    /// Whether bytes after the pattern are accepted
+    open_ended: bool,
+}
+
+impl SysExPattern {
+    /// Parse a pattern from its hex form
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut tokens: Vec<&str> = text.split_whitespace().collect();
+        let open_ended = tokens.last() == Some(&"*");
+        if open_ended {
+            tokens.pop();
+        }
+        if tokens.is_empty() {
+            return Err(format!("SysEx pattern \"{text}\" has no bytes"));
+        }
+
+        let bytes = tokens
+            .iter()
+            .map(|token| match *token {
+                "??" => Ok(None),
+                token => match hex::parse(token)?[..] {
+                    [byte] => Ok(Some(byte)),
+                    _ => Err(format!(
+                        "expected one hex byte or `??`, got \"{token}\" in \"{text}\""
+                    )),
+                },
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self { bytes, open_ended })
+    }
+
+    /// Whether a complete SysEx message (including F0/F7) matches the pattern.
+    ///
+    /// F0 and F7 may be left out of the pattern.
+    pub fn matches(&self, message: &[u8]) -> bool {
+        let mut data = message;
+        if self.bytes.first() != Some(&Some(SYSEX_START)) {
+            data = data.strip_prefix(&[SYSEX_START]).unwrap_or(data);
+        }
+        if self.open_ended {
+            return data.len() >= self.bytes.len() && Self::matches_bytes(&self.bytes, data);
+        }
+        if self.bytes.last() != Some(&Some(SYSEX_END)) {
+            data = data.strip_suffix(&[SYSEX_END]).unwrap_or(data);
+        }
+        data.len() == self.bytes.len() && Self::matches_bytes(&self.bytes, data)
+    }
+
+    fn matches_bytes(pattern: &[Option<u8>], data: &[u8]) -> bool {
+        pattern
+            .iter()
+            .zip(data)
+            .all(|(expected, byte)| expected.is_none_or(|expected| expected == *byte))
+    }
+}
+
+impl std::fmt::Display for SysExPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut tokens: Vec<String> = self
+            .bytes
+            .iter()
+            .map(|byte| match byte {
+                Some(byte) => format!("{byte:02X}"),
+                None => "??".to_string(),
+            })
+            .collect();
+        if self.open_ended {
+            tokens.push("*".to_string());
+        }
+        f.write_str(&tokens.join(" "))
+    }
+}
+
+impl Serialize for SysExPattern {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for SysExPattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        SysExPattern::parse(&text).map_err(de::Error::custom)
+    }
+}
+
+/// Serde helpers for byte strings written as hex, e.g. `"F0 00 21 24"`
+pub mod hex {
+    use serde::{Deserialize, Deserializer, Serializer, de};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        let hex: Vec<String> = bytes.iter().map(|byte| format!("{byte:02X}")).collect();
+        serializer.serialize_str(&hex.join(" "))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        parse(&text).map_err(de::Error::custom)
+    }
+
+    /// Parse hex bytes separated by optional whitespace
+    pub fn parse(text: &str) -> Result<Vec<u8>, String> {
+        let digits: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+        if !digits.len().is_multiple_of(2) {
+            return Err(format!("odd number of hex digits in \"{text}\""));
+        }
+
+        (0..digits.len())
+            .step_by(2)
+            .map(|i| {
+                u8::from_str_radix(&digits[i..i + 2], 16).map_err(|_| {
+                    format!("invalid hex byte \"{}\" in \"{text}\"", &digits[i..i + 2])
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,175 @@
+use crate::mapping::{MAIN_TEMPO_DOMAIN, MapConfig, TapInput};
+#[cfg(any(feature = "http", feature = "osc-input"))]
+use std::collections::BTreeMap;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tracing::info;
+
+/// Current tempo of each tempo domain
+pub struct TempoDomains {
+    domains: HashMap<String, DomainTempo>,
+}
+
+struct DomainTempo {
+    bpm: Option<f64>,
+    // Time of the last change, for rate limiting
+    changed_at: Option<Instant>,
+    // Bumped on every change so running tap tempo sequences stop
+    taps: watch::Sender<u64>,
+}
+
+impl TempoDomains {
+    pub fn new() -> Self {
+        Self {
+            domains: HashMap::new(),
+        }
+    }
+
+    /// Current tempo of a domain, if one has been received
+    pub fn bpm(&self, domain: &str) -> Option<f64> {
+        self.domains.get(domain).and_then(|tempo| tempo.bpm)
+    }
+
+    /// Current tempo of every domain that has received one
+    #[cfg(any(feature = "http", feature = "osc-input"))]
+    pub fn all(&self) -> BTreeMap<String, f64> {
+        self.domains
+            .iter()
+            .filter_map(|(name, tempo)| Some((name.clone(), tempo.bpm?)))
+            .collect()
+    }
+
+    /// Set a domain's tempo, limiting how fast it can change, and cancel its running
+    /// tap tempo sequences. Returns the tempo applied and a token for the new taps.
+    pub fn set(
+        &mut self,
+        domain: &str,
+        bpm: f64,
+        max_change_per_sec: Option<f64>,
+    ) -> (f64, TapToken) {
+        let tempo = self.entry(domain);
+        let bpm = match (tempo.bpm, tempo.changed_at, max_change_per_sec) {
+            (Some(current), Some(changed), Some(max_change)) => {
+                let max_delta = max_change * changed.elapsed().as_secs_f64();
+                let limited = bpm.clamp(current - max_delta, current + max_delta);
+                if limited != bpm {
+                    info!(
+                        "Limiting tempo change from {:.1} to {:.1} BPM (requested {:.1})",
+                        current, limited, bpm
+                    );
+                }
+                limited
+            }
+            _ => bpm,
+        };
+        tempo.bpm = Some(bpm);
+        tempo.changed_at = Some(Instant::now());
+        tempo.taps.send_modify(|id| *id += 1);
+        (bpm, TapToken::new(&tempo.taps))
+    }
+
+    /// Token for tap tempo sequences at a domain's current tempo, cancelled by its next change
+    pub fn tap_token(&mut self, domain: &str) -> TapToken {
+        TapToken::new(&self.entry(domain).taps)
+    }
+
+    fn entry(&mut self, domain: &str) -> &mut DomainTempo {
+        self.domains
+            .entry(domain.to_string())
+            .or_insert_with(|| DomainTempo {
+                bpm: None,
+                changed_at: None,
+                taps: watch::channel(0).0,
+            })
+    }
+}
+
+/// Lets a tap tempo sequence notice that a newer tempo replaced it
+#[derive(Clone)]
+pub struct TapToken {
+    id: u64,
+    receiver: watch::Receiver<u64>,
+}
+
+impl TapToken {
+    fn new(taps: &watch::Sender<u64>) -> Self {
+        let receiver = taps.subscribe();
+        let id = *receiver.borrow();
+        Self { id, receiver }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.receiver.borrow() != self.id
+    }
+
+    /// Wait until a newer tempo replaces this one
+    pub async fn cancelled(&mut self) {
+        while !self.is_cancelled() {
+            if self.receiver.changed().await.is_err() {
+                std::future::pending::<()>().await;
+            }
+        }
+    }
+}
+
+/// Recent taps of each tap input
+#[derive(Debug, Default)]
+pub struct TapTimes {
+    taps: HashMap<String, VecDeque<Instant>>,
+}
+
+impl TapTimes {
+    /// Record a tap, returning the tempo of the average recent interval once there
+    /// have been two taps within the input's timeout
+    pub fn tap(&mut self, input: &TapInput, now: Instant) -> Option<f64> {
+        let taps = self.taps.entry(input.name.clone()).or_default();
+        let timeout = Duration::from_millis(input.timeout_ms);
+        if taps.back().is_some_and(|last| now - *last > timeout) {
+            taps.clear();
+        }
+        taps.push_back(now);
+        while taps.len() > input.average.max(1) + 1 {
+            taps.pop_front();
+        }
+
+        let (first, last) = (taps.front()?, taps.back()?);
+        let intervals = taps.len() - 1;
+        if intervals == 0 {
+            return None;
+        }
+        let interval = (*last - *first).as_secs_f64() / intervals as f64;
+        (interval > 0.0).then(|| 60.0 / interval)
+    }
+}
+
+/// Tempo domain a device follows
+pub fn device_domain<'a>(map_config: &'a MapConfig, device_id: &str) -> &'a str {
+    map_config
+        .tempo_domains
+        .iter()
+        .find(|domain| domain.devices.iter().any(|id| id == device_id))
+        .map_or(MAIN_TEMPO_DOMAIN, |domain| domain.name.as_str())
+}
+
+/// Tempo domain set by `/tempo/raw` from an OSC source
+pub fn source_domain<'a>(map_config: &'a MapConfig, source: &str) -> &'a str {
+    map_config
+        .tempo_domains
+        .iter()
+        .find(|domain| domain.sources.iter().any(|name| name == source))
+        .map_or(MAIN_TEMPO_DOMAIN, |domain| domain.name.as_str())
+}
+
+/// Whether a tempo domain is configured
+pub fn has_domain(map_config: &MapConfig, domain: &str) -> bool {
+    domain == MAIN_TEMPO_DOMAIN
+        || map_config
+            .tempo_domains
+            .iter()
+            .any(|configured| configured.name == domain)
+}
@@ -0,0 +1,183 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Most samples kept per domain or source, so a flooding source can't grow them unbounded
+const MAX_SAMPLES: usize = 10_000;
+
+/// Tempo changes of each tempo domain and tempo messages from each source over a recent
+/// window, for telling a drifting click source from a jittery network
+#[derive(Default)]
+pub struct TempoStats {
+    /// Tempos applied to each domain
+    domains: HashMap<String, VecDeque<(Instant, f64)>>,
+    /// Tempos received from each source, before smoothing and limits
+    sources: HashMap<String, Samples>,
+}
+
+/// Tempo messages received from one source
+struct Samples {
+    domain: String,
+    samples: VecDeque<(Instant, f64)>,
+}
+
+/// Statistics of every tempo domain and tempo source
+#[derive(Debug, Clone, Serialize)]
+pub struct TempoReport {
+    /// Time covered by the statistics
+    pub window_secs: u64,
+    pub domains: Vec<DomainStats>,
+    pub sources: Vec<SourceStats>,
+}
+
+/// Tempo of one domain over the window
+#[derive(Debug, Clone, Serialize)]
+pub struct DomainStats {
+    pub domain: String,
+    /// Current tempo
+    pub bpm: f64,
+    /// Lowest and highest tempo, including the current one
+    pub min_bpm: f64,
+    pub max_bpm: f64,
+    /// Tempo changes applied
+    pub changes: usize,
+}
+
+/// Tempo messages from one source over the window
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceStats {
+    pub source: String,
+    /// Tempo domain the source sets
+    pub domain: String,
+    pub messages: usize,
+    /// Most recent tempo received
+    pub last_bpm: f64,
+    /// Standard deviation of the tempos received
+    pub bpm_jitter: f64,
+    /// Mean time between messages
+    pub interval_ms: f64,
+    /// Standard deviation of the time between messages
+    pub interval_jitter_ms: f64,
+}
+
+impl TempoStats {
+    /// Record a tempo applied to a domain
+    pub fn record_tempo(&mut self, domain: &str, bpm: f64, window: Duration) {
+        let samples = self.domains.entry(domain.to_string()).or_default();
+        push(samples, bpm, window);
+    }
+
+    /// Record a tempo message received from a source
+    #[cfg(feature = "osc-input")]
+    pub fn record_source(&mut self, source: &str, domain: &str, bpm: f64, window: Duration) {
+        let samples = self
+            .sources
+            .entry(source.to_string())
+            .or_insert_with(|| Samples {
+                domain: domain.to_string(),
+                samples: VecDeque::new(),
+            });
+        samples.domain = domain.to_string();
+        push(&mut samples.samples, bpm, window);
+    }
+
+    /// Statistics over the window, given each domain's current tempo
+    pub fn report(&mut self, window: Duration, current: &BTreeMap<String, f64>) -> TempoReport {
+        let cutoff = Instant::now().checked_sub(window);
+        let domains = current
+            .iter()
+            .map(|(domain, &bpm)| {
+                let samples = self.domains.get_mut(domain);
+                let recent: Vec<f64> = samples
+                    .map(|samples| {
+                        trim(samples, cutoff);
+                        samples.iter().map(|&(_, bpm)| bpm).collect()
+                    })
+                    .unwrap_or_default();
+                DomainStats {
+                    domain: domain.clone(),
+                    bpm,
+                    min_bpm: recent.iter().copied().fold(bpm, f64::min),
+                    max_bpm: recent.iter().copied().fold(bpm, f64::max),
+                    changes: recent.len(),
+                }
+            })
+            .collect();
+
+        self.sources.retain(|_, source| {
+            trim(&mut source.samples, cutoff);
+            !source.samples.is_empty()
+        });
+        let mut sources: Vec<SourceStats> = self
+            .sources
+            .iter()
+            .map(|(name, source)| {
+                let bpms: Vec<f64> = source.samples.iter().map(|&(_, bpm)| bpm).collect();
+                let intervals: Vec<f64> = source
+                    .samples
+                    .iter()
+                    .zip(source.samples.iter().skip(1))
+                    .map(|((earlier, _), (later, _))| {
+                        later.duration_since(*earlier).as_secs_f64() * 1000.0
+                    })
+                    .collect();
+                SourceStats {
+                    source: name.clone(),
+                    domain: source.domain.clone(),
+                    messages: bpms.len(),
+                    last_bpm: bpms.last().copied().unwrap_or_default(),
+                    bpm_jitter: deviation(&bpms),
+                    interval_ms: mean(&intervals),
+                    interval_jitter_ms: deviation(&intervals),
+                }
+            })
+            .collect();
+        sources.sort_by(|a, b| a.source.cmp(&b.source));
+
+        TempoReport {
+            window_secs: window.as_secs(),
+            domains,
+            sources,
+        }
+    }
+}
+
+/// Add a sample, dropping those older than the window
+fn push(samples: &mut VecDeque<(Instant, f64)>, bpm: f64, window: Duration) {
+    let now = Instant::now();
+    trim(samples, now.checked_sub(window));
+    if samples.len() == MAX_SAMPLES {
+        samples.pop_front();
+    }
+    samples.push_back((now, bpm));
+}
+
+fn trim(samples: &mut VecDeque<(Instant, f64)>, cutoff: Option<Instant>) {
+    let Some(cutoff) = cutoff else {
+        return;
+    };
+    while samples.front().is_some_and(|&(time, _)| time < cutoff) {
+        samples.pop_front();
+    }
+}
+
+fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// Population standard deviation
+fn deviation(values: &[f64]) -> f64 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+    let mean = mean(values);
+    let variance = values
+        .iter()
+        .map(|value| (value - mean).powi(2))
+        .sum::<f64>()
+        / values.len() as f64;
+    variance.sqrt()
+}
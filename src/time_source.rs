@@ -0,0 +1,92 @@
+use crate::mapping::{ClockSource, TimeSourceConfig};
+use crate::supervisor::supervise;
+use rosc::OscTime;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// How often the wall clock is compared against the monotonic clock
+const STEP_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Wall-clock time for absolute timestamps sent to peers, such as OSC bundle timetags
+pub struct TimeSource {
+    config: TimeSourceConfig,
+    /// Wall-clock time and monotonic time captured together at startup
+    anchor: (SystemTime, Instant),
+}
+
+impl TimeSource {
+    pub fn new(config: TimeSourceConfig) -> Self {
+        if config.offset_ms != 0 || config.source != ClockSource::System {
+            info!(
+                "Using {:?} clock with an offset of {}ms for timestamps",
+                config.source, config.offset_ms
+            );
+        }
+        Self {
+            config,
+            anchor: (SystemTime::now(), Instant::now()),
+        }
+    }
+
+    /// Current time with the configured offset applied
+    pub fn now(&self) -> SystemTime {
+        let now = match self.config.source {
+            ClockSource::System => SystemTime::now(),
+            ClockSource::Monotonic => self.anchor.0 + self.anchor.1.elapsed(),
+        };
+        let offset = Duration::from_millis(self.config.offset_ms.unsigned_abs());
+        if self.config.offset_ms >= 0 {
+            now + offset
+        } else {
+            now - offset
+        }
+    }
+
+    /// OSC timetag for a moment `delay` from now
+    pub fn timetag_in(&self, delay: Duration) -> OscTime {
+        // Only fails for times before 1900
+        OscTime::try_from(self.now() + delay).unwrap_or(OscTime {
+            seconds: 0,
+            fractional: 1,
+        })
+    }
+
+    /// Warn whenever the system clock steps, e.g. when NTP corrects it
+    pub fn start_monitor(self: &Arc<Self>) -> JoinHandle<()> {
+        let time_source = Arc::clone(self);
+        supervise("clock step monitor", move || {
+            let time_source = Arc::clone(&time_source);
+            async move {
+                let threshold = Duration::from_millis(time_source.config.step_threshold_ms);
+                let mut interval = tokio::time::interval(STEP_CHECK_INTERVAL);
+                let mut last = (SystemTime::now(), Instant::now());
+                loop {
+                    interval.tick().await;
+                    let now = (SystemTime::now(), Instant::now());
+                    let monotonic = now.1 - last.1;
+                    let (step, forward) = match now.0.duration_since(last.0) {
+                        Ok(wall) if wall >= monotonic => (wall - monotonic, true),
+                        Ok(wall) => (monotonic - wall, false),
+                        Err(e) => (monotonic + e.duration(), false),
+                    };
+                    if step >= threshold {
+                        warn!(
+                            step_ms = step.as_millis() as u64,
+                            "System clock stepped {} by {}ms{}",
+                            if forward { "forward" } else { "back" },
+                            step.as_millis(),
+                            match time_source.config.source {
+                                ClockSource::System => "; timestamps sent to peers jumped with it",
+                                ClockSource::Monotonic =>
+                                    "; timestamps keep following the startup clock",
+                            }
+                        );
+                    }
+                    last = now;
+                }
+            }
+        })
+    }
+}
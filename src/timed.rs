@@ -0,0 +1,159 @@
+use crate::device::{CommandBatch, SequenceStep};
+use crate::processor::MidiProcessor;
+use crate::supervisor::supervise;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+
+/// How often timed programs are checked for batches whose pause has passed
+const TICK_INTERVAL: Duration = Duration::from_millis(5);
+
+/// The rest of a program paused between its commands
+pub struct TimedRun {
+    /// Program being run, for logs
+    program: String,
+    batches: VecDeque<CommandBatch>,
+    /// MIDI channel the commands are sent on
+    channel: Option<u8>,
+    /// When the next batch is due; `None` while the previous batch is being sent
+    due: Option<Instant>,
+    /// Sequence started once the last batch has been sent, with the device it is for
+    sequence: Option<(String, Vec<SequenceStep>)>,
+}
+
+impl TimedRun {
+    /// The batches left of a program, the first of them starting with a pause
+    pub fn new(program: String, batches: Vec<CommandBatch>, channel: Option<u8>) -> Self {
+        Self {
+            program,
+            batches: batches.into(),
+            channel,
+            due: None,
+            sequence: None,
+        }
+    }
+
+    /// Start a device's program sequence once the run has finished
+    pub fn then_sequence(mut self, device_id: &str, steps: &[SequenceStep]) -> Self {
+        if !steps.is_empty() {
+            self.sequence = Some((device_id.to_string(), steps.to_vec()));
+        }
+        self
+    }
+}
+
+/// A batch of a timed program whose pause has passed
+pub struct DueBatch {
+    /// Mapping the program runs through
+    pub key: String,
+    /// Program the batch belongs to
+    pub program: String,
+    pub batch: CommandBatch,
+    pub channel: Option<u8>,
+}
+
+/// A sequence to start now that the program that declares it has finished
+pub struct FinishedRun {
+    pub program: String,
+    pub device_id: String,
+    pub steps: Vec<SequenceStep>,
+}
+
+/// Programs waiting between their commands, at most one per mapping
+#[derive(Default)]
+pub struct TimedPrograms {
+    running: HashMap<String, TimedRun>,
+}
+
+impl TimedPrograms {
+    /// Start sending the rest of a program once its first pause has passed
+    pub fn start(&mut self, key: &str, mut run: TimedRun, now: Instant) {
+        if let Some(first) = run.batches.front() {
+            run.due = Some(now + Duration::from_millis(first.delay_ms));
+            self.running.insert(key.to_string(), run);
+        }
+    }
+
+    /// Stop a mapping's unfinished program, returning its name
+    pub fn cancel(&mut self, key: &str) -> Option<String> {
+        self.running.remove(key).map(|cancelled| cancelled.program)
+    }
+
+    /// Stop every unfinished program, returning how many there were
+    #[cfg(feature = "tui")]
+    pub fn cancel_all(&mut self) -> usize {
+        self.running.drain().count()
+    }
+
+    /// Remove and return the batches whose pause has passed. Their programs wait for
+    /// `sent` before the next pause starts.
+    pub fn take_due(&mut self, now: Instant) -> Vec<DueBatch> {
+        let mut due = Vec::new();
+        for (key, run) in &mut self.running {
+            if run.due.is_none_or(|at| at > now) {
+                continue;
+            }
+            let Some(batch) = run.batches.pop_front() else {
+                continue;
+            };
+            due.push(DueBatch {
+                key: key.clone(),
+                program: run.program.clone(),
+                batch,
+                channel: run.channel,
+            });
+            run.due = None;
+        }
+        due
+    }
+
+    /// Start the pause before the next batch of a program after one has been sent,
+    /// returning its sequence if that was the last batch
+    pub fn sent(&mut self, key: &str, program: &str, now: Instant) -> Option<FinishedRun> {
+        let run = self
+            .running
+            .get_mut(key)
+            .filter(|run| run.due.is_none() && run.program == program)?;
+        if let Some(next) = run.batches.front() {
+            run.due = Some(now + Duration::from_millis(next.delay_ms));
+            return None;
+        }
+        let run = self.running.remove(key)?;
+        let (device_id, steps) = run.sequence?;
+        Some(FinishedRun {
+            program: run.program,
+            device_id,
+            steps,
+        })
+    }
+}
+
+/// Sends the commands of timed programs once their pauses have passed
+pub struct TimedProgramMonitor {
+    processor: Arc<MidiProcessor>,
+}
+
+impl TimedProgramMonitor {
+    pub fn new(processor: Arc<MidiProcessor>) -> Self {
+        Self { processor }
+    }
+
+    /// Start sending timed commands in the background
+    pub fn start(&self) -> JoinHandle<()> {
+        let processor = Arc::clone(&self.processor);
+
+        supervise("timed programs", move || {
+            let processor = Arc::clone(&processor);
+            async move {
+                let mut interval = tokio::time::interval(TICK_INTERVAL);
+                interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+                loop {
+                    interval.tick().await;
+                    processor.run_due_timed_batches().await;
+                }
+            }
+        })
+    }
+}
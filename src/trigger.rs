@@ -0,0 +1,151 @@
+use crate::mapping::{ComboMapping, Trigger};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+/// Incoming event that may activate a trigger
+#[derive(Debug, Clone, Copy)]
+pub enum TriggerEvent {
+    /// A note was pressed
+    NotePressed { channel: u8, note: u8 },
+    /// A controller value was received, along with its previous value
+    ControlChange {
+        channel: u8,
+        controller: u8,
+        previous: Option<u8>,
+        value: u8,
+    },
+}
+
+/// Matching of config triggers against incoming events
+pub trait Activation {
+    /// Whether this event activates the trigger (note press or CC rising past threshold)
+    fn is_activated_by(&self, event: &TriggerEvent) -> bool;
+}
+
+impl Activation for Trigger {
+    fn is_activated_by(&self, event: &TriggerEvent) -> bool {
+        match (self, event) {
+            (
+                Trigger::Note { channel, note },
+                TriggerEvent::NotePressed {
+                    channel: event_channel,
+                    note: event_note,
+                },
+            ) => channel == event_channel && note == event_note,
+            (
+                Trigger::ControlChange {
+                    channel,
+                    controller,
+                    threshold,
+                },
+                TriggerEvent::ControlChange {
+                    channel: event_channel,
+                    controller: event_controller,
+                    previous,
+                    value,
+                },
+            ) => {
+                channel == event_channel
+                    && controller == event_controller
+                    && value >= threshold
+                    && previous.is_none_or(|previous| previous < *threshold)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Tracks held notes and controller values to evaluate combinatorial triggers
+pub struct TriggerTracker {
+    /// Held notes keyed by (channel, note) with the time they were pressed
+    notes: HashMap<(u8, u8), Instant>,
+    /// Levels each controller has stayed at or above keyed by (channel, controller), with
+    /// the time since when, lowest first; the last level is the current value
+    controllers: HashMap<(u8, u8), Vec<(u8, Instant)>>,
+    /// Indices of combos that have fired and not yet been released
+    fired: HashSet<usize>,
+}
+
+impl TriggerTracker {
+    pub fn new() -> Self {
+        Self {
+            notes: HashMap::new(),
+            controllers: HashMap::new(),
+            fired: HashSet::new(),
+        }
+    }
+
+    /// Record a note being pressed or released
+    pub fn note(&mut self, channel: u8, note: u8, pressed: bool) {
+        if pressed {
+            self.notes.insert((channel, note), Instant::now());
+        } else {
+            self.notes.remove(&(channel, note));
+        }
+    }
+
+    /// Record a controller value, returning the previous value if any
+    pub fn control_change(&mut self, channel: u8, controller: u8, value: u8) -> Option<u8> {
+        let levels = self.controllers.entry((channel, controller)).or_default();
+        let previous = levels.last().map(|&(level, _)| level);
+        // A value that doesn't drop below a threshold keeps the time it was crossed
+        let mut since = Instant::now();
+        while let Some(&(level, time)) = levels.last()
+            && level >= value
+        {
+            since = time;
+            levels.pop();
+        }
+        levels.push((value, since));
+        previous
+    }
+
+    /// Return the indices of combos that have just become satisfied
+    pub fn evaluate(&mut self, combos: &[ComboMapping]) -> Vec<usize> {
+        let mut newly_fired = Vec::new();
+
+        for (index, combo) in combos.iter().enumerate() {
+            let times: Option<Vec<Instant>> = combo
+                .triggers
+                .iter()
+                .map(|trigger| self.activation_time(trigger))
+                .collect();
+
+            match times {
+                Some(times) if !times.is_empty() => {
+                    let first = times.iter().min().copied().unwrap_or_else(Instant::now);
+                    let last = times.iter().max().copied().unwrap_or_else(Instant::now);
+                    let within_window =
+                        last.duration_since(first) <= Duration::from_millis(combo.window_ms);
+
+                    if within_window && self.fired.insert(index) {
+                        newly_fired.push(index);
+                    }
+                }
+                _ => {
+                    // At least one trigger released, so the combo can fire again
+                    self.fired.remove(&index);
+                }
+            }
+        }
+
+        newly_fired
+    }
+
+    /// Time at which a trigger became active, if it currently is
+    fn activation_time(&self, trigger: &Trigger) -> Option<Instant> {
+        match trigger {
+            Trigger::Note { channel, note } => self.notes.get(&(*channel, *note)).copied(),
+            Trigger::ControlChange {
+                channel,
+                controller,
+                threshold,
+            } => self
+                .controllers
+                .get(&(*channel, *controller))?
+                .iter()
+                .find(|(level, _)| level >= threshold)
+                .map(|&(_, time)| time),
+        }
+    }
+}
@@ -0,0 +1,258 @@
+use crate::processor::MidiProcessor;
+use crate::session_manager::SessionStatus;
+use crate::status::RouterStatus;
+use anyhow::Result;
+use futures::StreamExt;
+use ratatui::crossterm::event::{Event, EventStream, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Cell, List, ListItem, Paragraph, Row, Table};
+use ratatui::{DefaultTerminal, Frame};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::error;
+
+/// How often the display is refreshed
+const REFRESH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Everything shown on one frame, gathered before drawing
+struct Snapshot {
+    bpm: Option<f64>,
+    banks: Vec<String>,
+    show_locked: bool,
+    read_only: bool,
+    sessions: Vec<SessionStatus>,
+    programs: Vec<(String, String)>,
+    recent: Vec<String>,
+    message: String,
+}
+
+/// Show live router state in the terminal until the user quits
+pub async fn run(status: Arc<RouterStatus>) {
+    let mut terminal = ratatui::init();
+    if let Err(e) = event_loop(&mut terminal, &status).await {
+        error!("Terminal display failed: {}", e);
+    }
+    ratatui::restore();
+}
+
+async fn event_loop(terminal: &mut DefaultTerminal, status: &RouterStatus) -> Result<()> {
+    let mut events = EventStream::new();
+    let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+    let mut message = String::from("Ready");
+
+    loop {
+        let snapshot = snapshot(status, &message).await;
+        terminal.draw(|frame| draw(frame, &snapshot))?;
+
+        tokio::select! {
+            _ = interval.tick() => {}
+            event = events.next() => {
+                let Some(Event::Key(key)) = event.transpose()? else {
+                    continue;
+                };
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                if key.code == KeyCode::Char('q')
+                    || key.code == KeyCode::Esc
+                    || (key.code == KeyCode::Char('c')
+                        && key.modifiers.contains(KeyModifiers::CONTROL))
+                {
+                    return Ok(());
+                }
+                if let Some(outcome) = handle_key(status, key.code).await {
+                    message = outcome;
+                }
+            }
+        }
+    }
+}
+
+/// Run the action bound to a key, describing its outcome
+async fn handle_key(status: &RouterStatus, code: KeyCode) -> Option<String> {
+    let outcome = match code {
+        KeyCode::Char('p') => {
+            for_each_processor(status, "Panic sent", |processor| async move {
+                processor.panic().await
+            })
+            .await
+        }
+        KeyCode::Char('r') => {
+            status.request_reload();
+            "Reload requested".to_string()
+        }
+        KeyCode::Right | KeyCode::Char('n') => {
+            for_each_processor(status, "Next bank", |processor| async move {
+                processor.step_bank(true).await
+            })
+            .await
+        }
+        KeyCode::Left | KeyCode::Char('b') => {
+            for_each_processor(status, "Previous bank", |processor| async move {
+                processor.step_bank(false).await
+            })
+            .await
+        }
+        _ => return None,
+    };
+    Some(outcome)
+}
+
+/// Run an action on every routing context, describing the outcome
+async fn for_each_processor<F, Fut>(status: &RouterStatus, done: &str, action: F) -> String
+where
+    F: Fn(Arc<MidiProcessor>) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    for (context, processor) in status.processors().await {
+        if let Err(e) = action(processor).await {
+            return format!("{done} failed in '{context}': {e}");
+        }
+    }
+    done.to_string()
+}
+
+async fn snapshot(status: &RouterStatus, message: &str) -> Snapshot {
+    let mut snapshot = Snapshot {
+        bpm: None,
+        banks: Vec::new(),
+        show_locked: status.show_locked().await,
+        read_only: status.is_read_only(),
+        sessions: status.session_statuses().await,
+        programs: Vec::new(),
+        recent: Vec::new(),
+        message: message.to_string(),
+    };
+
+    for (_, processor) in status.processors().await {
+        snapshot.bpm = snapshot.bpm.or(processor.current_bpm().await);
+        if let Some(bank) = processor.active_bank_name().await {
+            snapshot.banks.push(bank);
+        }
+        snapshot.programs.extend(processor.active_programs().await);
+        snapshot.recent.extend(processor.recent_inputs().await);
+    }
+    snapshot
+}
+
+fn draw(frame: &mut Frame, snapshot: &Snapshot) {
+    let [header, sessions, lower, footer] = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Length(snapshot.sessions.len() as u16 + 3),
+        Constraint::Min(5),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let [programs, recent] =
+        Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)]).areas(lower);
+
+    // Tempo, bank and modes, large enough to read from a distance
+    let mut state = vec![
+        format!(
+            "BPM {}",
+            snapshot
+                .bpm
+                .map(|bpm| format!("{bpm:.1}"))
+                .unwrap_or_else(|| "-".to_string())
+        ),
+        format!(
+            "Bank {}",
+            if snapshot.banks.is_empty() {
+                "-".to_string()
+            } else {
+                snapshot.banks.join(", ")
+            }
+        ),
+    ];
+    if snapshot.show_locked {
+        state.push("LOCKED".to_string());
+    }
+    if snapshot.read_only {
+        state.push("READ-ONLY".to_string());
+    }
+    frame.render_widget(
+        Paragraph::new(state.join("   "))
+            .style(Style::default().add_modifier(Modifier::BOLD))
+            .block(Block::bordered().title(" MIDI Router ")),
+        header,
+    );
+
+    let rows = snapshot.sessions.iter().map(|session| {
+        let color = if !session.bound {
+            Color::Red
+        } else if session.participants == 0 {
+            Color::Yellow
+        } else {
+            Color::Green
+        };
+        Row::new(vec![
+            Cell::from(session.name.clone()),
+            Cell::from(session.port.to_string()),
+            Cell::from(session.participants.to_string()),
+            Cell::from(session.messages_received.to_string()),
+            Cell::from(session.messages_sent.to_string()),
+            Cell::from(
+                session
+                    .last_received_ms_ago
+                    .map(|ms| format!("{:.1}s", ms as f64 / 1000.0))
+                    .unwrap_or_else(|| "-".to_string()),
+            ),
+        ])
+        .style(Style::default().fg(color))
+    });
+    frame.render_widget(
+        Table::new(
+            rows,
+            [
+                Constraint::Fill(2),
+                Constraint::Length(6),
+                Constraint::Length(6),
+                Constraint::Length(9),
+                Constraint::Length(9),
+                Constraint::Length(9),
+            ],
+        )
+        .header(
+            Row::new(vec![
+                "Session", "Port", "Peers", "Received", "Sent", "Last rx",
+            ])
+            .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .block(Block::bordered().title(" Sessions ")),
+        sessions,
+    );
+
+    let items: Vec<ListItem> = snapshot
+        .programs
+        .iter()
+        .map(|(device, program)| ListItem::new(format!("{device}: {program}")))
+        .collect();
+    frame.render_widget(
+        List::new(items).block(Block::bordered().title(" Active programs ")),
+        programs,
+    );
+
+    // Newest messages at the top
+    let visible = recent.height.saturating_sub(2) as usize;
+    let items: Vec<ListItem> = snapshot
+        .recent
+        .iter()
+        .rev()
+        .take(visible)
+        .map(|line| ListItem::new(line.as_str()))
+        .collect();
+    frame.render_widget(
+        List::new(items).block(Block::bordered().title(" Recent messages ")),
+        recent,
+    );
+
+    frame.render_widget(
+        Line::from(format!(
+            " q quit  p panic  r reload  ←/→ bank  |  {}",
+            snapshot.message
+        )),
+        footer,
+    );
+}
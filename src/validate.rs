@@ -0,0 +1,83 @@
+use crate::cli::RunArgs;
+use crate::config::ConfigLoader;
+use crate::context::ContextsFile;
+use crate::validator::{ConfigFile, ConfigValidator};
+use anyhow::{Context, Result, bail};
+use std::path::{Path, PathBuf};
+
+/// Check the configs (or every enabled context's configs) and print each problem
+/// as `file: path: problem`, failing if any were found
+pub fn run(current: &RunArgs) -> Result<()> {
+    let pairs = config_pairs(current)?;
+
+    let mut count = 0;
+    for (device_path, map_path) in &pairs {
+        let problems = check(device_path, map_path);
+        for problem in &problems {
+            println!("{problem}");
+        }
+        count += problems.len();
+    }
+
+    if count > 0 {
+        bail!("Found {count} problem(s) in the configs");
+    }
+    println!("Configs are valid");
+    Ok(())
+}
+
+/// Device and map config paths of every enabled context, or the current configs
+pub fn config_pairs(current: &RunArgs) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let Some(ref contexts_path) = current.contexts else {
+        return Ok(vec![(
+            current.device_config.clone(),
+            current.map_config.clone(),
+        )]);
+    };
+    let content = std::fs::read_to_string(contexts_path)
+        .with_context(|| format!("Failed to read contexts file: {contexts_path:?}"))?;
+    let file: ContextsFile = serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse contexts file: {contexts_path:?}"))?;
+    Ok(file
+        .contexts
+        .into_iter()
+        .filter(|context| context.enabled)
+        .map(|context| (context.device_config, context.map_config))
+        .collect())
+}
+
+/// Problems in one pair of configs, including unknown keys, prefixed with the file they were found in
+pub fn check(device_path: &Path, map_path: &Path) -> Vec<String> {
+    let device_config = ConfigLoader::read_device_config(device_path);
+    let map_config = ConfigLoader::read_map_config(map_path);
+    match (device_config, map_config) {
+        (Ok((device_config, device_unknown)), Ok((map_config, map_unknown))) => {
+            let unknown = [(device_path, device_unknown), (map_path, map_unknown)]
+                .into_iter()
+                .flat_map(|(path, unknown)| {
+                    unknown.into_iter().map(move |key| {
+                        format!("{}: {}: unknown key is ignored", path.display(), key)
+                    })
+                });
+            let problems = ConfigValidator::new(&device_config, &map_config)
+                .problems()
+                .into_iter()
+                .map(|problem| {
+                    let path = match problem.file {
+                        ConfigFile::Device => device_path,
+                        ConfigFile::Map => map_path,
+                    };
+                    format!("{}: {}: {}", path.display(), problem.path, problem.message)
+                });
+            unknown.chain(problems).collect()
+        }
+        // Cross-reference checks need both configs, so report why they failed to load
+        (device_config, map_config) => [
+            (device_path, device_config.err()),
+            (map_path, map_config.err()),
+        ]
+        .into_iter()
+        .filter_map(|(path, error)| Some(format!("{}: {:#}", path.display(), error?)))
+        .collect(),
+    }
+}
@@ -0,0 +1,1283 @@
+//! Cross-reference checks between a device config and a map config.
+
+use crate::device::{Command, CommandValue, DeviceConfig, Program, SequenceStep, TempoSpec};
+use crate::mapping::{
+    BankSelector, CaptureSource, Destination, EventSink, MAIN_TEMPO_DOMAIN, MapConfig, NoteAction,
+    Trigger, ValueClamp,
+};
+use crate::midi_fields::{self, MidiFields, MidiMessageKind};
+use anyhow::{Result, bail};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+/// A problem found by the cross-reference checks
+#[derive(Debug, Clone)]
+pub struct Problem {
+    /// Config file the offending value is in
+    pub file: ConfigFile,
+    /// JSON path of the offending value, e.g. `device_mappings[3].device_id`
+    pub path: String,
+    pub message: String,
+}
+
+/// Which of the two configs a problem is in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFile {
+    Device,
+    Map,
+}
+
+impl Problem {
+    /// A problem in the map config
+    fn new(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            file: ConfigFile::Map,
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+
+    /// A problem in the device config
+    fn device(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            file: ConfigFile::Device,
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}`: {}", self.path, self.message)
+    }
+}
+
+/// Checks that a device config and a map config refer to each other consistently,
+/// so typos surface when the configs are loaded instead of at show time
+pub struct ConfigValidator<'a> {
+    device_config: &'a DeviceConfig,
+    map_config: &'a MapConfig,
+}
+
+impl<'a> ConfigValidator<'a> {
+    pub fn new(device_config: &'a DeviceConfig, map_config: &'a MapConfig) -> Self {
+        Self {
+            device_config,
+            map_config,
+        }
+    }
+
+    /// Check the configs, failing with every problem found
+    pub fn validate(&self) -> Result<()> {
+        let problems = self.problems();
+        if !problems.is_empty() {
+            let problems: Vec<String> = problems.iter().map(Problem::to_string).collect();
+            bail!("Invalid configuration:\n  - {}", problems.join("\n  - "));
+        }
+        Ok(())
+    }
+
+    /// Every problem found in the configs
+    pub fn problems(&self) -> Vec<Problem> {
+        let mut problems = Vec::new();
+        self.check_devices(&mut problems);
+        self.check_sequences(&mut problems);
+        self.check_destinations(&mut problems);
+        self.check_command_destinations(&mut problems);
+        self.check_channels(&mut problems);
+        self.check_ports(&mut problems);
+        self.check_tempo_domains(&mut problems);
+        self.check_tempo_stats(&mut problems);
+        self.check_tap_inputs(&mut problems);
+        self.check_clock_outputs(&mut problems);
+        self.check_link(&mut problems);
+        self.check_step_mappings(&mut problems);
+        self.check_command_values(&mut problems);
+        self.check_tempo_displays(&mut problems);
+        self.check_event_sinks(&mut problems);
+        self.check_osc_control_rules(&mut problems);
+        self.check_osc_forward_rules(&mut problems);
+        self.check_value_clamps(&mut problems);
+        self.check_shaping(&mut problems);
+        problems
+    }
+
+    /// Device IDs referenced by mappings must be configured
+    fn check_devices(&self, problems: &mut Vec<Problem>) {
+        let map = self.map_config;
+        let mut referenced: Vec<(String, String, &str)> = map
+            .device_mappings
+            .iter()
+            .enumerate()
+            .map(|(i, m)| {
+                (
+                    format!("device mapping for '{}'", m.device_id),
+                    format!("device_mappings[{i}].device_id"),
+                    m.device_id.as_str(),
+                )
+            })
+            .collect();
+        for (i, mapping) in map.note_mappings.iter().enumerate() {
+            if let NoteAction::Program { ref device_id, .. } = mapping.action {
+                referenced.push((
+                    format!("note mapping '{}'", mapping.name),
+                    format!("note_mappings[{i}].action.device_id"),
+                    device_id,
+                ));
+            }
+        }
+
+        for (what, path, device_id) in referenced {
+            if self.device_config.get_device(device_id).is_none() {
+                problems.push(Problem::new(
+                    path,
+                    format!("{what} refers to unknown device '{device_id}'"),
+                ));
+            }
+        }
+    }
+
+    /// Sessions, OSC destinations and destination groups referenced by mappings must be
+    /// configured, and OSC heartbeats must have a valid address
+    fn check_destinations(&self, problems: &mut Vec<Problem>) {
+        let sessions = self.session_names();
+        for (what, path, destination) in self.destinations() {
+            self.check_destination(&sessions, &what, &path, destination, problems);
+        }
+
+        let mut groups: Vec<_> = self.map_config.destination_groups.iter().collect();
+        groups.sort_by_key(|(name, _)| *name);
+        for (group_name, members) in groups {
+            let path = format!("destination_groups.{group_name}");
+            if members.is_empty() {
+                problems.push(Problem::new(
+                    path.clone(),
+                    format!("Destination group '{group_name}' has no members"),
+                ));
+            }
+            for (i, member) in members.iter().enumerate() {
+                let member_path = format!("{path}[{i}]");
+                if let Destination::Group { .. } = member {
+                    problems.push(Problem::new(
+                        format!("{member_path}.group_name"),
+                        format!("Destination group '{group_name}' contains another group"),
+                    ));
+                    continue;
+                }
+                let what = format!("destination group '{group_name}'");
+                self.check_destination(&sessions, &what, &member_path, member, problems);
+            }
+        }
+
+        let mut osc_destinations: Vec<_> = self.map_config.osc_destinations.iter().collect();
+        osc_destinations.sort_by_key(|(name, _)| *name);
+        for (name, destination) in osc_destinations {
+            if let Some(ref queue) = destination.offline_queue
+                && !queue.heartbeat_address.starts_with('/')
+            {
+                problems.push(Problem::new(
+                    format!("osc_destinations.{name}.offline_queue.heartbeat_address"),
+                    format!(
+                        "OSC destination '{name}' heartbeat address '{}' must start with '/'",
+                        queue.heartbeat_address
+                    ),
+                ));
+            }
+        }
+    }
+
+    /// Destinations given to single program commands must be configured and able to
+    /// send the command
+    fn check_command_destinations(&self, problems: &mut Vec<Problem>) {
+        let sessions = self.session_names();
+        let mut devices: Vec<_> = self.device_config.devices.iter().collect();
+        devices.sort_by_key(|(id, _)| *id);
+        for (id, device) in devices {
+            for (i, program) in device.programs.iter().enumerate() {
+                let what = format!("command of program '{}' on '{id}'", program.name);
+                for (j, command) in program.commands.iter().enumerate() {
+                    let Some(ref destination) = command.options.destination else {
+                        continue;
+                    };
+                    let path = format!("devices.{id}.programs[{i}].commands[{j}].destination");
+                    let mut found = Vec::new();
+                    self.check_destination(&sessions, &what, &path, destination, &mut found);
+                    if found.is_empty() && !destination.carries(&command.command) {
+                        let to = match destination {
+                            Destination::RtpMidi { session_name } => {
+                                format!("RTP MIDI session '{session_name}'")
+                            }
+                            Destination::Osc { destination_name } => {
+                                format!("OSC destination '{destination_name}'")
+                            }
+                            Destination::Group { group_name } => format!("group '{group_name}'"),
+                        };
+                        found.push(Problem::new(path, format!("{what} can't be sent to {to}")));
+                    }
+                    problems.extend(
+                        found
+                            .into_iter()
+                            .map(|problem| Problem::device(problem.path, problem.message)),
+                    );
+                }
+            }
+        }
+    }
+
+    fn check_destination(
+        &self,
+        sessions: &HashSet<&str>,
+        what: &str,
+        path: &str,
+        destination: &Destination,
+        problems: &mut Vec<Problem>,
+    ) {
+        let map = self.map_config;
+        match destination {
+            Destination::RtpMidi { session_name } => {
+                if !sessions.contains(session_name.as_str()) {
+                    problems.push(Problem::new(
+                        format!("{path}.session_name"),
+                        format!("{what} sends to unknown RTP MIDI session '{session_name}'"),
+                    ));
+                }
+            }
+            Destination::Osc { destination_name } => {
+                if !map.osc_destinations.contains_key(destination_name) {
+                    problems.push(Problem::new(
+                        format!("{path}.destination_name"),
+                        format!("{what} sends to unknown OSC destination '{destination_name}'"),
+                    ));
+                }
+            }
+            Destination::Group { group_name } => {
+                if !map.destination_groups.contains_key(group_name) {
+                    problems.push(Problem::new(
+                        format!("{path}.group_name"),
+                        format!("{what} sends to unknown destination group '{group_name}'"),
+                    ));
+                }
+            }
+        }
+    }
+
+    /// MIDI channels must be 1-16
+    fn check_channels(&self, problems: &mut Vec<Problem>) {
+        for (what, path, channel) in self.channels() {
+            if !(1..=16).contains(&channel) {
+                problems.push(Problem::new(
+                    path,
+                    format!("{what} uses channel {channel} (must be 1-16)"),
+                ));
+            }
+        }
+    }
+
+    /// Listening ports must not be shared
+    fn check_ports(&self, problems: &mut Vec<Problem>) {
+        let map = self.map_config;
+        let mut ports: BTreeMap<u16, Vec<(String, String)>> = BTreeMap::new();
+        for (i, session) in map.rtp_midi_sessions.iter().enumerate() {
+            // Disabled sessions don't bind; send-only sessions without a port use free ports
+            if !session.enabled || (session.send_only && session.port == 0) {
+                continue;
+            }
+            // RTP MIDI uses the control port and the data port after it
+            let Some(data_port) = session.port.checked_add(1) else {
+                problems.push(Problem::new(
+                    format!("rtp_midi_sessions[{i}].port"),
+                    format!(
+                        "session '{}' needs port {} and the one after it for data",
+                        session.name, session.port
+                    ),
+                ));
+                continue;
+            };
+            for port in [session.port, data_port] {
+                ports.entry(port).or_default().push((
+                    format!("session '{}'", session.name),
+                    format!("rtp_midi_sessions[{i}].port"),
+                ));
+            }
+        }
+        if map.subsystems.osc_listener {
+            for (i, source) in map.osc_sources.iter().enumerate() {
+                if !source.enabled {
+                    continue;
+                }
+                ports.entry(source.port).or_default().push((
+                    format!("OSC source '{}'", source.name),
+                    format!("osc_sources[{i}].port"),
+                ));
+            }
+        }
+
+        for (port, users) in ports {
+            if users.len() > 1 {
+                // Reported at the later user, which is the one that can't bind
+                let path = users[users.len() - 1].1.clone();
+                let users: Vec<&str> = users.iter().map(|(user, _)| user.as_str()).collect();
+                problems.push(Problem::new(
+                    path,
+                    format!("port {port} is used by {}", users.join(" and ")),
+                ));
+            }
+        }
+    }
+
+    /// Tempo domains must have unique names and refer to configured devices and OSC
+    /// sources, and each device and source can belong to only one domain
+    fn check_tempo_domains(&self, problems: &mut Vec<Problem>) {
+        let map = self.map_config;
+        let mut names = HashSet::from([MAIN_TEMPO_DOMAIN]);
+        let mut devices: BTreeMap<&str, &str> = BTreeMap::new();
+        let mut sources: BTreeMap<&str, &str> = BTreeMap::new();
+        for (i, domain) in map.tempo_domains.iter().enumerate() {
+            if !names.insert(&domain.name) {
+                problems.push(Problem::new(
+                    format!("tempo_domains[{i}].name"),
+                    format!("tempo domain '{}' is defined twice", domain.name),
+                ));
+            }
+            for (j, device_id) in domain.devices.iter().enumerate() {
+                let path = format!("tempo_domains[{i}].devices[{j}]");
+                if self.device_config.get_device(device_id).is_none() {
+                    problems.push(Problem::new(
+                        &path,
+                        format!(
+                            "tempo domain '{}' refers to unknown device '{device_id}'",
+                            domain.name
+                        ),
+                    ));
+                }
+                if let Some(other) = devices.insert(device_id, &domain.name) {
+                    problems.push(Problem::new(
+                        path,
+                        format!(
+                            "device '{device_id}' is in tempo domains '{other}' and '{}'",
+                            domain.name
+                        ),
+                    ));
+                }
+            }
+            for (j, source) in domain.sources.iter().enumerate() {
+                let path = format!("tempo_domains[{i}].sources[{j}]");
+                if !map.osc_sources.iter().any(|s| s.name == *source) {
+                    problems.push(Problem::new(
+                        &path,
+                        format!(
+                            "tempo domain '{}' refers to unknown OSC source '{source}'",
+                            domain.name
+                        ),
+                    ));
+                }
+                if let Some(other) = sources.insert(source, &domain.name) {
+                    problems.push(Problem::new(
+                        path,
+                        format!(
+                            "OSC source '{source}' is in tempo domains '{other}' and '{}'",
+                            domain.name
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Tempo statistics must cover some time
+    fn check_tempo_stats(&self, problems: &mut Vec<Problem>) {
+        if self.map_config.tempo_stats.window_mins == 0 {
+            problems.push(Problem::new(
+                "tempo_stats.window_mins",
+                "tempo statistics window must be at least 1 minute",
+            ));
+        }
+    }
+
+    /// Tap inputs must set known tempo domains
+    fn check_tap_inputs(&self, problems: &mut Vec<Problem>) {
+        let map = self.map_config;
+        for (i, input) in map.tap_inputs.iter().enumerate() {
+            if !self.has_tempo_domain(&input.tempo_domain) {
+                problems.push(Problem::new(
+                    format!("tap_inputs[{i}].tempo_domain"),
+                    format!(
+                        "tap input '{}' sets unknown tempo domain '{}'",
+                        input.name, input.tempo_domain
+                    ),
+                ));
+            }
+        }
+    }
+
+    /// Tempo displays must send to configured OSC destinations and show known tempo domains
+    fn check_tempo_displays(&self, problems: &mut Vec<Problem>) {
+        let map = self.map_config;
+        for (i, display) in map.tempo_displays.iter().enumerate() {
+            if !map.osc_destinations.contains_key(&display.destination_name) {
+                problems.push(Problem::new(
+                    format!("tempo_displays[{i}].destination_name"),
+                    format!(
+                        "tempo display sends to unknown OSC destination '{}'",
+                        display.destination_name
+                    ),
+                ));
+            }
+            if !self.has_tempo_domain(&display.tempo_domain) {
+                problems.push(Problem::new(
+                    format!("tempo_displays[{i}].tempo_domain"),
+                    format!(
+                        "tempo display shows unknown tempo domain '{}'",
+                        display.tempo_domain
+                    ),
+                ));
+            }
+        }
+    }
+
+    /// Event sinks must send to known OSC destinations with a valid syslog facility
+    fn check_event_sinks(&self, problems: &mut Vec<Problem>) {
+        let map = self.map_config;
+        for (i, sink) in map.event_sinks.iter().enumerate() {
+            match sink {
+                EventSink::Osc {
+                    destination_name, ..
+                } if !map.osc_destinations.contains_key(destination_name) => {
+                    problems.push(Problem::new(
+                        format!("event_sinks[{i}].destination_name"),
+                        format!("event sink sends to unknown OSC destination '{destination_name}'"),
+                    ));
+                }
+                EventSink::Syslog { facility, .. } if *facility > 23 => {
+                    problems.push(Problem::new(
+                        format!("event_sinks[{i}].facility"),
+                        format!("syslog facility {facility} must be 0-23"),
+                    ));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Clock outputs must send to configured sessions and follow known tempo domains
+    fn check_clock_outputs(&self, problems: &mut Vec<Problem>) {
+        let map = self.map_config;
+        let sessions = self.session_names();
+        for (i, output) in map.clock_outputs.iter().enumerate() {
+            if !sessions.contains(output.session_name.as_str()) {
+                problems.push(Problem::new(
+                    format!("clock_outputs[{i}].session_name"),
+                    format!(
+                        "clock output sends to unknown RTP MIDI session '{}'",
+                        output.session_name
+                    ),
+                ));
+            }
+            if !self.has_tempo_domain(&output.tempo_domain) {
+                problems.push(Problem::new(
+                    format!("clock_outputs[{i}].tempo_domain"),
+                    format!(
+                        "clock output for '{}' follows unknown tempo domain '{}'",
+                        output.session_name, output.tempo_domain
+                    ),
+                ));
+            }
+        }
+    }
+
+    /// Link must follow a known tempo domain
+    fn check_link(&self, problems: &mut Vec<Problem>) {
+        let Some(ref link) = self.map_config.link else {
+            return;
+        };
+        let domain = &link.tempo_domain;
+        if !self.has_tempo_domain(domain) {
+            problems.push(Problem::new(
+                "link.tempo_domain",
+                format!("Ableton Link follows unknown tempo domain '{domain}'"),
+            ));
+        }
+    }
+
+    /// Step mappings must have unique names, since their positions are kept by name
+    fn check_step_mappings(&self, problems: &mut Vec<Problem>) {
+        let mut names = HashSet::new();
+        for (i, mapping) in self.map_config.step_mappings.iter().enumerate() {
+            if !names.insert(&mapping.name) {
+                problems.push(Problem::new(
+                    format!("step_mappings[{i}].name"),
+                    format!("step mapping '{}' is defined twice", mapping.name),
+                ));
+            }
+        }
+    }
+
+    /// Command values must be MIDI data bytes, and random choices need something to pick
+    fn check_command_values(&self, problems: &mut Vec<Problem>) {
+        for (file, path, command) in self.commands() {
+            let (field, value) = match command {
+                Command::ProgramChange { program, .. } => ("program", program),
+                Command::ControlChange { value, .. } => ("value", value),
+                Command::NoteOn { velocity, .. } => ("velocity", velocity),
+                _ => continue,
+            };
+            let message = match value {
+                CommandValue::Fixed(value) if *value > 127 => {
+                    format!("{field} {value} is outside 0-127")
+                }
+                CommandValue::Range { min, max } if *min.max(max) > 127 => {
+                    format!("random {field} range {min}-{max} is outside 0-127")
+                }
+                CommandValue::Choice { choices } if choices.is_empty() => {
+                    format!("random {field} has no choices")
+                }
+                CommandValue::Choice { choices } => match choices.iter().find(|&&c| c > 127) {
+                    Some(choice) => format!("random {field} choice {choice} is outside 0-127"),
+                    None => continue,
+                },
+                _ => continue,
+            };
+            problems.push(Problem {
+                file,
+                path: format!("{path}.{field}"),
+                message,
+            });
+        }
+    }
+
+    /// OSC control rules must send a valid controller to configured sessions and
+    /// scale from a non-empty range
+    fn check_osc_control_rules(&self, problems: &mut Vec<Problem>) {
+        let sessions = self.session_names();
+        for (i, rule) in self.map_config.osc_control_rules.iter().enumerate() {
+            if !sessions.contains(rule.session_name.as_str()) {
+                problems.push(Problem::new(
+                    format!("osc_control_rules[{i}].session_name"),
+                    format!(
+                        "OSC control rule '{}' sends to unknown RTP MIDI session '{}'",
+                        rule.name, rule.session_name
+                    ),
+                ));
+            }
+            if rule.controller > 127 {
+                problems.push(Problem::new(
+                    format!("osc_control_rules[{i}].controller"),
+                    format!(
+                        "OSC control rule '{}' sends controller {} (must be 0-127)",
+                        rule.name, rule.controller
+                    ),
+                ));
+            }
+            if rule.min == rule.max {
+                problems.push(Problem::new(
+                    format!("osc_control_rules[{i}].max"),
+                    format!(
+                        "OSC control rule '{}' scales from an empty range ({} to {})",
+                        rule.name, rule.min, rule.max
+                    ),
+                ));
+            }
+        }
+    }
+
+    /// OSC forwarding rules must send to configured destinations, and their templates
+    /// must only use known placeholders and expand to valid arguments
+    fn check_osc_forward_rules(&self, problems: &mut Vec<Problem>) {
+        for (i, rule) in self.map_config.osc_forward_rules.iter().enumerate() {
+            let path = format!("osc_forward_rules[{i}]");
+            if !self
+                .map_config
+                .osc_destinations
+                .contains_key(&rule.destination_name)
+            {
+                problems.push(Problem::new(
+                    format!("{path}.destination_name"),
+                    format!(
+                        "OSC forwarding rule '{}' sends to unknown OSC destination '{}'",
+                        rule.name, rule.destination_name
+                    ),
+                ));
+            }
+            if rule.number.is_some_and(|number| number > 127) {
+                problems.push(Problem::new(
+                    format!("{path}.number"),
+                    format!(
+                        "OSC forwarding rule '{}' forwards number {} (must be 0-127)",
+                        rule.name,
+                        rule.number.unwrap_or_default()
+                    ),
+                ));
+            }
+            if !rule.address.starts_with('/') {
+                problems.push(Problem::new(
+                    format!("{path}.address"),
+                    format!(
+                        "OSC forwarding rule '{}' address '{}' must start with '/'",
+                        rule.name, rule.address
+                    ),
+                ));
+            }
+
+            let templates = std::iter::once((format!("{path}.address"), &rule.address)).chain(
+                rule.args
+                    .iter()
+                    .enumerate()
+                    .map(|(j, arg)| (format!("{path}.args[{j}]"), arg)),
+            );
+            for (template_path, template) in templates {
+                for name in midi_fields::placeholders(template) {
+                    if !midi_fields::COMMON_PLACEHOLDERS.contains(&name)
+                        && !midi_fields::NUMBER_PLACEHOLDERS.contains(&name)
+                    {
+                        problems.push(Problem::new(
+                            template_path.clone(),
+                            format!(
+                                "OSC forwarding rule '{}' uses unknown placeholder '{{{}}}'",
+                                rule.name, name
+                            ),
+                        ));
+                    }
+                }
+            }
+
+            // Expand the arguments for a sample of each message to catch bad types and literals
+            let kinds = if rule.messages.is_empty() {
+                MidiMessageKind::ALL
+            } else {
+                &rule.messages[..]
+            };
+            let error = kinds.iter().find_map(|&kind| {
+                let sample =
+                    MidiFields::sample(kind, rule.listen_channel.unwrap_or(1), rule.number);
+                rule.render(&sample)
+                    .err()
+                    .map(|e| format!("{:#} for {} messages", e, kind.name()))
+            });
+            if let Some(error) = error {
+                problems.push(Problem::new(
+                    format!("{path}.args"),
+                    format!("OSC forwarding rule '{}': {}", rule.name, error),
+                ));
+            }
+        }
+    }
+
+    /// Value clamps must limit a valid controller to a valid range
+    fn check_value_clamps(&self, problems: &mut Vec<Problem>) {
+        for (what, path, clamps) in self.value_clamps() {
+            for (j, clamp) in clamps.iter().enumerate() {
+                let path = format!("{path}[{j}]");
+                if clamp.controller > 127 {
+                    problems.push(Problem::new(
+                        format!("{path}.controller"),
+                        format!(
+                            "Value clamp of {what} limits controller {} (must be 0-127)",
+                            clamp.controller
+                        ),
+                    ));
+                }
+                if clamp.max > 127 {
+                    problems.push(Problem::new(
+                        format!("{path}.max"),
+                        format!(
+                            "Value clamp of {what} has max {} (must be 0-127)",
+                            clamp.max
+                        ),
+                    ));
+                }
+                if clamp.min > clamp.max {
+                    problems.push(Problem::new(
+                        format!("{path}.min"),
+                        format!(
+                            "Value clamp of {what} has min {} above max {}",
+                            clamp.min, clamp.max
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Shaping must let packets through
+    fn check_shaping(&self, problems: &mut Vec<Problem>) {
+        for (i, session) in self.map_config.rtp_midi_sessions.iter().enumerate() {
+            let Some(ref shaping) = session.shaping else {
+                continue;
+            };
+            let path = format!("rtp_midi_sessions[{i}].shaping");
+            if shaping.max_packets_per_sec == 0 {
+                problems.push(Problem::new(
+                    format!("{path}.max_packets_per_sec"),
+                    format!(
+                        "Shaping of session '{}' allows no packets per second",
+                        session.name
+                    ),
+                ));
+            }
+            if shaping.max_burst == 0 {
+                problems.push(Problem::new(
+                    format!("{path}.max_burst"),
+                    format!(
+                        "Shaping of session '{}' has a burst of 0 (must be at least 1)",
+                        session.name
+                    ),
+                ));
+            }
+        }
+    }
+
+    /// Value clamps of every session and virtual port, with a description and JSON path
+    fn value_clamps(&self) -> Vec<(String, String, &'a [ValueClamp])> {
+        let map = self.map_config;
+        let sessions = map.rtp_midi_sessions.iter().enumerate().map(|(i, s)| {
+            (
+                format!("session '{}'", s.name),
+                format!("rtp_midi_sessions[{i}].value_clamps"),
+                &s.value_clamps[..],
+            )
+        });
+        let ports = map.virtual_ports.iter().enumerate().map(|(i, p)| {
+            (
+                format!("virtual port '{}'", p.name),
+                format!("virtual_ports[{i}].value_clamps"),
+                &p.value_clamps[..],
+            )
+        });
+        sessions.chain(ports).collect()
+    }
+
+    /// Every command in the configs, with the file and path it is at
+    fn commands(&self) -> Vec<(ConfigFile, String, &'a Command)> {
+        let map = self.map_config;
+        let mut devices: Vec<_> = self.device_config.devices.iter().collect();
+        devices.sort_by_key(|(id, _)| *id);
+
+        let mut commands = Vec::new();
+        for (id, device) in &devices {
+            for (i, program) in device.programs.iter().enumerate() {
+                for (j, command) in program.commands.iter().enumerate() {
+                    commands.push((
+                        ConfigFile::Device,
+                        format!("devices.{id}.programs[{i}].commands[{j}]"),
+                        &command.command,
+                    ));
+                }
+            }
+        }
+        let mut add = |file: ConfigFile, path: String, list: &'a [Command]| {
+            for (j, command) in list.iter().enumerate() {
+                commands.push((file, format!("{path}[{j}]"), command));
+            }
+        };
+
+        for (id, device) in devices {
+            if let Some(
+                TempoSpec::TapTempo { commands, .. } | TempoSpec::RawTempo { commands, .. },
+            ) = &device.tempo_spec
+            {
+                add(
+                    ConfigFile::Device,
+                    format!("devices.{id}.tempo_spec.commands"),
+                    commands,
+                );
+            }
+        }
+
+        let map_file = ConfigFile::Map;
+        for (i, m) in map.note_mappings.iter().enumerate() {
+            if let NoteAction::Commands { ref commands } = m.action {
+                add(
+                    map_file,
+                    format!("note_mappings[{i}].action.commands"),
+                    commands,
+                );
+            }
+        }
+        for (i, m) in map.combo_mappings.iter().enumerate() {
+            add(
+                map_file,
+                format!("combo_mappings[{i}].commands"),
+                &m.commands,
+            );
+        }
+        for (i, m) in map.step_mappings.iter().enumerate() {
+            for (j, step) in m.steps.iter().enumerate() {
+                let path = format!("step_mappings[{i}].steps[{j}]");
+                add(map_file, format!("{path}.commands"), &step.commands);
+                if let Some(ref feedback) = step.feedback {
+                    add(
+                        map_file,
+                        format!("{path}.feedback.commands"),
+                        &feedback.commands,
+                    );
+                }
+            }
+        }
+        for (i, m) in map.osc_mappings.iter().enumerate() {
+            add(map_file, format!("osc_mappings[{i}].commands"), &m.commands);
+        }
+        for (i, m) in map.sysex_mappings.iter().enumerate() {
+            add(
+                map_file,
+                format!("sysex_mappings[{i}].commands"),
+                &m.commands,
+            );
+        }
+        for (i, r) in map.inactivity_rules.iter().enumerate() {
+            add(
+                map_file,
+                format!("inactivity_rules[{i}].commands"),
+                &r.commands,
+            );
+        }
+        for (i, a) in map.automations.iter().enumerate() {
+            for (j, step) in a.steps.iter().enumerate() {
+                let path = format!("automations[{i}].steps[{j}].commands");
+                add(map_file, path, &step.commands);
+            }
+        }
+        for (i, list) in map.cue_lists.iter().enumerate() {
+            for (j, cue) in list.cues.iter().enumerate() {
+                add(
+                    map_file,
+                    format!("cue_lists[{i}].cues[{j}].commands"),
+                    &cue.commands,
+                );
+            }
+        }
+        if let Some(ref banks) = map.banks {
+            let mut feedback: Vec<_> = banks.feedback.iter().collect();
+            feedback.sort_by_key(|(name, _)| *name);
+            for (name, feedback) in feedback {
+                add(
+                    map_file,
+                    format!("banks.feedback.{name}.commands"),
+                    &feedback.commands,
+                );
+            }
+        }
+        commands
+    }
+
+    /// Whether a tempo domain is the main one or configured
+    fn has_tempo_domain(&self, domain: &str) -> bool {
+        domain == MAIN_TEMPO_DOMAIN
+            || self
+                .map_config
+                .tempo_domains
+                .iter()
+                .any(|d| d.name == domain)
+    }
+
+    /// Names and aliases of RTP MIDI sessions and virtual ports
+    fn session_names(&self) -> HashSet<&'a str> {
+        let map = self.map_config;
+        map.rtp_midi_sessions
+            .iter()
+            .flat_map(|s| std::iter::once(&s.name).chain(&s.aliases))
+            .chain(map.virtual_ports.iter().map(|p| &p.name))
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Program sequences must run programs that exist, without leading back to the
+    /// program that started them
+    fn check_sequences(&self, problems: &mut Vec<Problem>) {
+        let mut devices: Vec<_> = self.device_config.devices.iter().collect();
+        devices.sort_by_key(|(id, _)| *id);
+        for (id, device) in devices {
+            for (i, program) in device.programs.iter().enumerate() {
+                let what = format!("sequence of program '{}' on '{id}'", program.name);
+                for (j, step) in program.sequence.iter().enumerate() {
+                    let path = format!("devices.{id}.programs[{i}].sequence[{j}]");
+                    match self.device_config.get_device(&step.device_id) {
+                        None => problems.push(Problem::device(
+                            format!("{path}.device_id"),
+                            format!("{what} refers to unknown device '{}'", step.device_id),
+                        )),
+                        Some(target)
+                            if !target.programs.iter().any(|p| p.number == step.program) =>
+                        {
+                            problems.push(Problem::device(
+                                format!("{path}.program"),
+                                format!(
+                                    "{what} runs program {} which is not on device '{}'",
+                                    step.program, step.device_id
+                                ),
+                            ));
+                        }
+                        _ => {}
+                    }
+                }
+                if self.sequence_loops(id, program) {
+                    problems.push(Problem::device(
+                        format!("devices.{id}.programs[{i}].sequence"),
+                        format!("{what} leads back to the program itself"),
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Whether following a program's sequence, and the sequences of the programs it
+    /// runs, reaches the program again
+    fn sequence_loops(&self, device_id: &str, program: &Program) -> bool {
+        let start = (device_id, program.number);
+        let mut visited = HashSet::new();
+        let mut pending: Vec<&SequenceStep> = program.sequence.iter().collect();
+        while let Some(step) = pending.pop() {
+            let key = (step.device_id.as_str(), step.program);
+            if key == start {
+                return true;
+            }
+            if !visited.insert(key) {
+                continue;
+            }
+            if let Some(next) = self
+                .device_config
+                .get_device(&step.device_id)
+                .and_then(|device| device.programs.iter().find(|p| p.number == step.program))
+            {
+                pending.extend(&next.sequence);
+            }
+        }
+        false
+    }
+
+    /// Every destination in the map config, with what sends to it and its JSON path
+    fn destinations(&self) -> Vec<(String, String, &'a Destination)> {
+        let map = self.map_config;
+        let mut destinations = Vec::new();
+        for (i, m) in map.device_mappings.iter().enumerate() {
+            let what = format!("device mapping for '{}'", m.device_id);
+            if let Some(ref display) = m.display {
+                destinations.push((
+                    format!("display of {what}"),
+                    format!("device_mappings[{i}].display.destination"),
+                    &display.destination,
+                ));
+            }
+            destinations.push((
+                what,
+                format!("device_mappings[{i}].destination"),
+                &m.destination,
+            ));
+        }
+        for (i, m) in map.note_mappings.iter().enumerate() {
+            destinations.push((
+                format!("note mapping '{}'", m.name),
+                format!("note_mappings[{i}].destination"),
+                &m.destination,
+            ));
+        }
+        for (i, r) in map.note_program_rules.iter().enumerate() {
+            destinations.push((
+                format!("note program rule '{}'", r.name),
+                format!("note_program_rules[{i}].destination"),
+                &r.destination,
+            ));
+        }
+        for (i, r) in map.note_forward_rules.iter().enumerate() {
+            destinations.push((
+                format!("note forwarding rule '{}'", r.name),
+                format!("note_forward_rules[{i}].destination"),
+                &r.destination,
+            ));
+        }
+        for (i, r) in map.control_forward_rules.iter().enumerate() {
+            destinations.push((
+                format!("control forwarding rule '{}'", r.name),
+                format!("control_forward_rules[{i}].destination"),
+                &r.destination,
+            ));
+        }
+        for (i, r) in map.banked_program_rules.iter().enumerate() {
+            destinations.push((
+                format!("banked program rule '{}'", r.name),
+                format!("banked_program_rules[{i}].destination"),
+                &r.destination,
+            ));
+        }
+        for (i, m) in map.combo_mappings.iter().enumerate() {
+            destinations.push((
+                format!("combo '{}'", m.name),
+                format!("combo_mappings[{i}].destination"),
+                &m.destination,
+            ));
+        }
+        for (i, m) in map.step_mappings.iter().enumerate() {
+            destinations.push((
+                format!("step mapping '{}'", m.name),
+                format!("step_mappings[{i}].destination"),
+                &m.destination,
+            ));
+            for (j, step) in m.steps.iter().enumerate() {
+                if let Some(ref feedback) = step.feedback {
+                    destinations.push((
+                        format!("feedback of step '{}' in '{}'", step.name, m.name),
+                        format!("step_mappings[{i}].steps[{j}].feedback.destination"),
+                        &feedback.destination,
+                    ));
+                }
+            }
+        }
+        for (i, m) in map.osc_mappings.iter().enumerate() {
+            destinations.push((
+                format!("OSC mapping '{}'", m.name),
+                format!("osc_mappings[{i}].destination"),
+                &m.destination,
+            ));
+        }
+        for (i, m) in map.sysex_mappings.iter().enumerate() {
+            destinations.push((
+                format!("SysEx mapping '{}'", m.name),
+                format!("sysex_mappings[{i}].destination"),
+                &m.destination,
+            ));
+        }
+        for (i, r) in map.inactivity_rules.iter().enumerate() {
+            destinations.push((
+                format!("inactivity rule '{}'", r.name),
+                format!("inactivity_rules[{i}].destination"),
+                &r.destination,
+            ));
+        }
+        for (i, a) in map.automations.iter().enumerate() {
+            destinations.push((
+                format!("automation '{}'", a.name),
+                format!("automations[{i}].destination"),
+                &a.destination,
+            ));
+        }
+        for (i, c) in map.cue_lists.iter().enumerate() {
+            destinations.push((
+                format!("cue list '{}'", c.name),
+                format!("cue_lists[{i}].destination"),
+                &c.destination,
+            ));
+        }
+        if let Some(ref banks) = map.banks {
+            for (bank, feedback) in &banks.feedback {
+                destinations.push((
+                    format!("feedback of bank '{bank}'"),
+                    format!("banks.feedback.{bank}.destination"),
+                    &feedback.destination,
+                ));
+            }
+        }
+        destinations
+    }
+
+    /// Every MIDI channel in the map config, with what uses it and its JSON path
+    fn channels(&self) -> Vec<(String, String, u8)> {
+        let map = self.map_config;
+        let mut channels = Vec::new();
+        let mut send = |what: &str, path: String, channel: Option<u8>| {
+            if let Some(channel) = channel {
+                channels.push((what.to_string(), path, channel));
+            }
+        };
+
+        for (i, session) in map.rtp_midi_sessions.iter().enumerate() {
+            let what = format!("channel remap of session '{}'", session.name);
+            for (from, to) in &session.receive_channel_map {
+                let path = format!("rtp_midi_sessions[{i}].receive_channel_map.{from}");
+                send(&what, path.clone(), Some(*from));
+                send(&what, path, Some(*to));
+            }
+        }
+        for (what, path, clamps) in self.value_clamps() {
+            let what = format!("value clamp of {what}");
+            for (j, clamp) in clamps.iter().enumerate() {
+                send(&what, format!("{path}[{j}].channel"), clamp.channel);
+            }
+        }
+        for (i, m) in map.device_mappings.iter().enumerate() {
+            let what = format!("device mapping for '{}'", m.device_id);
+            let path = format!("device_mappings[{i}]");
+            send(
+                &what,
+                format!("{path}.listen_channel"),
+                Some(m.listen_channel),
+            );
+            send(&what, format!("{path}.send_channel"), m.send_channel);
+        }
+        for (i, m) in map.note_mappings.iter().enumerate() {
+            let what = format!("note mapping '{}'", m.name);
+            let path = format!("note_mappings[{i}]");
+            send(
+                &what,
+                format!("{path}.listen_channel"),
+                Some(m.listen_channel),
+            );
+            send(&what, format!("{path}.send_channel"), m.send_channel);
+        }
+        for (i, r) in map.note_program_rules.iter().enumerate() {
+            let what = format!("note program rule '{}'", r.name);
+            let path = format!("note_program_rules[{i}]");
+            send(
+                &what,
+                format!("{path}.listen_channel"),
+                Some(r.listen_channel),
+            );
+            send(&what, format!("{path}.send_channel"), r.send_channel);
+        }
+        for (i, r) in map.note_forward_rules.iter().enumerate() {
+            let what = format!("note forwarding rule '{}'", r.name);
+            let path = format!("note_forward_rules[{i}]");
+            send(
+                &what,
+                format!("{path}.listen_channel"),
+                Some(r.listen_channel),
+            );
+            send(&what, format!("{path}.send_channel"), r.send_channel);
+        }
+        for (i, r) in map.control_forward_rules.iter().enumerate() {
+            let what = format!("control forwarding rule '{}'", r.name);
+            let path = format!("control_forward_rules[{i}]");
+            send(
+                &what,
+                format!("{path}.listen_channel"),
+                Some(r.listen_channel),
+            );
+            send(&what, format!("{path}.send_channel"), r.send_channel);
+        }
+        for (i, r) in map.banked_program_rules.iter().enumerate() {
+            let what = format!("banked program rule '{}'", r.name);
+            let path = format!("banked_program_rules[{i}]");
+            send(
+                &what,
+                format!("{path}.listen_channel"),
+                Some(r.listen_channel),
+            );
+            send(&what, format!("{path}.send_channel"), r.send_channel);
+        }
+        for (i, m) in map.combo_mappings.iter().enumerate() {
+            let what = format!("combo '{}'", m.name);
+            let path = format!("combo_mappings[{i}]");
+            for (j, trigger) in m.triggers.iter().enumerate() {
+                send(
+                    &what,
+                    format!("{path}.triggers[{j}].channel"),
+                    Some(trigger_channel(trigger)),
+                );
+            }
+            send(&what, format!("{path}.send_channel"), m.send_channel);
+        }
+        for (i, m) in map.step_mappings.iter().enumerate() {
+            let what = format!("step mapping '{}'", m.name);
+            let path = format!("step_mappings[{i}]");
+            send(
+                &what,
+                format!("{path}.trigger.channel"),
+                Some(trigger_channel(&m.trigger)),
+            );
+            send(
+                &what,
+                format!("{path}.reset_trigger.channel"),
+                m.reset_trigger.as_ref().map(trigger_channel),
+            );
+            send(&what, format!("{path}.send_channel"), m.send_channel);
+        }
+        for (i, m) in map.osc_mappings.iter().enumerate() {
+            send(
+                &format!("OSC mapping '{}'", m.name),
+                format!("osc_mappings[{i}].send_channel"),
+                m.send_channel,
+            );
+        }
+        for (i, r) in map.osc_control_rules.iter().enumerate() {
+            send(
+                &format!("OSC control rule '{}'", r.name),
+                format!("osc_control_rules[{i}].send_channel"),
+                Some(r.send_channel),
+            );
+        }
+        for (i, r) in map.osc_forward_rules.iter().enumerate() {
+            send(
+                &format!("OSC forwarding rule '{}'", r.name),
+                format!("osc_forward_rules[{i}].listen_channel"),
+                r.listen_channel,
+            );
+        }
+        for (i, m) in map.sysex_mappings.iter().enumerate() {
+            send(
+                &format!("SysEx mapping '{}'", m.name),
+                format!("sysex_mappings[{i}].send_channel"),
+                m.send_channel,
+            );
+        }
+        for (i, r) in map.inactivity_rules.iter().enumerate() {
+            send(
+                &format!("inactivity rule '{}'", r.name),
+                format!("inactivity_rules[{i}].send_channel"),
+                r.send_channel,
+            );
+        }
+        for (i, a) in map.automations.iter().enumerate() {
+            let what = format!("automation '{}'", a.name);
+            let path = format!("automations[{i}]");
+            send(
+                &what,
+                format!("{path}.start_trigger.channel"),
+                Some(trigger_channel(&a.start_trigger)),
+            );
+            send(
+                &what,
+                format!("{path}.stop_trigger.channel"),
+                a.stop_trigger.as_ref().map(trigger_channel),
+            );
+            send(&what, format!("{path}.send_channel"), a.send_channel);
+        }
+        for (i, c) in map.cue_lists.iter().enumerate() {
+            let what = format!("cue list '{}'", c.name);
+            let path = format!("cue_lists[{i}]");
+            send(
+                &what,
+                format!("{path}.go_trigger.channel"),
+                c.go_trigger.as_ref().map(trigger_channel),
+            );
+            send(&what, format!("{path}.send_channel"), c.send_channel);
+        }
+        for (i, t) in map.tap_inputs.iter().enumerate() {
+            send(
+                &format!("tap input '{}'", t.name),
+                format!("tap_inputs[{i}].trigger.channel"),
+                Some(trigger_channel(&t.trigger)),
+            );
+        }
+        for (i, capture) in map.captures.iter().enumerate() {
+            if let CaptureSource::ControlChange { channel, .. } = capture.source {
+                send(
+                    &format!("capture '{}'", capture.variable),
+                    format!("captures[{i}].source.channel"),
+                    Some(channel),
+                );
+            }
+        }
+        if let Some(ref banks) = map.banks {
+            for (i, selector) in banks.selectors.iter().enumerate() {
+                let (field, channel) = match selector {
+                    BankSelector::ProgramChange { channel }
+                    | BankSelector::ControlChange { channel, .. } => ("channel", *channel),
+                    BankSelector::Next { trigger } | BankSelector::Previous { trigger } => {
+                        ("trigger.channel", trigger_channel(trigger))
+                    }
+                };
+                send(
+                    "bank selector",
+                    format!("banks.selectors[{i}].{field}"),
+                    Some(channel),
+                );
+            }
+        }
+        channels
+    }
+}
+
+fn trigger_channel(trigger: &Trigger) -> u8 {
+    match trigger {
+        Trigger::Note { channel, .. } | Trigger::ControlChange { channel, .. } => *channel,
+    }
+}
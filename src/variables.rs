@@ -0,0 +1,94 @@
+use crate::mapping::{Capture, CaptureSource};
+use anyhow::{Context, Result};
+#[cfg(feature = "osc-input")]
+use rosc::OscType;
+use std::collections::HashMap;
+use tracing::debug;
+
+/// Values captured from incoming messages, by variable name
+pub struct Variables {
+    values: HashMap<String, f64>,
+}
+
+impl Variables {
+    pub fn new() -> Self {
+        Self {
+            values: HashMap::new(),
+        }
+    }
+
+    /// Store the value of a Control Change in every variable capturing it
+    pub fn capture_control_change(
+        &mut self,
+        captures: &[Capture],
+        channel: u8,
+        controller: u8,
+        value: u8,
+    ) {
+        for capture in captures {
+            if let CaptureSource::ControlChange {
+                channel: c,
+                controller: n,
+            } = capture.source
+                && c == channel
+                && n == controller
+            {
+                self.set(&capture.variable, f64::from(value));
+            }
+        }
+    }
+
+    /// Store a numeric argument of an OSC message in every variable capturing it
+    #[cfg(feature = "osc-input")]
+    pub fn capture_osc(&mut self, captures: &[Capture], address: &str, args: &[OscType]) {
+        for capture in captures {
+            let CaptureSource::Osc {
+                address: ref captured,
+                arg,
+            } = capture.source
+            else {
+                continue;
+            };
+            if captured != address {
+                continue;
+            }
+            let value = match args.get(arg) {
+                Some(OscType::Float(value)) => f64::from(*value),
+                Some(OscType::Double(value)) => *value,
+                Some(OscType::Int(value)) => f64::from(*value),
+                Some(OscType::Long(value)) => *value as f64,
+                _ => continue,
+            };
+            self.set(&capture.variable, value);
+        }
+    }
+
+    /// Add `offset` to a variable, clamped to `min`-`max`, keeping and returning the result.
+    ///
+    /// Variables that haven't been captured yet start from their capture's initial value.
+    pub fn nudge(
+        &mut self,
+        captures: &[Capture],
+        name: &str,
+        offset: f64,
+        min: f64,
+        max: f64,
+    ) -> Result<f64> {
+        let current = match self.values.get(name) {
+            Some(value) => *value,
+            None => captures
+                .iter()
+                .filter(|capture| capture.variable == name)
+                .find_map(|capture| capture.initial)
+                .with_context(|| format!("Variable '{name}' has not been captured yet"))?,
+        };
+        let value = (current + offset).clamp(min, max);
+        self.set(name, value);
+        Ok(value)
+    }
+
+    fn set(&mut self, name: &str, value: f64) {
+        debug!("Variable '{}' = {}", name, value);
+        self.values.insert(name.to_string(), value);
+    }
+}
@@ -0,0 +1,154 @@
+use crate::mapping::{ValueClamp, VirtualPort as VirtualPortConfig};
+use crate::processor::MidiProcessor;
+use crate::session_manager::SessionManager;
+use anyhow::Result;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+/// Client name the ports are registered under
+#[cfg(all(unix, feature = "virtual-ports"))]
+const CLIENT_NAME: &str = "midi-router";
+
+/// Incoming messages buffered per port before new ones are dropped
+#[cfg(all(unix, feature = "virtual-ports"))]
+const INGEST_CAPACITY: usize = 1024;
+
+/// A pair of virtual MIDI ports; closed when dropped
+#[cfg_attr(not(all(unix, feature = "virtual-ports")), allow(dead_code))]
+pub struct VirtualPort {
+    #[cfg(all(unix, feature = "virtual-ports"))]
+    output: std::sync::Mutex<midir::MidiOutputConnection>,
+    #[cfg(all(unix, feature = "virtual-ports"))]
+    _input: std::sync::Mutex<Option<midir::MidiInputConnection<()>>>,
+    /// Limits on controller values sent to the port
+    pub value_clamps: Vec<ValueClamp>,
+}
+
+impl VirtualPort {
+    /// Send complete MIDI messages to applications connected to the "Out" port
+    #[cfg(all(unix, feature = "virtual-ports"))]
+    pub fn send(&self, bytes: &[u8]) -> Result<()> {
+        let mut output = self
+            .output
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Virtual port lock poisoned"))?;
+        output.send(bytes)?;
+        Ok(())
+    }
+
+    #[cfg(not(all(unix, feature = "virtual-ports")))]
+    pub fn send(&self, _bytes: &[u8]) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Create the configured virtual ports, registering them with the session manager
+/// and routing messages from their "In" ports to the processor
+#[cfg(all(unix, feature = "virtual-ports"))]
+pub async fn start(
+    configs: &[VirtualPortConfig],
+    processor: Arc<MidiProcessor>,
+    session_manager: &SessionManager,
+) -> Result<Vec<JoinHandle<()>>> {
+    use crate::supervisor::supervise;
+    use crate::wire;
+    use anyhow::{Context, anyhow};
+    use midir::os::unix::{VirtualInput, VirtualOutput};
+    use midir::{MidiInput, MidiOutput};
+    use tokio::sync::mpsc;
+    use tracing::{debug, error, info};
+
+    let mut tasks = Vec::new();
+    for config in configs {
+        let output = MidiOutput::new(CLIENT_NAME)?
+            .create_virtual(&format!("{} Out", config.name))
+            .map_err(|e| anyhow!("{e}"))
+            .with_context(|| format!("Failed to create virtual port '{} Out'", config.name))?;
+
+        let input = if config.listen {
+            // A single consumer per port keeps messages in order
+            let (tx, rx) = mpsc::channel::<Vec<u8>>(INGEST_CAPACITY);
+            let processor = Arc::clone(&processor);
+            let source = config.name.clone();
+            // Shared so a restarted consumer picks up where the last one stopped
+            let rx = Arc::new(tokio::sync::Mutex::new(rx));
+            tasks.push(supervise(
+                format!("virtual port '{}' input", config.name),
+                move || {
+                    let rx = Arc::clone(&rx);
+                    let processor = Arc::clone(&processor);
+                    let source = source.clone();
+                    async move {
+                        let mut rx = rx.lock().await;
+                        while let Some(bytes) = rx.recv().await {
+                            let result = if bytes.first() == Some(&0xF0) {
+                                processor.handle_sysex(&source, &bytes).await
+                            } else if let Some(message) = wire::decode(&bytes) {
+                                debug!(
+                                    "Received MIDI message on virtual port '{source}' {message:?}"
+                                );
+                                processor.process_midi_message(&source, message).await
+                            } else {
+                                continue;
+                            };
+                            if let Err(e) = result {
+                                error!("Error processing MIDI message: {}", e);
+                            }
+                        }
+                    }
+                },
+            ));
+
+            let name = config.name.clone();
+            let connection = MidiInput::new(CLIENT_NAME)?
+                .create_virtual(
+                    &format!("{} In", config.name),
+                    move |_timestamp, bytes, _| {
+                        if tx.try_send(bytes.to_vec()).is_err() {
+                            warn!(
+                                "Virtual port '{} In' is falling behind, dropped a message",
+                                name
+                            );
+                        }
+                    },
+                    (),
+                )
+                .map_err(|e| anyhow!("{e}"))
+                .with_context(|| format!("Failed to create virtual port '{} In'", config.name))?;
+            Some(connection)
+        } else {
+            None
+        };
+
+        info!(
+            "Created virtual MIDI ports '{0} In' and '{0} Out'",
+            config.name
+        );
+        session_manager
+            .add_virtual_port(
+                &config.name,
+                VirtualPort {
+                    output: std::sync::Mutex::new(output),
+                    _input: std::sync::Mutex::new(input),
+                    value_clamps: config.value_clamps.clone(),
+                },
+            )
+            .await;
+    }
+    Ok(tasks)
+}
+
+#[cfg(not(all(unix, feature = "virtual-ports")))]
+pub async fn start(
+    configs: &[VirtualPortConfig],
+    _processor: Arc<MidiProcessor>,
+    _session_manager: &SessionManager,
+) -> Result<Vec<JoinHandle<()>>> {
+    if !configs.is_empty() {
+        warn!(
+            "Virtual ports not created: only available on macOS and Linux with the `virtual-ports` feature"
+        );
+    }
+    Ok(Vec::new())
+}
@@ -0,0 +1,97 @@
+use midi_types::{Channel, MidiMessage};
+
+/// The MIDI 1.0 wire bytes of a message
+pub fn encode(message: &MidiMessage) -> Vec<u8> {
+    let status = |kind: u8, channel: Channel| kind | (u8::from(channel) & 0x0F);
+    match *message {
+        MidiMessage::NoteOff(channel, note, velocity) => {
+            vec![status(0x80, channel), note.into(), velocity.into()]
+        }
+        MidiMessage::NoteOn(channel, note, velocity) => {
+            vec![status(0x90, channel), note.into(), velocity.into()]
+        }
+        MidiMessage::KeyPressure(channel, note, pressure) => {
+            vec![status(0xA0, channel), note.into(), pressure.into()]
+        }
+        MidiMessage::ControlChange(channel, controller, value) => {
+            vec![status(0xB0, channel), controller.into(), value.into()]
+        }
+        MidiMessage::ProgramChange(channel, program) => {
+            vec![status(0xC0, channel), program.into()]
+        }
+        MidiMessage::ChannelPressure(channel, pressure) => {
+            vec![status(0xD0, channel), pressure.into()]
+        }
+        MidiMessage::PitchBendChange(channel, value) => {
+            let value = u16::from(value);
+            vec![
+                status(0xE0, channel),
+                (value & 0x7F) as u8,
+                ((value >> 7) & 0x7F) as u8,
+            ]
+        }
+        MidiMessage::QuarterFrame(frame) => vec![0xF1, frame.into()],
+        MidiMessage::SongPositionPointer(position) => {
+            let position = u16::from(position);
+            vec![
+                0xF2,
+                (position & 0x7F) as u8,
+                ((position >> 7) & 0x7F) as u8,
+            ]
+        }
+        MidiMessage::SongSelect(song) => vec![0xF3, song.into()],
+        MidiMessage::TuneRequest => vec![0xF6],
+        MidiMessage::TimingClock => vec![0xF8],
+        MidiMessage::Start => vec![0xFA],
+        MidiMessage::Continue => vec![0xFB],
+        MidiMessage::Stop => vec![0xFC],
+        MidiMessage::ActiveSensing => vec![0xFE],
+        MidiMessage::Reset => vec![0xFF],
+    }
+}
+
+/// Decode a complete channel or real-time message.
+///
+/// Messages the router doesn't act on (pitch bend, MTC and other system common
+/// messages) and malformed ones decode to `None`.
+#[cfg(all(unix, feature = "virtual-ports"))]
+pub fn decode(bytes: &[u8]) -> Option<MidiMessage> {
+    let (&status, data) = bytes.split_first()?;
+    let channel = Channel::new(status & 0x0F);
+    let data_byte = |index: usize| data.get(index).map(|byte| byte & 0x7F);
+
+    let message = match status & 0xF0 {
+        0x80 => MidiMessage::NoteOff(
+            channel,
+            Note::new(data_byte(0)?),
+            Value7::new(data_byte(1)?),
+        ),
+        0x90 => MidiMessage::NoteOn(
+            channel,
+            Note::new(data_byte(0)?),
+            Value7::new(data_byte(1)?),
+        ),
+        0xA0 => MidiMessage::KeyPressure(
+            channel,
+            Note::new(data_byte(0)?),
+            Value7::new(data_byte(1)?),
+        ),
+        0xB0 => MidiMessage::ControlChange(
+            channel,
+            Control::new(data_byte(0)?),
+            Value7::new(data_byte(1)?),
+        ),
+        0xC0 => MidiMessage::ProgramChange(channel, Program::new(data_byte(0)?)),
+        0xD0 => MidiMessage::ChannelPressure(channel, Value7::new(data_byte(0)?)),
+        _ => match status {
+            0xF8 => MidiMessage::TimingClock,
+            0xFA => MidiMessage::Start,
+            0xFB => MidiMessage::Continue,
+            0xFC => MidiMessage::Stop,
+            0xFE => MidiMessage::ActiveSensing,
+            0xFF => MidiMessage::Reset,
+            _ => return None,
+        },
+    };
+    Some(message)
+}